@@ -0,0 +1,55 @@
+/*
+ * With the `grpc` feature, generates the tonic service code for the services of
+ * proto/anon_survey.proto. The messages are written out in src/grpc.rs, so no protoc is needed
+ *
+ * With the `ffi` feature, writes the C header include/anon_survey.h for src/ffi.rs
+*/
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "grpc")]
+    grpc_services();
+    #[cfg(feature = "ffi")]
+    c_header();
+}
+
+#[cfg(feature = "grpc")]
+fn grpc_services() {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    let method = |name: &str, route: &str, input: &str, output: &str| Method::builder()
+        .name(name)
+        .route_name(route)
+        .input_type(format!("crate::grpc::proto::{}", input))
+        .output_type(format!("crate::grpc::proto::{}", output))
+        .codec_path("tonic::codec::ProstCodec")
+        .build();
+
+    let registration = Service::builder()
+        .name("Registration")
+        .package("anon_survey.v1")
+        .method(method("register", "Register", "RegistrationRequest", "BlindedCredential"))
+        .build();
+    let surveys = Service::builder()
+        .name("Surveys")
+        .package("anon_survey.v1")
+        .method(method("gen_survey", "GenSurvey", "GenSurveyRequest", "Survey"))
+        .method(method("fetch_survey", "FetchSurvey", "FetchSurveyRequest", "Survey"))
+        .method(method("submit", "Submit", "Submission", "SubmitResponse"))
+        .method(method("verify", "Verify", "Submission", "VerifyResponse"))
+        .build();
+    Builder::new().compile(&[registration, surveys]);
+}
+
+#[cfg(feature = "ffi")]
+fn c_header() {
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    let config = cbindgen::Config::from_file("cbindgen.toml").expect("cbindgen.toml is not valid");
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src("src/ffi.rs")
+        .generate()
+        .expect("Could not generate the C header")
+        .write_to_file("include/anon_survey.h");
+}
@@ -0,0 +1,316 @@
+extern crate anon_survey;
+extern crate rand;
+extern crate tbn;
+
+use rand::{Rng, SeedableRng};
+use rand::prng::ChaChaRng;
+use tbn::{Group, Fr, G1, G2};
+
+use anon_survey::{User, SurveyAuthority, RegistrationAuthority, Survey, SubmissionLedger, SurveyError, SystemParams};
+
+/*
+ * ----------------------------------------------
+ * |    FULL FLOW EXAMPLE                       |
+ * ----------------------------------------------
+ *
+ * Every step of the protocol on a small but realistic dataset, generated from a seed so each run
+ * with the same seed is identical:
+ *
+ *      cargo run --example full_flow           (seed 0)
+ *      cargo run --example full_flow -- 42
+ *
+ * 50 users register with the RA. One SA runs three surveys (a yes/no question, a 1-5 rating, and
+ * free text) for different groups of them, and most participants answer. Along the way some
+ * submissions are bad: an outsider and an unregistered user try to answer, a participant answers
+ * twice, an answer is altered in transit, and a participant answers after the survey closed. The
+ * ledger accepts exactly the honest answers, and the SA tallies them.
+*/
+
+const USERS:usize = 50;
+
+// Answers to a free-text question are drawn from these
+const REMARKS:[&str; 5] = [
+    "More evening sessions, please.",
+    "The new schedule works well for me.",
+    "Too many meetings.",
+    "Nothing to add.",
+    "The online forms are hard to find."
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Question {
+    YesNo,
+    Rating,
+    FreeText
+}
+
+pub struct SurveyPlan {
+    pub title: &'static str,
+    pub question: Question,
+    // Users (by index) the SA authorizes
+    pub participants: Vec<usize>,
+    // Participants who answer while the survey is open, with their answers
+    pub answers: Vec<(usize, Vec<u8>)>
+}
+
+// A submission that should not make it into the ledger
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Misbehaviour {
+    // A registered user who is not a participant answers
+    Outsider { survey: usize, user: usize },
+    // Someone who never registered answers
+    Unregistered { survey: usize },
+    // A participant answers a second time
+    Repeat { survey: usize, user: usize },
+    // A participant's answer is altered on its way to the ledger (they send it again afterwards)
+    Tampered { survey: usize, user: usize },
+    // A participant who did not answer tries once the survey closed
+    Late { survey: usize, user: usize }
+}
+
+impl Misbehaviour {
+
+    pub fn survey(&self) -> usize {
+        match *self {
+            Misbehaviour::Outsider { survey, .. } | Misbehaviour::Unregistered { survey } | Misbehaviour::Repeat { survey, .. }
+                | Misbehaviour::Tampered { survey, .. } | Misbehaviour::Late { survey, .. } => survey
+        }
+    }
+
+    // How the protocol refuses it
+    pub fn expected(&self) -> SurveyError {
+        match self {
+            Misbehaviour::Outsider { .. } => SurveyError::NotAuthorized,
+            Misbehaviour::Unregistered { .. } => SurveyError::UnregisteredUser,
+            Misbehaviour::Repeat { .. } => SurveyError::DuplicateSubmission,
+            Misbehaviour::Tampered { .. } => SurveyError::InvalidSubmission,
+            Misbehaviour::Late { .. } => SurveyError::SurveyClosed
+        }
+    }
+}
+
+pub struct Dataset {
+    pub users: Vec<String>,
+    pub surveys: Vec<SurveyPlan>,
+    pub misbehaviour: Vec<Misbehaviour>
+}
+
+// The results of running a dataset
+pub struct Outcome {
+    // Responses the ledger accepted, per survey
+    pub accepted: Vec<usize>,
+    // Every misbehaviour, with the error it was refused with
+    pub refused: Vec<(Misbehaviour, SurveyError)>,
+    // Distinct answers and how often each was given, per survey
+    pub tallies: Vec<Vec<(String, usize)>>
+}
+
+
+/*
+ * Dataset generator
+ */
+
+fn answer(question: Question, rng: &mut impl Rng) -> Vec<u8> {
+    let text:String = match question {
+        Question::YesNo => String::from(if rng.gen_bool(0.6) { "yes" } else { "no" }),
+        Question::Rating => rng.gen_range(1, 6).to_string(),
+        Question::FreeText => String::from(REMARKS[rng.gen_range(0, REMARKS.len())])
+    };
+    text.into_bytes()
+}
+
+pub fn generate(rng: &mut impl Rng) -> Dataset {
+    let users:Vec<String> = (0..USERS).map(|i| format!("user{:02}", i)).collect();
+
+    // Everyone is asked the first question, a random half the second, and a random fifth the third
+    let mut surveys:Vec<SurveyPlan> = Vec::new();
+    for (title, question, size) in [("Keep the current opening hours?", Question::YesNo, USERS),
+                                    ("Rate this year's events (1-5)", Question::Rating, USERS / 2),
+                                    ("Anything else we should know?", Question::FreeText, USERS / 5)].iter() {
+        let mut participants:Vec<usize> = (0..USERS).collect();
+        rng.shuffle(&mut participants);
+        participants.truncate(*size);
+        participants.sort_unstable();
+        // Four in five participants answer
+        let mut answers:Vec<(usize, Vec<u8>)> = Vec::new();
+        for user in &participants {
+            if rng.gen_bool(0.8) {
+                answers.push((*user, answer(*question, rng)));
+            }
+        }
+        surveys.push(SurveyPlan { title, question: *question, participants, answers });
+    }
+
+    let mut misbehaviour:Vec<Misbehaviour> = Vec::new();
+    for (survey, plan) in surveys.iter().enumerate() {
+        let outsider:Option<usize> = (0..USERS).find(|user| !plan.participants.contains(user));
+        if let Some(user) = outsider {
+            misbehaviour.push(Misbehaviour::Outsider { survey, user });
+        }
+        misbehaviour.push(Misbehaviour::Unregistered { survey });
+        misbehaviour.push(Misbehaviour::Repeat { survey, user: plan.answers[0].0 });
+        misbehaviour.push(Misbehaviour::Tampered { survey, user: plan.answers[plan.answers.len() - 1].0 });
+        let silent:Option<usize> = plan.participants.iter().cloned().find(|user| plan.answers.iter().all(|(u, _)| u != user));
+        if let Some(user) = silent {
+            misbehaviour.push(Misbehaviour::Late { survey, user });
+        }
+    }
+    Dataset { users, surveys, misbehaviour }
+}
+
+
+/*
+ * Running the protocol on a dataset
+ */
+
+pub fn run(dataset: &Dataset, rng: &mut ChaChaRng) -> Outcome {
+    // Setup: fixed (seeded) generators and authorities
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let (g, g2):(G1, G2) = params.generators();
+    let mut ra = RegistrationAuthority::new_with_rng(&params, rng);
+    let mut sa = SurveyAuthority::new_with_rng(&params, rng);
+    let mut ledger = SubmissionLedger::new();
+
+    // RegUser
+    let mut users:Vec<User> = Vec::new();
+    for _ in &dataset.users {
+        let mut user = User::new_with_rng(rng);
+        user.reg_user_with_rng(&mut ra, rng).expect("Registration failed");
+        users.push(user);
+    }
+
+    // GenSurvey
+    let mut surveys:Vec<Survey> = Vec::new();
+    for plan in &dataset.surveys {
+        let ids:Vec<Fr> = plan.participants.iter().map(|user| users[*user].id()).collect();
+        surveys.push(sa.gen_survey_with_rng(&ids, g, g2, &ra.vk, rng).expect("SA survey creation failed!"));
+    }
+
+    let mut refused:Vec<(Misbehaviour, SurveyError)> = Vec::new();
+    let mut refuse = |bad: Misbehaviour, result: Result<(), SurveyError>| {
+        refused.push((bad, result.expect_err("Misbehaviour went unnoticed")));
+    };
+    let stranger = User::new_with_rng(rng);
+
+    // SubmitSurvey and Check, with the bad submissions mixed in
+    for (i, (plan, survey)) in dataset.surveys.iter().zip(surveys.iter()).enumerate() {
+        let mut submit = |user: &User, message: &[u8], ledger: &mut SubmissionLedger, tamper: bool| {
+            let mut submission = user.submit_survey_with_rng(survey, message, (g, g2), &sa.vk, &ra.vk, rng)?;
+            if tamper {
+                submission.message = b"altered".to_vec();
+            }
+            ledger.accept(survey.vid, submission, &sa.vk, &ra.vk, g, g2)
+        };
+
+        for bad in dataset.misbehaviour.iter().filter(|bad| bad.survey() == i) {
+            match *bad {
+                Misbehaviour::Outsider { user, .. } => refuse(*bad, submit(&users[user], b"yes", &mut ledger, false)),
+                Misbehaviour::Unregistered { .. } => refuse(*bad, submit(&stranger, b"yes", &mut ledger, false)),
+                Misbehaviour::Tampered { user, .. } => {
+                    let message:&[u8] = &plan.answers.iter().find(|(u, _)| *u == user).unwrap().1;
+                    refuse(*bad, submit(&users[user], message, &mut ledger, true))
+                },
+                _ => ()
+            }
+        }
+        for (user, message) in &plan.answers {
+            submit(&users[*user], message, &mut ledger, false).expect("Honest submission was rejected");
+        }
+        for bad in dataset.misbehaviour.iter().filter(|bad| bad.survey() == i) {
+            if let Misbehaviour::Repeat { user, .. } = *bad {
+                refuse(*bad, submit(&users[user], b"changed my mind", &mut ledger, false));
+            }
+        }
+        ledger.close(survey.vid);
+        for bad in dataset.misbehaviour.iter().filter(|bad| bad.survey() == i) {
+            if let Misbehaviour::Late { user, .. } = *bad {
+                refuse(*bad, submit(&users[user], b"sorry I'm late", &mut ledger, false));
+            }
+        }
+    }
+
+    // Tally what the ledger accepted
+    let accepted:Vec<usize> = surveys.iter().map(|survey| ledger.submissions(survey.vid).len()).collect();
+    let tallies:Vec<Vec<(String, usize)>> = surveys.iter().map(|survey| {
+        let mut tally:Vec<(String, usize)> = Vec::new();
+        for submission in ledger.submissions(survey.vid) {
+            let text = String::from_utf8_lossy(submission.answer()).into_owned();
+            match tally.iter_mut().find(|(answer, _)| *answer == text) {
+                Some((_, count)) => *count += 1,
+                None => tally.push((text, 1))
+            }
+        }
+        tally.sort();
+        tally
+    }).collect();
+    Outcome { accepted, refused, tallies }
+}
+
+fn main() {
+    let seed:u64 = match std::env::args().nth(1) {
+        Some(arg) => arg.parse().expect("The seed must be a number"),
+        None => 0
+    };
+    let mut seed_bytes = [0u8; 32];
+    seed_bytes[..8].copy_from_slice(&seed.to_be_bytes());
+    let rng = &mut ChaChaRng::from_seed(seed_bytes);
+
+    let dataset = generate(rng);
+    println!("Dataset (seed {}): {} users, {} surveys", seed, dataset.users.len(), dataset.surveys.len());
+    let outcome = run(&dataset, rng);
+
+    for (i, plan) in dataset.surveys.iter().enumerate() {
+        println!();
+        println!("{} ({:?}, {} participants)", plan.title, plan.question, plan.participants.len());
+        println!("\t{} responses accepted", outcome.accepted[i]);
+        for (answer, count) in &outcome.tallies[i] {
+            println!("\t{:>3} × {}", count, answer);
+        }
+    }
+    println!();
+    println!("Refused submissions:");
+    for (bad, error) in &outcome.refused {
+        println!("\t{:?}: {}", bad, error);
+    }
+}
+
+
+/*
+ * Integration tests
+ */
+
+// The same seed always produces the same dataset
+#[test]
+fn test_dataset_is_reproducible() {
+    let (a, b) = (generate(&mut ChaChaRng::from_seed([7; 32])), generate(&mut ChaChaRng::from_seed([7; 32])));
+    assert!( a.users == b.users && a.misbehaviour == b.misbehaviour );
+    for (x, y) in a.surveys.iter().zip(b.surveys.iter()) {
+        assert!( x.participants == y.participants && x.answers == y.answers );
+    }
+}
+
+// The ledger holds exactly the honest answers, and every bad submission is refused for its reason
+#[test]
+fn test_full_flow() {
+    let rng = &mut ChaChaRng::from_seed([0; 32]);
+    let dataset = generate(rng);
+    let outcome = run(&dataset, rng);
+
+    for (i, plan) in dataset.surveys.iter().enumerate() {
+        assert!( outcome.accepted[i] == plan.answers.len() );
+        let mut expected:Vec<(String, usize)> = Vec::new();
+        for (_, message) in &plan.answers {
+            let text = String::from_utf8_lossy(message).into_owned();
+            match expected.iter_mut().find(|(answer, _)| *answer == text) {
+                Some((_, count)) => *count += 1,
+                None => expected.push((text, 1))
+            }
+        }
+        expected.sort();
+        assert!( outcome.tallies[i] == expected );
+    }
+    assert!( outcome.refused.len() == dataset.misbehaviour.len() );
+    for (bad, error) in &outcome.refused {
+        assert!( *error == bad.expected() );
+    }
+}
@@ -0,0 +1,131 @@
+#ifndef ANON_SURVEY_H
+#define ANON_SURVEY_H
+
+/* Generated by cbindgen from src/ffi.rs (cargo build --features ffi); do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum AnonSurveyStatus {
+  ANON_SURVEY_STATUS_OK = 0,
+  // A pointer argument was null
+  ANON_SURVEY_STATUS_NULL_ARGUMENT = 1,
+  // An input was not UTF-8, or not the JSON of the expected message
+  ANON_SURVEY_STATUS_INVALID_INPUT = 2,
+  // A sealed wallet or pending registration did not open (wrong passphrase or corrupted)
+  ANON_SURVEY_STATUS_INVALID_KEYSTORE = 3,
+  // The user holds no credential yet
+  ANON_SURVEY_STATUS_UNREGISTERED_USER = 4,
+  // The user is not on the survey's participant list
+  ANON_SURVEY_STATUS_NOT_AUTHORIZED = 5,
+  // The RA's credential did not verify
+  ANON_SURVEY_STATUS_INVALID_SIGNATURE = 6,
+  // Any other protocol error
+  ANON_SURVEY_STATUS_PROTOCOL_ERROR = 7,
+} AnonSurveyStatus;
+
+// Opaque handle to a User
+typedef struct AnonSurveyUser AnonSurveyUser;
+
+// Bytes allocated by the library, to release with anon_survey_buffer_free
+typedef struct AnonSurveyBuffer {
+  uint8_t *data;
+  size_t len;
+} AnonSurveyBuffer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// New user with a random id and PRF key, to release with anon_survey_user_free
+struct AnonSurveyUser *anon_survey_user_new(void);
+
+// Release a user handle (null is ignored)
+//
+// # Safety
+// user is null or a handle from this library that has not been released
+void anon_survey_user_free(struct AnonSurveyUser *user);
+
+// Release a buffer written by this library (an empty one is ignored)
+//
+// # Safety
+// buffer was written by this library and has not been released
+void anon_survey_buffer_free(struct AnonSurveyBuffer buffer);
+
+// Registered user from a wallet sealed by anon_survey_complete_registration or anon_survey_user_seal_wallet
+//
+// # Safety
+// wallet points to wallet_len readable bytes, passphrase is a NUL-terminated string, and out is writable
+enum AnonSurveyStatus anon_survey_user_open_wallet(const uint8_t *wallet,
+                                                   size_t wallet_len,
+                                                   const char *passphrase,
+                                                   struct AnonSurveyUser **out);
+
+// The user's credential sealed under passphrase
+//
+// # Safety
+// user is a live handle, passphrase is a NUL-terminated string, and out is writable
+enum AnonSurveyStatus anon_survey_user_seal_wallet(const struct AnonSurveyUser *user,
+                                                   const char *passphrase,
+                                                   struct AnonSurveyBuffer *out);
+
+// The id (32 bytes) to give an SA for its participant lists
+//
+// # Safety
+// user is a live handle and out is writable
+enum AnonSurveyStatus anon_survey_user_id(const struct AnonSurveyUser *user,
+                                          struct AnonSurveyBuffer *out);
+
+// RegUser: the JSON request to send to the RA, and the secrets behind it sealed under passphrase
+//
+// # Safety
+// Every (pointer, length) pair is readable, passphrase is a NUL-terminated string, and the outs are writable
+enum AnonSurveyStatus anon_survey_request_registration(const struct AnonSurveyUser *user,
+                                                       const uint8_t *params,
+                                                       size_t params_len,
+                                                       const uint8_t *vk_ra,
+                                                       size_t vk_ra_len,
+                                                       const char *passphrase,
+                                                       struct AnonSurveyBuffer *out_request,
+                                                       struct AnonSurveyBuffer *out_pending);
+
+// Unblind the RA's answer (JSON BlindedCredential) with the sealed pending registration, into a sealed wallet
+//
+// # Safety
+// Every (pointer, length) pair is readable, passphrase is a NUL-terminated string, and out is writable
+enum AnonSurveyStatus anon_survey_complete_registration(const uint8_t *pending,
+                                                        size_t pending_len,
+                                                        const uint8_t *credential,
+                                                        size_t credential_len,
+                                                        const uint8_t *params,
+                                                        size_t params_len,
+                                                        const uint8_t *vk_ra,
+                                                        size_t vk_ra_len,
+                                                        const char *passphrase,
+                                                        struct AnonSurveyBuffer *out);
+
+// SubmitSurvey: the JSON Submission answering survey (JSON) with message
+//
+// # Safety
+// user is a live handle, every (pointer, length) pair is readable, and out is writable
+enum AnonSurveyStatus anon_survey_submit(const struct AnonSurveyUser *user,
+                                         const uint8_t *survey,
+                                         size_t survey_len,
+                                         const uint8_t *message,
+                                         size_t message_len,
+                                         const uint8_t *params,
+                                         size_t params_len,
+                                         const uint8_t *vk_sa,
+                                         size_t vk_sa_len,
+                                         const uint8_t *vk_ra,
+                                         size_t vk_ra_len,
+                                         struct AnonSurveyBuffer *out);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* ANON_SURVEY_H */
@@ -0,0 +1,40 @@
+-- Tables of PostgresStorage (see src/storage/postgres.rs). Keys are the canonical encodings of
+-- S', vid and H(id) (see src/encoding.rs), records are the JSON of the serde feature
+
+CREATE TABLE users (
+    seq BIGSERIAL PRIMARY KEY,
+    commitment BYTEA NOT NULL UNIQUE,
+    record JSONB NOT NULL
+);
+
+CREATE TABLE members (
+    id_hash BYTEA PRIMARY KEY
+);
+
+CREATE TABLE surveys (
+    seq BIGSERIAL PRIMARY KEY,
+    vid BYTEA NOT NULL UNIQUE,
+    key_version INTEGER NOT NULL,
+    survey JSONB NOT NULL
+);
+
+-- Written in the same transaction as the survey, so no instance sees a partial participant list
+CREATE TABLE survey_participants (
+    vid BYTEA NOT NULL REFERENCES surveys (vid) ON DELETE CASCADE,
+    id BYTEA NOT NULL,
+    PRIMARY KEY (vid, id)
+);
+
+CREATE TABLE submissions (
+    seq BIGSERIAL PRIMARY KEY,
+    vid BYTEA NOT NULL,
+    token BYTEA NOT NULL,
+    submission JSONB NOT NULL,
+    -- One response per one-time token, whichever SA instance accepted it
+    CONSTRAINT one_submission_per_token UNIQUE (vid, token)
+);
+
+CREATE TABLE revocations (
+    seq BIGSERIAL PRIMARY KEY,
+    id_hash BYTEA NOT NULL UNIQUE
+);
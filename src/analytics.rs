@@ -0,0 +1,175 @@
+use crate::error::SurveyError;
+use crate::definition::{SurveyDefinition, Question, Answer, Response};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/*
+ * ----------------------------------------------
+ * |    TALLIES                                 |
+ * ----------------------------------------------
+ *
+ * Once a survey is closed, the SA tallies the accepted responses against the survey's definition:
+ * for every question, how often each option, scale point or (for free text) distinct answer was
+ * given, and what share of the responses that is. Tallies are built from Response objects alone,
+ * i.e. the answers in the submitted messages -- tokens, proofs and anything else that could link a
+ * response to a submission are never looked at -- and export as CSV or JSON:
+ *
+ *      let responses = ledger.submissions(vid).iter().filter_map(|s| Response::from_bytes(s.answer()));
+ *      let tally = Tally::new(&definition, &responses.collect::<Vec<Response>>())?;
+ *      fs::write("tally.csv", tally.to_csv())?;
+*/
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct AnswerCount {
+    pub answer: String,
+    pub count: usize,
+    // Share of the responses, from 0 to 100
+    pub percent: f64
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct QuestionTally {
+    pub prompt: String,
+    // Every option and scale point in order, or the distinct free-text answers, most frequent first
+    pub counts: Vec<AnswerCount>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Tally {
+    pub title: String,
+    pub responses: usize,
+    pub questions: Vec<QuestionTally>
+}
+
+impl Tally {
+
+    // Tally responses to the survey defined by definition. Every response must be valid for it
+    pub fn new(definition: &SurveyDefinition, responses: &[Response]) -> Result<Self, SurveyError> {
+        for response in responses {
+            (*definition).validate(response)?;
+        }
+        let questions:Vec<QuestionTally> = (*definition).questions.iter().enumerate().map(|(i, question)| {
+            let answers = responses.iter().map(|response| &response.answers[i]);
+            let counts:Vec<(String, usize)> = match question {
+                Question::MultipleChoice { options, .. } => options.iter().enumerate()
+                    .map(|(choice, option)| (option.clone(), answers.clone().filter(|answer| **answer == Answer::Choice(choice)).count()))
+                    .collect(),
+                Question::Likert { points, .. } => (1..=*points)
+                    .map(|point| (point.to_string(), answers.clone().filter(|answer| **answer == Answer::Scale(point)).count()))
+                    .collect(),
+                Question::FreeText { .. } => {
+                    let mut counts:Vec<(String, usize)> = Vec::new();
+                    for answer in answers {
+                        if let Answer::Text(text) = answer {
+                            match counts.iter_mut().find(|(seen, _)| seen == text) {
+                                Some((_, count)) => *count += 1,
+                                None => counts.push((text.clone(), 1))
+                            }
+                        }
+                    }
+                    counts.sort_by(|(a, m), (b, n)| n.cmp(m).then(a.cmp(b)));
+                    counts
+                }
+            };
+            QuestionTally {
+                prompt: prompt(question).clone(),
+                counts: counts.into_iter().map(|(answer, count)| AnswerCount { answer, count, percent: percent(count, responses.len()) }).collect()
+            }
+        }).collect();
+        Ok(Tally { title: (*definition).title.clone(), responses: responses.len(), questions })
+    }
+
+    // One row per question and answer: question,answer,count,percent
+    pub fn to_csv(&self) -> String {
+        let mut csv:String = String::from("question,answer,count,percent\n");
+        for question in &(*self).questions {
+            for count in &question.counts {
+                csv.push_str(&format!("{},{},{},{:.1}\n", csv_field(&question.prompt), csv_field(&count.answer), count.count, count.percent));
+            }
+        }
+        csv
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Tallies always serialize")
+    }
+}
+
+fn prompt(question: &Question) -> &String {
+    match question {
+        Question::MultipleChoice { prompt, .. } | Question::FreeText { prompt, .. } | Question::Likert { prompt, .. } => prompt
+    }
+}
+
+fn percent(count: usize, total: usize) -> f64 {
+    match total {
+        0 => 0.0,
+        _ => 100.0 * count as f64 / total as f64
+    }
+}
+
+// Quote fields with separators, quotes or line breaks (RFC 4180)
+fn csv_field(field: &str) -> String {
+    match field.contains(&[',', '"', '\n', '\r'][..]) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => String::from(field)
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use crate::users::SurveyAuthority;
+#[cfg(test)]
+use crate::params::SystemParams;
+
+#[cfg(test)]
+fn sample_tally() -> Tally {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let sa = SurveyAuthority::new(&params);
+    let definition = sa.define_survey("Course feedback", "", vec![
+        Question::MultipleChoice { prompt: String::from("Track"), options: vec![String::from("Systems"), String::from("Theory, mostly")] },
+        Question::Likert { prompt: String::from("Organized"), points: 3 },
+        Question::FreeText { prompt: String::from("Comments"), max_len: 100 }
+    ], g, g2).expect("Definition was refused");
+    let response = |choice: usize, point: u8, text: &str| Response { answers: vec![Answer::Choice(choice), Answer::Scale(point), Answer::Text(String::from(text))] };
+    let responses = vec![response(0, 3, "More \"labs\""), response(0, 2, ""), response(1, 3, "More \"labs\""), response(0, 3, "")];
+    assert!( Tally::new(&definition, &[response(2, 3, "")]).is_err() );
+    Tally::new(&definition, &responses).expect("Responses are valid")
+}
+
+// Every option and scale point is counted, and free-text answers are grouped
+#[test]
+fn test_tally() {
+    let tally = sample_tally();
+    assert!( tally.responses == 4 && tally.questions.len() == 3 );
+    let counts = |i: usize| tally.questions[i].counts.iter().map(|count| (count.answer.as_str(), count.count)).collect::<Vec<(&str, usize)>>();
+    assert!( counts(0) == vec![("Systems", 3), ("Theory, mostly", 1)] );
+    assert!( counts(1) == vec![("1", 0), ("2", 1), ("3", 3)] );
+    assert!( counts(2) == vec![("", 2), ("More \"labs\"", 2)] );
+    assert!( tally.questions[0].counts[0].percent == 75.0 );
+}
+
+// Exports quote what needs quoting, and JSON carries the same numbers
+#[test]
+fn test_tally_export() {
+    let tally = sample_tally();
+    let csv = tally.to_csv();
+    assert!( csv.starts_with("question,answer,count,percent\nTrack,Systems,3,75.0\nTrack,\"Theory, mostly\",1,25.0\n") );
+    assert!( csv.contains("Comments,\"More \"\"labs\"\"\",2,50.0\n") && csv.lines().count() == 1 + 2 + 3 + 2 );
+
+    #[cfg(feature = "serde")]
+    {
+        let json:serde_json::Value = serde_json::from_str(&tally.to_json()).unwrap();
+        assert!( json["responses"] == 4 && json["questions"][1]["counts"][2]["count"] == 3 );
+        assert!( json["questions"][2]["counts"][1]["answer"] == "More \"labs\"" );
+    }
+}
@@ -0,0 +1,298 @@
+extern crate tbn;
+extern crate sha2;
+
+use std::time::SystemTime;
+use sha2::{Sha256, Digest};
+use tbn::{G1, G2};
+
+use crate::users::{VerificationKey, KeyHolder};
+use crate::proofs::{Proof, verify_gt};
+use crate::encoding::{G1_BYTES, g1_to_bytes, g1_from_bytes};
+use crate::policy::unix_time;
+use crate::error::SurveyError;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/*
+ * ----------------------------------------------
+ * |    RA AUDIT LOG                            |
+ * ----------------------------------------------
+ *
+ * The RA appends an entry to its audit log every time a registration is linked to a user, or retired:
+ *      - Registration: a credential was issued on a new registration
+ *      - Reidentification: a user re-identified under a new id (User::re_identify), and their old
+ *        registration left the anonymity set
+ *      - Revocation: a registration was taken out of the anonymity set
+ *      - Erasure: a registration record was erased on the user's request (erase_user_data). The
+ *        entry, like the earlier ones naming the same S', stays in the log
+ * Entries name the registration by its commitment S', never by the id or its hash, so the log can
+ * be handed to auditors without handing them the anonymity set.
+ *
+ * Each entry is chained to the one before it:
+ *
+ *      digest_i = SHA-256(AUDIT_DOMAIN ‖ digest_(i-1) ‖ seq ‖ time ‖ action ‖ S'),   digest_0 = 0^32
+ *
+ * and the RA signs the latest digest into an AuditHead (a Schnorr signature in Gt, see proofs.rs).
+ * An auditor takes the exported transcript and a signed head, and verify_transcript recomputes the
+ * chain and checks it ends in that head:
+ *
+ *      let entries = verify_transcript(&ra.audit_log().transcript(), &ra.audit_head(), &ra.vk, g, g2)?;
+ *      assert!( earlier_head.extended_by(&entries) );
+ *
+ * Keeping the heads seen on earlier audits (extended_by) is what catches a rewritten log: the RA
+ * can always sign a fresh chain, but not one that still passes through a head it signed before.
+*/
+
+pub const AUDIT_DOMAIN:&[u8] = b"anon-survey/audit/v1";
+
+// Bytes of one entry in a transcript: seq ‖ time ‖ action ‖ S'
+const ENTRY_BYTES:usize = 8 + 8 + 1 + G1_BYTES;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AuditAction {
+    Registration = 1,
+    Reidentification = 2,
+    Revocation = 3,
+    Erasure = 4
+}
+
+impl AuditAction {
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(AuditAction::Registration),
+            2 => Some(AuditAction::Reidentification),
+            3 => Some(AuditAction::Revocation),
+            4 => Some(AuditAction::Erasure),
+            _ => None
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AuditEntry {
+    // Position in the log, from 0
+    pub seq: u64,
+    // Seconds since the Unix epoch
+    pub time: u64,
+    pub action: AuditAction,
+    // Commitment S' of the registration concerned
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g1"))]
+    pub commitment: G1,
+    // Chain digest up to and including this entry
+    pub digest: [u8; 32]
+}
+
+// Length and latest digest of the log, signed by the RA
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AuditHead {
+    pub length: u64,
+    pub digest: [u8; 32],
+    // Signature (c, z) under vk_RA
+    pub signature: Proof
+}
+
+impl AuditHead {
+
+    // Sign the head of log with the RA's keys (see RegistrationAuthority::audit_head)
+    pub(crate) fn sign(log: &AuditLog, ra: &impl KeyHolder, g: G1, g2: G2) -> Self {
+        let (length, digest):(u64, [u8; 32]) = ((*log).entries.len() as u64, (*log).head_digest());
+        let signature:Proof = (*ra).sign_body(AUDIT_DOMAIN, g, g2, &head_body(length, &digest));
+        AuditHead { length, digest, signature }
+    }
+
+    // Whether the head was signed by the RA with key vk_ra
+    pub fn verify(&self, vk_ra: &VerificationKey, g: G1, g2: G2) -> bool {
+        verify_gt(AUDIT_DOMAIN, g, g2, (*vk_ra).pk, &head_body((*self).length, &(*self).digest), &(*self).signature)
+    }
+
+    // Whether the verified entries of a later audit pass through this head, i.e. the log was only
+    // appended to since
+    pub fn extended_by(&self, entries: &[AuditEntry]) -> bool {
+        match (*self).length {
+            0 => true,
+            length => entries.get(length as usize - 1).is_some_and(|entry| entry.digest == (*self).digest)
+        }
+    }
+}
+
+// Append-only log kept by the RA
+#[derive(Clone, Debug, Default)]
+pub struct AuditLog {
+    entries: Vec<AuditEntry>
+}
+
+impl AuditLog {
+
+    pub fn new() -> Self {
+        AuditLog { entries: Vec::new() }
+    }
+
+    pub(crate) fn append(&mut self, action: AuditAction, commitment: G1) {
+        let (seq, time):(u64, u64) = ((*self).entries.len() as u64, unix_time(SystemTime::now()));
+        let digest:[u8; 32] = chain_digest(&(*self).head_digest(), seq, time, action, commitment);
+        (*self).entries.push(AuditEntry { seq, time, action, commitment, digest });
+    }
+
+    pub fn entries(&self) -> &[AuditEntry] {
+        &(*self).entries
+    }
+
+    // Digest of the last entry (all zeroes for an empty log)
+    pub fn head_digest(&self) -> [u8; 32] {
+        (*self).entries.last().map_or([0u8; 32], |entry| entry.digest)
+    }
+
+    // Export the log for auditors: AUDIT_DOMAIN ‖ n ‖ (seq ‖ time ‖ action ‖ S')_1..n.
+    // Digests are left out, the auditor recomputes them
+    pub fn transcript(&self) -> Vec<u8> {
+        let mut transcript:Vec<u8> = AUDIT_DOMAIN.to_vec();
+        transcript.extend_from_slice(&((*self).entries.len() as u64).to_be_bytes());
+        for entry in &(*self).entries {
+            transcript.extend_from_slice(&entry.seq.to_be_bytes());
+            transcript.extend_from_slice(&entry.time.to_be_bytes());
+            transcript.push(entry.action as u8);
+            transcript.extend(g1_to_bytes(entry.commitment));
+        }
+        transcript
+    }
+
+    // Rebuild a log from its transcript, recomputing the digests (e.g. when the RA is restored from
+    // a snapshot). Fails with InvalidAuditLog if the transcript is malformed or out of sequence
+    pub(crate) fn from_transcript(transcript: &[u8]) -> Result<Self, SurveyError> {
+        let header_len:usize = AUDIT_DOMAIN.len() + 8;
+        if transcript.len() < header_len || &transcript[..AUDIT_DOMAIN.len()] != AUDIT_DOMAIN {
+            return Err(SurveyError::InvalidAuditLog);
+        }
+        let mut count = [0u8; 8];
+        count.copy_from_slice(&transcript[AUDIT_DOMAIN.len()..header_len]);
+        let count:u64 = u64::from_be_bytes(count);
+        let body:&[u8] = &transcript[header_len..];
+        if body.len() as u64 != count * ENTRY_BYTES as u64 {
+            return Err(SurveyError::InvalidAuditLog);
+        }
+
+        let mut entries:Vec<AuditEntry> = Vec::new();
+        let mut previous = [0u8; 32];
+        for (expected_seq, bytes) in body.chunks(ENTRY_BYTES).enumerate() {
+            let mut field = [0u8; 8];
+            field.copy_from_slice(&bytes[0..8]);
+            let seq:u64 = u64::from_be_bytes(field);
+            field.copy_from_slice(&bytes[8..16]);
+            let time:u64 = u64::from_be_bytes(field);
+            let action = AuditAction::from_byte(bytes[16]).ok_or(SurveyError::InvalidAuditLog)?;
+            let commitment:G1 = g1_from_bytes(&bytes[17..]).ok_or(SurveyError::InvalidAuditLog)?;
+            if seq != expected_seq as u64 {
+                return Err(SurveyError::InvalidAuditLog);
+            }
+            let digest:[u8; 32] = chain_digest(&previous, seq, time, action, commitment);
+            entries.push(AuditEntry { seq, time, action, commitment, digest });
+            previous = digest;
+        }
+        Ok(AuditLog { entries })
+    }
+}
+
+/* Recompute the chain of an exported transcript and check it ends in head, signed under vk_ra */
+// Returns the entries with their digests. Fails with InvalidAuditLog if the transcript is
+// malformed, out of sequence, or does not hash to the signed head
+pub fn verify_transcript(transcript: &[u8], head: &AuditHead, vk_ra: &VerificationKey, g: G1, g2: G2) -> Result<Vec<AuditEntry>, SurveyError> {
+    if !(*head).verify(vk_ra, g, g2) {
+        return Err(SurveyError::InvalidSignature);
+    }
+    let log:AuditLog = AuditLog::from_transcript(transcript)?;
+    if log.entries.len() as u64 != (*head).length || log.head_digest() != (*head).digest {
+        return Err(SurveyError::InvalidAuditLog);
+    }
+    Ok(log.entries)
+}
+
+
+fn chain_digest(previous: &[u8; 32], seq: u64, time: u64, action: AuditAction, commitment: G1) -> [u8; 32] {
+    let mut input:Vec<u8> = AUDIT_DOMAIN.to_vec();
+    input.extend_from_slice(previous);
+    input.extend_from_slice(&seq.to_be_bytes());
+    input.extend_from_slice(&time.to_be_bytes());
+    input.push(action as u8);
+    input.extend(g1_to_bytes(commitment));
+    let mut digest = [0u8; 32];
+    digest.copy_from_slice(&Sha256::digest(&input));
+    digest
+}
+
+fn head_body(length: u64, digest: &[u8; 32]) -> Vec<u8> {
+    let mut body:Vec<u8> = length.to_be_bytes().to_vec();
+    body.extend_from_slice(digest);
+    body
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use crate::users::{User, RegistrationAuthority};
+#[cfg(test)]
+use crate::params::SystemParams;
+#[cfg(test)]
+use tbn::Group;
+
+// Registrations, re-identifications and revocations are logged by commitment, and the exported
+// transcript verifies against the signed head
+#[test]
+fn test_audit_log() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let (mut alice, mut bob) = (User::new(), User::new());
+    alice.reg_user(&mut ra).expect("Registration failed");
+    bob.reg_user(&mut ra).expect("Registration failed");
+    let first_head = ra.audit_head();
+
+    let old_commitment:G1 = alice.registration_commitment().expect("Alice is registered");
+    alice.re_identify(&mut ra).expect("Re-identification failed");
+    ra.revoke_batch(&[bob.registration_commitment().expect("Bob is registered")]).expect("Revocation failed");
+
+    let actions:Vec<AuditAction> = ra.audit_log().entries().iter().map(|entry| entry.action).collect();
+    assert!( actions == vec![AuditAction::Registration, AuditAction::Registration, AuditAction::Registration,
+                             AuditAction::Reidentification, AuditAction::Revocation] );
+    assert!( ra.audit_log().entries()[2].commitment == alice.registration_commitment().unwrap() && ra.audit_log().entries()[3].commitment == old_commitment );
+
+    let head = ra.audit_head();
+    let entries = verify_transcript(&ra.audit_log().transcript(), &head, &ra.vk, g, g2).expect("Audit log does not verify");
+    assert!( entries == ra.audit_log().entries() && first_head.extended_by(&entries) && head.extended_by(&entries) );
+}
+
+// A rewritten, truncated or re-signed log is caught
+#[test]
+fn test_audit_log_tampering() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    for _ in 0..3 {
+        User::new().reg_user(&mut ra).expect("Registration failed");
+    }
+    let head = ra.audit_head();
+    let transcript:Vec<u8> = ra.audit_log().transcript();
+
+    // Change the action of the second entry
+    let mut rewritten:Vec<u8> = transcript.clone();
+    rewritten[AUDIT_DOMAIN.len() + 8 + ENTRY_BYTES + 16] = AuditAction::Reidentification as u8;
+    assert!( verify_transcript(&rewritten, &head, &ra.vk, g, g2) == Err(SurveyError::InvalidAuditLog) );
+    // Drop the last entry
+    assert!( verify_transcript(&transcript[..transcript.len() - ENTRY_BYTES], &head, &ra.vk, g, g2) == Err(SurveyError::InvalidAuditLog) );
+    // A head signed by another RA
+    let other = RegistrationAuthority::new(&params);
+    assert!( verify_transcript(&transcript, &head, &other.vk, g, g2) == Err(SurveyError::InvalidSignature) );
+
+    // A fresh chain the RA signs later does not pass through the head it signed before
+    let mut forged = AuditLog::new();
+    forged.append(AuditAction::Registration, G1::random(&mut rand::thread_rng()));
+    let forged_head = AuditHead::sign(&forged, &ra, g, g2);
+    let entries = verify_transcript(&forged.transcript(), &forged_head, &ra.vk, g, g2).expect("Forged log is well-formed");
+    assert!( !head.extended_by(&entries) );
+}
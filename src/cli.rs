@@ -0,0 +1,611 @@
+extern crate anon_survey;
+extern crate tbn;
+extern crate clap;
+extern crate serde;
+extern crate serde_yaml;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, Subcommand, Args};
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+use tbn::{Fr, G1, G2};
+
+use anon_survey::{User, SurveyAuthority, RegistrationAuthority, RegistrationRequest, BlindedCredential, VerificationKey};
+use anon_survey::{Survey, SurveyDescription, Submission, SubmissionLedger, Wallet, PendingRegistration, ParamsManifest, SurveyError, SystemParams};
+use anon_survey::encoding::{fr_to_bytes, fr_from_bytes};
+use anon_survey::padding::PaddingPolicy;
+use anon_survey::panel::Panel;
+use anon_survey::trust::export_graphviz;
+
+use crate::exit::Failure;
+use crate::{repl, scenario};
+
+/*
+ * ----------------------------------------------
+ * |    COMMAND LINE                            |
+ * ----------------------------------------------
+ *
+ * Every party can run as its own process, exchanging protocol messages as YAML files:
+ *
+ *      anon_survey params init --out params.yaml
+ *      anon_survey ra init --params params.yaml --keystore ra.keys --public ra.yaml
+ *      anon_survey sa init --ra ra.yaml --keystore sa.keys --public sa.yaml
+ *      anon_survey user register --ra ra.yaml --pending user.pending --out request.yaml
+ *      anon_survey ra register --keystore ra.keys --request request.yaml --out credential.yaml
+ *      anon_survey user register --ra ra.yaml --pending user.pending --credential credential.yaml --wallet user.wallet
+ *      anon_survey sa create-survey --ra ra.yaml --keystore sa.keys --participants ids.txt --out survey.yaml
+ *      anon_survey user submit --ra ra.yaml --sa sa.yaml --survey survey.yaml --wallet user.wallet --message yes --out answer.yaml
+ *      anon_survey sa verify --ra ra.yaml --sa sa.yaml --survey survey.yaml answer.yaml ...
+ *
+ * `params init` is the setup ceremony: it writes the system parameters (the curve's standard
+ * generators, or with --tag generators hashed from a deployment tag) for `ra init` to build on.
+ * Without --params, `ra init` uses the standard ones. Parameters are validated whenever they are
+ * read (see params.rs).
+ *
+ * `params freeze` has the RA sign the generators, its key, and the SA keys it vouches for into a
+ * manifest (see manifest.rs). Given --manifest (or $ANON_SURVEY_MANIFEST), the sa and user
+ * subcommands refuse to run unless the RA and SA files match it.
+ *
+ * With --title, `sa create-survey` derives the survey id from the title, its key and a fresh
+ * nonce, and writes them to --description for publishing next to the survey; `user submit
+ * --description` refuses a survey whose id was not derived from it (see hash.rs).
+ *
+ * `ra revoke-batch` works on a snapshot of the RA (RegistrationAuthority::snapshot) and a panel
+ * saved by its operator (see panel.rs): the departed members listed in --csv are revoked together,
+ * the snapshot is rewritten, and the signed RevocationBatch goes to --out for the SAs.
+ *
+ * `user submit` pads the answer to a fixed bucket size (see padding.rs), so a submission file's
+ * size does not give away how long the answer is. The buckets come with the survey, from `sa
+ * create-survey --buckets`, so every participant pads alike. `sa verify` strips the padding again.
+ *
+ * Secrets (authority keys, pending registrations, wallets) are only written sealed under the
+ * passphrase from --passphrase or $ANON_SURVEY_PASSPHRASE. The RA's public file carries the
+ * generators (g, g2) every other party builds on. Participant lists hold one hex-encoded id per
+ * line, as printed by `user register` when it completes. Without a subcommand, the demo runs.
+*/
+
+#[derive(Parser, Debug)]
+#[command(name = "anon_survey", about = "ANONIZE anonymous surveys on BN curves")]
+pub struct Cli {
+    #[arg(long, global = true, env = "ANON_SURVEY_MANIFEST", help = "Refuse parameters not frozen in this manifest")]
+    pub manifest: Option<PathBuf>,
+    #[command(subcommand)]
+    pub command: Option<Command>
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    #[command(about = "Explore the protocol interactively")]
+    Repl,
+    #[command(about = "Run a scripted multi-party flow and check its outcomes")]
+    Scenario { file: PathBuf },
+    #[command(about = "Print a machine-readable description of the protocol (YAML)")]
+    Spec,
+    #[command(subcommand, about = "Registration Authority")]
+    Ra(RaCommand),
+    #[command(subcommand, about = "Survey Authority")]
+    Sa(SaCommand),
+    #[command(subcommand, about = "Survey participant")]
+    User(UserCommand),
+    #[command(subcommand, about = "Signed manifest of the deployment's parameters")]
+    Params(ParamsCommand)
+}
+
+#[derive(Args, Debug)]
+pub struct Passphrase {
+    #[arg(long, env = "ANON_SURVEY_PASSPHRASE", hide_env_values = true, help = "Passphrase sealing the secret files")]
+    pub passphrase: String
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RaCommand {
+    #[command(about = "Generate the RA's keys")]
+    Init {
+        #[arg(long, help = "System parameters from params init (default: the standard generators)")]
+        params: Option<PathBuf>,
+        #[arg(long, help = "Where to write the sealed keys")]
+        keystore: PathBuf,
+        #[arg(long, help = "Where to write (g, g2) and vk_RA")]
+        public: PathBuf,
+        #[command(flatten)]
+        passphrase: Passphrase
+    },
+    #[command(about = "Issue a blinded credential for a user's registration request")]
+    Register {
+        #[arg(long)]
+        keystore: PathBuf,
+        #[arg(long)]
+        request: PathBuf,
+        #[arg(long, help = "Where to write the blinded credential")]
+        out: PathBuf,
+        #[command(flatten)]
+        passphrase: Passphrase
+    },
+    #[command(about = "Revoke the panel members listed in an HR feed, in one signed batch")]
+    RevokeBatch {
+        #[arg(long, help = "The RA's snapshot, updated in place")]
+        snapshot: PathBuf,
+        #[arg(long, help = "The panel the members were invited from")]
+        panel: PathBuf,
+        #[arg(long, help = "Departed members, one identifier per line (further comma-separated columns are ignored)")]
+        csv: PathBuf,
+        #[arg(long, help = "Where to write the signed revocation batch for the SAs")]
+        out: PathBuf,
+        #[command(flatten)]
+        passphrase: Passphrase
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SaCommand {
+    #[command(about = "Generate the SA's keys")]
+    Init {
+        #[arg(long, help = "The RA's public file")]
+        ra: PathBuf,
+        #[arg(long, help = "Where to write the sealed keys")]
+        keystore: PathBuf,
+        #[arg(long, help = "Where to write vk_SA")]
+        public: PathBuf,
+        #[command(flatten)]
+        passphrase: Passphrase
+    },
+    #[command(about = "Sign a survey for a list of participant ids")]
+    CreateSurvey {
+        #[arg(long)]
+        ra: PathBuf,
+        #[arg(long)]
+        keystore: PathBuf,
+        #[arg(long, help = "One hex-encoded id per line")]
+        participants: PathBuf,
+        #[arg(long, help = "Where to write the published survey")]
+        out: PathBuf,
+        #[arg(long, requires = "description", help = "Derive the survey id from this title (and a fresh nonce)")]
+        title: Option<String>,
+        #[arg(long, requires = "title", help = "Where to write the description to publish with the survey")]
+        description: Option<PathBuf>,
+        #[arg(long, value_delimiter = ',', help = "Bucket sizes every participant pads their answer to [default: 64,256,1024,4096]")]
+        buckets: Vec<usize>,
+        #[command(flatten)]
+        passphrase: Passphrase
+    },
+    #[command(about = "Check submissions, accepting each one-time token once")]
+    Verify {
+        #[arg(long)]
+        ra: PathBuf,
+        #[arg(long, help = "The SA's public file")]
+        sa: PathBuf,
+        #[arg(long)]
+        survey: PathBuf,
+        #[arg(required = true)]
+        submissions: Vec<PathBuf>
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum UserCommand {
+    #[command(about = "Request registration, or complete it with the RA's credential")]
+    Register {
+        #[arg(long)]
+        ra: PathBuf,
+        #[arg(long, help = "Sealed state kept between request and credential")]
+        pending: PathBuf,
+        #[arg(long, required_unless_present = "credential", conflicts_with = "credential", help = "Where to write the request")]
+        out: Option<PathBuf>,
+        #[arg(long, requires = "wallet", help = "The RA's blinded credential")]
+        credential: Option<PathBuf>,
+        #[arg(long, help = "Where to write the sealed wallet")]
+        wallet: Option<PathBuf>,
+        #[command(flatten)]
+        passphrase: Passphrase
+    },
+    #[command(about = "Answer a survey")]
+    Submit {
+        #[arg(long)]
+        ra: PathBuf,
+        #[arg(long)]
+        sa: PathBuf,
+        #[arg(long)]
+        survey: PathBuf,
+        #[arg(long, help = "Refuse the survey unless its id was derived from this description")]
+        description: Option<PathBuf>,
+        #[arg(long)]
+        wallet: PathBuf,
+        #[arg(long)]
+        message: String,
+        #[arg(long, help = "Where to write the submission")]
+        out: PathBuf,
+        #[command(flatten)]
+        passphrase: Passphrase
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ParamsCommand {
+    #[command(about = "Generate the system parameters every party builds on")]
+    Init {
+        #[arg(long, help = "Hash the generator from this deployment tag instead of using the standard one")]
+        tag: Option<String>,
+        #[arg(long, help = "Where to write the parameters")]
+        out: PathBuf
+    },
+    #[command(about = "Sign the RA's parameters and the accepted SA keys into a manifest")]
+    Freeze {
+        #[arg(long, help = "The RA's sealed keys")]
+        keystore: PathBuf,
+        #[arg(long = "sa", help = "Public file of an SA clients should accept (repeatable)")]
+        sa: Vec<PathBuf>,
+        #[arg(long, help = "Where to write the manifest")]
+        out: PathBuf,
+        #[command(flatten)]
+        passphrase: Passphrase
+    },
+    #[command(about = "Check RA and SA files against the manifest given with --manifest")]
+    Check {
+        #[arg(long)]
+        ra: PathBuf,
+        #[arg(long)]
+        sa: Option<PathBuf>
+    },
+    #[command(about = "Print the manifest's trust graph (RA and accepted SAs) as Graphviz DOT")]
+    Graph {
+        #[arg(long, help = "The RA's public file, to check the manifest against first")]
+        ra: PathBuf
+    }
+}
+
+// What the RA publishes: the generators and its verification key
+#[derive(Serialize, Deserialize)]
+struct RaPublic {
+    #[serde(with = "anon_survey::encoding::g1")]
+    g: G1,
+    #[serde(with = "anon_survey::encoding::g2")]
+    g2: G2,
+    vk: VerificationKey
+}
+
+type CliResult<T> = Result<T, (Failure, String)>;
+
+// Run a subcommand, printing what an operator needs to know along the way
+pub fn run(command: Command, manifest: Option<PathBuf>) -> CliResult<()> {
+    let manifest:Option<ParamsManifest> = match manifest {
+        Some(path) => Some(read_yaml(&path)?),
+        None => None
+    };
+    let trusted = Trusted { manifest };
+    match command {
+        Command::Repl => repl::run(),
+        Command::Scenario { file } => {
+            let yaml = fs::read_to_string(&file).map_err(|e| (Failure::NoInput, format!("{}: {}", file.display(), e)))?;
+            let scenario = scenario::Scenario::from_yaml(&yaml).map_err(|msg| (Failure::InvalidInput, msg))?;
+            scenario.run().map_err(|msg| (Failure::CheckFailed, msg))?;
+        },
+        Command::Spec => {
+            let yaml = serde_yaml::to_string(&anon_survey::spec::protocol_spec())
+                .map_err(|e| (Failure::ProtocolError, format!("spec: {}", e)))?;
+            print!("{}", yaml);
+        },
+        Command::Ra(command) => run_ra(command)?,
+        Command::Sa(command) => run_sa(command, &trusted)?,
+        Command::User(command) => run_user(command, &trusted)?,
+        Command::Params(command) => run_params(command, &trusted)?
+    }
+    Ok(())
+}
+
+fn run_ra(command: RaCommand) -> CliResult<()> {
+    match command {
+        RaCommand::Init { params, keystore, public, passphrase } => {
+            let params:SystemParams = match params {
+                Some(path) => read_yaml(&path)?,
+                None => SystemParams::standard()
+            };
+            let ra = RegistrationAuthority::new(&params);
+            write(&keystore, &ra.export_keys(&passphrase.passphrase))?;
+            write_yaml(&public, &RaPublic { g: params.g, g2: params.g2, vk: ra.vk })?;
+        },
+        RaCommand::Register { keystore, request, out, passphrase } => {
+            let mut ra = RegistrationAuthority::from_keys(&read(&keystore)?, &passphrase.passphrase).map_err(refused("ra register"))?;
+            let request:RegistrationRequest = read_yaml(&request)?;
+            let credential = ra.register_request(&request).map_err(refused("ra register"))?;
+            write_yaml(&out, &credential)?;
+            println!("Registered {}", hex_id(request.id));
+        },
+        RaCommand::RevokeBatch { snapshot, panel, csv, out, passphrase } => {
+            let mut ra = RegistrationAuthority::restore(&read(&snapshot)?, &passphrase.passphrase).map_err(refused("ra revoke-batch"))?;
+            let panel:Panel = read_yaml(&panel)?;
+            let feed = String::from_utf8(read(&csv)?).map_err(|e| (Failure::InvalidInput, format!("{}: {}", csv.display(), e)))?;
+            let (batch, unknown) = panel.revoke(&feed, &mut ra).map_err(refused("ra revoke-batch"))?;
+            write(&snapshot, &ra.snapshot(&passphrase.passphrase))?;
+            write_yaml(&out, &batch)?;
+            for identifier in &unknown {
+                println!("Not on panel {}: {}", panel.name, identifier);
+            }
+            println!("Revoked {} registrations", batch.id_hashes.len());
+        }
+    }
+    Ok(())
+}
+
+fn run_sa(command: SaCommand, trusted: &Trusted) -> CliResult<()> {
+    match command {
+        SaCommand::Init { ra, keystore, public, passphrase } => {
+            let ra:RaPublic = read_yaml(&ra)?;
+            (*trusted).check(&ra, None)?;
+            let params = SystemParams::new(ra.g, ra.g2).map_err(refused("sa init"))?;
+            let sa = SurveyAuthority::new(&params);
+            write(&keystore, &sa.export_keys(&passphrase.passphrase))?;
+            write_yaml(&public, &sa.vk)?;
+        },
+        SaCommand::CreateSurvey { ra, keystore, participants, out, title, description, buckets, passphrase } => {
+            let ra:RaPublic = read_yaml(&ra)?;
+            let mut sa = SurveyAuthority::from_keys(&read(&keystore)?, &passphrase.passphrase).map_err(refused("sa create-survey"))?;
+            if !buckets.is_empty() {
+                sa.padding = PaddingPolicy::new(buckets).map_err(|e| (Failure::Usage, e.to_string()))?;
+            }
+            (*trusted).check(&ra, Some(&sa.vk))?;
+            let ids = read_ids(&participants)?;
+            let survey = match (&title, &description) {
+                (Some(title), Some(path)) => {
+                    let description = SurveyDescription::new(title);
+                    write_yaml(path, &description)?;
+                    sa.gen_described_survey(&description, &ids, ra.g, ra.g2, &ra.vk)
+                },
+                _ => sa.gen_survey(&ids, ra.g, ra.g2, &ra.vk)
+            }.map_err(refused("sa create-survey"))?;
+            write_yaml(&out, &survey)?;
+            println!("Survey {} for {} participants", hex_id(survey.vid), survey.len());
+        },
+        SaCommand::Verify { ra, sa, survey, submissions } => {
+            let ra:RaPublic = read_yaml(&ra)?;
+            let vk_sa:VerificationKey = read_yaml(&sa)?;
+            (*trusted).check(&ra, Some(&vk_sa))?;
+            let survey:Survey = read_yaml(&survey)?;
+            let mut ledger = SubmissionLedger::new();
+            let mut rejected:usize = 0;
+            for path in &submissions {
+                let submission:Submission = read_yaml(path)?;
+                let answer:Vec<u8> = submission.answer().to_vec();
+                match ledger.accept(survey.vid, submission, &vk_sa, &ra.vk, ra.g, ra.g2) {
+                    Ok(())  => println!("{}: \u{2713} {:?}", path.display(), String::from_utf8_lossy(&answer)),
+                    Err(e)  => {
+                        println!("{}: \u{2717} ({})", path.display(), e);
+                        rejected += 1;
+                    }
+                }
+            }
+            println!("{} responses accepted", ledger.submissions(survey.vid).len());
+            if rejected > 0 {
+                return Err((Failure::CheckFailed, format!("{} of {} submissions rejected", rejected, submissions.len())));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_user(command: UserCommand, trusted: &Trusted) -> CliResult<()> {
+    match command {
+        UserCommand::Register { ra, pending, out: Some(out), passphrase, .. } => {
+            let ra:RaPublic = read_yaml(&ra)?;
+            (*trusted).check(&ra, None)?;
+            let (request, state) = User::new().request_registration(&ra.vk, ra.g);
+            write(&pending, &state.seal(&passphrase.passphrase))?;
+            write_yaml(&out, &request)?;
+        },
+        UserCommand::Register { ra, pending, credential: Some(credential), wallet: Some(wallet), passphrase, .. } => {
+            let ra:RaPublic = read_yaml(&ra)?;
+            (*trusted).check(&ra, None)?;
+            let state = PendingRegistration::open(&read(&pending)?, &passphrase.passphrase).map_err(refused("user register"))?;
+            let credential:BlindedCredential = read_yaml(&credential)?;
+            let registered = state.complete(&credential, &ra.vk, ra.g2).map_err(refused("user register"))?;
+            write(&wallet, &registered.seal(&passphrase.passphrase))?;
+            println!("{}", hex_id(registered.id()));
+        },
+        UserCommand::Register { .. } => return Err((Failure::Usage, String::from("user register needs --out, or --credential with --wallet"))),
+        UserCommand::Submit { ra, sa, survey, description, wallet, message, out, passphrase } => {
+            let ra:RaPublic = read_yaml(&ra)?;
+            let vk_sa:VerificationKey = read_yaml(&sa)?;
+            (*trusted).check(&ra, Some(&vk_sa))?;
+            let survey:Survey = read_yaml(&survey)?;
+            if let Some(path) = description {
+                let description:SurveyDescription = read_yaml(&path)?;
+                if !description.describes(&survey, &vk_sa) {
+                    return Err((Failure::ProtocolError, format!("user submit: survey is not the one described as {:?}", description.title)));
+                }
+            }
+            let user = User::from_wallet(Wallet::open(&read(&wallet)?, &passphrase.passphrase).map_err(refused("user submit"))?);
+            let submission = user.submit_survey(&survey, message.as_bytes(), (ra.g, ra.g2), &vk_sa, &ra.vk).map_err(refused("user submit"))?;
+            write_yaml(&out, &submission)?;
+        }
+    }
+    Ok(())
+}
+
+
+fn run_params(command: ParamsCommand, trusted: &Trusted) -> CliResult<()> {
+    match command {
+        ParamsCommand::Init { tag, out } => {
+            let params = match &tag {
+                Some(tag) => SystemParams::hashed(tag.as_bytes()),
+                None => SystemParams::standard()
+            };
+            write_yaml(&out, &params)?;
+            println!("System parameters on {}, {} generators", params.curve, if tag.is_some() { "hashed" } else { "standard" });
+        },
+        ParamsCommand::Freeze { keystore, sa, out, passphrase } => {
+            let ra = RegistrationAuthority::from_keys(&read(&keystore)?, &passphrase.passphrase).map_err(refused("params freeze"))?;
+            let sa_keys = sa.iter().map(|path| read_yaml(path)).collect::<CliResult<Vec<VerificationKey>>>()?;
+            let manifest = ra.freeze_params(&sa_keys.iter().collect::<Vec<&VerificationKey>>());
+            write_yaml(&out, &manifest)?;
+            println!("Froze RA {} with {} SA keys", manifest.ra, manifest.sa.len());
+        },
+        ParamsCommand::Check { ra, sa } => {
+            if (*trusted).manifest.is_none() {
+                return Err((Failure::Usage, String::from("params check needs --manifest")));
+            }
+            let ra:RaPublic = read_yaml(&ra)?;
+            let vk_sa:Option<VerificationKey> = match sa {
+                Some(path) => Some(read_yaml(&path)?),
+                None => None
+            };
+            (*trusted).check(&ra, vk_sa.as_ref())?;
+            println!("Parameters match the manifest");
+        },
+        ParamsCommand::Graph { ra } => {
+            let manifest:&ParamsManifest = match &(*trusted).manifest {
+                Some(manifest) => manifest,
+                None => return Err((Failure::Usage, String::from("params graph needs --manifest")))
+            };
+            let ra:RaPublic = read_yaml(&ra)?;
+            (*trusted).check(&ra, None)?;
+            print!("{}", export_graphviz(manifest));
+        }
+    }
+    Ok(())
+}
+
+// The manifest given on the command line, if any
+struct Trusted {
+    manifest: Option<ParamsManifest>
+}
+
+impl Trusted {
+
+    // Refuse the RA (and SA) files unless the manifest was frozen with them
+    fn check(&self, ra: &RaPublic, vk_sa: Option<&VerificationKey>) -> CliResult<()> {
+        match &(*self).manifest {
+            Some(manifest) => manifest.check((*ra).g, (*ra).g2, &(*ra).vk, vk_sa).map_err(refused("manifest")),
+            None => Ok(())
+        }
+    }
+}
+
+/*
+ * Files
+ */
+
+fn read(path: &Path) -> CliResult<Vec<u8>> {
+    fs::read(path).map_err(|e| (Failure::NoInput, format!("{}: {}", path.display(), e)))
+}
+
+fn read_yaml<T: DeserializeOwned>(path: &Path) -> CliResult<T> {
+    serde_yaml::from_slice(&read(path)?).map_err(|e| (Failure::InvalidInput, format!("{}: {}", path.display(), e)))
+}
+
+fn write(path: &Path, bytes: &[u8]) -> CliResult<()> {
+    fs::write(path, bytes).map_err(|e| (Failure::CannotWrite, format!("{}: {}", path.display(), e)))
+}
+
+fn write_yaml<T: Serialize>(path: &Path, object: &T) -> CliResult<()> {
+    let yaml = serde_yaml::to_string(object).map_err(|e| (Failure::ProtocolError, format!("{}: {}", path.display(), e)))?;
+    write(path, yaml.as_bytes())
+}
+
+// Ids one per line, ignoring blank lines and # comments
+fn read_ids(path: &Path) -> CliResult<Vec<Fr>> {
+    let text = String::from_utf8(read(path)?).map_err(|e| (Failure::InvalidInput, format!("{}: {}", path.display(), e)))?;
+    text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| hex::decode(line).ok().and_then(|bytes| fr_from_bytes(&bytes))
+             .ok_or_else(|| (Failure::InvalidInput, format!("{}: invalid id '{}'", path.display(), line))))
+        .collect()
+}
+
+fn hex_id(id: Fr) -> String {
+    hex::encode(fr_to_bytes(id))
+}
+
+// A keystore or snapshot that does not open is bad input, anything else a refused protocol step
+fn refused(step: &'static str) -> impl Fn(SurveyError) -> (Failure, String) {
+    move |e| match e {
+        SurveyError::InvalidKeystore | SurveyError::InvalidSnapshot => (Failure::InvalidInput, format!("{}: {}", step, e)),
+        _ => (Failure::ProtocolError, format!("{}: {}", step, e))
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use std::env;
+
+// Run a command line as the binary would
+#[cfg(test)]
+fn run_args(dir: &Path, args: &str) -> CliResult<()> {
+    let argv = args.split_whitespace().map(|arg| match arg.contains('.') {
+        true => dir.join(arg).display().to_string(),
+        false => String::from(arg)
+    });
+    let cli = Cli::try_parse_from(std::iter::once(String::from("anon_survey")).chain(argv))
+        .map_err(|e| (Failure::Usage, e.to_string()))?;
+    run(cli.command.expect("No subcommand"), cli.manifest)
+}
+
+// Test the three roles as separate processes, passing nothing but files between them
+#[test]
+fn test_role_subcommands() {
+    let dir = env::temp_dir().join(format!("anon_survey_cli_{}", std::process::id()));
+    fs::create_dir_all(&dir).expect("Could not create test directory");
+    let run = |args: &str| run_args(&dir, args);
+
+    run("params init --tag test-deployment --out params.yaml").unwrap();
+    run("ra init --params params.yaml --keystore ra.keys --public ra.yaml --passphrase ra-secret").unwrap();
+    run("sa init --ra ra.yaml --keystore sa.keys --public sa.yaml --passphrase sa-secret").unwrap();
+    run("user register --ra ra.yaml --pending user.pending --out request.yaml --passphrase user-secret").unwrap();
+    assert!( run("ra register --keystore ra.keys --request request.yaml --out credential.yaml --passphrase wrong").unwrap_err().0 == Failure::InvalidInput );
+    run("ra register --keystore ra.keys --request request.yaml --out credential.yaml --passphrase ra-secret").unwrap();
+    run("user register --ra ra.yaml --pending user.pending --credential credential.yaml --wallet user.wallet --passphrase user-secret").unwrap();
+
+    let request:RegistrationRequest = read_yaml(&dir.join("request.yaml")).unwrap();
+    fs::write(dir.join("ids.txt"), format!("# listed\n{}\n", hex_id(request.id))).unwrap();
+    assert!( Wallet::open(&fs::read(dir.join("user.wallet")).unwrap(), "user-secret").unwrap().id() == request.id );
+
+    run("sa create-survey --ra ra.yaml --keystore sa.keys --participants ids.txt --out survey.yaml --title Feedback --description description.yaml --passphrase sa-secret").unwrap();
+    run("sa create-survey --ra ra.yaml --keystore sa.keys --participants ids.txt --out other.yaml --buckets 16 --passphrase sa-secret").unwrap();
+    assert!( run("sa create-survey --ra ra.yaml --keystore sa.keys --participants ids.txt --out other.yaml --buckets 0 --passphrase sa-secret").unwrap_err().0 == Failure::Usage );
+    assert!( run("sa create-survey --ra ra.yaml --keystore sa.keys --participants ids.txt --out other.yaml --title Feedback --passphrase sa-secret").unwrap_err().0 == Failure::Usage );
+
+    // Clients holding the manifest only accept the SA it vouches for
+    run("sa init --ra ra.yaml --keystore rogue.keys --public rogue.yaml --passphrase rogue").unwrap();
+    run("params freeze --keystore ra.keys --sa sa.yaml --out manifest.yaml --passphrase ra-secret").unwrap();
+    run("params check --manifest manifest.yaml --ra ra.yaml --sa sa.yaml").unwrap();
+    run("params graph --manifest manifest.yaml --ra ra.yaml").unwrap();
+    assert!( run("params check --manifest manifest.yaml --ra ra.yaml --sa rogue.yaml").unwrap_err().0 == Failure::ProtocolError );
+    assert!( run("user submit --manifest manifest.yaml --ra ra.yaml --sa rogue.yaml --survey survey.yaml --wallet user.wallet --message yes --out first.yaml --passphrase user-secret").unwrap_err().0 == Failure::ProtocolError );
+    assert!( run("user submit --ra ra.yaml --sa sa.yaml --survey other.yaml --description description.yaml --wallet user.wallet --message yes --out first.yaml --passphrase user-secret").unwrap_err().0 == Failure::ProtocolError );
+    run("user submit --manifest manifest.yaml --ra ra.yaml --sa sa.yaml --survey survey.yaml --description description.yaml --wallet user.wallet --message yes --out first.yaml --passphrase user-secret").unwrap();
+    run("user submit --ra ra.yaml --sa sa.yaml --survey other.yaml --wallet user.wallet --message no --out second.yaml --passphrase user-secret").unwrap();
+    let (first, second):(Submission, Submission) = (read_yaml(&dir.join("first.yaml")).unwrap(), read_yaml(&dir.join("second.yaml")).unwrap());
+    assert!( first.message.len() == 64 && second.message.len() == 16 && second.answer() == b"no" );
+    run("sa verify --ra ra.yaml --sa sa.yaml --survey survey.yaml first.yaml").unwrap();
+    assert!( run("sa verify --ra ra.yaml --sa sa.yaml --survey survey.yaml first.yaml second.yaml").unwrap_err().0 == Failure::CheckFailed );
+    assert!( run("user register --ra ra.yaml --pending user.pending --passphrase user-secret").unwrap_err().0 == Failure::Usage );
+    fs::remove_dir_all(&dir).ok();
+}
+
+// Test revoking departed panel members from an HR feed, on an RA snapshot
+#[test]
+fn test_ra_revoke_batch() {
+    let dir = env::temp_dir().join(format!("anon_survey_cli_revoke_{}", std::process::id()));
+    fs::create_dir_all(&dir).expect("Could not create test directory");
+    let run = |args: &str| run_args(&dir, args);
+
+    let params = SystemParams::standard();
+    let mut ra = RegistrationAuthority::new(&params);
+    let (panel, invitations) = Panel::import("staff", "alice@example.org\nbob@example.org\n", &mut ra, std::time::Duration::from_secs(3600));
+    let mut alice = User::new();
+    alice.redeem_code(&invitations[0].1, &mut ra).expect("Registration failed");
+    fs::write(dir.join("ra.snapshot"), ra.snapshot("ra-secret")).unwrap();
+    write_yaml(&dir.join("panel.yaml"), &panel).unwrap();
+    fs::write(dir.join("departed.csv"), "alice@example.org,Engineering\ndave@example.org\n").unwrap();
+
+    assert!( run("ra revoke-batch --snapshot ra.snapshot --panel panel.yaml --csv departed.csv --out batch.yaml --passphrase wrong").unwrap_err().0 == Failure::InvalidInput );
+    run("ra revoke-batch --snapshot ra.snapshot --panel panel.yaml --csv departed.csv --out batch.yaml --passphrase ra-secret").unwrap();
+    let batch:anon_survey::RevocationBatch = read_yaml(&dir.join("batch.yaml")).unwrap();
+    assert!( batch.verify(&ra.vk, params.g, params.g2) && batch.id_hashes == vec![anon_survey::hash::id_hash(alice.id())] );
+    let restored = RegistrationAuthority::restore(&fs::read(dir.join("ra.snapshot")).unwrap(), "ra-secret").expect("Snapshot was refused");
+    assert!( restored.is_revoked(batch.id_hashes[0]) );
+    fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,260 @@
+extern crate tbn;
+extern crate hex;
+extern crate rand;
+
+use std::io;
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use rand::RngCore;
+use tonic::{Code, Request, Status};
+use tonic::codegen::{Service, Pin, Future, Context, Poll};
+use tonic::transport::{Channel, Endpoint, Uri};
+use tokio::net::TcpStream;
+use tokio_socks::tcp::Socks5Stream;
+use hyper_util::rt::TokioIo;
+use tbn::{Fr, G1, G2};
+
+use crate::grpc::proto;
+use crate::grpc::proto::registration_client::RegistrationClient;
+use crate::grpc::proto::surveys_client::SurveysClient;
+use crate::users::{User, VerificationKey, BlindedCredential};
+use crate::survey::Survey;
+use crate::submission::Submission;
+use crate::wallet::Wallet;
+use crate::encoding::fr_to_bytes;
+use crate::error::SurveyError;
+
+/*
+ * ----------------------------------------------
+ * |    CLIENT                                  |
+ * ----------------------------------------------
+ *
+ * A user's client for the gRPC services of grpc.rs: registering with the RA, fetching surveys and
+ * submitting to the SA.
+ *
+ * Unlinkable tokens do not help if every submission arrives from the user's own address, so the
+ * client can connect through a SOCKS5 proxy, typically a local Tor client:
+ *
+ *      let client = SurveyClient::new("http://ra.example:50051", "http://sa.example:50051")
+ *          .with_proxy(Socks5Proxy { address: "127.0.0.1:9050".parse()? });
+ *      let wallet = client.register(&user, &vk_ra, g, g2).await?;
+ *
+ * Every call opens its own connection, and through a proxy every connection authenticates with
+ * fresh random SOCKS credentials. Tor puts streams with different credentials on different
+ * circuits (IsolateSOCKSAuth, on by default), so registration, survey fetches and each submission
+ * leave through separate circuits, and the SA cannot link two submissions by the circuit they
+ * came over. Host names are resolved by the proxy, so .onion addresses work and no DNS lookup
+ * leaves the machine.
+*/
+
+// Bytes of the random SOCKS password that isolates a connection
+const ISOLATION_BYTES:usize = 16;
+
+// SOCKS5 proxy to route the client's connections through
+#[derive(Clone, Debug)]
+pub struct Socks5Proxy {
+    // e.g. 127.0.0.1:9050 for a local Tor client
+    pub address: SocketAddr
+}
+
+pub struct SurveyClient {
+    ra_endpoint: String,
+    sa_endpoint: String,
+    proxy: Option<Socks5Proxy>
+}
+
+impl SurveyClient {
+
+    /* Client for the RA and SA at the given endpoints (e.g. http://ra.example:50051), connecting directly */
+    pub fn new(ra_endpoint: &str, sa_endpoint: &str) -> Self {
+        SurveyClient { ra_endpoint: String::from(ra_endpoint), sa_endpoint: String::from(sa_endpoint), proxy: None }
+    }
+
+    /* Connect through proxy instead, on a separate circuit per call */
+    pub fn with_proxy(mut self, proxy: Socks5Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /* Register user with the RA, and complete the credential into a wallet. Fails with AlreadyRegistered if its id was registered before */
+    pub async fn register(&self, user: &User, vk_ra: &VerificationKey, g: G1, g2: G2) -> Result<Wallet, SurveyError> {
+        let (request, pending) = (*user).request_registration(vk_ra, g);
+        let mut client = RegistrationClient::new((*self).connect(&(*self).ra_endpoint, "registration").await?);
+        let credential = client.register(Request::new(proto::RegistrationRequest::from(&request))).await
+            .map_err(remote_error(Some(SurveyError::AlreadyRegistered)))?;
+        pending.complete(&BlindedCredential::try_from(credential.into_inner())?, vk_ra, g2)
+    }
+
+    /* Survey vid as published by the SA, with its participant list */
+    pub async fn fetch_survey(&self, vid: Fr) -> Result<Survey, SurveyError> {
+        let mut client = SurveysClient::new((*self).connect(&(*self).sa_endpoint, "survey").await?);
+        let survey = client.fetch_survey(Request::new(proto::FetchSurveyRequest { vid: fr_to_bytes(vid) })).await.map_err(remote_error(None))?;
+        Survey::try_from(survey.into_inner())
+    }
+
+    /* Hand submission to the SA's ledger. Fails with DuplicateSubmission if its token was already used */
+    pub async fn submit(&self, submission: &Submission) -> Result<(), SurveyError> {
+        let mut client = SurveysClient::new((*self).connect(&(*self).sa_endpoint, "submission").await?);
+        client.submit(Request::new(proto::Submission::from(submission))).await.map(|_| ()).map_err(remote_error(Some(SurveyError::DuplicateSubmission)))
+    }
+
+    // New connection to endpoint, through the proxy (if any) under fresh credentials
+    async fn connect(&self, endpoint: &str, purpose: &'static str) -> Result<Channel, SurveyError> {
+        let endpoint:Endpoint = Endpoint::from_shared(String::from(endpoint)).map_err(transport_error)?;
+        match &(*self).proxy {
+            None => endpoint.connect().await.map_err(transport_error),
+            Some(proxy) => {
+                let mut password = [0u8; ISOLATION_BYTES];
+                rand::thread_rng().fill_bytes(&mut password);
+                let connector = Socks5Connector { proxy: proxy.address, username: purpose, password: hex::encode(password) };
+                endpoint.connect_with_connector(connector).await.map_err(transport_error)
+            }
+        }
+    }
+}
+
+// Opens connections through a SOCKS5 proxy, authenticating with one pair of credentials
+#[derive(Clone)]
+struct Socks5Connector {
+    proxy: SocketAddr,
+    username: &'static str,
+    password: String
+}
+
+impl Service<Uri> for Socks5Connector {
+    type Response = TokioIo<Socks5Stream<TcpStream>>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, io::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let connector:Socks5Connector = (*self).clone();
+        Box::pin(async move {
+            let host:String = uri.host().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "endpoint has no host"))?.to_string();
+            let port:u16 = uri.port_u16().unwrap_or(if uri.scheme_str() == Some("https") { 443 } else { 80 });
+            let stream = Socks5Stream::connect_with_password(connector.proxy, (host, port), connector.username, &connector.password).await
+                .map_err(io::Error::other)?;
+            Ok(TokioIo::new(stream))
+        })
+    }
+}
+
+fn transport_error(error: impl std::fmt::Display) -> SurveyError {
+    SurveyError::Transport(error.to_string())
+}
+
+// The error an authority refused a call with. AlreadyExists means something different for each RPC,
+// so the caller says which error it stands for (None if the RPC never answers with it)
+fn remote_error(already_exists: Option<SurveyError>) -> impl FnOnce(Status) -> SurveyError {
+    move |status| match (status.code(), already_exists) {
+        (Code::AlreadyExists, Some(error)) => error,
+        _ => SurveyError::Transport(format!("{:?}: {}", status.code(), status.message()))
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use std::sync::{Arc, Mutex};
+#[cfg(test)]
+use tokio::net::TcpListener;
+#[cfg(test)]
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+#[cfg(test)]
+use crate::grpc::{RegistrationService, SurveyService, RegistrationServer, SurveysServer};
+#[cfg(test)]
+use crate::users::{RegistrationAuthority, SurveyAuthority};
+#[cfg(test)]
+use crate::ledger::SubmissionLedger;
+#[cfg(test)]
+use crate::params::SystemParams;
+
+// Minimal SOCKS5 proxy (username/password authentication, CONNECT only) that records the
+// credentials of every connection
+#[cfg(test)]
+async fn socks5_proxy(listener: TcpListener, seen: Arc<Mutex<Vec<(String, String)>>>) {
+    loop {
+        let (mut client, _) = listener.accept().await.expect("Proxy could not accept");
+        let seen = seen.clone();
+        tokio::spawn(async move {
+            let mut header = [0u8; 2];
+            client.read_exact(&mut header).await.unwrap();
+            let mut methods = vec![0u8; header[1] as usize];
+            client.read_exact(&mut methods).await.unwrap();
+            client.write_all(&[5, 2]).await.unwrap();
+            let field = |len: usize| vec![0u8; len];
+            let mut version_len = [0u8; 2];
+            client.read_exact(&mut version_len).await.unwrap();
+            let mut username = field(version_len[1] as usize);
+            client.read_exact(&mut username).await.unwrap();
+            let mut password_len = [0u8; 1];
+            client.read_exact(&mut password_len).await.unwrap();
+            let mut password = field(password_len[0] as usize);
+            client.read_exact(&mut password).await.unwrap();
+            seen.lock().unwrap().push((String::from_utf8(username).unwrap(), String::from_utf8(password).unwrap()));
+            client.write_all(&[1, 0]).await.unwrap();
+
+            let mut request = [0u8; 4];
+            client.read_exact(&mut request).await.unwrap();
+            let host:String = match request[3] {
+                1 => { let mut ip = [0u8; 4]; client.read_exact(&mut ip).await.unwrap(); std::net::Ipv4Addr::from(ip).to_string() },
+                _ => { let mut len = [0u8; 1]; client.read_exact(&mut len).await.unwrap();
+                       let mut name = field(len[0] as usize); client.read_exact(&mut name).await.unwrap(); String::from_utf8(name).unwrap() }
+            };
+            let mut port = [0u8; 2];
+            client.read_exact(&mut port).await.unwrap();
+            let mut target = TcpStream::connect((host.as_str(), u16::from_be_bytes(port))).await.unwrap();
+            client.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).await.unwrap();
+            let _ = tokio::io::copy_bidirectional(&mut client, &mut target).await;
+        });
+    }
+}
+
+// Registration, fetching and each submission go through the proxy under their own credentials
+#[test]
+fn test_client_over_socks5() {
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().expect("Could not start a runtime");
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let ra = RegistrationAuthority::new(&params);
+    let vk_ra:VerificationKey = ra.vk.clone();
+    let mut sa = SurveyAuthority::new(&params);
+    let vk_sa:VerificationKey = sa.vk.clone();
+    let user = User::new();
+    let survey = sa.gen_survey(&vec![user.id()], g, g2, &vk_ra);
+
+    runtime.block_on(async {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("Could not listen");
+        let address:SocketAddr = listener.local_addr().expect("Listener has no address");
+        let incoming = tonic::transport::server::TcpIncoming::from_listener(listener, true, None).expect("Could not listen");
+        tokio::spawn(tonic::transport::Server::builder()
+            .add_service(RegistrationServer::new(RegistrationService::new(ra)))
+            .add_service(SurveysServer::new(SurveyService::new(sa, SubmissionLedger::new(), vk_ra.clone(), g, g2)))
+            .serve_with_incoming(incoming));
+        let proxy_listener = TcpListener::bind("127.0.0.1:0").await.expect("Could not listen");
+        let proxy = Socks5Proxy { address: proxy_listener.local_addr().expect("Listener has no address") };
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        tokio::spawn(socks5_proxy(proxy_listener, seen.clone()));
+
+        let endpoint:String = format!("http://{}", address);
+        let client = SurveyClient::new(&endpoint, &endpoint).with_proxy(proxy);
+        let user = User::from_wallet(client.register(&user, &vk_ra, g, g2).await.expect("Registration failed"));
+        let survey = client.fetch_survey(survey.expect("Survey was refused").vid).await.expect("Survey not found");
+        let submission = user.submit_survey(&survey, b"yes", (g, g2), &vk_sa, &vk_ra).expect("Submission failed");
+        client.submit(&submission).await.expect("Submission was refused");
+        assert!( client.submit(&submission).await == Err(SurveyError::DuplicateSubmission) );
+        // AlreadyExists from the RA is a second registration, not a duplicate submission
+        assert!( client.register(&user, &vk_ra, g, g2).await.err() == Some(SurveyError::AlreadyRegistered) );
+
+        let seen = seen.lock().unwrap();
+        let purposes:Vec<&str> = seen.iter().map(|(username, _)| username.as_str()).collect();
+        assert!( purposes == vec!["registration", "survey", "submission", "submission", "registration"] );
+        assert!( (0..seen.len()).all(|i| (0..i).all(|j| seen[i].1 != seen[j].1)) );
+    });
+}
@@ -0,0 +1,75 @@
+extern crate tbn;
+
+use tbn::arith::U256;
+
+/*
+ * ----------------------------------------------
+ * |    U256 CODEC                              |
+ * ----------------------------------------------
+ *
+ * tbn's field elements and coordinates all come out as U256 (into_u256), which every byte encoding
+ * in this crate -- wire formats, keystores, transcripts -- builds on. A U256 is always exactly 32
+ * big-endian bytes here, whatever its value, so encodings never depend on leading zeros.
+*/
+
+pub const U256_BYTES:usize = 32;
+
+// Fold the bits of n (most significant first) into 32 bytes
+pub fn u256_to_be_bytes(n: U256) -> [u8; U256_BYTES] {
+    let mut bytes = [0u8; U256_BYTES];
+    for (i, bit) in n.bits().enumerate() {
+        if bit {
+            bytes[i / 8] |= 0x80 >> (i % 8);
+        }
+    }
+    bytes
+}
+
+// Exactly 32 big-endian bytes back into a U256 (None for any other length)
+pub fn u256_from_be_bytes(bytes: &[u8]) -> Option<U256> {
+    if bytes.len() != U256_BYTES {
+        return None;
+    }
+    U256::from_slice(bytes).ok()
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use rand::Rng;
+#[cfg(test)]
+use tbn::{Fr, Fq};
+
+// Known values encode as expected, whatever their leading zeros
+#[test]
+fn test_u256_codec_fixed() {
+    let mut one = [0u8; U256_BYTES];
+    one[31] = 1;
+    assert!( u256_to_be_bytes(U256::from_slice(&one).unwrap()) == one );
+    assert!( u256_to_be_bytes(U256::from_slice(&[0; U256_BYTES]).unwrap()) == [0; U256_BYTES] );
+    assert!( u256_to_be_bytes(U256::from_slice(&[0xff; U256_BYTES]).unwrap()) == [0xff; U256_BYTES] );
+    assert!( u256_to_be_bytes(Fr::one().into_u256()) == one );
+    assert!( hex::encode(u256_to_be_bytes(Fq::modulus())) == "30644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd47" );
+    assert!( u256_from_be_bytes(&one[1..]).is_none() && u256_from_be_bytes(&[0; 33]).is_none() );
+}
+
+// Random values of every magnitude round-trip through exactly 32 bytes
+#[test]
+fn test_u256_codec_round_trip() {
+    let rng = &mut rand::thread_rng();
+    for _ in 0..1000 {
+        let mut bytes = [0u8; U256_BYTES];
+        rng.fill(&mut bytes[..]);
+        // Clear a random number of leading bytes to cover short values
+        let zeros:usize = rng.gen_range(0, U256_BYTES + 1);
+        for b in bytes.iter_mut().take(zeros) {
+            *b = 0;
+        }
+        let n:U256 = u256_from_be_bytes(&bytes).unwrap();
+        assert!( u256_to_be_bytes(n) == bytes );
+        assert!( u256_from_be_bytes(&u256_to_be_bytes(n)) == Some(n) );
+    }
+}
@@ -0,0 +1,181 @@
+extern crate tbn;
+extern crate rand;
+
+use rand::{RngCore, CryptoRng};
+use tbn::{Fr, G1, G2};
+
+use crate::users::{SurveyAuthority, RegistrationAuthority, RegistrationRequest, BlindedCredential, VerificationKey};
+use crate::survey::Survey;
+use crate::keyring::VerificationKeyRing;
+use crate::params::SystemParams;
+use crate::hash::id_hash;
+use crate::error::SurveyError;
+
+/*
+ * ----------------------------------------------
+ * |    COMBINED AUTHORITY                      |
+ * ----------------------------------------------
+ *
+ * The single-authority deployment of the ANONIZE paper: one entity acts as both the RA and the SA,
+ * holding both key pairs. Nothing changes for users and verifiers, who register against vk_RA and
+ * check surveys and submissions against vk_SA as usual:
+ *
+ *      let mut authority = CombinedAuthority::new(&params, 5);
+ *      let (request, pending) = alice.request_registration(authority.vk_ra(), g);
+ *      let alice = User::from_wallet(pending.complete(&authority.register_request(&request)?, authority.vk_ra(), g2)?);
+ *      let survey = authority.gen_survey(&participants)?;
+ *
+ * Since the same party sees every registration and picks every participant list, it could single
+ * out a respondent by listing them alone (or next to ids nobody holds): each submission would
+ * then come from a known user. The combined authority refuses to do so:
+ *      - both key pairs are generated independently, and never share u, v, h or pk
+ *      - every listed id must be registered (and not revoked)
+ *      - a survey needs at least min_participants distinct registered ids
+ *
+ * Anonymity of submissions still relies on the anonymous channel they travel over.
+*/
+
+pub struct CombinedAuthority {
+    ra: RegistrationAuthority,
+    sa: SurveyAuthority,
+    g: G1,
+    g2: G2,
+    // Smallest participant list a survey may have
+    min_participants: usize
+}
+
+impl CombinedAuthority {
+
+    /* Create both key pairs on the deployment's system parameters */
+    pub fn new(params: &SystemParams, min_participants: usize) -> Self {
+        // crytpographiclaly secure thread-local rng
+        CombinedAuthority::new_with_rng(params, min_participants, &mut rand::thread_rng())
+    }
+
+    pub fn new_with_rng(params: &SystemParams, min_participants: usize, rng: &mut (impl RngCore + CryptoRng)) -> Self {
+        let (g, g2):(G1, G2) = (*params).generators();
+        let ra = RegistrationAuthority::new_with_rng(params, rng);
+        let mut sa = SurveyAuthority::new_with_rng(params, rng);
+        // Only happens with negligible probability, but a shared component would let a participant
+        // signature pass for a credential (or the other way around)
+        while !independent(&ra.vk, &sa.vk) {
+            sa = SurveyAuthority::new_with_rng(params, rng);
+        }
+        CombinedAuthority { ra, sa, g, g2, min_participants }
+    }
+
+    pub fn vk_ra(&self) -> &VerificationKey {
+        &(*self).ra.vk
+    }
+
+    pub fn vk_sa(&self) -> &VerificationKey {
+        &(*self).sa.vk
+    }
+
+    // Every SA key the authority has signed surveys with
+    pub fn key_ring(&self) -> VerificationKeyRing {
+        (*self).sa.key_ring()
+    }
+
+    pub fn generators(&self) -> (G1, G2) {
+        ((*self).g, (*self).g2)
+    }
+
+    pub fn min_participants(&self) -> usize {
+        (*self).min_participants
+    }
+
+    /* Register a user from their RegistrationRequest, as RegistrationAuthority::register_request */
+    pub fn register_request(&mut self, request: &RegistrationRequest) -> Result<BlindedCredential, SurveyError> {
+        (*self).ra.register_request(request)
+    }
+
+    pub fn register_request_with_rng(&mut self, request: &RegistrationRequest, rng: &mut (impl RngCore + CryptoRng))
+                                     -> Result<BlindedCredential, SurveyError> {
+        (*self).ra.register_request_with_rng(request, rng)
+    }
+
+    // Take the id with hash id_hash out of the anonymity set, and off every survey signed from now on
+    pub fn revoke(&mut self, id_hash: Fr) -> Result<(), SurveyError> {
+        let batch = (*self).ra.revoke(id_hash)?;
+        (*self).sa.apply_revocations(&batch, &(*self).ra.vk, (*self).g, (*self).g2)
+    }
+
+    /* Create a survey for the participants in L, under a fresh vid */
+    // Refuses ids that are not registered (UnregisteredUser) and lists with fewer than
+    // min_participants distinct ids (TooFewParticipants)
+    #[allow(non_snake_case)]
+    pub fn gen_survey(&mut self, L: &Vec<Fr>) -> Result<Survey, SurveyError> {
+        // crytpographiclaly secure thread-local rng
+        (*self).gen_survey_with_rng(L, &mut rand::thread_rng())
+    }
+
+    #[allow(non_snake_case)]
+    pub fn gen_survey_with_rng(&mut self, L: &Vec<Fr>, rng: &mut (impl RngCore + CryptoRng)) -> Result<Survey, SurveyError> {
+        let mut participants:Vec<Fr> = Vec::new();
+        for id in L {
+            if !(*self).ra.in_anonymity_set(id_hash(*id)) {
+                return Err(SurveyError::UnregisteredUser);
+            }
+            if !participants.contains(id) {
+                participants.push(*id);
+            }
+        }
+        if participants.len() < (*self).min_participants {
+            return Err(SurveyError::TooFewParticipants((*self).min_participants));
+        }
+        let (g, g2):(G1, G2) = ((*self).g, (*self).g2);
+        (*self).sa.gen_survey_with_rng(&participants, g, g2, &(*self).ra.vk, rng)
+    }
+}
+
+// The two keys have no component in common
+fn independent(vk_ra: &VerificationKey, vk_sa: &VerificationKey) -> bool {
+    let ra_bases:[G1; 3] = [(*vk_ra).u, (*vk_ra).v, (*vk_ra).h];
+    [(*vk_sa).u, (*vk_sa).v, (*vk_sa).h].iter().all(|base| !ra_bases.contains(base))
+        && (*vk_ra).pk != (*vk_sa).pk
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use crate::users::User;
+#[cfg(test)]
+use crate::submission::check_submission;
+#[cfg(test)]
+use crate::authorized;
+
+// One authority registers users and creates surveys, and only for lists that hide every respondent
+#[test]
+fn test_combined_authority() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let mut authority = CombinedAuthority::new(&params, 3);
+    assert!( independent(authority.vk_ra(), authority.vk_sa()) && !independent(authority.vk_ra(), authority.vk_ra()) );
+
+    let mut users:Vec<User> = Vec::new();
+    for _ in 0..3 {
+        let user = User::new();
+        let (request, pending) = user.request_registration(authority.vk_ra(), g);
+        let credential = authority.register_request(&request).expect("Request was refused");
+        users.push(User::from_wallet(pending.complete(&credential, authority.vk_ra(), g2).expect("Credential was refused")));
+    }
+    let ids:Vec<Fr> = users.iter().map(|user| user.id()).collect();
+
+    // Too few distinct participants, or someone the authority never registered
+    assert!( authority.gen_survey(&vec![ids[0], ids[1], ids[0]]) == Err(SurveyError::TooFewParticipants(3)) );
+    assert!( authority.gen_survey(&vec![ids[0], ids[1], User::new().id()]) == Err(SurveyError::UnregisteredUser) );
+
+    let survey = authority.gen_survey(&ids).expect("Survey was refused");
+    assert!( ids.iter().all(|id| authorized(*id, &survey, authority.vk_sa(), authority.vk_ra(), g2)) );
+    let submission = users[1].submit_survey(&survey, b"yes", (g, g2), authority.vk_sa(), authority.vk_ra()).expect("Submission failed");
+    assert!( check_submission(survey.vid, &submission, &authority.key_ring(), authority.vk_ra(), g, g2) );
+
+    // A revoked user no longer counts toward the minimum
+    authority.revoke(id_hash(ids[2])).expect("Revocation failed");
+    assert!( authority.gen_survey(&ids) == Err(SurveyError::UnregisteredUser) );
+    assert!( authority.gen_survey(&ids[..2].to_vec()) == Err(SurveyError::TooFewParticipants(3)) );
+}
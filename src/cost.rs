@@ -0,0 +1,118 @@
+extern crate tbn;
+
+use tbn::{Fr, G1, G2};
+
+use crate::gt::{self, Gt};
+#[cfg(feature = "cost-accounting")]
+use std::cell::Cell;
+
+/*
+ * ----------------------------------------------
+ * |    COST ACCOUNTING                         |
+ * ----------------------------------------------
+ *
+ * Pairings and exponentiations in Gt dominate the cost of every protocol step, and are what
+ * ANONIZE states its complexity in. The protocol code computes them through pairing, pairing_batch
+ * and pow below;
+ * with the `cost-accounting` feature these count every call, and measure reports how many a piece
+ * of code performed:
+ *
+ *      let (valid, cost) = measure(|| check_submission(vid, &submission, &vk_sa, &vk_ra, g, g2));
+ *      // cost == CostReport { pairings: 4, gt_exponentiations: 2 }
+ *
+ * Counts are per thread, so work that gen_survey_parallel hands to other threads is not included.
+ * Scalar multiplications in G1 and G2 are tbn operators and are not counted. Without the feature
+ * these functions call gt.rs directly.
+*/
+
+// Expensive operations performed while measuring
+#[cfg(feature = "cost-accounting")]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CostReport {
+    pub pairings: u64,
+    pub gt_exponentiations: u64
+}
+
+#[cfg(feature = "cost-accounting")]
+thread_local! {
+    static COUNTS: Cell<CostReport> = Cell::new(CostReport::default());
+}
+
+#[cfg(feature = "cost-accounting")]
+fn record(count: impl FnOnce(&mut CostReport)) {
+    COUNTS.with(|counts| {
+        let mut report = counts.get();
+        count(&mut report);
+        counts.set(report);
+    });
+}
+
+// Run f, and report what it cost on this thread
+#[cfg(feature = "cost-accounting")]
+pub fn measure<T>(f: impl FnOnce() -> T) -> (T, CostReport) {
+    let before:CostReport = COUNTS.with(|counts| counts.get());
+    let result:T = f();
+    let after:CostReport = COUNTS.with(|counts| counts.get());
+    (result, CostReport {
+        pairings: after.pairings - before.pairings,
+        gt_exponentiations: after.gt_exponentiations - before.gt_exponentiations
+    })
+}
+
+// e(p, q)
+pub(crate) fn pairing(p: G1, q: G2) -> Gt {
+    #[cfg(feature = "cost-accounting")]
+    record(|report| report.pairings += 1);
+    gt::pairing(p, q)
+}
+
+// Π e(p_i, q_i), as one product: a Miller loop per pair and a single final exponentiation. Counted
+// as one pairing per pair
+pub(crate) fn pairing_batch(pairs: &[(G1, G2)]) -> Gt {
+    #[cfg(feature = "cost-accounting")]
+    record(|report| report.pairings += pairs.len() as u64);
+    gt::multi_pairing(pairs)
+}
+
+// t^x
+pub(crate) fn pow(t: Gt, x: Fr) -> Gt {
+    #[cfg(feature = "cost-accounting")]
+    record(|report| report.gt_exponentiations += 1);
+    t.pow(x)
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(all(test, feature = "cost-accounting"))]
+use crate::users::{User, SurveyAuthority, RegistrationAuthority};
+#[cfg(all(test, feature = "cost-accounting"))]
+use crate::submission::check_submission;
+#[cfg(all(test, feature = "cost-accounting"))]
+use crate::params::SystemParams;
+
+// Checking a submission takes four pairings and two exponentiations, and batch-verifying a survey
+// of n participants n + 1 pairings in one product
+#[cfg(feature = "cost-accounting")]
+#[test]
+fn test_cost_report() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let mut users:Vec<User> = vec![User::new(), User::new(), User::new()];
+    for user in users.iter_mut() {
+        user.reg_user(&mut ra).expect("Registration failed");
+    }
+    let ids:Vec<Fr> = users.iter().map(|user| user.id()).collect();
+    let survey = sa.gen_survey(&ids, g, g2, &ra.vk).expect("SA survey creation failed!");
+    let submission = users[0].submit_survey(&survey, b"yes", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+
+    let (valid, cost) = measure(|| check_submission(survey.vid, &submission, &sa.vk, &ra.vk, g, g2));
+    assert!( valid && cost == CostReport { pairings: 4, gt_exponentiations: 2 } );
+    let (invalid, cost) = measure(|| survey.verify_all(&sa.vk, &ra.vk, g2));
+    assert!( invalid.is_empty() && cost == CostReport { pairings: 4, gt_exponentiations: 1 } );
+    assert!( measure(|| ()).1 == CostReport::default() );
+}
@@ -0,0 +1,86 @@
+extern crate tbn;
+extern crate subtle;
+
+use tbn::{Fr, G1};
+pub use subtle::{Choice, ConstantTimeEq};
+
+use crate::gt::Gt;
+use crate::encoding::{GtElement, fr_to_bytes, g1_to_bytes, gt_to_bytes};
+use crate::secret::SecretScalar;
+use crate::engine::PairingEngine;
+
+/*
+ * ----------------------------------------------
+ * |    CONSTANT-TIME COMPARISONS               |
+ * ----------------------------------------------
+ *
+ * tbn compares field and group elements limb by limb and stops at the first difference, so the
+ * time a check takes says how much of a forged value was right. Every comparison on a
+ * verification path (Fiat-Shamir challenges, pairing equations, unblinded credentials, one-time
+ * tokens) goes through the functions below instead, which compare the canonical encodings (see
+ * encoding.rs) with subtle's ConstantTimeEq, and SecretScalar and GtElement implement
+ * ConstantTimeEq (their == uses it too):
+ *
+ *      fr_eq(challenge(statement, t_ra, t_sa, t_tok), c)
+ *      gt_eq(pairing(σ1, g2), pk_SA · e(M, σ2))
+ *
+ * Only the comparison is constant-time: encoding a point normalizes it to affine coordinates with
+ * tbn's inversion, which is not, and neither are the pairing (see gt.rs) and tbn's scalar
+ * multiplication.
+*/
+
+pub fn fr_eq(a: Fr, b: Fr) -> bool {
+    fr_to_bytes(a).ct_eq(&fr_to_bytes(b)).into()
+}
+
+pub fn g1_eq(a: G1, b: G1) -> bool {
+    g1_to_bytes(a).ct_eq(&g1_to_bytes(b)).into()
+}
+
+pub fn gt_eq(a: Gt, b: Gt) -> bool {
+    gt_to_bytes(a).ct_eq(&gt_to_bytes(b)).into()
+}
+
+// gt_eq on any PairingEngine, comparing its own encodings (the same as gt_eq for Tbn)
+pub fn engine_gt_eq<E: PairingEngine>(a: E::Gt, b: E::Gt) -> bool {
+    E::gt_to_bytes(a).ct_eq(&E::gt_to_bytes(b)).into()
+}
+
+impl ConstantTimeEq for SecretScalar {
+    fn ct_eq(&self, other: &SecretScalar) -> Choice {
+        fr_to_bytes((*self).expose()).ct_eq(&fr_to_bytes((*other).expose()))
+    }
+}
+
+impl ConstantTimeEq for GtElement {
+    fn ct_eq(&self, other: &GtElement) -> Choice {
+        gt_to_bytes((*self).0).ct_eq(&gt_to_bytes((*other).0))
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use tbn::{Group, G2};
+#[cfg(test)]
+use crate::gt::pairing;
+
+// The constant-time comparisons agree with tbn's ==
+#[test]
+fn test_constant_time_eq() {
+    let rng = &mut rand::thread_rng();
+    let (x, y):(Fr, Fr) = (Fr::random(rng), Fr::random(rng));
+    assert!( fr_eq(x, x) && !fr_eq(x, y) && fr_eq(x + y, y + x) );
+
+    let (g, g2):(G1, G2) = (G1::random(rng), G2::random(rng));
+    // Different Jacobian representations of the same point
+    assert!( g1_eq(g * x + g * y, g * (x + y)) && !g1_eq(g * x, g * y) && g1_eq(G1::zero(), g - g) );
+    let e:Gt = pairing(g, g2);
+    assert!( gt_eq(e.pow(x) * e.pow(y), e.pow(x + y)) && !gt_eq(e.pow(x), e.pow(y)) );
+
+    assert!( bool::from(SecretScalar::new(x).ct_eq(&SecretScalar::new(x))) && SecretScalar::new(x) != SecretScalar::new(y) );
+    assert!( GtElement(e) == GtElement(e.pow(Fr::one())) && GtElement(e) != GtElement(e.pow(x)) );
+}
@@ -0,0 +1,313 @@
+extern crate tbn;
+extern crate rand;
+
+use rand::{RngCore, CryptoRng};
+use tbn::{Fr, G1, G2};
+
+use crate::error::SurveyError;
+use crate::users::{VerificationKey, KeyHolder};
+use crate::survey::Survey;
+use crate::hash::{survey_id, NONCE_BYTES};
+use crate::proofs::{Proof, verify_gt, push_bytes};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/*
+ * ----------------------------------------------
+ * |    SURVEY DEFINITIONS                      |
+ * ----------------------------------------------
+ *
+ * What a survey asks: a title, a description, and its questions, each one of
+ *      - MultipleChoice: pick one of the options (answered with its index)
+ *      - FreeText: any text up to max_len bytes
+ *      - Likert: a point on a scale of 1 to points
+ *
+ * The SA defines the survey (SurveyAuthority::define_survey) and creates it from the definition
+ * (gen_defined_survey): the vid is hashed from the encoded definition, the SA's key and a nonce (see
+ * hash.rs), and the definition is signed with the SA's key y (pk_SA = e(g, g2)^y) like a manifest
+ * (see proofs.rs). Participants receive the definition with the survey, check both bindings
+ * (verify), and answer with a Response, whose encoding is the message they submit. The SA validates
+ * every response against the definition before counting it.
+*/
+
+pub const DEFINITION_DOMAIN:&[u8] = b"anon-survey/definition/v1";
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Question {
+    MultipleChoice { prompt: String, options: Vec<String> },
+    FreeText { prompt: String, max_len: usize },
+    Likert { prompt: String, points: u8 }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SurveyDefinition {
+    pub title: String,
+    pub description: String,
+    pub questions: Vec<Question>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::nonce"))]
+    pub nonce: [u8; NONCE_BYTES],
+    // Signature (c, z) under vk_SA
+    pub signature: Proof
+}
+
+// One answer per question, in order
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Answer {
+    // Index of the chosen option
+    Choice(usize),
+    Text(String),
+    // Point on the scale, from 1
+    Scale(u8)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Response {
+    pub answers: Vec<Answer>
+}
+
+impl SurveyDefinition {
+
+    // Define and sign a survey with the SA's keys (see SurveyAuthority::define_survey)
+    pub(crate) fn sign(title: &str, description: &str, questions: Vec<Question>, sa: &impl KeyHolder,
+                       g: G1, g2: G2, rng: &mut (impl RngCore + CryptoRng)) -> Result<Self, SurveyError> {
+        let invalid = |what: &str| Err(SurveyError::InvalidDefinition(String::from(what)));
+        if questions.is_empty() {
+            return invalid("survey has no questions");
+        }
+        for question in &questions {
+            match question {
+                Question::MultipleChoice { options, .. } if options.len() < 2 => return invalid("multiple choice needs at least two options"),
+                Question::Likert { points, .. } if *points < 2 => return invalid("scale needs at least two points"),
+                _ => ()
+            }
+        }
+        let mut nonce = [0u8; NONCE_BYTES];
+        rng.fill_bytes(&mut nonce);
+        let mut definition = SurveyDefinition {
+            title: String::from(title),
+            description: String::from(description),
+            questions,
+            nonce,
+            signature: Proof { c: Fr::zero(), z: Vec::new() }
+        };
+        definition.signature = (*sa).sign_body(DEFINITION_DOMAIN, g, g2, &definition.body());
+        Ok(definition)
+    }
+
+    // The vid the SA with key vk_sa derives from this definition
+    pub fn vid(&self, vk_sa: &VerificationKey) -> Fr {
+        survey_id(&(*self).content(), vk_sa, &(*self).nonce)
+    }
+
+    // The definition was signed by the SA with key vk_sa, and the survey is the one it defines
+    pub fn verify(&self, survey: &Survey, vk_sa: &VerificationKey, g: G1, g2: G2) -> Result<(), SurveyError> {
+        if !verify_gt(DEFINITION_DOMAIN, g, g2, (*vk_sa).pk, &(*self).body(), &(*self).signature) {
+            return Err(SurveyError::InvalidSignature);
+        }
+        if (*self).vid(vk_sa) != (*survey).vid {
+            return Err(SurveyError::InvalidDefinition(String::from("survey id was not derived from the definition")));
+        }
+        Ok(())
+    }
+
+    // The response answers every question, each within what the question allows
+    pub fn validate(&self, response: &Response) -> Result<(), SurveyError> {
+        let invalid = |what: String| Err(SurveyError::InvalidResponse(what));
+        if (*response).answers.len() != (*self).questions.len() {
+            return invalid(format!("{} answers to {} questions", (*response).answers.len(), (*self).questions.len()));
+        }
+        for (i, (question, answer)) in (*self).questions.iter().zip((*response).answers.iter()).enumerate() {
+            let valid:bool = match (question, answer) {
+                (Question::MultipleChoice { options, .. }, Answer::Choice(choice)) => *choice < options.len(),
+                (Question::FreeText { max_len, .. }, Answer::Text(text)) => text.len() <= *max_len,
+                (Question::Likert { points, .. }, Answer::Scale(point)) => *point >= 1 && *point <= *points,
+                _ => false
+            };
+            if !valid {
+                return invalid(format!("answer {} does not fit its question", i + 1));
+            }
+        }
+        Ok(())
+    }
+
+    // Title, description and questions, length-prefixed
+    fn content(&self) -> Vec<u8> {
+        let mut content:Vec<u8> = Vec::new();
+        push_bytes(&mut content, (*self).title.as_bytes());
+        push_bytes(&mut content, (*self).description.as_bytes());
+        content.extend_from_slice(&((*self).questions.len() as u64).to_be_bytes());
+        for question in &(*self).questions {
+            match question {
+                Question::MultipleChoice { prompt, options } => {
+                    content.push(0);
+                    push_bytes(&mut content, prompt.as_bytes());
+                    content.extend_from_slice(&(options.len() as u64).to_be_bytes());
+                    for option in options {
+                        push_bytes(&mut content, option.as_bytes());
+                    }
+                },
+                Question::FreeText { prompt, max_len } => {
+                    content.push(1);
+                    push_bytes(&mut content, prompt.as_bytes());
+                    content.extend_from_slice(&(*max_len as u64).to_be_bytes());
+                },
+                Question::Likert { prompt, points } => {
+                    content.push(2);
+                    push_bytes(&mut content, prompt.as_bytes());
+                    content.push(*points);
+                }
+            }
+        }
+        content
+    }
+
+    // What the SA signs: the content and the nonce, i.e. everything the vid is derived from
+    fn body(&self) -> Vec<u8> {
+        let mut body:Vec<u8> = (*self).content();
+        body.extend_from_slice(&(*self).nonce);
+        body
+    }
+}
+
+impl Response {
+
+    // The message submitted for the survey: one tagged answer after another
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes:Vec<u8> = Vec::new();
+        for answer in &(*self).answers {
+            match answer {
+                Answer::Choice(choice) => {
+                    bytes.push(0);
+                    bytes.extend_from_slice(&(*choice as u64).to_be_bytes());
+                },
+                Answer::Text(text) => {
+                    bytes.push(1);
+                    push_bytes(&mut bytes, text.as_bytes());
+                },
+                Answer::Scale(point) => {
+                    bytes.push(2);
+                    bytes.push(*point);
+                }
+            }
+        }
+        bytes
+    }
+
+    // A submitted message back into a response (None if it is not one)
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut answers:Vec<Answer> = Vec::new();
+        let mut rest:&[u8] = bytes;
+        while let Some((tag, tail)) = rest.split_first() {
+            let (answer, tail) = match tag {
+                0 => {
+                    let (choice, tail) = take_u64(tail)?;
+                    (Answer::Choice(choice as usize), tail)
+                },
+                1 => {
+                    let (len, tail) = take_u64(tail)?;
+                    if (tail.len() as u64) < len {
+                        return None;
+                    }
+                    let (text, tail) = tail.split_at(len as usize);
+                    (Answer::Text(String::from_utf8(text.to_vec()).ok()?), tail)
+                },
+                2 => {
+                    let (point, tail) = tail.split_first()?;
+                    (Answer::Scale(*point), tail)
+                },
+                _ => return None
+            };
+            answers.push(answer);
+            rest = tail;
+        }
+        Some(Response { answers })
+    }
+}
+
+// A big-endian u64 off the front of bytes, and what follows it
+fn take_u64(bytes: &[u8]) -> Option<(u64, &[u8])> {
+    if bytes.len() < 8 {
+        return None;
+    }
+    let (word, rest) = bytes.split_at(8);
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(word);
+    Some((u64::from_be_bytes(buf), rest))
+}
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use crate::users::{User, SurveyAuthority, RegistrationAuthority};
+#[cfg(test)]
+use crate::params::SystemParams;
+#[cfg(test)]
+use crate::submission::check_submission;
+
+#[cfg(test)]
+fn course_feedback() -> Vec<Question> {
+    vec![
+        Question::MultipleChoice { prompt: String::from("Which track did you take?"), options: vec![String::from("Systems"), String::from("Theory")] },
+        Question::Likert { prompt: String::from("The course was well organized"), points: 5 },
+        Question::FreeText { prompt: String::from("Anything else?"), max_len: 200 }
+    ]
+}
+
+// Participants can check the definition behind a survey, and answer it with a valid response
+#[test]
+fn test_survey_definition() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let other_sa = SurveyAuthority::new(&params);
+    let mut alice = User::new();
+    alice.reg_user(&mut ra).expect("Registration failed");
+
+    let definition = sa.define_survey("Course feedback", "End of term", course_feedback(), g, g2).expect("Definition was refused");
+    let survey = sa.gen_defined_survey(&definition, &vec![alice.id()], g, g2, &ra.vk).expect("SA survey creation failed!");
+    assert!( definition.verify(&survey, &sa.vk, g, g2) == Ok(()) );
+    assert!( definition.verify(&survey, &other_sa.vk, g, g2) == Err(SurveyError::InvalidSignature) );
+    let mut edited = definition.clone();
+    edited.questions.pop();
+    assert!( edited.verify(&survey, &sa.vk, g, g2) == Err(SurveyError::InvalidSignature) );
+    let other = sa.define_survey("Course feedback", "End of term", course_feedback(), g, g2).unwrap();
+    assert!( other.verify(&survey, &sa.vk, g, g2).is_err() );
+
+    let response = Response { answers: vec![Answer::Choice(1), Answer::Scale(4), Answer::Text(String::from("More exercises"))] };
+    assert!( Response::from_bytes(&response.to_bytes()) == Some(response.clone()) );
+    let submission = alice.submit_survey(&survey, &response.to_bytes(), (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    assert!( check_submission(survey.vid, &submission, &sa.vk, &ra.vk, g, g2) );
+    assert!( definition.validate(&Response::from_bytes(submission.answer()).unwrap()) == Ok(()) );
+}
+
+// Definitions need questions that can be answered, and responses must fit them
+#[test]
+fn test_response_validation() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let sa = SurveyAuthority::new(&params);
+    assert!( sa.define_survey("Empty", "", vec![], g, g2).is_err() );
+    assert!( sa.define_survey("Scale", "", vec![Question::Likert { prompt: String::new(), points: 1 }], g, g2).is_err() );
+    let definition = sa.define_survey("Course feedback", "", course_feedback(), g, g2).unwrap();
+
+    let text = |s: &str| Answer::Text(String::from(s));
+    for answers in [
+        vec![Answer::Choice(0), Answer::Scale(5)],
+        vec![Answer::Choice(2), Answer::Scale(5), text("")],
+        vec![Answer::Choice(0), Answer::Scale(0), text("")],
+        vec![Answer::Choice(0), Answer::Scale(6), text("")],
+        vec![Answer::Choice(0), Answer::Scale(5), text(&"x".repeat(201))],
+        vec![Answer::Scale(1), Answer::Choice(0), text("")]
+    ] {
+        assert!( definition.validate(&Response { answers }).is_err() );
+    }
+    assert!( definition.validate(&Response { answers: vec![Answer::Choice(0), Answer::Scale(5), text(&"x".repeat(200))] }) == Ok(()) );
+    assert!( Response::from_bytes(&[1, 0, 0, 0, 0, 0, 0, 0, 9, b'x']).is_none() && Response::from_bytes(&[3]).is_none() );
+}
@@ -0,0 +1,247 @@
+extern crate anon_survey;
+extern crate tbn;
+extern crate rand;
+extern crate hex;
+
+use crate::exit::{self, Failure};
+use anon_survey::{User, SurveyAuthority, RegistrationAuthority, SystemParams, to_hex_string, authorized, SubmissionLedger, Survey, GtElement};
+use anon_survey::ledger::COUNT_BATCH;
+
+use tbn::{Group, Fq, G1, Fq2, G2, Fr};
+use anon_survey::gt::pairing;
+use tbn::arith::U256;
+
+use hex::FromHex;
+use std::time::Duration;
+
+/*
+ * ----------------------------------------------
+ * |    DEMO                                    |
+ * ----------------------------------------------
+ *
+ * One process playing every party: prints the curve parameters, then runs registration, survey
+ * creation, and submission end to end (`anon_survey` without a subcommand).
+*/
+
+pub fn run() {
+
+    /* ------------------------------------------------------------------------------
+     *                          Barreto-Naehrig (BN) Curves                         
+     * 
+     * Pairing-friendly bilinear elliptic curve (see code for in-depth description)
+     *
+     * Sources:
+     *  - Barreto-Naehrig Curves (Kasamatsu et al., 2014)
+     *      https://tools.ietf.org/id/draft-kasamatsu-bncurves-01.html
+     *  - A Family of Implementation-Friendly BN Elliptic Curves (Pereira et al., 2011)
+     *      https://eprint.iacr.org/2010/429.pdf
+     *
+     * ------------------------------------------------------------------------------
+     */
+
+    const BN_BYTES:usize = 32;
+    println!("256-bit Barreto-Naehrig curve (Fp256BN):");
+    println!();
+    println!("BN curves are bilinear pairings e : G1 × G2 -> Gt with:");
+    let p:U256 = Fq::modulus();
+    println!("\tp (prime modulus for elliptic curves) = 0x{}", to_hex_string(p));
+
+    // Known q parameter (prime order of G1) for 256-bit BN curve (Kasamatsu et al., 2014)
+    let q_hex = String::from("fffffffffffcf0cd46e5f25eee71a49e0cdc65fb1299921af62d536cd10b500d");
+    let q_slice = <[u8; BN_BYTES]>::from_hex(q_hex.clone()).expect("Could not decode q");
+    let q = U256::from_slice(&q_slice).expect("Could not convert q to U256"); 
+    println!("\tq (prime order of G1, G2, and Gt) = 0x{}", q_hex);
+
+    // TODO: Figure out what z does in G1 and G2
+    
+    println!("\tG1 = E/𝔽_q is a q-order additive cyclic subgroup of E(𝔽_p), where E : y^2 = x^3 + b\tmod p is an elliptic curve with:");
+    println!("\t\t(x,y) ∈ E(𝔽_p) (base point):");
+    let x:U256 = G1::one().x().into_u256();
+    println!("\t\t\tx = 0x{}", to_hex_string(x));
+    let y:U256 = G1::one().y().into_u256();
+    println!("\t\t\ty = 0x{}", to_hex_string(y));
+    let b:U256 = G1::b().into_u256();
+    println!("\t\tb ∈ 𝔽_p (constant coefficient) = 0x{}", to_hex_string(b));
+    println!();
+    
+    println!("\tG2 = E'/𝔽_q2 is an additive cyclic subgroup of E(𝔽_{{p^k}}), where E' : y^2 = x^3 + b/xi\tmod p  is a twisted elliptic curve with:");
+    let mut k_slice:[u8;BN_BYTES] = [0;BN_BYTES];
+    k_slice[BN_BYTES-1] = 12;
+    let k:U256 = U256::from_slice(&k_slice).expect("Could not convert k to U256");
+        println!("\t\tk (embedding degree of G2) = {}", to_hex_string(k));
+
+    println!("\t\t(x,y) ∈ E(𝔽_{{p^k}}), (base point):");
+
+    let base_pt:(Fq2, Fq2) = (G2::one().x(), G2::one().y());
+    let x2_real:U256 = base_pt.0.real().into_u256();
+    let x2_i:U256 = base_pt.0.imaginary().into_u256();
+    println!("\t\t\tx = 0x{} + 0x{} i", to_hex_string(x2_real), to_hex_string(x2_i));
+    let y2_real:U256 = base_pt.1.real().into_u256();
+    let y2_i:U256 = base_pt.1.imaginary().into_u256();
+    println!("\t\t\ty = 0x{} + 0x{} i", to_hex_string(y2_real), to_hex_string(y2_i));
+    let b2_real:U256 = G2::b().real().into_u256();
+    let b2_i:U256 = G2::b().imaginary().into_u256();    
+    println!("\t\tb' ∈ 𝔽_q2 (constant coefficient) = 0x{} + 0x{} i", to_hex_string(b2_real), to_hex_string(b2_i));
+    println!();
+
+    println!("With these parameters, e returns a element in the multiplicative group Gt with the same order as G2");
+    println!();
+
+    let params = SystemParams::random();
+    let (g, g2):(G1, G2) = params.generators();
+    println!("g ∈ G1 (generator) = {:?}", g);
+    println!("g2 ∈ G2 (generator) = {:?}", g2);
+
+    println!("Then, we can compute e(g, g2) ∈ Gt (generator) = {}", GtElement(pairing(g, g2)));
+    println!();
+    println!();
+    
+    
+    
+    /* ------------------------------------------------------------------------------
+     *                                  GenRA                                       
+     * ------------------------------------------------------------------------------
+     */
+
+    // Instantiate new Registration Authority
+    println!("Generating signature-verification key pair (x, vk_RA) for Registration Authority (RA)...");
+    let mut ra:RegistrationAuthority = RegistrationAuthority::new(&params);
+    println!("sk_RA = x ∈ ℤ_q = (secret signature key)");
+    println!("vk_RA.u ∈ G1 = {:?}", ra.vk.u);
+    println!("vk_RA.v ∈ G1 = {:?}", ra.vk.v);
+    println!("vk_RA.h ∈ G1 = {:?}", ra.vk.h);
+    println!();
+
+
+    
+    /* ------------------------------------------------------------------------------
+     *                                  GenSA                                       
+     * ------------------------------------------------------------------------------
+     */
+
+    // Instantiate new Survey Authority
+    println!("Generating signature-verification key pair (y, vk_SA) for Survey Authority (SA)...");
+    let mut sa = SurveyAuthority::new(&params); 
+    println!("sk_SA = y ∈ ℤ_q = (secret signature key)");
+    println!("vk_SA.u ∈ G1 = {:?}", sa.vk.u);
+    println!("vk_SA.v ∈ G1 = {:?}", sa.vk.v);
+    println!("vk_SA.h ∈ G1 = {:?}", sa.vk.h);
+    println!();
+    
+
+    /* ------------------------------------------------------------------------------
+     *                                  ***NOTE***                                  
+     * The setup of every exchange between the users is NOT supposed to go
+     * through a central or third party like it is here. This was done only as a
+     * proof-of-concept and would likely VIOLATE ANONYMITY in production code.
+     * A proper implementation of ANONIZE should (at least) establish private 
+     * connections between all users, and ESPECIALLY an anonymous connection
+     * between +
+     * ------------------------------------------------------------------------------
+    */
+    
+    // Initialize 5 users in the userbase and register their ID with the RA
+    let mut userbase:Vec<User> = Vec::new();
+    for _ in 0..5 {
+        let mut new_user = User::new();
+        if let Err(e) = new_user.reg_user(&mut ra) {
+            exit::fail(Failure::ProtocolError, &format!("RegUser: {}", e));
+        }
+        userbase.push(new_user);
+    }
+    // Just for fun, some users will change their identities
+    for i in &[0, 3] {
+        if let Err(e) = userbase[*i].re_identify(&mut ra) {
+            exit::fail(Failure::ProtocolError, &format!("RegUser: {}", e));
+        }
+    }
+
+    // A user without a device of their own is handed a printed one-time code by the RA, and
+    // redeems it later from any client to complete registration
+    let reg_code = ra.issue_registration_code("no-device cohort", Duration::from_secs(7 * 24 * 60 * 60));
+    println!("Printed one-time registration code: {} (metadata: {})", reg_code.code, reg_code.metadata);
+    let mut offline_user = User::new();
+    match offline_user.redeem_code(&reg_code.code, &mut ra) {
+        Ok(_) => userbase.push(offline_user),
+        Err(e) => println!("Could not redeem registration code: {}", e)
+    }
+    println!();
+
+    // The RA only keeps hashes of the ids, and hands them out for audits
+    println!("Anonymity set (audit export):");
+    for id_hash in ra.audit_anonymity_set() {
+        println!("H(id) ∈ ℤ_q : {:?}", id_hash);
+    }
+    println!();
+
+    /* ------------------------------------------------------------------------------
+     *                                  GenSurvey                                       
+     * ------------------------------------------------------------------------------
+     */
+    // Users hand their ids to the SA themselves. Could theoretically choose a list of any ids, even
+    // for users who have not yet registered with the RA.
+    let rng = &mut rand::thread_rng();
+    let unregistered_userid = Fr::random(rng);
+    let mut part_list:Vec<Fr> = userbase.iter().map(|user| user.id()).collect();
+    println!("Unregistered user with id ∈ ℤ_q : {:?}", unregistered_userid);
+    part_list.push(unregistered_userid);
+    println!();
+
+    println!("SA: Generating survey signatures for {} potential users...", part_list.len());
+    let survey:Survey = sa.gen_survey(&part_list, g, g2, &ra.vk)
+        .unwrap_or_else(|e| exit::fail(Failure::ProtocolError, &format!("GenSurvey: {}", e)));
+    println!("Ad-hoc survey generated:");
+    println!("\tvid ∈ ℤ_q (survey ID) = {:?}", survey.vid);
+    println!("\tList of authorized users:");
+    for entry in survey.participants() {
+        println!("\t\tParticipant id:\t{:?}", entry.id);
+        println!();
+        println!("\t\t\t(σ1, σ2) ∈ G1 × G2 (SA signature for participant) = ({:?}, {:?})", entry.sigma_1, entry.sigma_2);
+        print!("\t\t\tAuthorized... ");
+        match authorized(entry.id, &survey, &sa.vk, &ra.vk, g2) {
+            true    => println!("\u{2713}"),    // Checkmark    (yes!)
+            false   => println!("\u{2717}")     // X mark       (no!)
+        }
+        println!();
+    }
+    println!();
+
+    /* ------------------------------------------------------------------------------
+     *                                  SubmitSurvey                                       
+     * ------------------------------------------------------------------------------
+     */
+    // The SA keeps a ledger of accepted submissions, so every token is only accepted once
+    let mut ledger = SubmissionLedger::new();
+    for (i, user) in userbase.iter().enumerate() {
+        println!("User #{}: Submitting survey response...", i+1);
+        match user.submit_survey(&survey, b"yes", (g, g2), &sa.vk, &ra.vk) {
+            Ok(submission) => {
+                println!("\tTok ∈ G1 (one-time token) = {:?}", submission.token);
+                println!("\tproof challenge c ∈ ℤ_q = {:?}", submission.proof.c);
+                print!("\tSA: Check... ");
+                match ledger.accept(survey.vid, submission, &sa.vk, &ra.vk, g, g2) {
+                    Ok(())  => println!("\u{2713}"),
+                    Err(e)  => println!("\u{2717} ({})", e)
+                }
+            },
+            Err(e) => println!("\t\u{2717} ({})", e)
+        }
+    }
+    // A second response from the same user carries the same token and is turned away
+    println!("User #1: Submitting a second survey response...");
+    if let Ok(submission) = userbase[0].submit_survey(&survey, b"no", (g, g2), &sa.vk, &ra.vk) {
+        print!("\tSA: Check... ");
+        match ledger.accept(survey.vid, submission, &sa.vk, &ra.vk, g, g2) {
+            Ok(())  => println!("\u{2713}"),
+            Err(e)  => println!("\u{2717} ({})", e)
+        }
+    }
+    println!("SA: {} responses accepted for survey", ledger.submissions(survey.vid).len());
+    println!("SA: Published response count (batches of {}): {}", COUNT_BATCH, ledger.published_count(survey.vid, COUNT_BATCH));
+    println!();
+
+
+    // TODO: Have all users run on separate threads for efficiency
+
+    println!();
+}
@@ -0,0 +1,373 @@
+extern crate tbn;
+extern crate hex;
+
+use std::fmt;
+use tbn::{Group, Fr, Fq, Fq2, G1, G2, AffineG1, AffineG2};
+use ark_ec::pairing::PairingOutput;
+use ark_ff::{BigInteger, PrimeField, Zero};
+use ark_serialize::Valid;
+
+use crate::ct::ConstantTimeEq;
+use crate::codec::u256_to_be_bytes;
+use crate::gt::Gt;
+
+/*
+ * ----------------------------------------------
+ * |    WIRE ENCODINGS                          |
+ * ----------------------------------------------
+ *
+ * Fixed-width big-endian encodings of the tbn primitives, so protocol objects can be sent between
+ * real parties:
+ *      - Fr:   32 bytes
+ *      - G1:   64 bytes, affine x ‖ y (all zero for the point at infinity, which is not on the curve)
+ *      - G2:  128 bytes, affine x.real ‖ x.imaginary ‖ y.real ‖ y.imaginary (all zero at infinity)
+ *      - Gt:  384 bytes, the twelve Fq coefficients of the Fq12 element in tbn's tower order
+ *             (c0.c0.c0, c0.c0.c1, c0.c1.c0, ..., c1.c2.c1), read through arkworks, which
+ *             computes the crate's pairings (see gt.rs)
+ * Decoding rejects anything out of range or off the curve, and for Gt anything outside the
+ * order-q subgroup (including zero).
+ *
+ * GtElement wraps a Gt so pairing results can be printed, compared, and sent like the other types.
+ *
+ * With the `serde` feature, the submodules fr, g1, g2, gt (and fr_vec, gt_vec for lists) plug
+ * these into #[serde(with = "...")]: hex strings for human-readable formats, raw bytes otherwise.
+*/
+
+pub const FR_BYTES:usize = 32;
+pub const G1_BYTES:usize = 64;
+pub const G2_BYTES:usize = 128;
+pub const GT_BYTES:usize = 384;
+
+fn u256_bytes(n: tbn::arith::U256) -> Vec<u8> {
+    u256_to_be_bytes(n).to_vec()
+}
+
+pub fn fr_to_bytes(x: Fr) -> Vec<u8> {
+    u256_bytes(x.into_u256())
+}
+
+pub fn fr_from_bytes(bytes: &[u8]) -> Option<Fr> {
+    if bytes.len() != FR_BYTES {
+        return None;
+    }
+    // from_slice silently reduces mod q, so only accept the canonical encoding
+    let x:Fr = Fr::from_slice(bytes).ok()?;
+    match fr_to_bytes(x) == bytes {
+        true => Some(x),
+        false => None
+    }
+}
+
+pub fn g1_to_bytes(p: G1) -> Vec<u8> {
+    match AffineG1::from_jacobian(p) {
+        Some(a) => [u256_bytes(a.x().into_u256()), u256_bytes(a.y().into_u256())].concat(),
+        None => vec![0; G1_BYTES]
+    }
+}
+
+pub fn g1_from_bytes(bytes: &[u8]) -> Option<G1> {
+    if bytes.len() != G1_BYTES {
+        return None;
+    }
+    if bytes.iter().all(|b| *b == 0) {
+        return Some(G1::zero());
+    }
+    let x:Fq = Fq::from_slice(&bytes[0..32]).ok()?;
+    let y:Fq = Fq::from_slice(&bytes[32..64]).ok()?;
+    AffineG1::new(x, y).ok().map(G1::from)
+}
+
+pub fn g2_to_bytes(p: G2) -> Vec<u8> {
+    match AffineG2::from_jacobian(p) {
+        Some(a) => {
+            let mut bytes:Vec<u8> = Vec::new();
+            for coord in &[a.x(), a.y()] {
+                bytes.extend(u256_bytes(coord.real().into_u256()));
+                bytes.extend(u256_bytes(coord.imaginary().into_u256()));
+            }
+            bytes
+        },
+        None => vec![0; G2_BYTES]
+    }
+}
+
+pub fn g2_from_bytes(bytes: &[u8]) -> Option<G2> {
+    if bytes.len() != G2_BYTES {
+        return None;
+    }
+    if bytes.iter().all(|b| *b == 0) {
+        return Some(G2::zero());
+    }
+    let fq = |i: usize| Fq::from_slice(&bytes[32 * i..32 * (i + 1)]).ok();
+    let x:Fq2 = Fq2::new(fq(0)?, fq(1)?);
+    let y:Fq2 = Fq2::new(fq(2)?, fq(3)?);
+    AffineG2::new(x, y).ok().map(G2::from)
+}
+
+// Fq12 coefficients in tbn's tower order c0.c0.c0, c0.c0.c1, c0.c1.c0, ..., c1.c2.c1 (see gt.rs)
+fn gt_coeffs(f: &mut ark_bn254::Fq12) -> [&mut ark_bn254::Fq; 12] {
+    let [a, b] = [&mut (*f).c0, &mut (*f).c1];
+    [&mut a.c0.c0, &mut a.c0.c1, &mut a.c1.c0, &mut a.c1.c1, &mut a.c2.c0, &mut a.c2.c1,
+     &mut b.c0.c0, &mut b.c0.c1, &mut b.c1.c0, &mut b.c1.c1, &mut b.c2.c0, &mut b.c2.c1]
+}
+
+pub fn gt_to_bytes(t: Gt) -> Vec<u8> {
+    let mut f:ark_bn254::Fq12 = (t.0).0;
+    gt_coeffs(&mut f).iter().flat_map(|a| a.into_bigint().to_bytes_be()).collect()
+}
+
+pub fn gt_from_bytes(bytes: &[u8]) -> Option<Gt> {
+    if bytes.len() != GT_BYTES {
+        return None;
+    }
+    let mut f:ark_bn254::Fq12 = ark_bn254::Fq12::zero();
+    for (coeff, slot) in bytes.chunks(32).zip(gt_coeffs(&mut f)) {
+        // from_be_bytes_mod_order silently reduces mod p, so only accept the canonical encoding
+        *slot = ark_bn254::Fq::from_be_bytes_mod_order(coeff);
+        if (*slot).into_bigint().to_bytes_be() != coeff {
+            return None;
+        }
+    }
+    // Only the order-q subgroup is Gt (t^q must be 1), which also rules out zero
+    let t:PairingOutput<ark_bn254::Bn254> = PairingOutput(f);
+    match t.check() {
+        Ok(()) => Some(Gt(t)),
+        Err(_) => None
+    }
+}
+
+// A Gt that prints as the hex of its coefficients (tbn's Gt has no Debug), e.g. to log pk or e(g, g2)
+#[derive(Clone, Copy)]
+pub struct GtElement(pub Gt);
+
+impl GtElement {
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        gt_to_bytes((*self).0)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        gt_from_bytes(bytes).map(GtElement)
+    }
+}
+
+impl From<Gt> for GtElement {
+    fn from(t: Gt) -> Self {
+        GtElement(t)
+    }
+}
+
+impl fmt::Display for GtElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", hex::encode((*self).to_bytes()))
+    }
+}
+
+// In constant time (see ct.rs)
+impl PartialEq for GtElement {
+    fn eq(&self, other: &GtElement) -> bool {
+        (*self).ct_eq(other).into()
+    }
+}
+
+impl fmt::Debug for GtElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "GtElement({})", self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for GtElement {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        gt::serialize(&(*self).0, s)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for GtElement {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        gt::deserialize(d).map(GtElement)
+    }
+}
+
+
+/*
+ * Serde adapters
+ */
+
+#[cfg(feature = "serde")]
+pub use self::adapters::{fr, fr_vec, g1, g2, gt, gt_vec, nonce};
+
+#[cfg(feature = "serde")]
+mod adapters {
+    extern crate serde;
+
+    use serde::{Serializer, Deserializer, Deserialize};
+    use serde::de::Error;
+    use tbn::Fr;
+
+    // Hex string for human-readable formats, raw bytes otherwise
+    pub(crate) fn serialize_bytes<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        if s.is_human_readable() {
+            s.serialize_str(&hex::encode(bytes))
+        } else {
+            s.serialize_bytes(bytes)
+        }
+    }
+
+    pub(crate) fn deserialize_bytes<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        if d.is_human_readable() {
+            let s = String::deserialize(d)?;
+            hex::decode(&s).map_err(D::Error::custom)
+        } else {
+            Vec::<u8>::deserialize(d)
+        }
+    }
+
+    macro_rules! adapter {
+        ($name:ident, $ty:ty, $to:path, $from:path, $what:expr) => {
+            pub mod $name {
+                use super::*;
+
+                pub fn serialize<S: Serializer>(x: &$ty, s: S) -> Result<S::Ok, S::Error> {
+                    serialize_bytes(&$to(*x), s)
+                }
+
+                pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<$ty, D::Error> {
+                    let bytes = deserialize_bytes(d)?;
+                    $from(&bytes).ok_or_else(|| D::Error::custom(concat!("invalid ", $what)))
+                }
+            }
+        };
+    }
+
+    adapter!(fr, tbn::Fr, super::super::fr_to_bytes, super::super::fr_from_bytes, "element of Fr");
+    adapter!(g1, tbn::G1, super::super::g1_to_bytes, super::super::g1_from_bytes, "point of G1");
+    adapter!(g2, tbn::G2, super::super::g2_to_bytes, super::super::g2_from_bytes, "point of G2");
+    adapter!(gt, crate::gt::Gt, super::super::gt_to_bytes, super::super::gt_from_bytes, "element of Gt");
+
+    // Fixed-length random bytes, e.g. the nonce of a survey description
+    pub mod nonce {
+        use super::*;
+        use std::convert::TryFrom;
+        use crate::hash::NONCE_BYTES;
+
+        pub fn serialize<S: Serializer>(nonce: &[u8; NONCE_BYTES], s: S) -> Result<S::Ok, S::Error> {
+            serialize_bytes(nonce, s)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<[u8; NONCE_BYTES], D::Error> {
+            let bytes = deserialize_bytes(d)?;
+            <[u8; NONCE_BYTES]>::try_from(&bytes[..]).map_err(|_| D::Error::custom("invalid nonce"))
+        }
+    }
+
+    // Vec<Fr>, e.g. the responses of a proof
+    pub mod fr_vec {
+        use super::*;
+        use serde::ser::SerializeSeq;
+
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Wrapped(#[serde(with = "super::fr")] Fr);
+
+        pub fn serialize<S: Serializer>(xs: &Vec<Fr>, s: S) -> Result<S::Ok, S::Error> {
+            let mut seq = s.serialize_seq(Some(xs.len()))?;
+            for x in xs {
+                seq.serialize_element(&Wrapped(*x))?;
+            }
+            seq.end()
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<Fr>, D::Error> {
+            let wrapped = Vec::<Wrapped>::deserialize(d)?;
+            Ok(wrapped.into_iter().map(|Wrapped(x)| x).collect())
+        }
+    }
+
+    // Vec<Gt>, e.g. the share keys of a threshold RA
+    pub mod gt_vec {
+        use super::*;
+        use serde::ser::SerializeSeq;
+
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Wrapped(#[serde(with = "super::gt")] crate::gt::Gt);
+
+        pub fn serialize<S: Serializer>(xs: &Vec<crate::gt::Gt>, s: S) -> Result<S::Ok, S::Error> {
+            let mut seq = s.serialize_seq(Some(xs.len()))?;
+            for x in xs {
+                seq.serialize_element(&Wrapped(*x))?;
+            }
+            seq.end()
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<crate::gt::Gt>, D::Error> {
+            let wrapped = Vec::<Wrapped>::deserialize(d)?;
+            Ok(wrapped.into_iter().map(|Wrapped(x)| x).collect())
+        }
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use crate::gt::pairing;
+#[cfg(test)]
+use crate::get_generator_pair;
+
+// Every encoding round-trips, including the points at infinity
+#[test]
+fn test_encoding_round_trip() {
+    let rng = &mut rand::thread_rng();
+    let (x, p, q):(Fr, G1, G2) = (Fr::random(rng), G1::random(rng), G2::random(rng));
+    let t:Gt = pairing(p, q);
+    assert!( fr_from_bytes(&fr_to_bytes(x)) == Some(x) );
+    assert!( g1_from_bytes(&g1_to_bytes(p)) == Some(p) );
+    assert!( g2_from_bytes(&g2_to_bytes(q)) == Some(q) );
+    assert!( gt_from_bytes(&gt_to_bytes(t)) == Some(t) );
+    assert!( g1_from_bytes(&g1_to_bytes(G1::zero())) == Some(G1::zero()) );
+    assert!( g2_from_bytes(&g2_to_bytes(G2::zero())) == Some(G2::zero()) );
+}
+
+// Wrong lengths, out-of-range coordinates, and points off the curve are rejected
+#[test]
+fn test_encoding_rejects_invalid() {
+    let rng = &mut rand::thread_rng();
+    let mut bytes = g1_to_bytes(G1::random(rng));
+    assert!( g1_from_bytes(&bytes[1..]).is_none() );
+    bytes[63] ^= 1;
+    assert!( g1_from_bytes(&bytes).is_none() );
+    assert!( g2_from_bytes(&[0xff; G2_BYTES]).is_none() );
+    assert!( fr_from_bytes(&[0xff; FR_BYTES]).is_none() );
+    assert!( gt_from_bytes(&[0xff; GT_BYTES]).is_none() );
+}
+
+// Zero and reduced Fq12 values outside the order-q subgroup do not decode as Gt
+#[test]
+fn test_gt_rejects_non_subgroup() {
+    let (g, g2) = get_generator_pair();
+    assert!( gt_from_bytes(&[0; GT_BYTES]).is_none() );
+    let mut two:Vec<u8> = vec![0; GT_BYTES];
+    two[31] = 2;
+    assert!( gt_from_bytes(&two).is_none() );
+    let mut bytes:Vec<u8> = gt_to_bytes(pairing(g, g2));
+    bytes[GT_BYTES - 1] ^= 1;
+    assert!( gt_from_bytes(&bytes).is_none() );
+}
+
+// Gt encodes as its plain coefficients: the identity is 1, 0, ..., 0, and GtElement prints them in hex
+#[test]
+fn test_gt_element() {
+    let mut one:Vec<u8> = vec![0; GT_BYTES];
+    one[31] = 1;
+    assert!( gt_to_bytes(Gt::one()) == one );
+    assert!( GtElement::from_bytes(&one) == Some(GtElement(Gt::one())) );
+
+    let (g, g2) = get_generator_pair();
+    let t = GtElement::from(pairing(g, g2));
+    assert!( GtElement::from_bytes(&t.to_bytes()) == Some(t) && t != GtElement(Gt::one()) );
+    assert!( t.to_string() == hex::encode(t.to_bytes()) && t.to_string().len() == 2 * GT_BYTES );
+    assert!( format!("{:?}", t) == format!("GtElement({})", t) );
+}
@@ -0,0 +1,364 @@
+extern crate tbn;
+extern crate rand;
+
+use std::fmt::Debug;
+use std::ops::{Add, Sub, Mul, Neg};
+use rand::{RngCore, CryptoRng};
+use tbn::Group;
+
+use crate::cost;
+use crate::gt::Gt;
+use crate::encoding::{fr_to_bytes, fr_from_bytes, g1_to_bytes, g1_from_bytes, g2_to_bytes, g2_from_bytes, gt_to_bytes, gt_from_bytes};
+
+/*
+ * ----------------------------------------------
+ * |    PAIRING ENGINES                         |
+ * ----------------------------------------------
+ *
+ * The group operations of the SA's participant signatures (signing, and checking one or all of
+ * them, see survey.rs), behind one trait, so that path runs on other pairing libraries than tbn:
+ *      - Tbn: tbn's BN curve, with pairings into the Gt of gt.rs (what Survey uses)
+ *      - ArkBn254: the same curve from arkworks (feature `arkworks-bn254`)
+ *      - ArkBls12_381: BLS12-381 from arkworks, for about 128-bit security (feature `arkworks-bls12-381`)
+ *
+ * Nothing else goes through the trait. Credentials, submissions and their proofs (users/,
+ * submission.rs, proofs.rs, ...) call tbn directly and their messages hold tbn points, so the
+ * protocol as a whole runs on tbn's curve whichever features are enabled: an arkworks feature adds
+ * a backend for participant signatures, and does not select the crate's curve.
+ *
+ * Scalars and G1/G2 points use operators (+, -, negation, point · scalar), like tbn's. Gt is written
+ * multiplicatively, as in the paper, with gt_mul and gt_pow. Encodings are each backend's own: the
+ * fixed-width big-endian ones of encoding.rs for Tbn, and arkworks' compressed ones otherwise, so
+ * keys and messages do not carry over between engines. Decoding refuses anything non-canonical, or
+ * a value that is not in its group (including Gt, see encoding.rs for Tbn).
+ *
+ * Randomness still comes from rand 0.5 (the crate's rng, see users/mod.rs): arkworks backends
+ * reduce 64 random bytes modulo the group order, so the bias is negligible.
+*/
+
+pub trait PairingEngine {
+    // Curve and library, e.g. "bn254 (arkworks)"
+    const NAME:&'static str;
+
+    type Scalar: Copy + Debug + PartialEq + Add<Output = Self::Scalar> + Sub<Output = Self::Scalar>
+                 + Mul<Output = Self::Scalar> + Neg<Output = Self::Scalar>;
+    type G1: Copy + Debug + PartialEq + Add<Output = Self::G1> + Sub<Output = Self::G1> + Neg<Output = Self::G1>
+             + Mul<Self::Scalar, Output = Self::G1>;
+    type G2: Copy + Debug + PartialEq + Add<Output = Self::G2> + Sub<Output = Self::G2> + Neg<Output = Self::G2>
+             + Mul<Self::Scalar, Output = Self::G2>;
+    type Gt: Copy + PartialEq;
+
+    fn scalar_random(rng: &mut (impl RngCore + CryptoRng)) -> Self::Scalar;
+    fn scalar_from_u64(n: u64) -> Self::Scalar;
+    // None for zero
+    fn scalar_inverse(x: Self::Scalar) -> Option<Self::Scalar>;
+    fn scalar_to_bytes(x: Self::Scalar) -> Vec<u8>;
+    fn scalar_from_bytes(bytes: &[u8]) -> Option<Self::Scalar>;
+
+    fn g1_generator() -> Self::G1;
+    fn g1_is_zero(p: Self::G1) -> bool;
+    fn g1_to_bytes(p: Self::G1) -> Vec<u8>;
+    fn g1_from_bytes(bytes: &[u8]) -> Option<Self::G1>;
+
+    fn g2_generator() -> Self::G2;
+    fn g2_is_zero(p: Self::G2) -> bool;
+    fn g2_to_bytes(p: Self::G2) -> Vec<u8>;
+    fn g2_from_bytes(bytes: &[u8]) -> Option<Self::G2>;
+
+    fn pairing(p: Self::G1, q: Self::G2) -> Self::Gt;
+    // Π e(p_i, q_i) in one product of Miller loops, with a single final exponentiation
+    fn multi_pairing(pairs: &[(Self::G1, Self::G2)]) -> Self::Gt;
+    fn gt_one() -> Self::Gt;
+    fn gt_mul(a: Self::Gt, b: Self::Gt) -> Self::Gt;
+    fn gt_pow(a: Self::Gt, x: Self::Scalar) -> Self::Gt;
+    fn gt_to_bytes(a: Self::Gt) -> Vec<u8>;
+    fn gt_from_bytes(bytes: &[u8]) -> Option<Self::Gt>;
+}
+
+// tbn's 254-bit BN curve
+#[derive(Clone, Copy, Debug)]
+pub struct Tbn;
+
+impl PairingEngine for Tbn {
+    const NAME:&'static str = "bn254 (tbn)";
+
+    type Scalar = tbn::Fr;
+    type G1 = tbn::G1;
+    type G2 = tbn::G2;
+    type Gt = Gt;
+
+    fn scalar_random(rng: &mut (impl RngCore + CryptoRng)) -> tbn::Fr {
+        tbn::Fr::random(rng)
+    }
+
+    fn scalar_from_u64(n: u64) -> tbn::Fr {
+        // Double and add, as tbn has no conversion from integers
+        (0..64).rev().fold(tbn::Fr::zero(), |acc, i| {
+            let acc = acc + acc;
+            if (n >> i) & 1 == 1 { acc + tbn::Fr::one() } else { acc }
+        })
+    }
+
+    fn scalar_inverse(x: tbn::Fr) -> Option<tbn::Fr> {
+        x.inverse()
+    }
+
+    fn scalar_to_bytes(x: tbn::Fr) -> Vec<u8> {
+        fr_to_bytes(x)
+    }
+
+    fn scalar_from_bytes(bytes: &[u8]) -> Option<tbn::Fr> {
+        fr_from_bytes(bytes)
+    }
+
+    fn g1_generator() -> tbn::G1 {
+        tbn::G1::one()
+    }
+
+    fn g1_is_zero(p: tbn::G1) -> bool {
+        p.is_zero()
+    }
+
+    fn g1_to_bytes(p: tbn::G1) -> Vec<u8> {
+        g1_to_bytes(p)
+    }
+
+    fn g1_from_bytes(bytes: &[u8]) -> Option<tbn::G1> {
+        g1_from_bytes(bytes)
+    }
+
+    fn g2_generator() -> tbn::G2 {
+        tbn::G2::one()
+    }
+
+    fn g2_is_zero(p: tbn::G2) -> bool {
+        p.is_zero()
+    }
+
+    fn g2_to_bytes(p: tbn::G2) -> Vec<u8> {
+        g2_to_bytes(p)
+    }
+
+    fn g2_from_bytes(bytes: &[u8]) -> Option<tbn::G2> {
+        g2_from_bytes(bytes)
+    }
+
+    // Counted with the cost-accounting feature, like the rest of the protocol's pairings (see cost.rs)
+    fn pairing(p: tbn::G1, q: tbn::G2) -> Gt {
+        cost::pairing(p, q)
+    }
+
+    fn multi_pairing(pairs: &[(tbn::G1, tbn::G2)]) -> Gt {
+        cost::pairing_batch(pairs)
+    }
+
+    fn gt_one() -> Gt {
+        Gt::one()
+    }
+
+    fn gt_mul(a: Gt, b: Gt) -> Gt {
+        a * b
+    }
+
+    fn gt_pow(a: Gt, x: tbn::Fr) -> Gt {
+        cost::pow(a, x)
+    }
+
+    fn gt_to_bytes(a: Gt) -> Vec<u8> {
+        gt_to_bytes(a)
+    }
+
+    fn gt_from_bytes(bytes: &[u8]) -> Option<Gt> {
+        gt_from_bytes(bytes)
+    }
+}
+
+// An arkworks curve as a PairingEngine: $name over ark's $curve (a Pairing), in crate $krate
+#[cfg(any(feature = "arkworks-bn254", feature = "arkworks-bls12-381"))]
+macro_rules! arkworks_engine {
+    ($name:ident, $label:expr, $curve:ty, $krate:ident) => {
+        #[derive(Clone, Copy, Debug)]
+        pub struct $name;
+
+        impl PairingEngine for $name {
+            const NAME:&'static str = $label;
+
+            type Scalar = $krate::Fr;
+            type G1 = $krate::G1Projective;
+            type G2 = $krate::G2Projective;
+            type Gt = ark_ec::pairing::PairingOutput<$curve>;
+
+            fn scalar_random(rng: &mut (impl RngCore + CryptoRng)) -> $krate::Fr {
+                let mut bytes = [0u8; 64];
+                rng.fill_bytes(&mut bytes);
+                ark_ff::PrimeField::from_le_bytes_mod_order(&bytes)
+            }
+
+            fn scalar_from_u64(n: u64) -> $krate::Fr {
+                $krate::Fr::from(n)
+            }
+
+            fn scalar_inverse(x: $krate::Fr) -> Option<$krate::Fr> {
+                ark_ff::Field::inverse(&x)
+            }
+
+            fn scalar_to_bytes(x: $krate::Fr) -> Vec<u8> {
+                ark_bytes(&x)
+            }
+
+            fn scalar_from_bytes(bytes: &[u8]) -> Option<$krate::Fr> {
+                ark_from_bytes(bytes)
+            }
+
+            fn g1_generator() -> $krate::G1Projective {
+                ark_ec::PrimeGroup::generator()
+            }
+
+            fn g1_is_zero(p: $krate::G1Projective) -> bool {
+                ark_ff::Zero::is_zero(&p)
+            }
+
+            fn g1_to_bytes(p: $krate::G1Projective) -> Vec<u8> {
+                ark_bytes(&p)
+            }
+
+            fn g1_from_bytes(bytes: &[u8]) -> Option<$krate::G1Projective> {
+                ark_from_bytes(bytes)
+            }
+
+            fn g2_generator() -> $krate::G2Projective {
+                ark_ec::PrimeGroup::generator()
+            }
+
+            fn g2_is_zero(p: $krate::G2Projective) -> bool {
+                ark_ff::Zero::is_zero(&p)
+            }
+
+            fn g2_to_bytes(p: $krate::G2Projective) -> Vec<u8> {
+                ark_bytes(&p)
+            }
+
+            fn g2_from_bytes(bytes: &[u8]) -> Option<$krate::G2Projective> {
+                ark_from_bytes(bytes)
+            }
+
+            fn pairing(p: $krate::G1Projective, q: $krate::G2Projective) -> Self::Gt {
+                <$curve as ark_ec::pairing::Pairing>::pairing(p, q)
+            }
+
+            fn multi_pairing(pairs: &[($krate::G1Projective, $krate::G2Projective)]) -> Self::Gt {
+                <$curve as ark_ec::pairing::Pairing>::multi_pairing(pairs.iter().map(|(p, _)| *p), pairs.iter().map(|(_, q)| *q))
+            }
+
+            // arkworks writes Gt additively: its zero is 1, + multiplies and · exponentiates
+            fn gt_one() -> Self::Gt {
+                ark_ff::Zero::zero()
+            }
+
+            fn gt_mul(a: Self::Gt, b: Self::Gt) -> Self::Gt {
+                a + b
+            }
+
+            fn gt_pow(a: Self::Gt, x: $krate::Fr) -> Self::Gt {
+                a * x
+            }
+
+            fn gt_to_bytes(a: Self::Gt) -> Vec<u8> {
+                ark_bytes(&a)
+            }
+
+            fn gt_from_bytes(bytes: &[u8]) -> Option<Self::Gt> {
+                ark_from_bytes(bytes)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "arkworks-bn254")]
+arkworks_engine!(ArkBn254, "bn254 (arkworks)", ark_bn254::Bn254, ark_bn254);
+#[cfg(feature = "arkworks-bls12-381")]
+arkworks_engine!(ArkBls12_381, "bls12-381 (arkworks)", ark_bls12_381::Bls12_381, ark_bls12_381);
+
+#[cfg(any(feature = "arkworks-bn254", feature = "arkworks-bls12-381"))]
+fn ark_bytes(x: &impl ark_serialize::CanonicalSerialize) -> Vec<u8> {
+    let mut bytes:Vec<u8> = Vec::new();
+    x.serialize_compressed(&mut bytes).expect("Serializing into a Vec does not fail");
+    bytes
+}
+
+// Compressed encoding of exactly one canonical, in-group value
+#[cfg(any(feature = "arkworks-bn254", feature = "arkworks-bls12-381"))]
+fn ark_from_bytes<T: ark_serialize::CanonicalDeserialize>(mut bytes: &[u8]) -> Option<T> {
+    let x = T::deserialize_compressed(&mut bytes).ok()?;
+    match bytes.is_empty() {
+        true => Some(x),
+        false => None
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use crate::survey::{participant_message, sign_participant, participant_verifies, participants_verify};
+
+// Bilinearity, inverses and encodings, the one-time token Tok = g·(1/(s + vid)) of the protocol
+// checked the way the SA checks it: e(Tok, g2·(s + vid)) = e(g, g2), and participant signatures
+#[cfg(test)]
+fn check_engine<E: PairingEngine>() {
+    let rng = &mut rand::thread_rng();
+    let (g, g2) = (E::g1_generator(), E::g2_generator());
+    let (a, b) = (E::scalar_random(rng), E::scalar_random(rng));
+    assert!( E::pairing(g * a, g2 * b) == E::gt_pow(E::pairing(g, g2), a * b) );
+    assert!( E::gt_mul(E::pairing(g * a, g2), E::pairing(g * b, g2)) == E::pairing(g * (a + b), g2) );
+    assert!( E::gt_mul(E::pairing(g, g2), E::gt_one()) == E::pairing(g, g2) );
+    assert!( E::multi_pairing(&[(g * a, g2), (g, g2 * b)]) == E::pairing(g, g2 * (a + b)) && E::multi_pairing(&[]) == E::gt_one() );
+    assert!( E::g1_is_zero(g * a + g * (-a)) && E::g2_is_zero(g2 * b + (-(g2 * b))) );
+    assert!( E::scalar_from_u64(6) == E::scalar_from_u64(2) * E::scalar_from_u64(3) );
+    assert!( E::scalar_inverse(E::scalar_from_u64(0)).is_none() );
+
+    let (s, vid) = (E::scalar_random(rng), E::scalar_random(rng));
+    let token:E::G1 = g * E::scalar_inverse(s + vid).expect("s + vid is not zero");
+    assert!( E::pairing(token, g2 * (s + vid)) == E::pairing(g, g2) );
+
+    assert!( E::scalar_from_bytes(&E::scalar_to_bytes(a)) == Some(a) );
+    assert!( E::g1_from_bytes(&E::g1_to_bytes(token)) == Some(token) );
+    assert!( E::g2_from_bytes(&E::g2_to_bytes(g2 * b)) == Some(g2 * b) );
+    assert!( E::gt_from_bytes(&E::gt_to_bytes(E::pairing(g, g2))) == Some(E::pairing(g, g2)) );
+    let mut trailing:Vec<u8> = E::g1_to_bytes(token);
+    trailing.push(0);
+    assert!( E::g1_from_bytes(&trailing).is_none() );
+
+    // The SA's participant signatures, one by one and in a batch
+    let (y, u, v, h) = (E::scalar_random(rng), g * E::scalar_random(rng), g * E::scalar_random(rng), g * E::scalar_random(rng));
+    let pk:E::Gt = E::gt_pow(E::pairing(g, g2), y);
+    let mut signed:Vec<(E::G1, E::G1, E::G2)> = (1..4).map(|id| {
+        let message:E::G1 = participant_message::<E>(u, v, h, vid, E::scalar_from_u64(id));
+        let (sigma_1, sigma_2) = sign_participant::<E>(g * y, message, E::scalar_random(rng), g2);
+        (message, sigma_1, sigma_2)
+    }).collect();
+    assert!( signed.iter().all(|(message, sigma_1, sigma_2)| participant_verifies::<E>(pk, *message, *sigma_1, *sigma_2, g2)) );
+    assert!( participants_verify::<E>(pk, &signed, g2, rng) );
+    signed[1].0 = participant_message::<E>(u, v, h, vid, E::scalar_from_u64(9));
+    assert!( !participant_verifies::<E>(pk, signed[1].0, signed[1].1, signed[1].2, g2) );
+    assert!( !participants_verify::<E>(pk, &signed, g2, rng) );
+}
+
+#[test]
+fn test_engine_tbn() {
+    check_engine::<Tbn>();
+}
+
+#[cfg(feature = "arkworks-bn254")]
+#[test]
+fn test_engine_arkworks_bn254() {
+    check_engine::<ArkBn254>();
+}
+
+#[cfg(feature = "arkworks-bls12-381")]
+#[test]
+fn test_engine_arkworks_bls12_381() {
+    check_engine::<ArkBls12_381>();
+}
@@ -0,0 +1,117 @@
+use std::error::Error;
+use std::fmt;
+
+/*
+ * ----------------------------------------------
+ * |    PROTOCOL ERRORS                         |
+ * ----------------------------------------------
+ *
+ * Every way a protocol step can be refused. Predicates that anyone can evaluate (authorized,
+ * check_submission) still answer with a bool; the steps a party performs return this error instead.
+*/
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SurveyError {
+    // A signature or credential did not verify
+    InvalidSignature,
+    // A zero-knowledge proof did not verify
+    InvalidProof,
+    // The user has no credential from the RA
+    UnregisteredUser,
+    // The RA revoked the user's registration
+    RevokedUser,
+    // The id was registered before (an id gets one credential, and stays spent once erased)
+    AlreadyRegistered,
+    // The credential was issued for an epoch the verifier no longer accepts
+    StaleCredential,
+    // The user is not on the survey's participant list
+    NotAuthorized,
+    // A survey needs at least one participant
+    EmptySurvey,
+    // A combined authority needs at least this many distinct registered participants per survey
+    TooFewParticipants(usize),
+    // The SA already created a survey with this id
+    DuplicateSurvey,
+    // A published survey lists the same participant twice
+    DuplicateParticipant,
+    // A survey definition has no answerable questions, or does not match its survey
+    InvalidDefinition(String),
+    // A response does not fit the survey's definition
+    InvalidResponse(String),
+    // The registration code is unknown, already redeemed, or expired
+    InvalidRegistrationCode,
+    // The submission failed the SA's check
+    InvalidSubmission,
+    // A submission with the same one-time token was already accepted for the survey
+    DuplicateSubmission,
+    // The survey was closed and takes no more responses
+    SurveyClosed,
+    // An amendment does not extend the survey's window, or does not follow the one before it
+    InvalidAmendment,
+    // Padding buckets are missing or empty, or a message is not padded to its survey's buckets
+    InvalidPadding,
+    // A threshold RA needs 1 <= t <= n nodes
+    InvalidThreshold,
+    // Fewer than t nodes returned a valid partial credential
+    InsufficientShares,
+    // s + vid = 0, so the user has no token for the survey (negligible probability)
+    DegenerateToken,
+    // A keystore could not be opened: wrong passphrase, wrong kind of keystore, or corrupted
+    InvalidKeystore,
+    // A mnemonic is not 12 to 24 lowercase words
+    InvalidMnemonic,
+    // An audit transcript is malformed or does not hash to the signed head
+    InvalidAuditLog,
+    // A snapshot is of an unknown version, malformed, or does not match its MAC
+    InvalidSnapshot,
+    // The storage backend failed to read or write (see storage/mod.rs)
+    Storage(String),
+    // A call to a remote RA or SA failed or was refused (see client.rs)
+    Transport(String),
+    // An SA name or key is already pinned to another SA in the registry
+    ConflictingAuthority(String),
+    // The parameters or keys in use are not the ones frozen in the manifest
+    UnexpectedParameters(String),
+    // A protocol object could not be encoded or decoded
+    SerializationError(String)
+}
+
+impl fmt::Display for SurveyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SurveyError::InvalidSignature           => write!(f, "signature or credential did not verify"),
+            SurveyError::InvalidProof               => write!(f, "zero-knowledge proof did not verify"),
+            SurveyError::UnregisteredUser           => write!(f, "user is not registered with the RA"),
+            SurveyError::RevokedUser                => write!(f, "user's registration was revoked by the RA"),
+            SurveyError::AlreadyRegistered          => write!(f, "id is already registered with the RA"),
+            SurveyError::StaleCredential            => write!(f, "credential is from an expired epoch, renew it with the RA"),
+            SurveyError::NotAuthorized              => write!(f, "user is not authorized for the survey"),
+            SurveyError::EmptySurvey                => write!(f, "survey has no participants"),
+            SurveyError::TooFewParticipants(min)    => write!(f, "survey needs at least {} registered participants", min),
+            SurveyError::DuplicateSurvey            => write!(f, "a survey with this id already exists"),
+            SurveyError::DuplicateParticipant       => write!(f, "survey lists a participant more than once"),
+            SurveyError::InvalidDefinition(msg)     => write!(f, "invalid survey definition: {}", msg),
+            SurveyError::InvalidResponse(msg)       => write!(f, "response does not fit the survey: {}", msg),
+            SurveyError::InvalidRegistrationCode    => write!(f, "registration code is unknown, already redeemed, or expired"),
+            SurveyError::InvalidSubmission          => write!(f, "submission did not pass the check"),
+            SurveyError::DuplicateSubmission        => write!(f, "survey was already answered with this token"),
+            SurveyError::SurveyClosed               => write!(f, "survey is closed"),
+            SurveyError::InvalidAmendment           => write!(f, "amendment does not extend the survey's window"),
+            SurveyError::InvalidPadding             => write!(f, "padding buckets are missing or empty, or the answer is not padded to them"),
+            SurveyError::InvalidThreshold           => write!(f, "threshold must be between 1 and the number of RA nodes"),
+            SurveyError::InsufficientShares         => write!(f, "too few valid partial credentials to reach the threshold"),
+            SurveyError::DegenerateToken            => write!(f, "no survey token exists for this key and survey"),
+            SurveyError::InvalidKeystore            => write!(f, "keystore could not be opened (wrong passphrase or corrupted)"),
+            SurveyError::InvalidMnemonic            => write!(f, "mnemonic must be 12, 15, 18, 21 or 24 lowercase words"),
+            SurveyError::InvalidAuditLog            => write!(f, "audit log does not match its signed head"),
+            SurveyError::InvalidSnapshot            => write!(f, "snapshot could not be restored (unknown version, corrupted or tampered with)"),
+            SurveyError::Storage(msg)               => write!(f, "storage failed: {}", msg),
+            SurveyError::Transport(msg)             => write!(f, "remote call failed: {}", msg),
+            SurveyError::ConflictingAuthority(name) => write!(f, "conflicts with the keys pinned for SA {}", name),
+            SurveyError::UnexpectedParameters(msg)  => write!(f, "parameters do not match the manifest: {}", msg),
+            SurveyError::SerializationError(msg)    => write!(f, "serialization error: {}", msg)
+        }
+    }
+}
+
+impl Error for SurveyError {}
@@ -0,0 +1,107 @@
+extern crate tbn;
+
+use tbn::{Fr, G1};
+
+/*
+ * ----------------------------------------------
+ * |    EVENTS                                  |
+ * ----------------------------------------------
+ *
+ * The RA, every SA, and the submission ledger each carry an event bus that embedders can subscribe
+ * to for side effects of their own (metrics, mirroring, notifications) without wrapping the
+ * authority types:
+ *
+ *      ra.events.subscribe(|event| if let Event::UserRegistered { .. } = event { registrations.fetch_add(1, SeqCst); });
+ *
+ * Subscribers run synchronously, in the order they subscribed, after the step succeeded. Events
+ * only carry what the publishing party already knows, so subscribing never learns more than the
+ * party itself.
+*/
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    // The RA issued a credential for the id with hash id_hash
+    UserRegistered { id_hash: Fr },
+    // An SA signed survey vid for its participants, given as their id hashes (see hash.rs)
+    SurveyOpened { vid: Fr, participants: usize, id_hashes: Vec<Fr> },
+    // The ledger accepted a response to vid, carrying the one-time token
+    SubmissionAccepted { vid: Fr, token: G1 },
+    // The ledger stopped accepting responses to vid
+    SurveyClosed { vid: Fr, accepted: usize }
+}
+
+type Subscriber = Box<dyn Fn(&Event) + Send + Sync>;
+
+pub struct EventBus {
+    subscribers: Vec<Subscriber>
+}
+
+impl EventBus {
+
+    pub fn new() -> Self {
+        EventBus { subscribers: Vec::new() }
+    }
+
+    // Call subscriber with every event published from now on
+    pub fn subscribe(&mut self, subscriber: impl Fn(&Event) + Send + Sync + 'static) {
+        (*self).subscribers.push(Box::new(subscriber));
+    }
+
+    pub(crate) fn publish(&self, event: Event) {
+        for subscriber in &(*self).subscribers {
+            subscriber(&event);
+        }
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        EventBus::new()
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use std::sync::{Arc, Mutex};
+#[cfg(test)]
+use crate::params::SystemParams;
+#[cfg(test)]
+use crate::users::{User, SurveyAuthority, RegistrationAuthority};
+#[cfg(test)]
+use crate::ledger::SubmissionLedger;
+#[cfg(test)]
+use crate::hash::id_hash;
+
+// Every party publishes its events, in protocol order, and only for steps that succeeded
+#[test]
+fn test_event_bus() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let mut ledger = SubmissionLedger::new();
+    let seen:Arc<Mutex<Vec<Event>>> = Arc::new(Mutex::new(Vec::new()));
+    for bus in [&mut ra.events, &mut sa.events, &mut ledger.events] {
+        let seen = Arc::clone(&seen);
+        bus.subscribe(move |event| seen.lock().unwrap().push(event.clone()));
+    }
+
+    let mut alice = User::new();
+    alice.reg_user(&mut ra).expect("Registration failed");
+    let survey = sa.gen_survey(&vec![alice.id()], g, g2, &ra.vk).expect("SA survey creation failed!");
+    let answer = alice.submit_survey(&survey, b"yes", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    ledger.accept(survey.vid, answer.clone(), &sa.vk, &ra.vk, g, g2).expect("Submission was rejected");
+    assert!( ledger.accept(survey.vid, answer.clone(), &sa.vk, &ra.vk, g, g2).is_err() );
+    ledger.close(survey.vid);
+
+    assert!( *seen.lock().unwrap() == vec![
+        Event::UserRegistered { id_hash: id_hash(alice.id()) },
+        Event::SurveyOpened { vid: survey.vid, participants: 1, id_hashes: vec![id_hash(alice.id())] },
+        Event::SubmissionAccepted { vid: survey.vid, token: answer.token },
+        Event::SurveyClosed { vid: survey.vid, accepted: 1 }
+    ] );
+}
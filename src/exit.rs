@@ -0,0 +1,105 @@
+use std::env;
+use std::process;
+
+/*
+ * ----------------------------------------------
+ * |    CLI EXIT CODES & MESSAGES               |
+ * ----------------------------------------------
+ *
+ * Every way the CLI can fail belongs to one failure class with a stable exit code, so scripts can
+ * branch on the code instead of parsing output. The operator-facing summary of each class comes
+ * from a small message catalog chosen by $LANG (falling back to English); the detail that follows
+ * it is passed through untranslated.
+*/
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Failure {
+    // A scenario step (or other check) did not have the expected outcome
+    CheckFailed,
+    // Wrong subcommand or missing arguments
+    Usage,
+    // Input was read but could not be parsed
+    InvalidInput,
+    // Input file could not be read
+    NoInput,
+    // Output file could not be written
+    CannotWrite,
+    // A party refused a protocol step (see SurveyError)
+    ProtocolError
+}
+
+impl Failure {
+
+    // Stable exit codes (all but the first follow sysexits.h)
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Failure::CheckFailed    => 1,
+            Failure::Usage          => 64,
+            Failure::InvalidInput   => 65,
+            Failure::NoInput        => 66,
+            Failure::CannotWrite    => 73,
+            Failure::ProtocolError  => 76
+        }
+    }
+
+    // Summary of the failure class in the given language (e.g. "es"), or English if no
+    // translation exists
+    pub fn message(self, lang: &str) -> &'static str {
+        let lookup = |l: &str| CATALOG.iter().find(|(cat_lang, failure, _)| *cat_lang == l && *failure == self);
+        match lookup(lang).or_else(|| lookup("en")) {
+            Some((_, _, msg)) => msg,
+            None => "error"
+        }
+    }
+}
+
+// (language, failure class, message)
+const CATALOG:&[(&str, Failure, &str)] = &[
+    ("en", Failure::CheckFailed,    "check failed"),
+    ("en", Failure::Usage,          "usage"),
+    ("en", Failure::InvalidInput,   "invalid input"),
+    ("en", Failure::NoInput,        "could not read input"),
+    ("en", Failure::CannotWrite,    "could not write output"),
+    ("en", Failure::ProtocolError,  "protocol step refused"),
+    ("es", Failure::CheckFailed,    "la comprobación falló"),
+    ("es", Failure::Usage,          "uso"),
+    ("es", Failure::InvalidInput,   "entrada no válida"),
+    ("es", Failure::NoInput,        "no se pudo leer la entrada"),
+    ("es", Failure::CannotWrite,    "no se pudo escribir la salida"),
+    ("es", Failure::ProtocolError,  "paso del protocolo rechazado")
+];
+
+// Language code from $LANG, e.g. "es_ES.UTF-8" -> "es"
+fn lang() -> String {
+    let locale = env::var("LANG").unwrap_or_default();
+    locale.split(['_', '.']).next().unwrap_or("").to_lowercase()
+}
+
+// Print the localized failure summary with its detail and exit with the class' exit code
+pub fn fail(failure: Failure, detail: &str) -> ! {
+    eprintln!("{}: {}", failure.message(&lang()), detail);
+    process::exit(failure.exit_code());
+}
+
+
+/*
+ * Unit tests
+ */
+
+// Exit codes are part of the CLI's interface, so they must stay distinct and unchanged
+#[test]
+fn test_exit_codes_stable() {
+    let codes:Vec<i32> = [Failure::CheckFailed, Failure::Usage, Failure::InvalidInput, Failure::NoInput, Failure::CannotWrite, Failure::ProtocolError]
+        .iter().map(|failure| failure.exit_code()).collect();
+    assert!( codes == vec![1, 64, 65, 66, 73, 76] );
+}
+
+// Every failure class has an English message, and unknown languages fall back to it
+#[test]
+fn test_catalog_fallback() {
+    for failure in [Failure::CheckFailed, Failure::Usage, Failure::InvalidInput, Failure::NoInput, Failure::CannotWrite, Failure::ProtocolError].iter() {
+        assert!( CATALOG.iter().any(|(lang, f, _)| *lang == "en" && f == failure) );
+        assert!( failure.message("xx") == failure.message("en") );
+    }
+    assert!( Failure::Usage.message("es") == "uso" );
+}
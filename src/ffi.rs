@@ -0,0 +1,321 @@
+extern crate serde_json;
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::{ptr, slice};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::users::{User, VerificationKey, BlindedCredential};
+use crate::wallet::{Wallet, PendingRegistration};
+use crate::params::SystemParams;
+use crate::survey::Survey;
+use crate::encoding::fr_to_bytes;
+use crate::error::SurveyError;
+
+/*
+ * ----------------------------------------------
+ * |    C FFI                                   |
+ * ----------------------------------------------
+ *
+ * A C ABI for the User role, for iOS and Android apps that embed the respondent logic. Build a
+ * static or dynamic library with `cargo rustc --release --lib --features ffi --crate-type staticlib`
+ * (or cdylib); the build also writes the header include/anon_survey.h with cbindgen (see build.rs
+ * and cbindgen.toml).
+ *
+ *      - A user is an opaque AnonSurveyUser handle, from anon_survey_user_new or
+ *        anon_survey_user_open_wallet, released with anon_survey_user_free
+ *      - Inputs are (pointer, length) byte buffers, borrowed for the call. Protocol messages (params,
+ *        keys, surveys, credentials) are the JSON of their serde encoding, the same as in wasm.rs
+ *      - Outputs are AnonSurveyBuffers the library allocates, which the caller releases with
+ *        anon_survey_buffer_free. Wallets and pending registrations only leave sealed under the
+ *        passphrase (see wallet.rs), ready for the platform's storage
+ *      - Every call that can fail returns an AnonSurveyStatus, and writes its outputs only on
+ *        ANON_SURVEY_STATUS_OK
+ *
+ * Handles are not thread-safe: a handle may move between threads, but not be used by two at once.
+*/
+
+/// Opaque handle to a User
+pub struct AnonSurveyUser {
+    user: User
+}
+
+/// Bytes allocated by the library, to release with anon_survey_buffer_free
+#[repr(C)]
+pub struct AnonSurveyBuffer {
+    pub data: *mut u8,
+    pub len: usize
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnonSurveyStatus {
+    Ok = 0,
+    /// A pointer argument was null
+    NullArgument = 1,
+    /// An input was not UTF-8, or not the JSON of the expected message
+    InvalidInput = 2,
+    /// A sealed wallet or pending registration did not open (wrong passphrase or corrupted)
+    InvalidKeystore = 3,
+    /// The user holds no credential yet
+    UnregisteredUser = 4,
+    /// The user is not on the survey's participant list
+    NotAuthorized = 5,
+    /// The RA's credential did not verify
+    InvalidSignature = 6,
+    /// Any other protocol error
+    ProtocolError = 7
+}
+
+impl From<SurveyError> for AnonSurveyStatus {
+    fn from(error: SurveyError) -> Self {
+        match error {
+            SurveyError::SerializationError(_) => AnonSurveyStatus::InvalidInput,
+            SurveyError::InvalidKeystore       => AnonSurveyStatus::InvalidKeystore,
+            SurveyError::UnregisteredUser      => AnonSurveyStatus::UnregisteredUser,
+            SurveyError::NotAuthorized         => AnonSurveyStatus::NotAuthorized,
+            SurveyError::InvalidSignature      => AnonSurveyStatus::InvalidSignature,
+            _                                  => AnonSurveyStatus::ProtocolError
+        }
+    }
+}
+
+/// New user with a random id and PRF key, to release with anon_survey_user_free
+#[no_mangle]
+pub extern "C" fn anon_survey_user_new() -> *mut AnonSurveyUser {
+    Box::into_raw(Box::new(AnonSurveyUser { user: User::new() }))
+}
+
+/// Release a user handle (null is ignored)
+///
+/// # Safety
+/// user is null or a handle from this library that has not been released
+#[no_mangle]
+pub unsafe extern "C" fn anon_survey_user_free(user: *mut AnonSurveyUser) {
+    if !user.is_null() {
+        drop(Box::from_raw(user));
+    }
+}
+
+/// Release a buffer written by this library (an empty one is ignored)
+///
+/// # Safety
+/// buffer was written by this library and has not been released
+#[no_mangle]
+pub unsafe extern "C" fn anon_survey_buffer_free(buffer: AnonSurveyBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len)));
+    }
+}
+
+/// Registered user from a wallet sealed by anon_survey_complete_registration or anon_survey_user_seal_wallet
+///
+/// # Safety
+/// wallet points to wallet_len readable bytes, passphrase is a NUL-terminated string, and out is writable
+#[no_mangle]
+pub unsafe extern "C" fn anon_survey_user_open_wallet(wallet: *const u8, wallet_len: usize, passphrase: *const c_char,
+                                                      out: *mut *mut AnonSurveyUser) -> AnonSurveyStatus {
+    status((|| {
+        let out = output(out)?;
+        let wallet = Wallet::open(bytes(wallet, wallet_len)?, string(passphrase)?)?;
+        *out = Box::into_raw(Box::new(AnonSurveyUser { user: User::from_wallet(wallet) }));
+        Ok(())
+    })())
+}
+
+/// The user's credential sealed under passphrase
+///
+/// # Safety
+/// user is a live handle, passphrase is a NUL-terminated string, and out is writable
+#[no_mangle]
+pub unsafe extern "C" fn anon_survey_user_seal_wallet(user: *const AnonSurveyUser, passphrase: *const c_char,
+                                                      out: *mut AnonSurveyBuffer) -> AnonSurveyStatus {
+    status((|| {
+        let out = output(out)?;
+        *out = buffer(handle(user)?.wallet()?.seal(string(passphrase)?));
+        Ok(())
+    })())
+}
+
+/// The id (32 bytes) to give an SA for its participant lists
+///
+/// # Safety
+/// user is a live handle and out is writable
+#[no_mangle]
+pub unsafe extern "C" fn anon_survey_user_id(user: *const AnonSurveyUser, out: *mut AnonSurveyBuffer) -> AnonSurveyStatus {
+    status((|| {
+        *output(out)? = buffer(fr_to_bytes(handle(user)?.id()));
+        Ok(())
+    })())
+}
+
+/// RegUser: the JSON request to send to the RA, and the secrets behind it sealed under passphrase
+///
+/// # Safety
+/// Every (pointer, length) pair is readable, passphrase is a NUL-terminated string, and the outs are writable
+#[no_mangle]
+pub unsafe extern "C" fn anon_survey_request_registration(user: *const AnonSurveyUser, params: *const u8, params_len: usize,
+                                                          vk_ra: *const u8, vk_ra_len: usize, passphrase: *const c_char,
+                                                          out_request: *mut AnonSurveyBuffer, out_pending: *mut AnonSurveyBuffer) -> AnonSurveyStatus {
+    status((|| {
+        let (out_request, out_pending) = (output(out_request)?, output(out_pending)?);
+        let (params, vk_ra):(SystemParams, VerificationKey) = (json(params, params_len)?, json(vk_ra, vk_ra_len)?);
+        let passphrase:&str = string(passphrase)?;
+        let (request, pending) = handle(user)?.request_registration(&vk_ra, params.g);
+        let request:Vec<u8> = to_json(&request)?;
+        *out_request = buffer(request);
+        *out_pending = buffer(pending.seal(passphrase));
+        Ok(())
+    })())
+}
+
+/// Unblind the RA's answer (JSON BlindedCredential) with the sealed pending registration, into a sealed wallet
+///
+/// # Safety
+/// Every (pointer, length) pair is readable, passphrase is a NUL-terminated string, and out is writable
+#[no_mangle]
+pub unsafe extern "C" fn anon_survey_complete_registration(pending: *const u8, pending_len: usize, credential: *const u8, credential_len: usize,
+                                                           params: *const u8, params_len: usize, vk_ra: *const u8, vk_ra_len: usize,
+                                                           passphrase: *const c_char, out: *mut AnonSurveyBuffer) -> AnonSurveyStatus {
+    status((|| {
+        let out = output(out)?;
+        let (credential, params, vk_ra):(BlindedCredential, SystemParams, VerificationKey) =
+            (json(credential, credential_len)?, json(params, params_len)?, json(vk_ra, vk_ra_len)?);
+        let passphrase:&str = string(passphrase)?;
+        let wallet = PendingRegistration::open(bytes(pending, pending_len)?, passphrase)?.complete(&credential, &vk_ra, params.g2)?;
+        *out = buffer(wallet.seal(passphrase));
+        Ok(())
+    })())
+}
+
+/// SubmitSurvey: the JSON Submission answering survey (JSON) with message
+///
+/// # Safety
+/// user is a live handle, every (pointer, length) pair is readable, and out is writable
+#[no_mangle]
+pub unsafe extern "C" fn anon_survey_submit(user: *const AnonSurveyUser, survey: *const u8, survey_len: usize, message: *const u8, message_len: usize,
+                                            params: *const u8, params_len: usize, vk_sa: *const u8, vk_sa_len: usize,
+                                            vk_ra: *const u8, vk_ra_len: usize, out: *mut AnonSurveyBuffer) -> AnonSurveyStatus {
+    status((|| {
+        let out = output(out)?;
+        let (survey, params):(Survey, SystemParams) = (json(survey, survey_len)?, json(params, params_len)?);
+        let (vk_sa, vk_ra):(VerificationKey, VerificationKey) = (json(vk_sa, vk_sa_len)?, json(vk_ra, vk_ra_len)?);
+        let submission = handle(user)?.submit_survey(&survey, bytes(message, message_len)?, (params.g, params.g2), &vk_sa, &vk_ra)?;
+        *out = buffer(to_json(&submission)?);
+        Ok(())
+    })())
+}
+
+impl From<std::str::Utf8Error> for AnonSurveyStatus {
+    fn from(_: std::str::Utf8Error) -> Self {
+        AnonSurveyStatus::InvalidInput
+    }
+}
+
+fn status(result: Result<(), AnonSurveyStatus>) -> AnonSurveyStatus {
+    result.err().unwrap_or(AnonSurveyStatus::Ok)
+}
+
+unsafe fn handle<'a>(user: *const AnonSurveyUser) -> Result<&'a User, AnonSurveyStatus> {
+    user.as_ref().map(|handle| &handle.user).ok_or(AnonSurveyStatus::NullArgument)
+}
+
+// A borrowed input buffer; null is only allowed for an empty one
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Result<&'a [u8], AnonSurveyStatus> {
+    match (data.is_null(), len) {
+        (true, 0) => Ok(&[]),
+        (true, _) => Err(AnonSurveyStatus::NullArgument),
+        (false, _) => Ok(slice::from_raw_parts(data, len))
+    }
+}
+
+unsafe fn string<'a>(s: *const c_char) -> Result<&'a str, AnonSurveyStatus> {
+    if s.is_null() {
+        return Err(AnonSurveyStatus::NullArgument);
+    }
+    Ok(CStr::from_ptr(s).to_str()?)
+}
+
+unsafe fn json<T: DeserializeOwned>(data: *const u8, len: usize) -> Result<T, AnonSurveyStatus> {
+    serde_json::from_slice(bytes(data, len)?).map_err(|_| AnonSurveyStatus::InvalidInput)
+}
+
+fn to_json<T: Serialize>(object: &T) -> Result<Vec<u8>, AnonSurveyStatus> {
+    serde_json::to_vec(object).map_err(|e| SurveyError::SerializationError(e.to_string()).into())
+}
+
+fn buffer(bytes: Vec<u8>) -> AnonSurveyBuffer {
+    let len:usize = bytes.len();
+    AnonSurveyBuffer { data: Box::into_raw(bytes.into_boxed_slice()) as *mut u8, len }
+}
+
+// An output argument, checked before anything is allocated for it
+unsafe fn output<'a, T>(out: *mut T) -> Result<&'a mut T, AnonSurveyStatus> {
+    out.as_mut().ok_or(AnonSurveyStatus::NullArgument)
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use std::ffi::CString;
+#[cfg(test)]
+use crate::users::{RegistrationAuthority, SurveyAuthority};
+#[cfg(test)]
+use crate::submission::{Submission, check_submission};
+
+#[cfg(test)]
+unsafe fn take(buffer: AnonSurveyBuffer) -> Vec<u8> {
+    let bytes:Vec<u8> = slice::from_raw_parts(buffer.data, buffer.len).to_vec();
+    anon_survey_buffer_free(buffer);
+    bytes
+}
+
+// An app registers a user, keeps only the sealed wallet, and submits, all through the C ABI
+#[test]
+fn test_ffi_user_flow() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let (params, vk_ra, vk_sa) = (serde_json::to_vec(&params).unwrap(), serde_json::to_vec(&ra.vk).unwrap(), serde_json::to_vec(&sa.vk).unwrap());
+    let passphrase = CString::new("correct horse").unwrap();
+    let empty = || AnonSurveyBuffer { data: ptr::null_mut(), len: 0 };
+
+    unsafe {
+        let user = anon_survey_user_new();
+        let (mut request, mut pending) = (empty(), empty());
+        assert!( anon_survey_request_registration(user, params.as_ptr(), params.len(), vk_ra.as_ptr(), vk_ra.len(), passphrase.as_ptr(),
+                                                  &mut request, &mut pending) == AnonSurveyStatus::Ok );
+        let mut wallet = empty();
+        assert!( anon_survey_user_seal_wallet(user, passphrase.as_ptr(), &mut wallet) == AnonSurveyStatus::UnregisteredUser );
+        anon_survey_user_free(user);
+
+        let credential = serde_json::to_vec(&ra.register_request(&serde_json::from_slice(&take(request)).unwrap()).expect("Registration was refused")).unwrap();
+        let pending:Vec<u8> = take(pending);
+        assert!( anon_survey_complete_registration(pending.as_ptr(), pending.len(), credential.as_ptr(), credential.len(), params.as_ptr(), params.len(),
+                                                   vk_ra.as_ptr(), vk_ra.len(), passphrase.as_ptr(), &mut wallet) == AnonSurveyStatus::Ok );
+        let wallet:Vec<u8> = take(wallet);
+        let mut user:*mut AnonSurveyUser = ptr::null_mut();
+        let wrong = CString::new("battery staple").unwrap();
+        assert!( anon_survey_user_open_wallet(wallet.as_ptr(), wallet.len(), wrong.as_ptr(), &mut user) == AnonSurveyStatus::InvalidKeystore );
+        assert!( anon_survey_user_open_wallet(wallet.as_ptr(), wallet.len(), passphrase.as_ptr(), &mut user) == AnonSurveyStatus::Ok );
+
+        let mut id = empty();
+        assert!( anon_survey_user_id(user, &mut id) == AnonSurveyStatus::Ok );
+        let survey = sa.gen_survey(&vec![crate::encoding::fr_from_bytes(&take(id)).unwrap()], g, g2, &ra.vk).expect("Survey was refused");
+        let survey_json = serde_json::to_vec(&survey).unwrap();
+        let mut submission = empty();
+        assert!( anon_survey_submit(user, survey_json.as_ptr(), survey_json.len(), b"yes".as_ptr(), 3, params.as_ptr(), params.len(),
+                                    vk_sa.as_ptr(), vk_sa.len(), vk_ra.as_ptr(), vk_ra.len(), &mut submission) == AnonSurveyStatus::Ok );
+        let submission:Submission = serde_json::from_slice(&take(submission)).unwrap();
+        assert!( check_submission(survey.vid, &submission, &sa.vk, &ra.vk, g, g2) );
+        let mut refused = empty();
+        assert!( anon_survey_submit(user, b"{}".as_ptr(), 2, ptr::null(), 0, params.as_ptr(), params.len(), vk_sa.as_ptr(), vk_sa.len(),
+                                    vk_ra.as_ptr(), vk_ra.len(), &mut refused) == AnonSurveyStatus::InvalidInput );
+        assert!( refused.data.is_null() );
+        anon_survey_user_free(user);
+    }
+}
@@ -0,0 +1,447 @@
+extern crate tbn;
+
+use std::convert::TryFrom;
+use std::sync::Mutex;
+use tonic::{Request, Response, Status};
+use tbn::{Fr, G1, G2};
+
+use crate::users::{RegistrationAuthority, SurveyAuthority, RegistrationRequest, BlindedCredential, VerificationKey};
+use crate::survey::{Survey, SurveyNamespace, ParticipantEntry};
+use crate::submission::{Submission, SubmissionProof, check_submission};
+use crate::ledger::SubmissionLedger;
+use crate::proofs::Proof;
+use crate::storage::Storage;
+use crate::hash::NONCE_BYTES;
+use crate::padding::PaddingPolicy;
+use crate::encoding::{fr_to_bytes, fr_from_bytes, g1_to_bytes, g1_from_bytes, g2_to_bytes, g2_from_bytes};
+use crate::error::SurveyError;
+
+pub use self::proto::registration_server::RegistrationServer;
+pub use self::proto::surveys_server::SurveysServer;
+
+/*
+ * ----------------------------------------------
+ * |    gRPC SERVICE                            |
+ * ----------------------------------------------
+ *
+ * The RA and SA behind a tonic gRPC server, for clients in other languages. The interface is
+ * proto/anon_survey.proto:
+ *      - Registration: Register (RegistrationRequest -> BlindedCredential), served by the RA
+ *      - Surveys: GenSurvey, FetchSurvey, Submit and Verify, served by the SA together with its
+ *        submission ledger
+ *
+ * The messages of the .proto are written out below (proto), so building needs no protoc, and
+ * build.rs only generates the service code. Every message converts to and from the protocol
+ * object it carries; group elements and scalars travel in the encodings of encoding.rs, and a
+ * message that does not decode is refused with INVALID_ARGUMENT before it reaches the authority.
+ *
+ *      let ra = RegistrationService::new(RegistrationAuthority::from_keys(&keystore, passphrase)?);
+ *      tonic::transport::Server::builder().add_service(RegistrationServer::new(ra)).serve(address).await?;
+ *
+ * GenSurvey signs a survey for whoever calls it, so deployments put the Surveys service behind
+ * authentication (e.g. a tonic interceptor) or only expose it to the SA's own operators.
+ * Authorities are behind a mutex and the pairing work runs on the calling task.
+*/
+
+pub mod proto {
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Proof {
+        #[prost(bytes = "vec", tag = "1")]
+        pub c: Vec<u8>,
+        #[prost(bytes = "vec", repeated, tag = "2")]
+        pub z: Vec<Vec<u8>>
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RegistrationRequest {
+        #[prost(bytes = "vec", tag = "1")]
+        pub id: Vec<u8>,
+        #[prost(bytes = "vec", tag = "2")]
+        pub commitment: Vec<u8>,
+        #[prost(message, optional, tag = "3")]
+        pub proof: Option<Proof>
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct BlindedCredential {
+        #[prost(uint64, tag = "1")]
+        pub epoch: u64,
+        #[prost(bytes = "vec", tag = "2")]
+        pub sigma_1: Vec<u8>,
+        #[prost(bytes = "vec", tag = "3")]
+        pub sigma_2: Vec<u8>,
+        #[prost(bytes = "vec", tag = "4")]
+        pub sigma_3: Vec<u8>
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ParticipantEntry {
+        #[prost(bytes = "vec", tag = "1")]
+        pub id: Vec<u8>,
+        #[prost(bytes = "vec", tag = "2")]
+        pub sigma_1: Vec<u8>,
+        #[prost(bytes = "vec", tag = "3")]
+        pub sigma_2: Vec<u8>
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SurveyNamespace {
+        #[prost(string, tag = "1")]
+        pub authority: String,
+        #[prost(bytes = "vec", tag = "2")]
+        pub nonce: Vec<u8>
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Survey {
+        #[prost(bytes = "vec", tag = "1")]
+        pub vid: Vec<u8>,
+        #[prost(uint32, tag = "2")]
+        pub key_version: u32,
+        #[prost(message, repeated, tag = "3")]
+        pub participants: Vec<ParticipantEntry>,
+        #[prost(message, optional, tag = "4")]
+        pub namespace: Option<SurveyNamespace>,
+        #[prost(uint64, repeated, tag = "5")]
+        pub buckets: Vec<u64>
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct GenSurveyRequest {
+        #[prost(bytes = "vec", repeated, tag = "1")]
+        pub participants: Vec<Vec<u8>>
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FetchSurveyRequest {
+        #[prost(bytes = "vec", tag = "1")]
+        pub vid: Vec<u8>
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubmissionProof {
+        #[prost(bytes = "vec", tag = "1")]
+        pub c: Vec<u8>,
+        #[prost(bytes = "vec", tag = "2")]
+        pub z_id: Vec<u8>,
+        #[prost(bytes = "vec", tag = "3")]
+        pub z_s: Vec<u8>,
+        #[prost(bytes = "vec", tag = "4")]
+        pub z_t1: Vec<u8>,
+        #[prost(bytes = "vec", tag = "5")]
+        pub z_t2: Vec<u8>
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Submission {
+        #[prost(bytes = "vec", tag = "1")]
+        pub vid: Vec<u8>,
+        #[prost(bytes = "vec", tag = "2")]
+        pub message: Vec<u8>,
+        #[prost(bytes = "vec", tag = "3")]
+        pub token: Vec<u8>,
+        #[prost(bytes = "vec", tag = "4")]
+        pub cred_1: Vec<u8>,
+        #[prost(bytes = "vec", tag = "5")]
+        pub cred_2: Vec<u8>,
+        #[prost(uint64, tag = "6")]
+        pub epoch: u64,
+        #[prost(bytes = "vec", tag = "7")]
+        pub sigma_1: Vec<u8>,
+        #[prost(bytes = "vec", tag = "8")]
+        pub sigma_2: Vec<u8>,
+        #[prost(message, optional, tag = "9")]
+        pub proof: Option<SubmissionProof>
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubmitResponse {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct VerifyResponse {
+        #[prost(bool, tag = "1")]
+        pub valid: bool,
+        #[prost(bool, tag = "2")]
+        pub accepted: bool
+    }
+
+    include!(concat!(env!("OUT_DIR"), "/anon_survey.v1.Registration.rs"));
+    include!(concat!(env!("OUT_DIR"), "/anon_survey.v1.Surveys.rs"));
+}
+
+
+/*
+ * Protocol objects to and from messages
+ */
+
+fn decoded<T>(value: Option<T>, field: &str) -> Result<T, SurveyError> {
+    value.ok_or_else(|| SurveyError::SerializationError(format!("{} is not a valid encoding", field)))
+}
+
+fn fr(bytes: &[u8], field: &str) -> Result<Fr, SurveyError> {
+    decoded(fr_from_bytes(bytes), field)
+}
+
+fn g1(bytes: &[u8], field: &str) -> Result<G1, SurveyError> {
+    decoded(g1_from_bytes(bytes), field)
+}
+
+fn g2(bytes: &[u8], field: &str) -> Result<G2, SurveyError> {
+    decoded(g2_from_bytes(bytes), field)
+}
+
+impl From<&RegistrationRequest> for proto::RegistrationRequest {
+    fn from(request: &RegistrationRequest) -> Self {
+        let proof = proto::Proof { c: fr_to_bytes((*request).proof.c), z: (*request).proof.z.iter().map(|z| fr_to_bytes(*z)).collect() };
+        proto::RegistrationRequest { id: fr_to_bytes((*request).id), commitment: g1_to_bytes((*request).commitment), proof: Some(proof) }
+    }
+}
+
+impl TryFrom<proto::RegistrationRequest> for RegistrationRequest {
+    type Error = SurveyError;
+
+    fn try_from(request: proto::RegistrationRequest) -> Result<Self, SurveyError> {
+        let proof:proto::Proof = decoded(request.proof, "proof")?;
+        let z:Vec<Fr> = proof.z.iter().map(|z| fr(z, "proof.z")).collect::<Result<_, _>>()?;
+        Ok(RegistrationRequest { id: fr(&request.id, "id")?, commitment: g1(&request.commitment, "commitment")?,
+                                 proof: Proof { c: fr(&proof.c, "proof.c")?, z } })
+    }
+}
+
+impl From<&BlindedCredential> for proto::BlindedCredential {
+    fn from(credential: &BlindedCredential) -> Self {
+        proto::BlindedCredential { epoch: (*credential).epoch, sigma_1: g1_to_bytes((*credential).sigma_1),
+                                   sigma_2: g2_to_bytes((*credential).sigma_2), sigma_3: g1_to_bytes((*credential).sigma_3) }
+    }
+}
+
+impl TryFrom<proto::BlindedCredential> for BlindedCredential {
+    type Error = SurveyError;
+
+    fn try_from(credential: proto::BlindedCredential) -> Result<Self, SurveyError> {
+        Ok(BlindedCredential { epoch: credential.epoch, sigma_1: g1(&credential.sigma_1, "sigma_1")?,
+                               sigma_2: g2(&credential.sigma_2, "sigma_2")?, sigma_3: g1(&credential.sigma_3, "sigma_3")? })
+    }
+}
+
+impl From<&Survey> for proto::Survey {
+    fn from(survey: &Survey) -> Self {
+        let participants:Vec<proto::ParticipantEntry> = (*survey).participants().map(|entry| proto::ParticipantEntry {
+            id: fr_to_bytes(entry.id), sigma_1: g1_to_bytes(entry.sigma_1), sigma_2: g2_to_bytes(entry.sigma_2)
+        }).collect();
+        let namespace = (*survey).namespace.as_ref().map(|namespace| proto::SurveyNamespace {
+            authority: namespace.authority.clone(), nonce: namespace.nonce.to_vec()
+        });
+        let buckets:Vec<u64> = (*survey).padding.buckets().iter().map(|bucket| *bucket as u64).collect();
+        proto::Survey { vid: fr_to_bytes((*survey).vid), key_version: (*survey).key_version, participants, namespace, buckets }
+    }
+}
+
+impl TryFrom<proto::Survey> for Survey {
+    type Error = SurveyError;
+
+    fn try_from(survey: proto::Survey) -> Result<Self, SurveyError> {
+        let mut decoded_survey = Survey::new(fr(&survey.vid, "vid")?);
+        decoded_survey.key_version = survey.key_version;
+        if let Some(namespace) = survey.namespace {
+            let nonce:[u8; NONCE_BYTES] = decoded(<[u8; NONCE_BYTES]>::try_from(&namespace.nonce[..]).ok(), "namespace.nonce")?;
+            decoded_survey.namespace = Some(SurveyNamespace { authority: namespace.authority, nonce });
+        }
+        if !survey.buckets.is_empty() {
+            let buckets:Vec<usize> = decoded(survey.buckets.iter().map(|bucket| usize::try_from(*bucket).ok()).collect(), "buckets")?;
+            decoded_survey.padding = PaddingPolicy::new(buckets)?;
+        }
+        for entry in &survey.participants {
+            decoded_survey.try_insert(ParticipantEntry { id: fr(&entry.id, "participants.id")?, sigma_1: g1(&entry.sigma_1, "participants.sigma_1")?,
+                                                         sigma_2: g2(&entry.sigma_2, "participants.sigma_2")? })?;
+        }
+        Ok(decoded_survey)
+    }
+}
+
+impl From<&Submission> for proto::Submission {
+    fn from(submission: &Submission) -> Self {
+        let proof:&SubmissionProof = &(*submission).proof;
+        proto::Submission {
+            vid: fr_to_bytes((*submission).vid), message: (*submission).message.clone(), token: g1_to_bytes((*submission).token),
+            cred_1: g1_to_bytes((*submission).cred_1), cred_2: g2_to_bytes((*submission).cred_2), epoch: (*submission).epoch,
+            sigma_1: g1_to_bytes((*submission).sigma_1), sigma_2: g2_to_bytes((*submission).sigma_2),
+            proof: Some(proto::SubmissionProof { c: fr_to_bytes(proof.c), z_id: fr_to_bytes(proof.z_id), z_s: fr_to_bytes(proof.z_s),
+                                                 z_t1: fr_to_bytes(proof.z_t1), z_t2: fr_to_bytes(proof.z_t2) })
+        }
+    }
+}
+
+impl TryFrom<proto::Submission> for Submission {
+    type Error = SurveyError;
+
+    fn try_from(submission: proto::Submission) -> Result<Self, SurveyError> {
+        let proof:proto::SubmissionProof = decoded(submission.proof, "proof")?;
+        Ok(Submission {
+            vid: fr(&submission.vid, "vid")?, message: submission.message, token: g1(&submission.token, "token")?,
+            cred_1: g1(&submission.cred_1, "cred_1")?, cred_2: g2(&submission.cred_2, "cred_2")?, epoch: submission.epoch,
+            sigma_1: g1(&submission.sigma_1, "sigma_1")?, sigma_2: g2(&submission.sigma_2, "sigma_2")?,
+            proof: SubmissionProof { c: fr(&proof.c, "proof.c")?, z_id: fr(&proof.z_id, "proof.z_id")?, z_s: fr(&proof.z_s, "proof.z_s")?,
+                                     z_t1: fr(&proof.z_t1, "proof.z_t1")?, z_t2: fr(&proof.z_t2, "proof.z_t2")? }
+        })
+    }
+}
+
+// gRPC status for a refused call
+fn status(error: SurveyError) -> Status {
+    let message:String = error.to_string();
+    match error {
+        SurveyError::DuplicateSubmission | SurveyError::DuplicateSurvey | SurveyError::AlreadyRegistered => Status::already_exists(message),
+        SurveyError::RevokedUser | SurveyError::NotAuthorized => Status::permission_denied(message),
+        SurveyError::UnregisteredUser | SurveyError::StaleCredential | SurveyError::SurveyClosed => Status::failed_precondition(message),
+        SurveyError::Storage(_) => Status::internal(message),
+        _ => Status::invalid_argument(message)
+    }
+}
+
+// A call panicked while holding the authority
+fn poisoned<E>(_: E) -> Status {
+    Status::internal("authority state is unavailable after a failed call")
+}
+
+
+/*
+ * Services
+ */
+
+// The Registration service of an RA
+pub struct RegistrationService<S: Storage> {
+    ra: Mutex<RegistrationAuthority<S>>
+}
+
+impl<S: Storage + Send + 'static> RegistrationService<S> {
+
+    pub fn new(ra: RegistrationAuthority<S>) -> Self {
+        RegistrationService { ra: Mutex::new(ra) }
+    }
+
+    /* The RA, e.g. to take a snapshot or shut down */
+    pub fn into_inner(self) -> RegistrationAuthority<S> {
+        self.ra.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[tonic::async_trait]
+impl<S: Storage + Send + 'static> proto::registration_server::Registration for RegistrationService<S> {
+
+    async fn register(&self, request: Request<proto::RegistrationRequest>) -> Result<Response<proto::BlindedCredential>, Status> {
+        let request = RegistrationRequest::try_from(request.into_inner()).map_err(status)?;
+        let credential:BlindedCredential = (*self).ra.lock().map_err(poisoned)?.register_request(&request).map_err(status)?;
+        Ok(Response::new(proto::BlindedCredential::from(&credential)))
+    }
+}
+
+// The Surveys service of an SA and the ledger its submissions go to, checked against the RA's key
+pub struct SurveyService<S: Storage, L: Storage> {
+    sa: Mutex<SurveyAuthority<S>>,
+    ledger: Mutex<SubmissionLedger<L>>,
+    vk_ra: VerificationKey,
+    g: G1,
+    g2: G2
+}
+
+impl<S: Storage + Send + 'static, L: Storage + Send + 'static> SurveyService<S, L> {
+
+    pub fn new(sa: SurveyAuthority<S>, ledger: SubmissionLedger<L>, vk_ra: VerificationKey, g: G1, g2: G2) -> Self {
+        SurveyService { sa: Mutex::new(sa), ledger: Mutex::new(ledger), vk_ra, g, g2 }
+    }
+
+    /* The SA and the ledger, e.g. to tally or shut down */
+    pub fn into_inner(self) -> (SurveyAuthority<S>, SubmissionLedger<L>) {
+        (self.sa.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner()),
+         self.ledger.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner()))
+    }
+}
+
+#[tonic::async_trait]
+impl<S: Storage + Send + 'static, L: Storage + Send + 'static> proto::surveys_server::Surveys for SurveyService<S, L> {
+
+    async fn gen_survey(&self, request: Request<proto::GenSurveyRequest>) -> Result<Response<proto::Survey>, Status> {
+        let participants:Vec<Fr> = request.into_inner().participants.iter().map(|id| fr(id, "participants")).collect::<Result<_, _>>().map_err(status)?;
+        let survey:Survey = (*self).sa.lock().map_err(poisoned)?.gen_survey(&participants, (*self).g, (*self).g2, &(*self).vk_ra).map_err(status)?;
+        Ok(Response::new(proto::Survey::from(&survey)))
+    }
+
+    async fn fetch_survey(&self, request: Request<proto::FetchSurveyRequest>) -> Result<Response<proto::Survey>, Status> {
+        let vid:Fr = fr(&request.into_inner().vid, "vid").map_err(status)?;
+        let sa = (*self).sa.lock().map_err(poisoned)?;
+        match sa.verid_list.iter().find(|survey| survey.vid == vid) {
+            Some(survey) => Ok(Response::new(proto::Survey::from(survey))),
+            None => Err(Status::not_found("no survey with this vid"))
+        }
+    }
+
+    async fn submit(&self, request: Request<proto::Submission>) -> Result<Response<proto::SubmitResponse>, Status> {
+        let submission = Submission::try_from(request.into_inner()).map_err(status)?;
+        let sa = (*self).sa.lock().map_err(poisoned)?;
+        (*self).ledger.lock().map_err(poisoned)?.accept(submission.vid, submission, &sa.vk, &(*self).vk_ra, (*self).g, (*self).g2).map_err(status)?;
+        Ok(Response::new(proto::SubmitResponse {}))
+    }
+
+    async fn verify(&self, request: Request<proto::Submission>) -> Result<Response<proto::VerifyResponse>, Status> {
+        let submission = Submission::try_from(request.into_inner()).map_err(status)?;
+        let valid:bool = check_submission(submission.vid, &submission, &(*self).sa.lock().map_err(poisoned)?.vk, &(*self).vk_ra, (*self).g, (*self).g2);
+        let accepted:bool = (*self).ledger.lock().map_err(poisoned)?.seen(submission.vid, submission.token);
+        Ok(Response::new(proto::VerifyResponse { valid, accepted }))
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use tonic::Code;
+#[cfg(test)]
+use proto::registration_server::Registration;
+#[cfg(test)]
+use proto::surveys_server::Surveys;
+#[cfg(test)]
+use crate::users::User;
+#[cfg(test)]
+use crate::params::SystemParams;
+
+// A user registers, is put on a survey and submits through the services, as a remote client would
+#[test]
+fn test_grpc_services() {
+    let runtime = tokio::runtime::Builder::new_current_thread().build().expect("Could not start a runtime");
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let ra = RegistrationAuthority::new(&params);
+    let vk_ra:VerificationKey = ra.vk.clone();
+    let registration = RegistrationService::new(ra);
+    let surveys = SurveyService::new(SurveyAuthority::new(&params), SubmissionLedger::new(), vk_ra.clone(), g, g2);
+
+    let user = User::new();
+    let (request, pending) = user.request_registration(&vk_ra, g);
+    let credential = runtime.block_on(registration.register(Request::new(proto::RegistrationRequest::from(&request)))).expect("Registration failed");
+    let credential = BlindedCredential::try_from(credential.into_inner()).expect("Credential does not decode");
+    let user = User::from_wallet(pending.complete(&credential, &vk_ra, g2).expect("Credential was rejected"));
+
+    let survey = runtime.block_on(surveys.gen_survey(Request::new(proto::GenSurveyRequest { participants: vec![fr_to_bytes(user.id())] })))
+        .expect("Survey was refused").into_inner();
+    let fetched = runtime.block_on(surveys.fetch_survey(Request::new(proto::FetchSurveyRequest { vid: survey.vid.clone() }))).expect("Survey not found");
+    assert!( fetched.into_inner() == survey );
+    let survey = Survey::try_from(survey).expect("Survey does not decode");
+
+    let (sa, ledger) = surveys.into_inner();
+    let submission = user.submit_survey(&survey, b"yes", (g, g2), &sa.vk, &vk_ra).expect("Submission failed");
+    let surveys = SurveyService::new(sa, ledger, vk_ra, g, g2);
+    let message = proto::Submission::from(&submission);
+    let verified = runtime.block_on(surveys.verify(Request::new(message.clone()))).expect("Verification failed").into_inner();
+    assert!( verified.valid && !verified.accepted );
+    assert!( runtime.block_on(surveys.submit(Request::new(message.clone()))).is_ok() );
+    assert!( runtime.block_on(surveys.submit(Request::new(message.clone()))).map_err(|status| status.code()).err() == Some(Code::AlreadyExists) );
+    assert!( runtime.block_on(surveys.verify(Request::new(message))).expect("Verification failed").into_inner().accepted );
+
+    let mut malformed = proto::Submission::from(&submission);
+    malformed.token = vec![0xff; 64];
+    assert!( runtime.block_on(surveys.submit(Request::new(malformed))).map_err(|status| status.code()).err() == Some(Code::InvalidArgument) );
+}
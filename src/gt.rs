@@ -0,0 +1,130 @@
+extern crate tbn;
+extern crate ark_bn254;
+extern crate ark_ec;
+extern crate ark_ff;
+
+use std::ops::Mul;
+use tbn::{Fr, Fq, G1, G2, AffineG1, AffineG2};
+use ark_ec::pairing::{Pairing, PairingOutput};
+use ark_ec::AffineRepr;
+use ark_ff::{PrimeField, Zero};
+
+use crate::codec::u256_to_be_bytes;
+
+/*
+ * ----------------------------------------------
+ * |    THE TARGET GROUP Gt                     |
+ * ----------------------------------------------
+ *
+ * tbn keeps its Gt opaque: it can multiply, exponentiate and compare elements, but gives no access
+ * to their Fq12 coefficients, so nothing built on it can hash, store or send a pairing result. The
+ * protocol needs all three (Fiat-Shamir challenges over pairing commitments, the authorities'
+ * verification keys pk = e(g, g2)^sk), so pairings are computed by arkworks on the same BN254
+ * curve instead, whose target group exposes its coefficients:
+ *
+ *      let t:Gt = pairing(cred_1, g2);     // tbn points in, an arkworks Gt out
+ *
+ * G1 and G2 points stay tbn's and are handed over through their public affine coordinates. Both
+ * libraries use the same curve y² = x³ + 3, the same twist y² = x³ + 3/(9 + i) for G2 and the same
+ * tower Fq2 = Fq[i]/(i² + 1), Fq6 = Fq2[v]/(v³ - (9 + i)), Fq12 = Fq6[w]/(w² - v), so a point
+ * means the same thing on either side, and Gt coefficients can be laid out in tbn's tower order
+ * (see encoding.rs). Gt is written multiplicatively as in the paper (arkworks writes it additively).
+*/
+
+// An element of the order-q subgroup Gt of Fq12
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Gt(pub(crate) PairingOutput<ark_bn254::Bn254>);
+
+impl Gt {
+
+    pub fn one() -> Gt {
+        Gt(PairingOutput::zero())
+    }
+
+    // self^x
+    pub fn pow(&self, x: Fr) -> Gt {
+        Gt((*self).0 * ark_fr(x))
+    }
+
+    pub fn inverse(&self) -> Gt {
+        Gt(-(*self).0)
+    }
+}
+
+impl Mul for Gt {
+    type Output = Gt;
+
+    fn mul(self, other: Gt) -> Gt {
+        Gt(PairingOutput((self.0).0 * (other.0).0))
+    }
+}
+
+// e(p, q)
+pub fn pairing(p: G1, q: G2) -> Gt {
+    Gt(ark_bn254::Bn254::pairing(ark_g1(p), ark_g2(q)))
+}
+
+// Π e(p_i, q_i), one Miller loop per pair and a single final exponentiation
+pub fn multi_pairing(pairs: &[(G1, G2)]) -> Gt {
+    let g1s:Vec<ark_bn254::G1Affine> = pairs.iter().map(|(p, _)| ark_g1(*p)).collect();
+    let g2s:Vec<ark_bn254::G2Affine> = pairs.iter().map(|(_, q)| ark_g2(*q)).collect();
+    Gt(ark_bn254::Bn254::multi_pairing(g1s, g2s))
+}
+
+// tbn keeps field elements reduced, so the big-endian encoding is already canonical
+fn ark_fq(x: Fq) -> ark_bn254::Fq {
+    ark_bn254::Fq::from_be_bytes_mod_order(&u256_to_be_bytes(x.into_u256()))
+}
+
+fn ark_fr(x: Fr) -> ark_bn254::Fr {
+    ark_bn254::Fr::from_be_bytes_mod_order(&u256_to_be_bytes(x.into_u256()))
+}
+
+// tbn only builds points on the curve and in the group, so they are not checked again
+fn ark_g1(p: G1) -> ark_bn254::G1Affine {
+    match AffineG1::from_jacobian(p) {
+        Some(a) => ark_bn254::G1Affine::new_unchecked(ark_fq(a.x()), ark_fq(a.y())),
+        None => ark_bn254::G1Affine::zero()
+    }
+}
+
+fn ark_g2(q: G2) -> ark_bn254::G2Affine {
+    match AffineG2::from_jacobian(q) {
+        Some(a) => {
+            let (x, y) = (a.x(), a.y());
+            ark_bn254::G2Affine::new_unchecked(
+                ark_bn254::Fq2::new(ark_fq(x.real()), ark_fq(x.imaginary())),
+                ark_bn254::Fq2::new(ark_fq(y.real()), ark_fq(y.imaginary()))
+            )
+        },
+        None => ark_bn254::G2Affine::zero()
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use tbn::Group;
+
+// tbn's points land on arkworks' curve and in its groups, and the pairing is bilinear across them
+#[test]
+fn test_pairing_across_libraries() {
+    let rng = &mut rand::thread_rng();
+    let (a, b):(Fr, Fr) = (Fr::random(rng), Fr::random(rng));
+    let (g, g2):(G1, G2) = (G1::one() * Fr::random(rng), G2::one() * Fr::random(rng));
+    for (p, q) in &[(ark_g1(g), ark_g2(g2)), (ark_g1(g * a), ark_g2(g2 * b))] {
+        assert!( p.is_on_curve() && p.is_in_correct_subgroup_assuming_on_curve() );
+        assert!( q.is_on_curve() && q.is_in_correct_subgroup_assuming_on_curve() );
+    }
+    assert!( ark_g1(G1::zero()).is_zero() && ark_g2(G2::zero()).is_zero() );
+
+    let e:Gt = pairing(g, g2);
+    assert!( pairing(g * a, g2 * b) == e.pow(a * b) && e != Gt::one() );
+    assert!( pairing(g * a, g2) * pairing(g * b, g2) == pairing(g * (a + b), g2) );
+    assert!( e * e.inverse() == Gt::one() && e.pow(-Fr::one()) == e.inverse() );
+    assert!( pairing(G1::zero(), g2) == Gt::one() && pairing(g, G2::zero()) == Gt::one() );
+    assert!( multi_pairing(&[(g * a, g2), (g, g2 * b)]) == e.pow(a + b) && multi_pairing(&[]) == Gt::one() );
+}
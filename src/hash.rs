@@ -0,0 +1,178 @@
+extern crate tbn;
+extern crate sha2;
+
+use sha2::{Sha256, Digest};
+use tbn::{Fr, Fq, G1, AffineG1};
+
+use crate::codec::u256_to_be_bytes;
+use crate::encoding::fr_to_bytes;
+use crate::proofs::{hash_to_fr, push_bytes, push_g1, push_gt};
+use crate::users::VerificationKey;
+
+/*
+ * ----------------------------------------------
+ * |    HASH TO FIELD AND CURVE                 |
+ * ----------------------------------------------
+ *
+ * Deterministic maps from bytes into ℤ_q and G1, SHA-256 based and separated by a domain tag, so a
+ * value hashed for one purpose can never be reused for another:
+ *      - hash_to_field(domain, msg): SHA-256(domain ‖ len ‖ msg) with the top three bits cleared
+ *      - hash_to_g1(domain, msg): try-and-increment, x = SHA-256(domain ‖ len ‖ msg ‖ counter) with
+ *        the top three bits cleared (so x < p), until x³ + 3 has a square root
+ *        y = (x³ + 3)^((p+1)/4), which works because p ≡ 3 mod 4. G1 has cofactor 1, so every such
+ *        point is in the group. Hashing onto G2 needs cofactor clearing that tbn does not expose.
+ *
+ * Survey ids are hashed the same way: vid = H(content ‖ vk_SA ‖ nonce), where the content is the
+ * title of a SurveyDescription or the encoded questions of a SurveyDefinition. Publishing it and the
+ * nonce next to a survey lets anyone confirm that a vid belongs to that content and that SA, and the
+ * random nonce keeps two surveys with the same content apart.
+ *
+ * Surveys of SAs that share an RA can be namespaced by the SA's registered name instead:
+ * vid = H(authority ‖ nonce) (see registry.rs). Every user's token for a survey is PRF_s(vid), so
+ * an SA that reused another SA's vid could link the respondents of both surveys; a namespaced vid
+ * can only be claimed by the SA pinned under that name.
+ *
+ * The RA keeps its anonymity set as id hashes, H(id), rather than the ids themselves: anyone who
+ * holds an id can check it for membership, but the set alone does not give the ids away (they are
+ * uniformly random in ℤ_q, so there is nothing to guess).
+ *
+ * Credentials are issued for an epoch, by replacing h_RA with h_RA + H(epoch) in the RA's
+ * signature (see RA.rs), where H(epoch) = hash_to_g1 of the epoch number. Nobody knows the discrete
+ * log of H(epoch), so a credential for one epoch says nothing about any other.
+*/
+
+pub const SURVEY_ID_DOMAIN:&[u8] = b"anon-survey/vid/v1";
+pub const USER_ID_DOMAIN:&[u8] = b"anon-survey/user-id/v1";
+pub const EPOCH_DOMAIN:&[u8] = b"anon-survey/epoch/v1";
+pub const SA_VID_DOMAIN:&[u8] = b"anon-survey/sa-vid/v1";
+
+// Length of the nonce that keeps surveys with the same content apart
+pub const NONCE_BYTES:usize = 32;
+
+// msg hashed into ℤ_q under domain
+pub fn hash_to_field(domain: &[u8], msg: &[u8]) -> Fr {
+    let mut transcript:Vec<u8> = domain.to_vec();
+    push_bytes(&mut transcript, msg);
+    hash_to_fr(&transcript)
+}
+
+// msg hashed onto G1 under domain
+pub fn hash_to_g1(domain: &[u8], msg: &[u8]) -> G1 {
+    // Curve constant of y² = x³ + 3
+    let b:Fq = Fq::from_str("3").unwrap();
+    let mut counter:u32 = 0;
+    loop {
+        let mut transcript:Vec<u8> = domain.to_vec();
+        push_bytes(&mut transcript, msg);
+        transcript.extend_from_slice(&counter.to_be_bytes());
+        let mut digest = Sha256::digest(&transcript);
+        digest[0] &= 0x1f;
+        let x:Fq = Fq::from_slice(&digest).expect("253-bit value is always below p");
+        let y2:Fq = x * x * x + b;
+        if let Some(y) = sqrt(y2) {
+            if let Ok(p) = AffineG1::new(x, y) {
+                return G1::from(p);
+            }
+        }
+        counter += 1;
+    }
+}
+
+// vid = H(content ‖ vk_SA ‖ nonce)
+pub fn survey_id(content: &[u8], vk_sa: &VerificationKey, nonce: &[u8; NONCE_BYTES]) -> Fr {
+    let mut msg:Vec<u8> = Vec::new();
+    push_bytes(&mut msg, content);
+    for p in &[(*vk_sa).u, (*vk_sa).v, (*vk_sa).h] {
+        push_g1(&mut msg, *p);
+    }
+    push_gt(&mut msg, (*vk_sa).pk);
+    msg.extend_from_slice(nonce);
+    hash_to_field(SURVEY_ID_DOMAIN, &msg)
+}
+
+// vid = H(authority ‖ nonce), a survey id in the namespace of the SA registered as authority
+pub fn namespaced_vid(authority: &str, nonce: &[u8; NONCE_BYTES]) -> Fr {
+    let mut msg:Vec<u8> = Vec::new();
+    push_bytes(&mut msg, authority.as_bytes());
+    msg.extend_from_slice(nonce);
+    hash_to_field(SA_VID_DOMAIN, &msg)
+}
+
+// H(id), what the RA stores in place of a registered id
+pub fn id_hash(id: Fr) -> Fr {
+    hash_to_field(USER_ID_DOMAIN, &fr_to_bytes(id))
+}
+
+// H(epoch), added to h_RA in credentials issued for epoch
+pub fn epoch_point(epoch: u64) -> G1 {
+    hash_to_g1(EPOCH_DOMAIN, &epoch.to_be_bytes())
+}
+
+// y with y² = a, if there is one
+fn sqrt(a: Fq) -> Option<Fq> {
+    // (p + 1) / 4 as big-endian bytes
+    let mut exponent = u256_to_be_bytes(Fq::modulus());
+    for byte in exponent.iter_mut().rev() {
+        let (sum, carry) = byte.overflowing_add(1);
+        *byte = sum;
+        if !carry {
+            break;
+        }
+    }
+    let mut shifted = [0u8; 32];
+    for (i, byte) in shifted.iter_mut().enumerate() {
+        *byte = exponent[i] >> 2 | if i > 0 { exponent[i - 1] << 6 } else { 0 };
+    }
+
+    // Square-and-multiply, most significant bit first
+    let mut y:Fq = Fq::one();
+    for byte in shifted.iter() {
+        for bit in (0..8).rev() {
+            y = y * y;
+            if byte >> bit & 1 == 1 {
+                y = y * a;
+            }
+        }
+    }
+    match y * y == a {
+        true => Some(y),
+        false => None
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use tbn::Group;
+#[cfg(test)]
+use crate::users::SurveyAuthority;
+#[cfg(test)]
+use crate::params::SystemParams;
+
+// Hashes are deterministic and separated by domain and by every part of their input
+#[test]
+fn test_hash_to_field_and_g1() {
+    assert!( hash_to_field(b"a", b"msg") == hash_to_field(b"a", b"msg") );
+    assert!( hash_to_field(b"a", b"msg") != hash_to_field(b"b", b"msg") );
+    // The length prefix keeps the domain from bleeding into the message
+    assert!( hash_to_field(b"ab", b"c") != hash_to_field(b"a", b"bc") );
+
+    let p:G1 = hash_to_g1(b"a", b"msg");
+    assert!( p == hash_to_g1(b"a", b"msg") && p != hash_to_g1(b"b", b"msg") && !p.is_zero() );
+    assert!( (p * -Fr::one() + p).is_zero() );
+}
+
+#[test]
+fn test_survey_id() {
+    let params = SystemParams::random();
+    let (sa, other_sa):(SurveyAuthority, SurveyAuthority) = (SurveyAuthority::new(&params), SurveyAuthority::new(&params));
+    let nonce = [7u8; NONCE_BYTES];
+    let vid:Fr = survey_id(b"Course feedback", &sa.vk, &nonce);
+    assert!( vid == survey_id(b"Course feedback", &sa.vk, &nonce) );
+    assert!( vid != survey_id(b"Course feedback ", &sa.vk, &nonce) );
+    assert!( vid != survey_id(b"Course feedback", &other_sa.vk, &nonce) );
+    assert!( vid != survey_id(b"Course feedback", &sa.vk, &[8u8; NONCE_BYTES]) );
+}
@@ -0,0 +1,155 @@
+extern crate tbn;
+
+use crate::users::VerificationKey;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/*
+ * ----------------------------------------------
+ * |    SA KEY RINGS                            |
+ * ----------------------------------------------
+ *
+ * An SA that rotates its signing key (SurveyAuthority::rotate_keys) numbers its keys: the first
+ * has version 0, and every rotation adds one. Each Survey records the version of the key that
+ * signed it, and the SA publishes every key it has signed with as a VerificationKeyRing, so
+ * surveys signed before a rotation (and the submissions to them) stay verifiable:
+ *
+ *      let ring = sa.key_ring();
+ *      authorized(id, &survey, &ring, &vk_ra, g2);             // checks under ring.key(survey.key_version)
+ *      check_submission(vid, &submission, &ring, &vk_ra, g, g2);
+ *
+ * authorized, check_submission and the ledger take anything that implements SurveyKeys, so a single
+ * VerificationKey still works where the SA never rotated; it stands for every version. A submission
+ * does not name a key version, so check_submission tries the ring's keys newest first. All
+ * submissions to a survey are under the same key, so which one verifies says nothing about who
+ * submitted.
+*/
+
+// One or more SA verification keys, by version
+pub trait SurveyKeys {
+    // The key with the given version, if known
+    fn key(&self, version: u32) -> Option<&VerificationKey>;
+    // Every key, newest first
+    fn keys(&self) -> Vec<&VerificationKey>;
+}
+
+impl SurveyKeys for VerificationKey {
+
+    fn key(&self, _version: u32) -> Option<&VerificationKey> {
+        Some(self)
+    }
+
+    fn keys(&self) -> Vec<&VerificationKey> {
+        vec![self]
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct VersionedKey {
+    pub version: u32,
+    pub vk: VerificationKey
+}
+
+// Every key an SA has signed surveys with, as published by the SA
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct VerificationKeyRing {
+    keys: Vec<VersionedKey>
+}
+
+impl VerificationKeyRing {
+
+    pub fn new() -> Self {
+        VerificationKeyRing { keys: Vec::new() }
+    }
+
+    // Add the key with the given version, replacing any key with that version
+    pub fn insert(&mut self, version: u32, vk: VerificationKey) {
+        (*self).keys.retain(|key| key.version != version);
+        (*self).keys.push(VersionedKey { version, vk });
+        (*self).keys.sort_by_key(|key| std::cmp::Reverse(key.version));
+    }
+
+    // Every key with its version, newest first
+    pub fn iter(&self) -> impl Iterator<Item = &VersionedKey> {
+        (*self).keys.iter()
+    }
+
+    // The newest key
+    pub fn current(&self) -> Option<&VersionedKey> {
+        (*self).keys.first()
+    }
+
+    pub fn len(&self) -> usize {
+        (*self).keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (*self).keys.is_empty()
+    }
+}
+
+impl SurveyKeys for VerificationKeyRing {
+
+    fn key(&self, version: u32) -> Option<&VerificationKey> {
+        (*self).keys.iter().find(|key| key.version == version).map(|key| &key.vk)
+    }
+
+    fn keys(&self) -> Vec<&VerificationKey> {
+        (*self).keys.iter().map(|key| &key.vk).collect()
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use crate::users::{User, SurveyAuthority, RegistrationAuthority};
+#[cfg(test)]
+use crate::params::SystemParams;
+#[cfg(test)]
+use crate::submission::check_submission;
+#[cfg(test)]
+use crate::ledger::SubmissionLedger;
+#[cfg(test)]
+use crate::authorized;
+
+// Surveys signed before a rotation stay verifiable under the ring, and only under the key that signed them
+#[test]
+fn test_sa_key_rotation() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let mut alice = User::new();
+    alice.reg_user(&mut ra).expect("Registration failed");
+    let before = sa.gen_survey(&vec![alice.id()], g, g2, &ra.vk).expect("Survey was refused");
+
+    assert!( sa.rotate_keys(g, g2) == 1 );
+    let after = sa.gen_survey(&vec![alice.id()], g, g2, &ra.vk).expect("Survey was refused");
+    let ring = sa.key_ring();
+    assert!( before.key_version == 0 && after.key_version == 1 );
+    assert!( ring.len() == 2 && ring.current().map(|key| key.version) == Some(1) );
+    assert!( authorized(alice.id(), &before, &ring, &ra.vk, g2) && authorized(alice.id(), &after, &ring, &ra.vk, g2) );
+    assert!( !authorized(alice.id(), &before, &sa.vk, &ra.vk, g2) && authorized(alice.id(), &after, &sa.vk, &ra.vk, g2) );
+    let mut partial = VerificationKeyRing::new();
+    partial.insert(1, sa.vk.clone());
+    assert!( !authorized(alice.id(), &before, &partial, &ra.vk, g2) && before.verify_all(&partial, &ra.vk, g2) == vec![alice.id()] );
+
+    // Submissions to either survey pass the check and the ledger under the ring
+    let mut ledger = SubmissionLedger::new();
+    for survey in &[&before, &after] {
+        let vk_sa = ring.key(survey.key_version).expect("Key is on the ring");
+        let submission = alice.submit_survey(survey, b"yes", (g, g2), vk_sa, &ra.vk).expect("Submission failed");
+        assert!( check_submission(survey.vid, &submission, &ring, &ra.vk, g, g2) );
+        ledger.accept(survey.vid, submission, &ring, &ra.vk, g, g2).expect("Submission was refused");
+    }
+    assert!( ledger.reverify(&[before.clone(), after], &ring, &ra.vk, g, g2).is_empty() );
+
+    // The retired key survives the keystore
+    let restored = SurveyAuthority::from_keys(&sa.export_keys("correct horse"), "correct horse").expect("Keystore was rejected");
+    assert!( authorized(alice.id(), &before, &restored.key_ring(), &ra.vk, g2) );
+}
@@ -0,0 +1,202 @@
+extern crate rand;
+extern crate sha2;
+extern crate pbkdf2;
+extern crate chacha20poly1305;
+extern crate zeroize;
+extern crate tracing;
+
+use rand::{RngCore, CryptoRng};
+use sha2::Sha256;
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use zeroize::Zeroizing;
+
+use tbn::{Fr, G1, G2};
+use crate::gt::Gt;
+
+use crate::error::SurveyError;
+use crate::users::VerificationKey;
+use crate::encoding::{FR_BYTES, G1_BYTES, G2_BYTES, GT_BYTES, fr_from_bytes, g1_to_bytes, g1_from_bytes, g2_from_bytes, gt_to_bytes, gt_from_bytes};
+
+/*
+ * ----------------------------------------------
+ * |    KEYSTORES                               |
+ * ----------------------------------------------
+ *
+ * Authorities keep their signing keys across restarts in a passphrase-protected keystore:
+ *
+ *      magic ‖ kind ‖ salt (16) ‖ rounds (4, big-endian) ‖ nonce (12) ‖ ciphertext
+ *
+ * The key is derived from the passphrase with PBKDF2-HMAC-SHA256, and the keys are sealed with
+ * ChaCha20-Poly1305. Everything before the ciphertext is authenticated as associated data, so a
+ * keystore cannot be passed off as another kind or with fewer rounds. A wrong passphrase and a
+ * corrupted file are indistinguishable, and both fail with InvalidKeystore.
+*/
+
+const MAGIC:&[u8] = b"anon-survey/keystore/v1";
+const SALT_BYTES:usize = 16;
+const NONCE_BYTES:usize = 12;
+
+// PBKDF2 rounds for new keystores, and the most a keystore may ask for
+pub const KDF_ROUNDS:u32 = 200_000;
+const MAX_KDF_ROUNDS:u32 = 10_000_000;
+
+// Whose keys (or credential) a keystore holds
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum KeystoreKind {
+    RegistrationAuthority = 1,
+    SurveyAuthority = 2,
+    Wallet = 3,
+    PendingRegistration = 4
+}
+
+// Encrypt plaintext under passphrase
+pub(crate) fn seal(kind: KeystoreKind, plaintext: &[u8], passphrase: &str, rng: &mut (impl RngCore + CryptoRng)) -> Vec<u8> {
+    let mut header:Vec<u8> = MAGIC.to_vec();
+    header.push(kind as u8);
+    let mut salt = [0u8; SALT_BYTES];
+    rng.fill_bytes(&mut salt);
+    header.extend_from_slice(&salt);
+    header.extend_from_slice(&KDF_ROUNDS.to_be_bytes());
+    let mut nonce = [0u8; NONCE_BYTES];
+    rng.fill_bytes(&mut nonce);
+    header.extend_from_slice(&nonce);
+
+    let cipher = cipher(passphrase, &salt, KDF_ROUNDS);
+    let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad: &header })
+        .expect("ChaCha20-Poly1305 encryption does not fail");
+    [header, ciphertext].concat()
+}
+
+// Decrypt a keystore of the given kind. The plaintext is cleared from memory when dropped
+pub(crate) fn open(kind: KeystoreKind, keystore: &[u8], passphrase: &str) -> Result<Zeroizing<Vec<u8>>, SurveyError> {
+    let header_len = MAGIC.len() + 1 + SALT_BYTES + 4 + NONCE_BYTES;
+    if keystore.len() < header_len || &keystore[..MAGIC.len()] != MAGIC || keystore[MAGIC.len()] != kind as u8 {
+        tracing::warn!(?kind, "keystore does not open: not a keystore of this kind");
+        return Err(SurveyError::InvalidKeystore);
+    }
+    let (header, ciphertext) = keystore.split_at(header_len);
+    let salt = &header[MAGIC.len() + 1..MAGIC.len() + 1 + SALT_BYTES];
+    let mut rounds = [0u8; 4];
+    rounds.copy_from_slice(&header[MAGIC.len() + 1 + SALT_BYTES..MAGIC.len() + 5 + SALT_BYTES]);
+    let rounds = u32::from_be_bytes(rounds);
+    if rounds == 0 || rounds > MAX_KDF_ROUNDS {
+        tracing::warn!(?kind, rounds, "keystore does not open: KDF rounds out of range");
+        return Err(SurveyError::InvalidKeystore);
+    }
+    let nonce = &header[header_len - NONCE_BYTES..];
+
+    let cipher = cipher(passphrase, salt, rounds);
+    cipher.decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: header })
+        .map(Zeroizing::new)
+        .map_err(|_| {
+            tracing::warn!(?kind, "keystore does not open: wrong passphrase or corrupted file");
+            SurveyError::InvalidKeystore
+        })
+}
+
+fn cipher(passphrase: &str, salt: &[u8], rounds: u32) -> ChaCha20Poly1305 {
+    let mut key = Zeroizing::new([0u8; 32]);
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, rounds, &mut *key);
+    ChaCha20Poly1305::new(Key::from_slice(&*key))
+}
+
+
+/*
+ * Key encodings inside a keystore
+ */
+
+pub(crate) fn push_vk(bytes: &mut Vec<u8>, vk: &VerificationKey) {
+    for p in &[(*vk).u, (*vk).v, (*vk).h] {
+        bytes.extend(g1_to_bytes(*p));
+    }
+    bytes.extend(gt_to_bytes((*vk).pk));
+}
+
+// Reads the fields of a decrypted keystore in order
+pub(crate) struct KeyReader<'a> {
+    bytes: &'a [u8]
+}
+
+impl<'a> KeyReader<'a> {
+
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        KeyReader { bytes }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], SurveyError> {
+        if (*self).bytes.len() < n {
+            return Err(SurveyError::InvalidKeystore);
+        }
+        let (field, rest) = (*self).bytes.split_at(n);
+        (*self).bytes = rest;
+        Ok(field)
+    }
+
+    pub(crate) fn fr(&mut self) -> Result<Fr, SurveyError> {
+        fr_from_bytes((*self).take(FR_BYTES)?).ok_or(SurveyError::InvalidKeystore)
+    }
+
+    pub(crate) fn g1(&mut self) -> Result<G1, SurveyError> {
+        g1_from_bytes((*self).take(G1_BYTES)?).ok_or(SurveyError::InvalidKeystore)
+    }
+
+    pub(crate) fn g2(&mut self) -> Result<G2, SurveyError> {
+        g2_from_bytes((*self).take(G2_BYTES)?).ok_or(SurveyError::InvalidKeystore)
+    }
+
+    fn gt(&mut self) -> Result<Gt, SurveyError> {
+        gt_from_bytes((*self).take(GT_BYTES)?).ok_or(SurveyError::InvalidKeystore)
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, SurveyError> {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice((*self).take(4)?);
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, SurveyError> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice((*self).take(8)?);
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    // A variable-length field written by proofs::push_bytes
+    pub(crate) fn bytes(&mut self) -> Result<&'a [u8], SurveyError> {
+        let len:u64 = (*self).u64()?;
+        if len > (*self).bytes.len() as u64 {
+            return Err(SurveyError::InvalidKeystore);
+        }
+        (*self).take(len as usize)
+    }
+
+    pub(crate) fn vk(&mut self) -> Result<VerificationKey, SurveyError> {
+        Ok(VerificationKey { u: (*self).g1()?, v: (*self).g1()?, h: (*self).g1()?, pk: (*self).gt()? })
+    }
+
+    // Every field was read, and nothing is left over
+    pub(crate) fn finish(&self) -> Result<(), SurveyError> {
+        match (*self).bytes.is_empty() {
+            true => Ok(()),
+            false => Err(SurveyError::InvalidKeystore)
+        }
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+// Keystores only open with the right passphrase, as the kind they were sealed as, and untampered
+#[test]
+fn test_keystore_seal_open() {
+    let rng = &mut rand::thread_rng();
+    let keystore = seal(KeystoreKind::SurveyAuthority, b"keys", "correct horse", rng);
+    assert!( open(KeystoreKind::SurveyAuthority, &keystore, "correct horse") == Ok(Zeroizing::new(b"keys".to_vec())) );
+    assert!( open(KeystoreKind::SurveyAuthority, &keystore, "battery staple") == Err(SurveyError::InvalidKeystore) );
+    assert!( open(KeystoreKind::RegistrationAuthority, &keystore, "correct horse") == Err(SurveyError::InvalidKeystore) );
+    let mut tampered = keystore.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    assert!( open(KeystoreKind::SurveyAuthority, &tampered, "correct horse") == Err(SurveyError::InvalidKeystore) );
+}
@@ -0,0 +1,561 @@
+extern crate tbn;
+extern crate rand;
+extern crate sha2;
+extern crate tracing;
+
+use std::collections::HashSet;
+use std::time::SystemTime;
+use sha2::{Sha256, Digest};
+use tbn::{Fr, G1, G2};
+
+use crate::encoding::{fr_to_bytes, g1_to_bytes};
+use crate::users::VerificationKey;
+use crate::keyring::SurveyKeys;
+use crate::proofs::{push_fr, push_g1, push_bytes};
+use crate::survey::Survey;
+use crate::submission::{Submission, check_submission};
+use crate::error::SurveyError;
+use crate::events::{Event, EventBus};
+use crate::policy::{SurveyPolicy, Amendment, unix_time};
+use crate::padding::PaddingPolicy;
+use crate::logging::{Redacted, vid_hex};
+use crate::storage::{Storage, MemoryStorage};
+
+/*
+ * ----------------------------------------------
+ * |    SUBMISSION LEDGER                       |
+ * ----------------------------------------------
+ *
+ * The SA's record of accepted submissions. A user always derives the same one-time token
+ * Tok = g^(1/(s + vid)) for the same survey, and a different one for every other survey, so the
+ * ledger can reject a second response to vid by its token alone -- without ever learning which
+ * user either response came from.
+ *
+ * While a survey is open, the SA can publish how many distinct tokens it accepted so far. The
+ * published count only moves in whole batches, so watching it does not reveal when any individual
+ * response arrived. Once the SA closes a survey, every further response to it is refused, and so is
+ * every response after the closing time of a window set from the survey's signed policy and its
+ * amendments (see policy.rs), and every response not padded to the policy's buckets.
+ *
+ * Credentials expire by epoch. Once the RA has advanced its epoch and given users time to renew,
+ * the SA raises the ledger's minimum epoch with set_min_epoch, and submissions made with a
+ * credential from an earlier epoch are refused from then on.
+ *
+ * Accepted submissions are only checked once, on arrival. Whenever the SA replaces its keys, the
+ * RA's key or the system parameters, it should run reverify against the new ones: every stored
+ * survey and submission is checked again, and whatever no longer validates is reported, e.g.
+ *
+ *      for discrepancy in ledger.reverify(&sa.verid_list, &sa.vk, &vk_ra, g, g2) { ... }
+ *
+ * Nothing is removed; deciding what to do with historical data that fails is left to the operator.
+ *
+ * An SA running many surveys should not keep every closed one in memory. Once it has tallied and
+ * archived a closed survey, compact folds it into a Checkpoint: the number of accepted submissions
+ * and a digest of what they were,
+ *      digest = SHA-256(domain ‖ vid ‖ n ‖ Tok_1 ‖ len ‖ message_1 ‖ ... ‖ Tok_n ‖ len ‖ message_n)
+ * so an archived copy can still be checked against the ledger (Checkpoint::matches). A compacted
+ * survey stays closed and keeps its published count.
+ *
+ * Accepted submissions are written through to the ledger's Storage (see storage/mod.rs), which keeps
+ * them after compaction. A ledger moved onto a storage with with_storage takes on the submissions
+ * stored there; closed surveys, windows and checkpoints are not stored.
+*/
+
+pub const CHECKPOINT_DOMAIN:&[u8] = b"anon-survey/checkpoint/v1";
+
+// Default number of accepted responses between two updates of the published count
+pub const COUNT_BATCH:usize = 10;
+
+// Stored data that does not validate when re-checked
+#[derive(Clone, Debug, PartialEq)]
+pub enum Discrepancy {
+    // The SA's signature for participant id on survey vid does not verify
+    ParticipantSignature { vid: Fr, id: Fr },
+    // The accepted submission with this token does not verify
+    Submission { vid: Fr, token: G1 },
+    // Submissions were accepted for a survey that is not among the surveys re-checked
+    UnknownSurvey { vid: Fr, submissions: usize }
+}
+
+// What the ledger keeps of a closed survey after compaction
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    pub vid: Fr,
+    pub accepted: usize,
+    // Digest of the accepted tokens and messages, in order of acceptance
+    pub digest: [u8; 32]
+}
+
+impl Checkpoint {
+
+    fn new(vid: Fr, submissions: &[Submission]) -> Self {
+        Checkpoint { vid, accepted: submissions.len(), digest: checkpoint_digest(vid, submissions) }
+    }
+
+    // The submissions are exactly the ones the survey accepted before compaction, e.g. an archive
+    pub fn matches(&self, submissions: &[Submission]) -> bool {
+        checkpoint_digest((*self).vid, submissions) == (*self).digest
+    }
+}
+
+pub struct SubmissionLedger<S: Storage = MemoryStorage> {
+    // Accepted submissions per survey as (vid, [submission])
+    surveys: Vec<(Fr, Vec<Submission>)>,
+    // Their tokens, keyed by the encodings of (vid, token) so a duplicate is found without a scan
+    tokens: HashSet<(Vec<u8>, Vec<u8>)>,
+    // Surveys that take no more responses
+    closed: Vec<Fr>,
+    // Compacted closed surveys
+    checkpoints: Vec<Checkpoint>,
+    // Closing times and padding from validated policies as (vid, seconds since the Unix epoch, buckets)
+    windows: Vec<(Fr, u64, PaddingPolicy)>,
+    // Submissions with credentials from an earlier epoch are refused
+    min_epoch: u64,
+    // Published to on every accepted submission and closed survey
+    pub events: EventBus,
+    // Where accepted submissions are written through to
+    storage: S
+}
+
+impl SubmissionLedger {
+
+    pub fn new() -> Self {
+        SubmissionLedger { surveys: Vec::new(), tokens: HashSet::new(), closed: Vec::new(), checkpoints: Vec::new(), windows: Vec::new(), min_epoch: 0, events: EventBus::new(),
+                           storage: MemoryStorage::new() }
+    }
+}
+
+impl Default for SubmissionLedger {
+    fn default() -> Self {
+        SubmissionLedger::new()
+    }
+}
+
+impl<S: Storage> SubmissionLedger<S> {
+
+    // Move the ledger onto storage: the submissions it holds are written there first, then it takes
+    // on every submission storage holds
+    pub fn with_storage<T: Storage>(self, mut storage: T) -> Result<SubmissionLedger<T>, SurveyError> {
+        for (vid, submissions) in &self.surveys {
+            for submission in submissions {
+                storage.put_submission(*vid, submission)?;
+            }
+        }
+        let mut surveys:Vec<(Fr, Vec<Submission>)> = Vec::new();
+        for (vid, submission) in storage.submissions()? {
+            match surveys.iter_mut().find(|(survey_vid, _)| *survey_vid == vid) {
+                Some((_, submissions)) => submissions.push(submission),
+                None => surveys.push((vid, vec![submission]))
+            }
+        }
+        let tokens:HashSet<(Vec<u8>, Vec<u8>)> = surveys.iter()
+            .flat_map(|(vid, submissions)| submissions.iter().map(move |submission| token_key(*vid, submission.token)))
+            .collect();
+        let SubmissionLedger { closed, checkpoints, windows, min_epoch, events, .. } = self;
+        Ok(SubmissionLedger { surveys, tokens, closed, checkpoints, windows, min_epoch, events, storage })
+    }
+
+    // The storage the ledger writes through to
+    pub fn into_storage(self) -> S {
+        self.storage
+    }
+
+    // Whether a submission with the given token was already accepted for survey vid. Tokens are
+    // public, so this is a hash lookup rather than a constant-time comparison
+    pub fn seen(&self, vid: Fr, token: G1) -> bool {
+        (*self).tokens.contains(&token_key(vid, token))
+    }
+
+    // Check the submission for survey vid, and store it if it verifies and its token is new.
+    // Nothing is stored for invalid submissions and repeats
+    pub fn accept(&mut self, vid: Fr, submission: Submission, vk_sa: &impl SurveyKeys, vk_ra: &VerificationKey,
+                  g: G1, g2: G2) -> Result<(), SurveyError> {
+        let _span = tracing::debug_span!("accept", vid = %vid_hex(vid), token = %Redacted(submission.token)).entered();
+        let result = (*self).try_accept(vid, submission, vk_sa, vk_ra, g, g2);
+        match &result {
+            Ok(()) => tracing::debug!("accepted submission"),
+            Err(error) => tracing::warn!(%error, "refused submission")
+        }
+        result
+    }
+
+    fn try_accept(&mut self, vid: Fr, submission: Submission, vk_sa: &impl SurveyKeys, vk_ra: &VerificationKey,
+                  g: G1, g2: G2) -> Result<(), SurveyError> {
+        if (*self).closed.contains(&vid) {
+            return Err(SurveyError::SurveyClosed);
+        }
+        if let Some((_, closes_at, padding)) = (*self).windows.iter().find(|(window_vid, _, _)| *window_vid == vid) {
+            if unix_time(SystemTime::now()) >= *closes_at {
+                return Err(SurveyError::SurveyClosed);
+            }
+            if !padding.is_padded(&submission.message) {
+                return Err(SurveyError::InvalidPadding);
+            }
+        }
+        if submission.epoch < (*self).min_epoch {
+            return Err(SurveyError::StaleCredential);
+        }
+        // Cheap duplicate check before verifying the proof
+        if (*self).seen(vid, submission.token) {
+            return Err(SurveyError::DuplicateSubmission);
+        }
+        if !check_submission(vid, &submission, vk_sa, vk_ra, g, g2) {
+            return Err(SurveyError::InvalidSubmission);
+        }
+
+        let token:G1 = submission.token;
+        (*self).storage.put_submission(vid, &submission)?;
+        (*self).tokens.insert(token_key(vid, token));
+        match (*self).surveys.iter_mut().find(|(survey_vid, _)| *survey_vid == vid) {
+            Some((_, submissions)) => submissions.push(submission),
+            None => (*self).surveys.push((vid, vec![submission]))
+        }
+        (*self).events.publish(Event::SubmissionAccepted { vid, token });
+        Ok(())
+    }
+
+    // Stop accepting responses to the policy's survey at its closing time, as extended by the
+    // amendments, and refuse answers not padded to its buckets. The chain must validate under vk_sa,
+    // and a window can only ever move later
+    pub fn set_window(&mut self, policy: &SurveyPolicy, amendments: &[Amendment], vk_sa: &VerificationKey,
+                      g: G1, g2: G2) -> Result<(), SurveyError> {
+        let closes_at:u64 = (*policy).closing_time(amendments, vk_sa, g, g2)?;
+        match (*self).windows.iter_mut().find(|(vid, _, _)| *vid == (*policy).vid) {
+            Some((_, current, _)) if closes_at < *current => return Err(SurveyError::InvalidAmendment),
+            Some((_, current, _)) => *current = closes_at,
+            None => (*self).windows.push(((*policy).vid, closes_at, (*policy).padding.clone()))
+        }
+        Ok(())
+    }
+
+    // Refuse submissions with credentials from before epoch from now on. The minimum only ever
+    // moves forward; returns the minimum in force
+    pub fn set_min_epoch(&mut self, epoch: u64) -> u64 {
+        (*self).min_epoch = (*self).min_epoch.max(epoch);
+        (*self).min_epoch
+    }
+
+    // Stop accepting responses to survey vid, returning how many were accepted
+    pub fn close(&mut self, vid: Fr) -> usize {
+        let accepted:usize = (*self).accepted(vid);
+        if !(*self).closed.contains(&vid) {
+            (*self).closed.push(vid);
+            (*self).events.publish(Event::SurveyClosed { vid, accepted });
+        }
+        accepted
+    }
+
+    // Fold every closed survey still held in full into a checkpoint, dropping its submissions.
+    // Returns the new checkpoints
+    pub fn compact(&mut self) -> Vec<Checkpoint> {
+        let closed:&Vec<Fr> = &(*self).closed;
+        let (compacted, open):(Vec<_>, Vec<_>) =
+            (*self).surveys.drain(..).partition(|(vid, _)| closed.contains(vid));
+        (*self).surveys = open;
+        for (vid, submissions) in &compacted {
+            for submission in submissions {
+                (*self).tokens.remove(&token_key(*vid, submission.token));
+            }
+        }
+        let mut checkpoints:Vec<Checkpoint> = compacted.iter().map(|(vid, submissions)| Checkpoint::new(*vid, submissions)).collect();
+        // Closed surveys that never accepted anything
+        for vid in &(*self).closed {
+            if !checkpoints.iter().chain((*self).checkpoints.iter()).any(|checkpoint| checkpoint.vid == *vid) {
+                checkpoints.push(Checkpoint::new(*vid, &[]));
+            }
+        }
+        (*self).checkpoints.extend(checkpoints.iter().cloned());
+        checkpoints
+    }
+
+    // The checkpoint survey vid was compacted into, if it was
+    pub fn checkpoint(&self, vid: Fr) -> Option<&Checkpoint> {
+        (*self).checkpoints.iter().find(|checkpoint| checkpoint.vid == vid)
+    }
+
+    // Number of submissions accepted for survey vid, whether held in full or compacted
+    fn accepted(&self, vid: Fr) -> usize {
+        match (*self).checkpoint(vid) {
+            Some(checkpoint) => checkpoint.accepted,
+            None => (*self).submissions(vid).len()
+        }
+    }
+
+    // Accepted submissions for survey vid, in order of acceptance (none once compacted)
+    pub fn submissions(&self, vid: Fr) -> &[Submission] {
+        match (*self).surveys.iter().find(|(survey_vid, _)| *survey_vid == vid) {
+            Some((_, submissions)) => submissions,
+            None => &[]
+        }
+    }
+
+    // Number of responses to survey vid that is safe to publish: the count of distinct tokens
+    // accepted so far, rounded down to a multiple of batch (so nothing is published until the
+    // first full batch)
+    pub fn published_count(&self, vid: Fr, batch: usize) -> usize {
+        assert!( batch > 0, "batch must hold at least one response" );
+        ((*self).accepted(vid) / batch) * batch
+    }
+
+    // Check the given surveys and every stored submission again, under the current keys and
+    // parameters (vk_sa may be the SA's key ring), and report everything that no longer validates
+    // (nothing if all is well)
+    pub fn reverify(&self, surveys: &[Survey], vk_sa: &impl SurveyKeys, vk_ra: &VerificationKey, g: G1, g2: G2) -> Vec<Discrepancy> {
+        let mut discrepancies:Vec<Discrepancy> = Vec::new();
+        for survey in surveys {
+            for id in survey.verify_all(vk_sa, vk_ra, g2) {
+                discrepancies.push(Discrepancy::ParticipantSignature { vid: survey.vid, id });
+            }
+        }
+        for (vid, submissions) in &(*self).surveys {
+            if !surveys.iter().any(|survey| survey.vid == *vid) {
+                discrepancies.push(Discrepancy::UnknownSurvey { vid: *vid, submissions: submissions.len() });
+            }
+            for submission in submissions {
+                if !check_submission(*vid, submission, vk_sa, vk_ra, g, g2) {
+                    discrepancies.push(Discrepancy::Submission { vid: *vid, token: submission.token });
+                }
+            }
+        }
+        discrepancies
+    }
+}
+
+
+fn checkpoint_digest(vid: Fr, submissions: &[Submission]) -> [u8; 32] {
+    let mut transcript:Vec<u8> = CHECKPOINT_DOMAIN.to_vec();
+    push_fr(&mut transcript, vid);
+    transcript.extend_from_slice(&(submissions.len() as u64).to_be_bytes());
+    for submission in submissions {
+        push_g1(&mut transcript, submission.token);
+        push_bytes(&mut transcript, &submission.message);
+    }
+    let mut digest = [0u8; 32];
+    digest.copy_from_slice(&Sha256::digest(&transcript));
+    digest
+}
+
+// Canonical encodings, so every representation of a token finds the same entry
+fn token_key(vid: Fr, token: G1) -> (Vec<u8>, Vec<u8>) {
+    (fr_to_bytes(vid), g1_to_bytes(token))
+}
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use crate::users::{User, SurveyAuthority, RegistrationAuthority};
+#[cfg(test)]
+use crate::params::SystemParams;
+#[cfg(test)]
+use tbn::Group;
+
+// A user can answer each survey once, but can answer every survey they are authorized for
+#[test]
+fn test_ledger_rejects_repeats() {
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let (g, g2):(G1, G2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let mut alice = User::new();
+    let mut bob = User::new();
+    alice.reg_user(&mut ra).expect("Registration failed");
+    bob.reg_user(&mut ra).expect("Registration failed");
+    let mut ledger = SubmissionLedger::new();
+
+    let survey = sa.gen_survey(&vec![alice.id(), bob.id()], g, g2, &ra.vk).expect("SA survey creation failed!");
+    let vid:Fr = survey.vid;
+    let first = alice.submit_survey(&survey, b"yes", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    let repeat = alice.submit_survey(&survey, b"no", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    let other = bob.submit_survey(&survey, b"no", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    assert!( !ledger.seen(vid, alice.survey_token(vid, g).unwrap()) );
+    assert!( ledger.accept(vid, first, &sa.vk, &ra.vk, g, g2).is_ok() );
+    assert!( ledger.seen(vid, alice.survey_token(vid, g).unwrap()) );
+    // The same token in another Jacobian representation
+    let token:G1 = alice.survey_token(vid, g).unwrap();
+    assert!( ledger.seen(vid, token + token - token) && !ledger.seen(vid, token + token) );
+    assert!( ledger.published_count(vid, 2) == 0 );
+    assert!( ledger.accept(vid, repeat, &sa.vk, &ra.vk, g, g2) == Err(SurveyError::DuplicateSubmission) );
+    assert!( ledger.accept(vid, other, &sa.vk, &ra.vk, g, g2).is_ok() );
+    assert!( ledger.submissions(vid).len() == 2 );
+    assert!( ledger.published_count(vid, 2) == 2 && ledger.published_count(vid, 1) == 2 );
+    assert!( ledger.submissions(vid)[0].answer() == b"yes" );
+
+    // Same user, different survey
+    let survey2 = sa.gen_survey(&vec![alice.id()], g, g2, &ra.vk).expect("SA survey creation failed!");
+    let vid2:Fr = survey2.vid;
+    let second_survey = alice.submit_survey(&survey2, b"yes", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    assert!( !ledger.seen(vid2, second_survey.token) );
+    assert!( ledger.accept(vid2, second_survey, &sa.vk, &ra.vk, g, g2).is_ok() );
+    assert!( ledger.submissions(vid2).len() == 1 );
+
+    // A closed survey refuses every further response
+    let late = alice.submit_survey(&survey, b"no", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    assert!( ledger.close(vid) == 2 );
+    assert!( ledger.accept(vid, late, &sa.vk, &ra.vk, g, g2) == Err(SurveyError::SurveyClosed) );
+}
+
+// Invalid submissions are never stored
+#[test]
+fn test_ledger_rejects_invalid() {
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let (g, g2):(G1, G2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let mut alice = User::new();
+    alice.reg_user(&mut ra).expect("Registration failed");
+    let mut ledger = SubmissionLedger::new();
+
+    let survey = sa.gen_survey(&vec![alice.id()], g, g2, &ra.vk).expect("SA survey creation failed!");
+    let vid:Fr = survey.vid;
+    let mut forged = alice.submit_survey(&survey, b"yes", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    forged.message = b"no".to_vec();
+    assert!( ledger.accept(vid, forged, &sa.vk, &ra.vk, g, g2) == Err(SurveyError::InvalidSubmission) );
+    assert!( ledger.submissions(vid).is_empty() );
+}
+
+// Re-verifying reports nothing for intact data, and everything once keys or surveys change
+#[test]
+fn test_ledger_reverify() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let mut alice = User::new();
+    alice.reg_user(&mut ra).expect("Registration failed");
+    let bob = User::new();
+    let mut ledger = SubmissionLedger::new();
+
+    let survey = sa.gen_survey(&vec![alice.id(), bob.id()], g, g2, &ra.vk).expect("SA survey creation failed!");
+    let submission = alice.submit_survey(&survey, b"yes", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    let token:G1 = submission.token;
+    ledger.accept(survey.vid, submission, &sa.vk, &ra.vk, g, g2).expect("Submission was refused");
+    assert!( ledger.reverify(&sa.verid_list, &sa.vk, &ra.vk, g, g2).is_empty() );
+
+    // The survey is no longer known
+    assert!( ledger.reverify(&[], &sa.vk, &ra.vk, g, g2) == vec![Discrepancy::UnknownSurvey { vid: survey.vid, submissions: 1 }] );
+
+    // A replaced RA key invalidates every signature and submission built on the old one
+    let new_ra = RegistrationAuthority::new(&params);
+    let discrepancies = ledger.reverify(&sa.verid_list, &sa.vk, &new_ra.vk, g, g2);
+    assert!( discrepancies.len() == 3 && discrepancies.contains(&Discrepancy::Submission { vid: survey.vid, token }) );
+    assert!( discrepancies.contains(&Discrepancy::ParticipantSignature { vid: survey.vid, id: bob.id() }) );
+}
+
+// Compaction drops closed surveys' submissions but keeps their count, and what they were
+#[test]
+fn test_ledger_compact() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let mut alice = User::new();
+    alice.reg_user(&mut ra).expect("Registration failed");
+    let mut ledger = SubmissionLedger::new();
+
+    let (closed, open) = (sa.gen_survey(&vec![alice.id()], g, g2, &ra.vk).unwrap(), sa.gen_survey(&vec![alice.id()], g, g2, &ra.vk).unwrap());
+    let empty = sa.gen_survey(&vec![alice.id()], g, g2, &ra.vk).unwrap();
+    for survey in &[&closed, &open] {
+        let submission = alice.submit_survey(survey, b"yes", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+        ledger.accept(survey.vid, submission, &sa.vk, &ra.vk, g, g2).expect("Submission was refused");
+    }
+    ledger.close(closed.vid);
+    ledger.close(empty.vid);
+    let archive:Vec<Submission> = ledger.submissions(closed.vid).to_vec();
+
+    let checkpoints = ledger.compact();
+    assert!( checkpoints.len() == 2 && ledger.compact().is_empty() );
+    let checkpoint = ledger.checkpoint(closed.vid).expect("Closed survey was not compacted");
+    assert!( checkpoint.accepted == 1 && checkpoint.matches(&archive) && !checkpoint.matches(&[]) );
+    assert!( ledger.checkpoint(empty.vid).map(|checkpoint| checkpoint.accepted) == Some(0) );
+    assert!( ledger.submissions(closed.vid).is_empty() && ledger.published_count(closed.vid, 1) == 1 );
+    assert!( ledger.close(closed.vid) == 1 );
+
+    // The open survey is untouched
+    assert!( ledger.checkpoint(open.vid).is_none() && ledger.submissions(open.vid).len() == 1 );
+    let late = alice.submit_survey(&closed, b"no", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    assert!( ledger.accept(closed.vid, late, &sa.vk, &ra.vk, g, g2) == Err(SurveyError::SurveyClosed) );
+}
+
+// Responses after the signed closing time are refused until a valid amendment extends it
+#[test]
+fn test_ledger_window() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let mut alice = User::new();
+    alice.reg_user(&mut ra).expect("Registration failed");
+    let mut ledger = SubmissionLedger::new();
+
+    let survey = sa.gen_survey(&vec![alice.id()], g, g2, &ra.vk).expect("SA survey creation failed!");
+    let now:u64 = unix_time(SystemTime::now());
+    let policy = sa.set_closing_time(survey.vid, now - 1, g, g2);
+    ledger.set_window(&policy, &[], &sa.vk, g, g2).expect("Policy was refused");
+    let submission = alice.submit_survey(&survey, b"yes", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    assert!( ledger.accept(survey.vid, submission.clone(), &sa.vk, &ra.vk, g, g2) == Err(SurveyError::SurveyClosed) );
+
+    let extension = sa.extend_closing_time(&policy, &[], now + 3600, g, g2).expect("Extension was refused");
+    ledger.set_window(&policy, &[extension], &sa.vk, g, g2).expect("Amendment was refused");
+    assert!( ledger.set_window(&policy, &[], &sa.vk, g, g2) == Err(SurveyError::InvalidAmendment) );
+    assert!( ledger.accept(survey.vid, submission, &sa.vk, &ra.vk, g, g2).is_ok() );
+}
+
+// Under a policy, only answers padded to its signed buckets are taken, so a participant handed other
+// buckets stands out by being refused rather than by the size of their answer
+#[test]
+fn test_ledger_padding() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    sa.padding = PaddingPolicy::new(vec![16, 32]).expect("Buckets are valid");
+    let (mut alice, mut bob) = (User::new(), User::new());
+    alice.reg_user(&mut ra).expect("Registration failed");
+    bob.reg_user(&mut ra).expect("Registration failed");
+    let mut ledger = SubmissionLedger::new();
+
+    let survey = sa.gen_survey(&vec![alice.id(), bob.id()], g, g2, &ra.vk).expect("SA survey creation failed!");
+    let policy = sa.set_closing_time(survey.vid, unix_time(SystemTime::now()) + 3600, g, g2);
+    assert!( policy.padding == survey.padding && survey.padding == sa.padding );
+    ledger.set_window(&policy, &[], &sa.vk, g, g2).expect("Policy was refused");
+
+    let submission = alice.submit_survey(&survey, b"yes", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    assert!( submission.message.len() == 16 && submission.answer() == b"yes" );
+    assert!( ledger.accept(survey.vid, submission, &sa.vk, &ra.vk, g, g2).is_ok() );
+    let mut repadded = survey.clone();
+    repadded.padding = PaddingPolicy::default();
+    let submission = bob.submit_survey(&repadded, b"no", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    assert!( ledger.accept(survey.vid, submission, &sa.vk, &ra.vk, g, g2) == Err(SurveyError::InvalidPadding) );
+}
+
+// Once the minimum epoch moves past a credential, its holder has to renew before answering again
+#[test]
+fn test_ledger_min_epoch() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let mut alice = User::new();
+    alice.reg_user(&mut ra).expect("Registration failed");
+    let mut ledger = SubmissionLedger::new();
+    let survey = sa.gen_survey(&vec![alice.id()], g, g2, &ra.vk).expect("SA survey creation failed!");
+
+    assert!( ra.advance_epoch() == 1 && ledger.set_min_epoch(1) == 1 && ledger.set_min_epoch(0) == 1 );
+    let stale = alice.submit_survey(&survey, b"yes", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    assert!( stale.epoch == 0 && check_submission(survey.vid, &stale, &sa.vk, &ra.vk, g, g2) );
+    assert!( ledger.accept(survey.vid, stale.clone(), &sa.vk, &ra.vk, g, g2) == Err(SurveyError::StaleCredential) );
+
+    // Claiming the current epoch for the old credential breaks the proof
+    let mut forged = stale;
+    forged.epoch = 1;
+    assert!( ledger.accept(survey.vid, forged, &sa.vk, &ra.vk, g, g2) == Err(SurveyError::InvalidSubmission) );
+
+    alice.renew_credential(&mut ra).expect("Renewal failed");
+    assert!( alice.credential_epoch() == 1 && ra.audit_anonymity_set().len() == 1 );
+    let renewed = alice.submit_survey(&survey, b"yes", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    assert!( ledger.accept(survey.vid, renewed, &sa.vk, &ra.vk, g, g2).is_ok() );
+}
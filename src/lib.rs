@@ -0,0 +1,386 @@
+extern crate tbn;
+extern crate rand;
+extern crate hex;
+
+/*
+ * ----------------------------------------------
+ * |    ANONIZE ANONYMOUS SURVEY LIBRARY        |
+ * ----------------------------------------------
+ *
+ * Protocol roles (users, Survey Authorities, the Registration Authority) and the public helpers
+ * shared by all of them. The anon_survey binary is a demo built on top of this crate.
+*/
+
+pub mod error;
+pub mod codec;
+pub mod hash;
+pub mod gt;
+pub mod cost;
+pub mod encoding;
+pub mod users;
+pub mod proofs;
+pub mod survey;
+pub mod definition;
+pub mod analytics;
+pub mod policy;
+pub mod submission;
+pub mod ledger;
+pub mod keystore;
+pub mod snapshot;
+pub mod seed;
+pub mod secret;
+pub mod ct;
+pub mod logging;
+pub mod audit;
+pub mod storage;
+pub mod wallet;
+pub mod manifest;
+pub mod events;
+pub mod padding;
+pub mod params;
+pub mod panel;
+pub mod revocation;
+pub mod keyring;
+pub mod registry;
+pub mod threshold;
+pub mod combined;
+pub mod roles;
+pub mod trust;
+pub mod engine;
+#[cfg(feature = "serde")]
+pub mod spec;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "websocket")]
+pub mod notifications;
+#[cfg(feature = "client")]
+pub mod client;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(test)]
+mod unforgeability;
+pub use error::SurveyError;
+pub use users::{User, SurveyAuthority, RegistrationAuthority, RegistrationCode, RegistrationRequest, BlindedCredential, VerificationKey};
+pub use users::{RegistrationRecord, NotHeld, DataExport, ErasureReport};
+pub use survey::{Survey, SurveyDescription, SurveyNamespace, ParticipantEntry};
+pub use definition::{SurveyDefinition, Question, Answer, Response};
+pub use analytics::Tally;
+pub use policy::{SurveyPolicy, Amendment};
+pub use submission::{Submission, SubmissionInput, SubmissionProof, check_submission, survey_token};
+pub use ledger::{SubmissionLedger, Discrepancy, Checkpoint};
+pub use panel::Panel;
+pub use revocation::RevocationBatch;
+pub use keyring::{SurveyKeys, VerificationKeyRing};
+pub use registry::SaRegistry;
+pub use threshold::{ThresholdRA, RaNode, PartialCredential};
+pub use combined::CombinedAuthority;
+pub use roles::{Registrar, Surveyor, Respondent};
+pub use wallet::{Wallet, PendingRegistration};
+pub use manifest::ParamsManifest;
+pub use seed::Seed;
+pub use secret::SecretScalar;
+pub use logging::Redacted;
+pub use storage::{Storage, MemoryStorage};
+pub use audit::{AuditLog, AuditEntry, AuditAction, AuditHead, verify_transcript};
+pub use events::{Event, EventBus};
+pub use encoding::GtElement;
+pub use params::SystemParams;
+
+use tbn::{Group, G1, G2, Fr};
+use tbn::arith::U256;
+
+// Returns generators (g, g2) in (G1, G2)
+// Because G1 and G2 are additive cyclic groups of prime order by construction of BN curves
+// It is sufficient to randomly choose elements in G1 and G2 to get g and g2
+pub fn get_generator_pair() -> (G1, G2) {
+    
+    // Crytpographiclaly secure thread-local rng
+    let rng = &mut rand::thread_rng();
+
+    // Generate random elements in G1 and G2
+    let (mut g, mut g2):(G1, G2) = (G1::random(rng), G2::random(rng));
+    // Ensure that g,g2 are both generators (i.e. non-zero in additive cyclic group of prime
+    // order)
+    while g.is_zero() {
+        g = G1::random(rng);
+    }
+    while g2.is_zero() {
+        g2 = G2::random(rng);
+    }
+
+    // Return generator pair
+    (g, g2)
+}
+
+// Convert U256 into hex string encoding (excluding 0x), always 64 digits
+pub fn to_hex_string(n:U256) -> String {
+
+    let bytes = codec::u256_to_be_bytes(n);
+
+    // Return hex encoding of byte vector
+    return hex::encode(bytes);
+}
+
+// Anyone can test if a user is authorized to take a survey, under the SA key (or key ring) that signed it
+pub fn authorized(id:Fr, survey:&Survey, vk_sa:&impl SurveyKeys, vk_ra:&VerificationKey, g2:G2) -> bool {
+    (*survey).is_authorized(id, vk_sa, vk_ra, g2)
+}
+
+// Same as authorized, for a survey expected from the SA pinned in registry as authority (see registry.rs)
+pub fn authorized_by(authority:&str, id:Fr, survey:&Survey, registry:&SaRegistry, vk_ra:&VerificationKey, g2:G2) -> bool {
+    (*registry).is_authorized(authority, id, survey, vk_ra, g2)
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use crate::gt::pairing;
+
+// Fuzzy test for if we have a good generator for pairing-based crypto
+#[test]
+fn test_generators() {
+
+    let (g, g2):(G1, G2) = get_generator_pair();
+    
+    // Try 5 different random values to see if assertion holds each time
+    // For random a and b, asserts that e(g^a, g_2^b) = e(g,g_2)^{ab} (RHS is generator for Gt)
+    let rng = &mut rand::thread_rng();
+    for _ in 0..5 {
+        let a = Fr::random(rng);
+        let b = Fr::random(rng);
+        assert!( pairing(g * a, g2 * b) == pairing(g, g2).pow(a * b) );
+    }
+}
+
+// U256 -> hex conversions keep their leading zeros, and nothing more
+#[test]
+fn test_to_hex_string() {
+    assert!( to_hex_string(Fr::one().into_u256()) == format!("{:064x}", 1) );
+    assert!( to_hex_string(tbn::Fq::modulus()) == "30644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd47" );
+}
+
+
+/*
+ * Integration tests
+ */
+
+
+/*
+ * Benchmark tests
+ */
+
+#[test]
+#[allow(non_snake_case)]
+// Test GenSurvey for 30 users to get mean and standard deviation
+fn bench_30_user_gen_survey() {
+
+    use std::time::{Duration, Instant};
+
+    // Setup 
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::random();
+    let (g, g2):(G1, G2) = params.generators();
+    let ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    const NUM_USERS:usize = 30;
+    assert!(NUM_USERS > 1);
+    let mut userids:Vec<Fr> = Vec::new();
+    for _ in 0..NUM_USERS {
+        // Skip registering user -- we only care about user ids for generating survey
+        userids.push(Fr::random(rng));
+    }
+
+    // 30-participant survey for GenSurvey
+    println!("GenSurvey Benchmark Test ({} users)", NUM_USERS);
+    let mut sum:Duration = Duration::new(0,0);
+    let mut durs:[Duration;NUM_USERS] = [Duration::new(0,0);NUM_USERS];
+    for i in 0..NUM_USERS {
+        let start = Instant::now();
+        // One user at a time
+        let _ = sa.gen_survey(&vec![userids[i]], g, g2, &ra.vk).expect("SA survey creation failed!");
+        durs[i] = start.elapsed();
+        sum += durs[i];
+        println!("User {}: {:?}", i+1, durs[i]);
+    }
+    println!();
+    // Calculate mean
+    let mean = sum / (NUM_USERS as u32);
+    // Calculate standard deviation
+    let mut sum_of_diff:f32 = 0.0;
+    for i in 0..NUM_USERS {
+        sum_of_diff += f32::powf((((durs[i].as_millis() as i128) - (mean.as_millis() as i128)) as f32)/1000.0, 2.0);
+    }
+    let sd = ( sum_of_diff / ((NUM_USERS as f32)- 1.0)).sqrt();
+ 
+    println!("Mean:\t\t{:?}", mean);
+    println!("Std Dev:\t{:?}s", sd);
+    println!("Total:\t\t{:?}", sum);
+}
+
+
+#[test]
+#[ignore]
+#[allow(non_snake_case)]
+// Test GenSurvey for 300 users to get mean and standard deviation
+fn bench_300_user_gen_survey() {
+
+    use std::time::{Duration, Instant};
+
+    // Setup 
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::random();
+    let (g, g2):(G1, G2) = params.generators();
+    let ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    const NUM_USERS:usize = 300;
+    assert!(NUM_USERS > 1);
+    let mut userids:Vec<Fr> = Vec::new();
+    for _ in 0..NUM_USERS {
+        // Skip registering user -- we only care about user ids for generating survey
+        userids.push(Fr::random(rng));
+    }
+ 
+    // 300-participant survey for GenSurvey
+    println!("GenSurvey Benchmark Test ({} users)", NUM_USERS);
+    let mut sum:Duration = Duration::new(0,0);
+    let mut durs:[Duration;NUM_USERS] = [Duration::new(0,0);NUM_USERS];
+    for i in 0..NUM_USERS {
+        let start = Instant::now();
+        // One user at a time
+        let _ = sa.gen_survey(&vec![userids[i]], g, g2, &ra.vk).expect("SA survey creation failed!");
+        durs[i] = start.elapsed();
+        sum += durs[i];
+        println!("User {}: {:?}", i+1, durs[i]);
+    }
+    println!();
+    // Calculate mean
+    let mean = sum / (NUM_USERS as u32);
+    // Calculate standard deviation
+    let mut sum_of_diff:f32 = 0.0;
+    for i in 0..NUM_USERS {
+        sum_of_diff += f32::powf((((durs[i].as_millis() as i128) - (mean.as_millis() as i128)) as f32)/1000.0, 2.0);
+    }
+    let sd = ( sum_of_diff / ((NUM_USERS as f32)- 1.0)).sqrt();
+ 
+    println!("Mean:\t\t{:?}", mean);
+    println!("Std Dev:\t{:?}s", sd);
+    println!("Total:\t\t{:?}", sum);
+}
+
+
+
+#[test]
+#[allow(non_snake_case)]
+// Test Authorized for 30 users to get mean and standard deviation
+fn bench_30_user_authorized() {
+
+    use std::time::{Duration, Instant};
+
+    // Setup 
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::random();
+    let (g, g2):(G1, G2) = params.generators();
+    let ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    const NUM_USERS:usize = 30;
+    assert!(NUM_USERS > 1);
+    let mut userids:Vec<Fr> = Vec::new();
+    for _ in 0..NUM_USERS {
+        // Skip registering user -- we only care about user ids for generating survey
+        userids.push(Fr::random(rng));
+    }
+
+    // 30-participant survey for GenSurvey
+    println!("Generating {} survey signatures...", userids.len());
+    let survey:Survey = sa.gen_survey(&userids, g, g2, &ra.vk).expect("SA survey creation failed!");
+ 
+    // Check authorized for each user
+    println!("User Authorized Benchmark Test ({} users)", NUM_USERS);
+    let mut sum:Duration = Duration::new(0,0);
+    let mut durs:[Duration;NUM_USERS] = [Duration::new(0,0);NUM_USERS];
+    let _ = sa.gen_survey(&userids, g, g2, &ra.vk).expect("SA survey creation failed!");
+    
+    for i in 0..NUM_USERS {
+        let start = Instant::now();
+        // One user at a time
+        let _ = authorized(userids[i], &survey, &sa.vk, &ra.vk, g2);
+        durs[i] = start.elapsed();
+        sum += durs[i];
+        println!("User {}: {:?}", i+1, durs[i]);
+    }
+    println!();
+    // Calculate mean
+    let mean = sum / (NUM_USERS as u32);
+    // Calculate standard deviation
+    let mut sum_of_diff:f32 = 0.0;
+    for i in 0..NUM_USERS {
+        sum_of_diff += f32::powf((((durs[i].as_millis() as i128) - (mean.as_millis() as i128)) as f32)/1000.0, 2.0);
+    }
+    let sd = ( sum_of_diff / ((NUM_USERS as f32)- 1.0)).sqrt();
+ 
+    println!("Mean:\t\t{:?}", mean);
+    println!("Std Dev:\t{:?}s", sd);
+    println!("Total:\t\t{:?}", sum);
+}
+
+
+
+#[test]
+#[ignore]
+#[allow(non_snake_case)]
+// Test Authorized for 300 users to get mean and standard deviation
+fn bench_300_user_authorized() {
+
+    use std::time::{Duration, Instant};
+
+    // Setup 
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::random();
+    let (g, g2):(G1, G2) = params.generators();
+    let ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    const NUM_USERS:usize = 300;
+    assert!(NUM_USERS > 1);
+    let mut userids:Vec<Fr> = Vec::new();
+    for _ in 0..NUM_USERS {
+        // Skip registering user -- we only care about user ids for generating survey
+        userids.push(Fr::random(rng));
+    }
+
+    // 300-participant survey for GenSurvey
+    println!("Generating {} survey signatures...", userids.len());
+    let survey:Survey = sa.gen_survey(&userids, g, g2, &ra.vk).expect("SA survey creation failed!");
+ 
+    // Check authorized for each user
+    println!("User Authorized Benchmark Test ({} users)", NUM_USERS);
+    let mut sum:Duration = Duration::new(0,0);
+    let mut durs:[Duration;NUM_USERS] = [Duration::new(0,0);NUM_USERS];
+    let _ = sa.gen_survey(&userids, g, g2, &ra.vk).expect("SA survey creation failed!");
+    
+    for i in 0..NUM_USERS {
+        let start = Instant::now();
+        // One user at a time
+        let _ = authorized(userids[i], &survey, &sa.vk, &ra.vk, g2);
+        durs[i] = start.elapsed();
+        sum += durs[i];
+        println!("User {}: {:?}", i+1, durs[i]);
+    }
+    println!();
+    // Calculate mean
+    let mean = sum / (NUM_USERS as u32);
+    // Calculate standard deviation
+    let mut sum_of_diff:f32 = 0.0;
+    for i in 0..NUM_USERS {
+        sum_of_diff += f32::powf((((durs[i].as_millis() as i128) - (mean.as_millis() as i128)) as f32)/1000.0, 2.0);
+    }
+    let sd = ( sum_of_diff / ((NUM_USERS as f32)- 1.0)).sqrt();
+ 
+    println!("Mean:\t\t{:?}", mean);
+    println!("Std Dev:\t{:?}s", sd);
+    println!("Total:\t\t{:?}", sum);
+}
@@ -0,0 +1,85 @@
+extern crate tbn;
+extern crate hex;
+
+use std::fmt;
+use tbn::{Fr, G1};
+
+use crate::encoding::{fr_to_bytes, g1_to_bytes};
+
+/*
+ * ----------------------------------------------
+ * |    LOGGING                                 |
+ * ----------------------------------------------
+ *
+ * Protocol code reports what it does through the tracing facade, with a span per protocol step
+ * (register, gen_survey, accept, ...) and events at these levels:
+ *      - warn: refused registrations and submissions, keystores that do not open
+ *      - info: changes to an authority's state: surveys opened, keys rotated, epochs advanced,
+ *        revocations applied
+ *      - debug: per-user steps: credentials issued, submissions accepted
+ *
+ * Nothing is written unless the application installs a subscriber; the anon_survey binary prints
+ * them to stderr, filtered by ANON_SURVEY_LOG (e.g. ANON_SURVEY_LOG=debug, see main.rs).
+ *
+ * Survey ids, key fingerprints, epochs and counts are public and logged as they are. Anything that
+ * singles out a user (ids, id hashes, registration commitments, one-time tokens) is only logged
+ * wrapped in Redacted, which prints <redacted> unless the `unredacted-logs` feature is on (for
+ * debugging against test data). Secret scalars are never logged.
+*/
+
+// Value that identifies a user, kept out of logs by default
+pub struct Redacted<T: LogHex>(pub T);
+
+// Hex encoding of a logged value
+pub trait LogHex {
+    fn log_hex(&self) -> String;
+}
+
+impl LogHex for Fr {
+    fn log_hex(&self) -> String {
+        hex::encode(fr_to_bytes(*self))
+    }
+}
+
+impl LogHex for G1 {
+    fn log_hex(&self) -> String {
+        hex::encode(g1_to_bytes(*self))
+    }
+}
+
+impl<T: LogHex> fmt::Display for Redacted<T> {
+    #[cfg(not(feature = "unredacted-logs"))]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<redacted>")
+    }
+
+    #[cfg(feature = "unredacted-logs")]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", (*self).0.log_hex())
+    }
+}
+
+// A survey id as logged: the first 8 bytes of its encoding, in hex
+pub(crate) fn vid_hex(vid: Fr) -> String {
+    let mut hex:String = vid.log_hex();
+    hex.truncate(16);
+    hex
+}
+
+
+/*
+ * Unit tests
+ */
+
+// Identifying values stay out of the logs unless unredacted-logs is on
+#[test]
+fn test_redacted() {
+    let id:Fr = Fr::random(&mut rand::thread_rng());
+    let logged:String = format!("{}", Redacted(id));
+    if cfg!(feature = "unredacted-logs") {
+        assert!( logged == id.log_hex() );
+    } else {
+        assert!( logged == "<redacted>" );
+    }
+    assert!( vid_hex(id).len() == 16 && id.log_hex().starts_with(&vid_hex(id)) );
+}
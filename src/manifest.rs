@@ -0,0 +1,157 @@
+extern crate tbn;
+extern crate sha2;
+
+use sha2::{Sha256, Digest};
+use tbn::{Fr, G1, G2};
+
+use crate::error::SurveyError;
+use crate::users::{VerificationKey, KeyHolder};
+use crate::proofs::{Proof, verify_gt, push_bytes, push_g1, push_g2};
+use crate::params::{CURVE, domains};
+use crate::keystore::push_vk;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/*
+ * ----------------------------------------------
+ * |    PARAMETER MANIFESTS                     |
+ * ----------------------------------------------
+ *
+ * The RA freezes the deployment's parameters into a manifest: the code version, the curve, the
+ * generators (g, g2), the hash domains, and the fingerprints (SHA-256 of the encoded key) of vk_RA
+ * and of every SA key clients should accept. Clients check the manifest at startup and refuse to
+ * run against anything else, e.g. a swapped RA key or an SA that was never announced.
+ *
+ * The manifest is signed with the RA's key x (pk_RA = e(g, g2)^x) by a Schnorr signature in Gt
+ * (see proofs.rs) under its own domain, so it cannot be mistaken for a credential or a proof:
+ *      T = e(g, g2)^k,     c = H(domain ‖ pk_RA ‖ T ‖ body),     z = k + c·x
+ * The nonce k is derived from x and the body, and the SA fingerprints are sorted, so freezing the
+ * same parameters always produces the same manifest.
+*/
+
+pub const MANIFEST_DOMAIN:&[u8] = b"anon-survey/manifest/v1";
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParamsManifest {
+    // Version of anon_survey that froze the parameters
+    pub version: String,
+    pub curve: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g1"))]
+    pub g: G1,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g2"))]
+    pub g2: G2,
+    // Hash domains of the proofs
+    pub domains: Vec<String>,
+    // Fingerprint of vk_RA
+    pub ra: String,
+    // Fingerprints of the accepted SA keys, sorted
+    pub sa: Vec<String>,
+    // Signature (c, z) under vk_RA
+    pub signature: Proof
+}
+
+// Hex SHA-256 of a verification key, to compare keys by
+pub fn fingerprint(vk: &VerificationKey) -> String {
+    let mut bytes:Vec<u8> = Vec::new();
+    push_vk(&mut bytes, vk);
+    hex::encode(Sha256::digest(&bytes))
+}
+
+impl ParamsManifest {
+
+    // Freeze the parameters of this build with the RA's generators and keys, accepting sa_keys
+    pub(crate) fn freeze(g: G1, g2: G2, ra: &impl KeyHolder, sa_keys: &[&VerificationKey]) -> Self {
+        let mut sa:Vec<String> = sa_keys.iter().map(|vk| fingerprint(vk)).collect();
+        sa.sort();
+        sa.dedup();
+        let mut manifest = ParamsManifest {
+            version: String::from(env!("CARGO_PKG_VERSION")),
+            curve: String::from(CURVE),
+            g,
+            g2,
+            domains: domains(),
+            ra: (*ra).fingerprint(),
+            sa,
+            signature: Proof { c: Fr::zero(), z: Vec::new() }
+        };
+
+        let body = manifest.body();
+        manifest.signature = (*ra).sign_body(MANIFEST_DOMAIN, g, g2, &body);
+        manifest
+    }
+
+    // Check the manifest against the parameters a client was handed, and the running code. The SA
+    // key, if given, must be one the manifest accepts
+    pub fn check(&self, g: G1, g2: G2, vk_ra: &VerificationKey, vk_sa: Option<&VerificationKey>) -> Result<(), SurveyError> {
+        let unexpected = |what: &str| Err(SurveyError::UnexpectedParameters(String::from(what)));
+        if (*self).version != env!("CARGO_PKG_VERSION") {
+            return unexpected("manifest was frozen by another version");
+        }
+        if (*self).curve != CURVE || (*self).domains != domains() {
+            return unexpected("curve or hash domains differ");
+        }
+        if (*self).g != g || (*self).g2 != g2 {
+            return unexpected("generators differ");
+        }
+        if (*self).ra != fingerprint(vk_ra) {
+            return unexpected("RA key differs");
+        }
+        if !(*self).verify_signature(vk_ra) {
+            return Err(SurveyError::InvalidSignature);
+        }
+        match vk_sa {
+            Some(vk) if !(*self).sa.contains(&fingerprint(vk)) => unexpected("SA key is not in the manifest"),
+            _ => Ok(())
+        }
+    }
+
+    fn verify_signature(&self, vk_ra: &VerificationKey) -> bool {
+        verify_gt(MANIFEST_DOMAIN, (*self).g, (*self).g2, (*vk_ra).pk, &(*self).body(), &(*self).signature)
+    }
+
+    // Everything but the signature, length-prefixed
+    fn body(&self) -> Vec<u8> {
+        let mut body:Vec<u8> = Vec::new();
+        push_bytes(&mut body, (*self).version.as_bytes());
+        push_bytes(&mut body, (*self).curve.as_bytes());
+        push_g1(&mut body, (*self).g);
+        push_g2(&mut body, (*self).g2);
+        for list in &[&(*self).domains, &vec![(*self).ra.clone()], &(*self).sa] {
+            body.extend_from_slice(&(list.len() as u64).to_be_bytes());
+            for item in list.iter() {
+                push_bytes(&mut body, item.as_bytes());
+            }
+        }
+        body
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use crate::users::{SurveyAuthority, RegistrationAuthority};
+#[cfg(test)]
+use crate::params::SystemParams;
+
+// Freezing is reproducible, and clients refuse swapped keys, unknown SAs, and edited manifests
+#[test]
+fn test_params_manifest() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let ra = RegistrationAuthority::new(&params);
+    let (sa, other_sa):(SurveyAuthority, SurveyAuthority) = (SurveyAuthority::new(&params), SurveyAuthority::new(&params));
+    let manifest = ra.freeze_params(&[&sa.vk]);
+    assert!( manifest == ra.freeze_params(&[&sa.vk, &sa.vk]) );
+    assert!( manifest.check(g, g2, &ra.vk, Some(&sa.vk)) == Ok(()) );
+
+    assert!( manifest.check(g, g2, &ra.vk, Some(&other_sa.vk)).is_err() );
+    assert!( manifest.check(g, g2, &RegistrationAuthority::new(&params).vk, None).is_err() );
+    assert!( manifest.check(g * Fr::from_str("2").unwrap(), g2, &ra.vk, None).is_err() );
+    let mut edited = manifest.clone();
+    edited.sa.push(fingerprint(&other_sa.vk));
+    assert!( edited.check(g, g2, &ra.vk, Some(&other_sa.vk)) == Err(SurveyError::InvalidSignature) );
+}
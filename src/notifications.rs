@@ -0,0 +1,182 @@
+extern crate tbn;
+extern crate hex;
+extern crate tracing;
+
+use std::io;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::{self, Sender, Receiver, error::RecvError};
+use tokio_tungstenite::tungstenite::Message;
+use futures_util::{StreamExt, SinkExt};
+use tbn::Fr;
+
+use crate::events::{Event, EventBus};
+use crate::encoding::{fr_to_bytes, fr_from_bytes};
+
+/*
+ * ----------------------------------------------
+ * |    SURVEY NOTIFICATIONS                    |
+ * ----------------------------------------------
+ *
+ * Pushes each new survey to the users on its participant list, so they do not have to poll the SA.
+ * A SurveyNotifier listens to an SA's events and serves a WebSocket channel of text frames:
+ *      - a client subscribes with its pseudonymous token, the hex of H(id) (see hash.rs), one token
+ *        per frame, and the server answers "subscribed <token>", or "error <reason>"
+ *      - when the SA opens a survey, every connection subscribed to one of its participants gets
+ *        "survey <vid>" (hex), and fetches the survey itself (e.g. FetchSurvey, see grpc.rs)
+ *
+ *      let notifier = SurveyNotifier::new();
+ *      notifier.attach(&mut sa.events);
+ *      tokio::spawn(notifier.serve(TcpListener::bind("0.0.0.0:8081").await?));
+ *
+ * The token is never the id itself, but the SA can compute H(id) for everyone it puts on a survey,
+ * so it learns which connection belongs to which participant. Clients that must not be linked to
+ * their network address connect the same way they submit. Notices are not stored: a survey opened
+ * while a user was not connected is not sent when they connect.
+*/
+
+// Notices held for connections that have not caught up yet
+const CHANNEL_CAPACITY:usize = 256;
+// Tokens one connection may subscribe (e.g. a household sharing a device)
+pub const MAX_SUBSCRIPTIONS:usize = 16;
+
+// A survey that was opened, with the id hashes of its participants
+#[derive(Clone, Debug, PartialEq)]
+pub struct SurveyNotice {
+    pub vid: Fr,
+    pub id_hashes: Vec<Fr>
+}
+
+#[derive(Clone)]
+pub struct SurveyNotifier {
+    sender: Sender<SurveyNotice>
+}
+
+impl SurveyNotifier {
+
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        SurveyNotifier { sender }
+    }
+
+    /* Notify subscribers of every survey opened on events (an SA's event bus) */
+    pub fn attach(&self, events: &mut EventBus) {
+        let sender:Sender<SurveyNotice> = (*self).sender.clone();
+        events.subscribe(move |event| if let Event::SurveyOpened { vid, id_hashes, .. } = event {
+            // Fails only when no connection is open, which is not an error
+            let _ = sender.send(SurveyNotice { vid: *vid, id_hashes: id_hashes.clone() });
+        });
+    }
+
+    /* Serve WebSocket connections from listener, until accepting fails */
+    pub async fn serve(self, listener: TcpListener) -> io::Result<()> {
+        loop {
+            let (stream, _) = listener.accept().await?;
+            tokio::spawn(connection(stream, self.sender.subscribe()));
+        }
+    }
+}
+
+impl Default for SurveyNotifier {
+    fn default() -> Self {
+        SurveyNotifier::new()
+    }
+}
+
+async fn connection(stream: TcpStream, mut notices: Receiver<SurveyNotice>) {
+    let mut socket = match tokio_tungstenite::accept_async(stream).await {
+        Ok(socket) => socket,
+        Err(error) => {
+            tracing::debug!(%error, "notification connection failed the WebSocket handshake");
+            return;
+        }
+    };
+    let mut subscribed:Vec<Fr> = Vec::new();
+    loop {
+        let reply:Option<String> = tokio::select! {
+            frame = socket.next() => match frame {
+                Some(Ok(Message::Text(token))) => Some(subscribe(&mut subscribed, token.trim())),
+                // Pings are answered by tungstenite itself
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => None
+            },
+            notice = notices.recv() => match notice {
+                Ok(notice) if notice.id_hashes.iter().any(|id_hash| subscribed.contains(id_hash)) =>
+                    Some(format!("survey {}", hex::encode(fr_to_bytes(notice.vid)))),
+                Ok(_) => None,
+                Err(RecvError::Lagged(missed)) => {
+                    tracing::warn!(missed, "notification connection fell behind and missed surveys");
+                    None
+                },
+                Err(RecvError::Closed) => return
+            }
+        };
+        if let Some(reply) = reply {
+            if socket.send(Message::Text(reply)).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+// Add a hex-encoded id hash to the connection's subscriptions, and the frame to answer with
+fn subscribe(subscribed: &mut Vec<Fr>, token: &str) -> String {
+    match hex::decode(token).ok().and_then(|bytes| fr_from_bytes(&bytes)) {
+        None => String::from("error not a hex-encoded id hash"),
+        Some(id_hash) if !subscribed.contains(&id_hash) && subscribed.len() >= MAX_SUBSCRIPTIONS =>
+            format!("error at most {} subscriptions per connection", MAX_SUBSCRIPTIONS),
+        Some(id_hash) => {
+            if !subscribed.contains(&id_hash) {
+                subscribed.push(id_hash);
+            }
+            format!("subscribed {}", hex::encode(fr_to_bytes(id_hash)))
+        }
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use crate::users::{User, SurveyAuthority, RegistrationAuthority};
+#[cfg(test)]
+use crate::params::SystemParams;
+#[cfg(test)]
+use crate::hash::id_hash;
+
+#[cfg(test)]
+async fn next_text(socket: &mut tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<TcpStream>>) -> String {
+    socket.next().await.expect("Connection closed").expect("Connection failed").into_text().expect("Not a text frame")
+}
+
+// A subscribed user hears of the surveys they are on, and only of those
+#[test]
+fn test_survey_notifications() {
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().expect("Could not start a runtime");
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let (mut alice, mut bob) = (User::new(), User::new());
+    alice.reg_user(&mut ra).expect("Registration failed");
+    bob.reg_user(&mut ra).expect("Registration failed");
+    let notifier = SurveyNotifier::new();
+    notifier.attach(&mut sa.events);
+
+    runtime.block_on(async {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("Could not listen");
+        let address = listener.local_addr().expect("Listener has no address");
+        tokio::spawn(notifier.serve(listener));
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}", address)).await.expect("Could not connect");
+        let token:String = hex::encode(fr_to_bytes(id_hash(alice.id())));
+        socket.send(Message::Text(token.clone())).await.expect("Could not subscribe");
+        assert!( next_text(&mut socket).await == format!("subscribed {}", token) );
+
+        sa.gen_survey(&vec![bob.id()], g, g2, &ra.vk).expect("Survey was refused");
+        let survey = sa.gen_survey(&vec![alice.id(), bob.id()], g, g2, &ra.vk).expect("Survey was refused");
+        assert!( next_text(&mut socket).await == format!("survey {}", hex::encode(fr_to_bytes(survey.vid))) );
+        socket.send(Message::Text(String::from("not a token"))).await.expect("Could not send");
+        assert!( next_text(&mut socket).await.starts_with("error") );
+    });
+}
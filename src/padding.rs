@@ -0,0 +1,145 @@
+use std::convert::TryFrom;
+
+use crate::error::SurveyError;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/*
+ * ----------------------------------------------
+ * |    ANSWER PADDING                          |
+ * ----------------------------------------------
+ *
+ * A submission carries its answer in the clear, so the size of a serialized submission gives away
+ * how long the answer is -- e.g. a one-word "no" versus a paragraph of free text -- to anyone who
+ * sees it in transit. Padding the answer up to one of a few fixed bucket sizes before submitting
+ * leaves only the bucket visible:
+ *
+ *      answer ‖ 0x80 ‖ 0x00 ... 0x00       (to the smallest bucket that fits, ISO/IEC 7816-4)
+ *
+ * Answers longer than the largest bucket are padded to a multiple of it. The padding is part of the
+ * message the submission proof binds, so it cannot be stripped or altered on the way either.
+ *
+ * The buckets are the same for every participant of a survey, or the bucket would tell respondents
+ * apart: the SA picks them (SurveyAuthority::padding), publishes them with the survey and signs them
+ * into its policy (see policy.rs), and User::submit_survey pads to them.
+*/
+
+// Bucket sizes (bytes) used unless configured otherwise
+pub const DEFAULT_BUCKETS:[usize; 4] = [64, 256, 1024, 4096];
+
+// Decoded through new, so a policy always has at least one bucket, and none of size zero
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "Buckets", into = "Buckets"))]
+pub struct PaddingPolicy {
+    // Sizes a padded answer may have, in increasing order
+    buckets: Vec<usize>
+}
+
+// Wire form of a padding policy
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct Buckets {
+    buckets: Vec<usize>
+}
+
+impl PaddingPolicy {
+
+    // Fails with InvalidPadding without a bucket, or with one of size zero
+    pub fn new(mut buckets: Vec<usize>) -> Result<Self, SurveyError> {
+        buckets.sort_unstable();
+        buckets.dedup();
+        match buckets.first() {
+            Some(smallest) if *smallest > 0 => Ok(PaddingPolicy { buckets }),
+            _ => Err(SurveyError::InvalidPadding)
+        }
+    }
+
+    pub fn buckets(&self) -> &[usize] {
+        &(*self).buckets
+    }
+
+    // Size of the padded form of an answer of len bytes (which needs one byte for the marker)
+    pub fn padded_len(&self, len: usize) -> usize {
+        let largest:usize = *(*self).buckets.last().unwrap();
+        match (*self).buckets.iter().find(|bucket| **bucket > len) {
+            Some(bucket) => *bucket,
+            None => (len / largest + 1) * largest
+        }
+    }
+
+    pub fn pad(&self, answer: &[u8]) -> Vec<u8> {
+        let mut padded:Vec<u8> = answer.to_vec();
+        padded.push(0x80);
+        padded.resize((*self).padded_len(answer.len()), 0);
+        padded
+    }
+
+    // Whether message is an answer padded under this policy
+    pub fn is_padded(&self, message: &[u8]) -> bool {
+        match unpad(message) {
+            Some(answer) => (*self).padded_len(answer.len()) == message.len(),
+            None => false
+        }
+    }
+}
+
+impl Default for PaddingPolicy {
+    fn default() -> Self {
+        PaddingPolicy { buckets: DEFAULT_BUCKETS.to_vec() }
+    }
+}
+
+impl TryFrom<Buckets> for PaddingPolicy {
+    type Error = SurveyError;
+
+    fn try_from(buckets: Buckets) -> Result<Self, SurveyError> {
+        PaddingPolicy::new(buckets.buckets)
+    }
+}
+
+impl From<PaddingPolicy> for Buckets {
+    fn from(policy: PaddingPolicy) -> Self {
+        Buckets { buckets: policy.buckets }
+    }
+}
+
+// The answer inside a padded message (None if it was not padded)
+pub fn unpad(padded: &[u8]) -> Option<&[u8]> {
+    let marker:usize = padded.iter().rposition(|b| *b != 0)?;
+    match padded[marker] {
+        0x80 => Some(&padded[..marker]),
+        _ => None
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+// Answers of different lengths in the same bucket are indistinguishable by size, and come back intact
+#[test]
+fn test_padding_buckets() {
+    let policy = PaddingPolicy::new(vec![256, 16, 64]).expect("Buckets are valid");
+    assert!( policy.buckets() == [16, 64, 256] );
+    assert!( policy.pad(b"no").len() == 16 && policy.pad(b"yes, mostly").len() == 16 );
+    assert!( policy.pad(&[0x80; 15]).len() == 16 && policy.pad(&[0x80; 16]).len() == 64 );
+    assert!( policy.pad(&[1; 300]).len() == 512 );
+    for answer in [&b""[..], b"no", &[0x80; 16], &[0; 70]].iter() {
+        assert!( unpad(&policy.pad(answer)) == Some(*answer) );
+    }
+    assert!( unpad(b"yes").is_none() && unpad(&[0; 16]).is_none() );
+    assert!( policy.is_padded(&policy.pad(b"no")) && !policy.is_padded(b"no") && !policy.is_padded(&PaddingPolicy::default().pad(b"no")) );
+}
+
+// A policy needs a bucket, and none of size zero, however it is made
+#[test]
+fn test_padding_rejects_invalid() {
+    assert!( PaddingPolicy::new(Vec::new()) == Err(SurveyError::InvalidPadding) );
+    assert!( PaddingPolicy::new(vec![0, 64]) == Err(SurveyError::InvalidPadding) );
+    #[cfg(feature = "serde")]
+    {
+        assert!( serde_json::from_str::<PaddingPolicy>(r#"{"buckets":[]}"#).is_err() );
+        assert!( serde_json::from_str::<PaddingPolicy>(r#"{"buckets":[64,16,64]}"#).unwrap().buckets() == [16, 64] );
+    }
+}
@@ -0,0 +1,222 @@
+extern crate rand;
+extern crate hex;
+extern crate sha2;
+extern crate tbn;
+
+use std::time::{Duration, SystemTime};
+use rand::Rng;
+use sha2::{Sha256, Digest};
+use tbn::G1;
+
+use crate::users::{RegistrationAuthority, RegistrationCode};
+use crate::storage::Storage;
+use crate::revocation::RevocationBatch;
+use crate::error::SurveyError;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/*
+ * ----------------------------------------------
+ * |    RESPONDENT PANEL IMPORT                 |
+ * ----------------------------------------------
+ *
+ * Bridges an existing panel roster (emails, employee ids, ...) into the anonymous registration
+ * flow. Every roster entry gets its own one-time registration code, and the RA only ever sees a
+ * salted hash of the identifier in the code's metadata -- never the identifier itself. The salt
+ * stays with the panel operator, who sends out the invitations and can track how many were
+ * redeemed (or whether a given member redeemed theirs) by recomputing the hash. The same hashes
+ * map an HR feed of departed members back to their registrations for revocation (revocation.rs).
+ *
+ * With the serde feature a panel can be saved between runs (`anon_survey ra revoke-batch --panel`
+ * reads it as YAML). The file holds the salt, so it stays with the operator as well.
+*/
+
+// Number of random bytes in a panel's salt
+const SALT_BYTES:usize = 16;
+
+// Held by the panel operator, not the RA
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Panel {
+    pub name: String,
+    salt: [u8; SALT_BYTES],
+    // Issued invitations as (salted identifier hash, registration code)
+    invitations: Vec<(String, RegistrationCode)>
+}
+
+impl Panel {
+
+    // Import a roster with one identifier per line (extra comma-separated columns, blank lines and
+    // duplicate identifiers are ignored), minting a registration code for every member.
+    // Returns the panel together with the (identifier, code) pairs to send out as invitations
+    pub fn import(name: &str, roster: &str, ra: &mut RegistrationAuthority<impl Storage>, valid_for: Duration)
+                  -> (Panel, Vec<(String, String)>) {
+
+        // crytpographiclaly secure thread-local rng
+        let rng = &mut rand::thread_rng();
+        let mut panel = Panel { name: String::from(name), salt: rng.gen(), invitations: Vec::new() };
+
+        let mut invitations:Vec<(String, String)> = Vec::new();
+        for line in roster.lines() {
+            let identifier = normalize(line.split(',').next().unwrap_or(""));
+            if identifier.is_empty() {
+                continue;
+            }
+            let hashed = panel.hash(&identifier);
+            if panel.invitations.iter().any(|(member, _)| *member == hashed) {
+                continue;
+            }
+            let reg_code = (*ra).issue_registration_code(&format!("panel:{}:{}", name, hashed), valid_for);
+            invitations.push((identifier, reg_code.code.clone()));
+            panel.invitations.push((hashed, reg_code));
+        }
+        (panel, invitations)
+    }
+
+    // Number of members invited
+    pub fn len(&self) -> usize {
+        (*self).invitations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (*self).invitations.is_empty()
+    }
+
+    // Number of invitations redeemed so far
+    pub fn redeemed(&self, ra: &RegistrationAuthority<impl Storage>) -> usize {
+        (*self).invitations.iter().filter(|(_, reg_code)| is_redeemed(reg_code, ra)).count()
+    }
+
+    // Number of invitations that expired without being redeemed
+    pub fn expired(&self, ra: &RegistrationAuthority<impl Storage>) -> usize {
+        let now = SystemTime::now();
+        (*self).invitations.iter().filter(|(_, reg_code)| now > reg_code.expires && !is_redeemed(reg_code, ra)).count()
+    }
+
+    // Fraction of invitations redeemed so far (0 for an empty panel)
+    pub fn redemption_rate(&self, ra: &RegistrationAuthority<impl Storage>) -> f64 {
+        if (*self).is_empty() {
+            return 0.0;
+        }
+        (*self).redeemed(ra) as f64 / (*self).len() as f64
+    }
+
+    // Whether the member with the given identifier redeemed their invitation (None if they are not
+    // on the panel)
+    pub fn member_redeemed(&self, identifier: &str, ra: &RegistrationAuthority<impl Storage>) -> Option<bool> {
+        let hashed = (*self).hash(&normalize(identifier));
+        (*self).invitations.iter().find(|(member, _)| *member == hashed)
+            .map(|(_, reg_code)| is_redeemed(reg_code, ra))
+    }
+
+    // Revoke the members listed in an HR feed (same format as the roster) in one batch: members who
+    // registered with their invitation leave the anonymity set, and pending invitations are
+    // withdrawn. Returns the signed batch and the identifiers that are not on the panel
+    pub fn revoke(&self, feed: &str, ra: &mut RegistrationAuthority<impl Storage>) -> Result<(RevocationBatch, Vec<String>), SurveyError> {
+        let mut commitments:Vec<G1> = Vec::new();
+        let mut pending:Vec<String> = Vec::new();
+        let mut unknown:Vec<String> = Vec::new();
+        for line in feed.lines() {
+            let identifier = normalize(line.split(',').next().unwrap_or(""));
+            if identifier.is_empty() {
+                continue;
+            }
+            let hashed = (*self).hash(&identifier);
+            match (*self).invitations.iter().find(|(member, _)| *member == hashed) {
+                Some((_, reg_code)) => match (*ra).registered_with(&reg_code.metadata) {
+                    Some(commitment) => commitments.push(commitment),
+                    None => pending.push(reg_code.code.clone())
+                },
+                None if !unknown.contains(&identifier) => unknown.push(identifier),
+                None => ()
+            }
+        }
+        let batch:RevocationBatch = (*ra).revoke_batch(&commitments)?;
+        for code in pending {
+            (*ra).withdraw_registration_code(&code);
+        }
+        Ok((batch, unknown))
+    }
+
+    // Salted SHA-256 of a normalized identifier, hex encoded
+    fn hash(&self, identifier: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update((*self).salt);
+        hasher.update(identifier.as_bytes());
+        hex::encode(hasher.finalize())
+    }
+}
+
+// Identifiers are compared case-insensitively and without surrounding whitespace
+fn normalize(identifier: &str) -> String {
+    identifier.trim().to_lowercase()
+}
+
+// Whether the RA holds a registration made with the code. Once redeemed, a code stays redeemed
+// after it would have expired (a code that is merely no longer pending may have expired unused)
+fn is_redeemed(reg_code: &RegistrationCode, ra: &RegistrationAuthority<impl Storage>) -> bool {
+    !(*ra).is_pending(&reg_code.code) && (*ra).registered_with(&reg_code.metadata).is_some()
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use crate::users::User;
+#[cfg(test)]
+use crate::params::SystemParams;
+
+// Duplicates and blank lines are skipped, and redemptions are counted per member
+#[test]
+fn test_panel_import() {
+    let params = SystemParams::random();
+    let mut ra = RegistrationAuthority::new(&params);
+    let roster = "alice@example.org,Engineering\nbob@example.org,Sales\n\n  Alice@Example.org \ncarol@example.org\n";
+    let (panel, invitations) = Panel::import("staff-2024", roster, &mut ra, Duration::from_secs(60 * 60));
+    assert!( panel.len() == 3 && invitations.len() == 3 );
+    assert!( panel.redemption_rate(&ra) == 0.0 );
+
+    let mut alice = User::new();
+    assert!( alice.redeem_code(&invitations[0].1, &mut ra).is_ok() );
+    assert!( panel.redeemed(&ra) == 1 );
+    assert!( panel.member_redeemed("ALICE@example.org", &ra) == Some(true) );
+    assert!( panel.member_redeemed("bob@example.org", &ra) == Some(false) );
+    assert!( panel.member_redeemed("dave@example.org", &ra).is_none() );
+}
+
+// A redeemed invitation still counts as redeemed once its code has expired, and an unused one
+// that lapsed is reported as expired instead
+#[test]
+fn test_panel_redemption_survives_expiry() {
+    let params = SystemParams::random();
+    let mut ra = RegistrationAuthority::new(&params);
+    let (mut panel, invitations) = Panel::import("staff-2024", "alice@example.org\nbob@example.org", &mut ra, Duration::from_secs(60 * 60));
+    let mut alice = User::new();
+    assert!( alice.redeem_code(&invitations[0].1, &mut ra).is_ok() );
+    assert!( panel.redeemed(&ra) == 1 && panel.expired(&ra) == 0 );
+
+    for (_, reg_code) in panel.invitations.iter_mut() {
+        reg_code.expires = SystemTime::now() - Duration::from_secs(1);
+    }
+    assert!( panel.redeemed(&ra) == 1 );
+    assert!( panel.member_redeemed("alice@example.org", &ra) == Some(true) );
+    assert!( panel.member_redeemed("bob@example.org", &ra) == Some(false) );
+
+    let (lapsed, _) = Panel::import("lapsed", "carol@example.org", &mut ra, Duration::from_secs(0));
+    std::thread::sleep(Duration::from_millis(5));
+    assert!( lapsed.redeemed(&ra) == 0 && lapsed.expired(&ra) == 1 );
+}
+
+// Only salted hashes of identifiers reach the RA, and different panels use different salts
+#[test]
+fn test_panel_hides_identifiers() {
+    let params = SystemParams::random();
+    let mut ra = RegistrationAuthority::new(&params);
+    let (first, _) = Panel::import("a", "alice@example.org", &mut ra, Duration::from_secs(60));
+    let (second, _) = Panel::import("b", "alice@example.org", &mut ra, Duration::from_secs(60));
+    let (first_hash, first_code) = &first.invitations[0];
+    assert!( !first_code.metadata.contains("alice") );
+    assert!( first_code.metadata.ends_with(first_hash.as_str()) );
+    assert!( *first_hash != second.invitations[0].0 );
+}
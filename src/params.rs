@@ -0,0 +1,174 @@
+extern crate tbn;
+
+use std::convert::TryFrom;
+use tbn::{Group, Fr, G1, G2};
+
+use crate::error::SurveyError;
+use crate::hash::hash_to_g1;
+use crate::proofs::{REGISTER_DOMAIN, PRF_DOMAIN};
+use crate::submission::SUBMIT_DOMAIN;
+use crate::get_generator_pair;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/*
+ * ----------------------------------------------
+ * |    SYSTEM PARAMETERS                       |
+ * ----------------------------------------------
+ *
+ * Every key, credential and survey signature is built on a pair of generators (g, g2). Picking them
+ * at random per run (get_generator_pair) makes keys from one run useless in the next, so
+ * deployments should agree on fixed ones:
+ *      - standard_generators(): the base points of tbn's BN curve, G1 = (1, 2) and tbn's G2 one
+ *      - hashed_generators(tag): an alternate g hashed onto G1 from a deployment tag (nobody knows
+ *        its discrete log to the standard point), with the standard g2
+ * The tag is hashed onto G1 under PARAMS_DOMAIN (see hash.rs). G1 has cofactor 1, so every such
+ * point is a generator; hashing onto G2 needs cofactor clearing that tbn does not expose.
+ *
+ * SystemParams bundles the pair with the curve and the hash domains of the proofs. It is generated
+ * once (the setup ceremony), serialized with serde (JSON, YAML, or any binary format) and handed to
+ * every party, and both authorities are constructed from it. Deserializing validates it: the curve
+ * and domains must be this build's, and both generators non-zero and of order q.
+*/
+
+pub const PARAMS_DOMAIN:&[u8] = b"anon-survey/params/v1";
+
+// Curve every key and manifest is built on
+pub const CURVE:&str = "BN254 (tbn)";
+
+// Hash domains of the proofs
+pub(crate) fn domains() -> Vec<String> {
+    [REGISTER_DOMAIN, PRF_DOMAIN, SUBMIT_DOMAIN].iter().map(|domain| String::from_utf8_lossy(domain).into_owned()).collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "Unchecked"))]
+pub struct SystemParams {
+    pub curve: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g1"))]
+    pub g: G1,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g2"))]
+    pub g2: G2,
+    pub domains: Vec<String>
+}
+
+impl SystemParams {
+
+    // Parameters on given generators, if they are valid ones
+    pub fn new(g: G1, g2: G2) -> Result<Self, SurveyError> {
+        SystemParams::try_from(Unchecked { curve: String::from(CURVE), g, g2, domains: domains() })
+    }
+
+    pub fn standard() -> Self {
+        let (g, g2) = standard_generators();
+        SystemParams { curve: String::from(CURVE), g, g2, domains: domains() }
+    }
+
+    pub fn hashed(tag: &[u8]) -> Self {
+        let (g, g2) = hashed_generators(tag);
+        SystemParams { curve: String::from(CURVE), g, g2, domains: domains() }
+    }
+
+    // Fresh random generators, only meaningful within one run (e.g. for tests)
+    pub fn random() -> Self {
+        let (g, g2) = get_generator_pair();
+        SystemParams { curve: String::from(CURVE), g, g2, domains: domains() }
+    }
+
+    pub fn generators(&self) -> (G1, G2) {
+        ((*self).g, (*self).g2)
+    }
+
+    // The parameters were made for this build, and both generators generate the order-q groups
+    pub fn validate(&self) -> Result<(), SurveyError> {
+        let unexpected = |what: &str| Err(SurveyError::UnexpectedParameters(String::from(what)));
+        if (*self).curve != CURVE || (*self).domains != domains() {
+            return unexpected("curve or hash domains differ");
+        }
+        if (*self).g.is_zero() || (*self).g2.is_zero() {
+            return unexpected("generator is zero");
+        }
+        // (q - 1)·P + P is zero iff the order of P divides q
+        let q_minus_one:Fr = -Fr::one();
+        if !((*self).g * q_minus_one + (*self).g).is_zero() || !((*self).g2 * q_minus_one + (*self).g2).is_zero() {
+            return unexpected("generator is not in the order-q subgroup");
+        }
+        Ok(())
+    }
+}
+
+// Parameters as read from outside, before validation
+#[cfg_attr(feature = "serde", derive(Deserialize))]
+struct Unchecked {
+    curve: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g1"))]
+    g: G1,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g2"))]
+    g2: G2,
+    domains: Vec<String>
+}
+
+impl TryFrom<Unchecked> for SystemParams {
+    type Error = SurveyError;
+
+    fn try_from(unchecked: Unchecked) -> Result<Self, SurveyError> {
+        let params = SystemParams { curve: unchecked.curve, g: unchecked.g, g2: unchecked.g2, domains: unchecked.domains };
+        params.validate()?;
+        Ok(params)
+    }
+}
+
+// The fixed base points of the curve
+pub fn standard_generators() -> (G1, G2) {
+    (G1::one(), G2::one())
+}
+
+// An alternate g hashed from tag, with the standard g2
+pub fn hashed_generators(tag: &[u8]) -> (G1, G2) {
+    (hash_to_g1(PARAMS_DOMAIN, tag), G2::one())
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use crate::gt::pairing;
+#[cfg(test)]
+use crate::encoding::g1_to_bytes;
+
+// Standard and hashed generators are fixed, non-trivial, and usable for pairings
+#[test]
+fn test_system_params() {
+    let standard = SystemParams::standard();
+    assert!( standard == SystemParams::standard() && standard.validate() == Ok(()) );
+    assert!( g1_to_bytes(standard.g)[31] == 1 && g1_to_bytes(standard.g)[63] == 2 );
+
+    let hashed = SystemParams::hashed(b"example deployment");
+    assert!( hashed == SystemParams::hashed(b"example deployment") );
+    assert!( hashed.g != standard.g && hashed.g != SystemParams::hashed(b"another deployment").g );
+    assert!( hashed.g2 == standard.g2 && hashed.validate() == Ok(()) );
+
+    let (g, g2) = hashed.generators();
+    let (a, b) = (Fr::random(&mut rand::thread_rng()), Fr::random(&mut rand::thread_rng()));
+    assert!( pairing(g * a, g2 * b) == pairing(g, g2).pow(a * b) );
+}
+
+// Parameters survive serialization, and loading refuses zero generators and foreign domains
+#[cfg(feature = "serde")]
+#[test]
+fn test_system_params_load() {
+    let params = SystemParams::hashed(b"example deployment");
+    let yaml = serde_yaml::to_string(&params).unwrap();
+    assert!( serde_yaml::from_str::<SystemParams>(&yaml).unwrap() == params );
+
+    let mut zero = params.clone();
+    zero.g = G1::zero();
+    assert!( zero.validate().is_err() && SystemParams::new(G1::zero(), params.g2).is_err() );
+    assert!( serde_yaml::from_str::<SystemParams>(&serde_yaml::to_string(&zero).unwrap()).is_err() );
+    let mut foreign = params.clone();
+    foreign.domains.push(String::from("other/v1"));
+    assert!( serde_yaml::from_str::<SystemParams>(&serde_yaml::to_string(&foreign).unwrap()).is_err() );
+}
@@ -0,0 +1,196 @@
+extern crate tbn;
+extern crate sha2;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+use sha2::{Sha256, Digest};
+use tbn::{Fr, G1, G2};
+
+use crate::error::SurveyError;
+use crate::padding::PaddingPolicy;
+use crate::users::{VerificationKey, KeyHolder};
+use crate::proofs::{Proof, verify_gt, push_fr};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/*
+ * ----------------------------------------------
+ * |    RESPONSE WINDOWS                        |
+ * ----------------------------------------------
+ *
+ * The SA announces when a survey stops taking responses with a SurveyPolicy, signed with its key
+ * (a Schnorr signature in Gt, see proofs.rs). To give participants more time, it signs an
+ * Amendment with a later closing time that names the hash of the policy, or of the amendment
+ * before it:
+ *
+ *      policy ← amendment_1 ← amendment_2 ← ...
+ *
+ * Clients and the ledger only honour the extended window if the whole chain validates: every link
+ * signed by the survey's SA, referencing the one before it, and moving the closing time later
+ * (windows are only ever extended). The chain is the audit trail of every change to the window.
+ * Times are seconds since the Unix epoch.
+ *
+ * The policy also fixes the buckets answers to the survey are padded to (see padding.rs), which
+ * amendments cannot change. A ledger that takes the policy refuses answers not padded to them.
+*/
+
+pub const POLICY_DOMAIN:&[u8] = b"anon-survey/policy/v1";
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SurveyPolicy {
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::fr"))]
+    pub vid: Fr,
+    pub closes_at: u64,
+    // Buckets every answer to the survey is padded to
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub padding: PaddingPolicy,
+    // Signature (c, z) under vk_SA
+    pub signature: Proof
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Amendment {
+    // Hash of the policy or amendment this one extends
+    pub previous: String,
+    pub closes_at: u64,
+    // Signature (c, z) under vk_SA
+    pub signature: Proof
+}
+
+impl SurveyPolicy {
+
+    // Sign a closing time and padding for survey vid with the SA's keys (see SurveyAuthority::set_closing_time)
+    pub(crate) fn sign(vid: Fr, closes_at: u64, padding: PaddingPolicy, sa: &impl KeyHolder, g: G1, g2: G2) -> Self {
+        let body:Vec<u8> = policy_body(vid, closes_at, &padding);
+        SurveyPolicy { vid, closes_at, padding, signature: (*sa).sign_body(POLICY_DOMAIN, g, g2, &body) }
+    }
+
+    // Hex SHA-256 of the signed policy, for amendments to reference
+    pub fn hash(&self) -> String {
+        link_hash(&policy_body((*self).vid, (*self).closes_at, &(*self).padding), &(*self).signature)
+    }
+
+    // The closing time in force after the amendments, if the policy and every amendment were
+    // signed by the SA with key vk_sa and each amendment extends the link before it
+    pub fn closing_time(&self, amendments: &[Amendment], vk_sa: &VerificationKey, g: G1, g2: G2) -> Result<u64, SurveyError> {
+        if !verify_gt(POLICY_DOMAIN, g, g2, (*vk_sa).pk, &policy_body((*self).vid, (*self).closes_at, &(*self).padding), &(*self).signature) {
+            return Err(SurveyError::InvalidSignature);
+        }
+        let (mut previous, mut closes_at):(String, u64) = ((*self).hash(), (*self).closes_at);
+        for amendment in amendments {
+            let body:Vec<u8> = amendment_body((*self).vid, &amendment.previous, amendment.closes_at);
+            if !verify_gt(POLICY_DOMAIN, g, g2, (*vk_sa).pk, &body, &amendment.signature) {
+                return Err(SurveyError::InvalidSignature);
+            }
+            if amendment.previous != previous || amendment.closes_at <= closes_at {
+                return Err(SurveyError::InvalidAmendment);
+            }
+            previous = link_hash(&body, &amendment.signature);
+            closes_at = amendment.closes_at;
+        }
+        Ok(closes_at)
+    }
+
+    // Whether the survey still takes responses at time now, under a valid chain
+    pub fn is_open(&self, amendments: &[Amendment], now: SystemTime, vk_sa: &VerificationKey, g: G1, g2: G2) -> Result<bool, SurveyError> {
+        let closes_at:u64 = (*self).closing_time(amendments, vk_sa, g, g2)?;
+        Ok(unix_time(now) < closes_at)
+    }
+}
+
+impl Amendment {
+
+    // Sign a later closing time, extending the policy or amendment with hash previous
+    pub(crate) fn sign(vid: Fr, previous: String, closes_at: u64, sa: &impl KeyHolder, g: G1, g2: G2) -> Self {
+        let body:Vec<u8> = amendment_body(vid, &previous, closes_at);
+        let signature:Proof = (*sa).sign_body(POLICY_DOMAIN, g, g2, &body);
+        Amendment { previous, closes_at, signature }
+    }
+
+    // Hex SHA-256 of the signed amendment, for the next amendment to reference
+    pub fn hash(&self, vid: Fr) -> String {
+        link_hash(&amendment_body(vid, &(*self).previous, (*self).closes_at), &(*self).signature)
+    }
+}
+
+// Seconds since the Unix epoch (zero before it)
+pub fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or(0)
+}
+
+// The policy and its amendments are told apart by their tag byte
+fn policy_body(vid: Fr, closes_at: u64, padding: &PaddingPolicy) -> Vec<u8> {
+    let mut body:Vec<u8> = vec![0];
+    push_fr(&mut body, vid);
+    body.extend_from_slice(&closes_at.to_be_bytes());
+    body.extend_from_slice(&((*padding).buckets().len() as u64).to_be_bytes());
+    for bucket in (*padding).buckets() {
+        body.extend_from_slice(&(*bucket as u64).to_be_bytes());
+    }
+    body
+}
+
+fn amendment_body(vid: Fr, previous: &str, closes_at: u64) -> Vec<u8> {
+    let mut body:Vec<u8> = vec![1];
+    push_fr(&mut body, vid);
+    body.extend_from_slice(previous.as_bytes());
+    body.extend_from_slice(&closes_at.to_be_bytes());
+    body
+}
+
+fn link_hash(body: &[u8], signature: &Proof) -> String {
+    let mut bytes:Vec<u8> = body.to_vec();
+    push_fr(&mut bytes, (*signature).c);
+    for z in &(*signature).z {
+        push_fr(&mut bytes, *z);
+    }
+    hex::encode(Sha256::digest(&bytes))
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use std::time::Duration;
+#[cfg(test)]
+use crate::users::SurveyAuthority;
+#[cfg(test)]
+use crate::params::SystemParams;
+
+// Only a chain of later closing times, each signed by the SA and naming the link before, extends
+// the window
+#[test]
+fn test_amendment_chain() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let sa = SurveyAuthority::new(&params);
+    let other_sa = SurveyAuthority::new(&params);
+    let vid:Fr = Fr::random(&mut rand::thread_rng());
+    let now:u64 = unix_time(SystemTime::now());
+
+    let policy = sa.set_closing_time(vid, now - 60, g, g2);
+    assert!( policy.is_open(&[], SystemTime::now(), &sa.vk, g, g2) == Ok(false) );
+    assert!( policy.closing_time(&[], &other_sa.vk, g, g2) == Err(SurveyError::InvalidSignature) );
+    let mut repadded = policy.clone();
+    repadded.padding = PaddingPolicy::new(vec![16]).expect("Buckets are valid");
+    assert!( repadded.closing_time(&[], &sa.vk, g, g2) == Err(SurveyError::InvalidSignature) );
+
+    let first:Vec<Amendment> = vec![sa.extend_closing_time(&policy, &[], now + 60, g, g2).expect("Extension was refused")];
+    let second = sa.extend_closing_time(&policy, &first, now + 120, g, g2).expect("Extension was refused");
+    assert!( second.previous == first[0].hash(vid) && first[0].previous == policy.hash() );
+    assert!( policy.closing_time(&[first[0].clone(), second.clone()], &sa.vk, g, g2) == Ok(now + 120) );
+    assert!( policy.is_open(&first, SystemTime::now(), &sa.vk, g, g2) == Ok(true) );
+    assert!( policy.is_open(&first, SystemTime::now() + Duration::from_secs(90), &sa.vk, g, g2) == Ok(false) );
+
+    // Out of order, shortened, tampered with, or signed by someone else
+    assert!( policy.closing_time(&[second], &sa.vk, g, g2) == Err(SurveyError::InvalidAmendment) );
+    assert!( sa.extend_closing_time(&policy, &first, now + 30, g, g2) == Err(SurveyError::InvalidAmendment) );
+    let mut tampered = first[0].clone();
+    tampered.closes_at += 3600;
+    assert!( policy.closing_time(&[tampered], &sa.vk, g, g2) == Err(SurveyError::InvalidSignature) );
+    let forged = other_sa.extend_closing_time(&policy, &[], now + 60, g, g2);
+    assert!( forged == Err(SurveyError::InvalidSignature) );
+}
@@ -0,0 +1,375 @@
+extern crate tbn;
+extern crate rand;
+extern crate sha2;
+
+use rand::{RngCore, CryptoRng};
+use sha2::{Sha256, Digest};
+use tbn::{Group, Fr, G1, G2, AffineG1, AffineG2};
+use crate::gt::Gt;
+
+use crate::ct::fr_eq;
+use crate::codec::u256_to_be_bytes;
+use crate::encoding::gt_to_bytes;
+use crate::cost::{pairing, pow};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/*
+ * ----------------------------------------------
+ * |    ZERO-KNOWLEDGE PROOFS                   |
+ * ----------------------------------------------
+ *
+ * Non-interactive (Fiat-Shamir) Σ-protocol proofs of knowledge of a representation
+ *
+ *      P = x_1·B_1 + ... + x_n·B_n        (P, B_i ∈ G1 public, x_i ∈ ℤ_q secret)
+ *
+ * The Prover picks random k_i, commits to T = Σ k_i·B_i, derives the challenge c by hashing the
+ * statement, T, and a caller-chosen context, and responds with z_i = k_i + c·x_i. The Verifier
+ * recomputes T = Σ z_i·B_i - c·P and accepts iff it hashes to the same challenge. Nothing about the
+ * x_i beyond the statement itself is revealed.
+ *
+ * Every relation the protocol proves has an explicit statement (public) and witness (secret) with
+ * prove/verify functions taking them:
+ *      - Registration:     knowledge of (s, b) such that S' = v_RA·s + g·b, bound to id
+ *      - PRF:              knowledge of s such that g = Tok·(s + vid), i.e. Tok = g^(1/(s + vid))
+ *      - Submission:       see submission.rs, which combines the PRF relation with the credential
+ *                          and signature checks
+ * The transcript helpers at the bottom are shared with the submission proof.
+ *
+ * Authorities sign their published documents (manifests, survey definitions) with the same kind of
+ * proof for their key x behind pk = e(g, g2)^x, a Schnorr signature in Gt (sign_gt).
+*/
+
+// Domain of the registration proof of knowledge of (s, b) behind S' = v_RA·s + g·b
+pub const REGISTER_DOMAIN:&[u8] = b"anon-survey/register/v1";
+
+// Registration proofs are bound to the id being registered, so they cannot be replayed for another
+pub fn registration_context(id: Fr) -> Vec<u8> {
+    let mut context:Vec<u8> = Vec::new();
+    push_fr(&mut context, id);
+    context
+}
+
+// Domain of the proof that a token was derived from the prover's PRF key
+pub const PRF_DOMAIN:&[u8] = b"anon-survey/prf/v1";
+
+// NIZK proof: the challenge and one response per witness
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Proof {
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::fr"))]
+    pub c: Fr,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::fr_vec"))]
+    pub z: Vec<Fr>
+}
+
+pub struct Prover {
+    domain: &'static [u8],
+    bases: Vec<G1>,
+    witness: Vec<Fr>
+}
+
+pub struct Verifier {
+    domain: &'static [u8],
+    bases: Vec<G1>,
+    public: G1
+}
+
+impl Prover {
+
+    // Prover for knowledge of witness such that P = Σ witness[i]·bases[i]. The domain separates
+    // the challenges of different kinds of statements
+    pub fn new(domain: &'static [u8], bases: Vec<G1>, witness: Vec<Fr>) -> Self {
+        assert!( bases.len() == witness.len(), "one witness per base" );
+        Prover { domain, bases, witness }
+    }
+
+    // The public value P the proof is about
+    pub fn public(&self) -> G1 {
+        linear_combination(&(*self).bases, &(*self).witness)
+    }
+
+    // Prove knowledge of the witness, binding the proof to context (e.g. the id being registered)
+    pub fn prove(&self, context: &[u8], rng: &mut (impl RngCore + CryptoRng)) -> Proof {
+
+        let k:Vec<Fr> = (*self).bases.iter().map(|_| Fr::random(rng)).collect();
+        let commitment:G1 = linear_combination(&(*self).bases, &k);
+        let c:Fr = representation_challenge((*self).domain, &(*self).bases, (*self).public(), commitment, context);
+        let z:Vec<Fr> = k.iter().zip((*self).witness.iter()).map(|(k_i, x_i)| *k_i + c * *x_i).collect();
+        Proof { c, z }
+    }
+}
+
+impl Verifier {
+
+    // Verifier for proofs of knowledge of a representation of public in bases
+    pub fn new(domain: &'static [u8], bases: Vec<G1>, public: G1) -> Self {
+        Verifier { domain, bases, public }
+    }
+
+    // Check a proof that was bound to context
+    pub fn verify(&self, proof: &Proof, context: &[u8]) -> bool {
+        if (*proof).z.len() != (*self).bases.len() {
+            return false;
+        }
+        let commitment:G1 = linear_combination(&(*self).bases, &(*proof).z) - (*self).public * (*proof).c;
+        fr_eq(representation_challenge((*self).domain, &(*self).bases, (*self).public, commitment, context), (*proof).c)
+    }
+}
+
+impl Proof {
+
+    // Serialized transcript: c, then the number of responses (one byte), then each response, with
+    // every element of ℤ_q as 32 big-endian bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes:Vec<u8> = Vec::new();
+        push_fr(&mut bytes, (*self).c);
+        bytes.push((*self).z.len() as u8);
+        for z_i in &(*self).z {
+            push_fr(&mut bytes, *z_i);
+        }
+        bytes
+    }
+
+    // Parse a serialized transcript (None if it is malformed)
+    pub fn from_bytes(bytes: &[u8]) -> Option<Proof> {
+        if bytes.len() < 33 {
+            return None;
+        }
+        let n = bytes[32] as usize;
+        if bytes.len() != 33 + 32 * n {
+            return None;
+        }
+        let c:Fr = Fr::from_slice(&bytes[0..32]).ok()?;
+        let mut z:Vec<Fr> = Vec::new();
+        for i in 0..n {
+            z.push(Fr::from_slice(&bytes[33 + 32 * i..65 + 32 * i]).ok()?);
+        }
+        Some(Proof { c, z })
+    }
+}
+
+
+/*
+ * Relations
+ */
+
+// Registration: S' = v_RA·s + g·b for the id being registered
+pub struct RegistrationStatement {
+    pub id: Fr,
+    // Commitment S' to the PRF key
+    pub commitment: G1,
+    // v from the RA's verification key, and the RA's generator g
+    pub v: G1,
+    pub g: G1
+}
+
+pub struct RegistrationWitness {
+    // PRF key
+    pub s: Fr,
+    // Blinding
+    pub b: Fr
+}
+
+impl RegistrationWitness {
+
+    // The commitment S' = v·s + g·b this witness opens
+    pub fn commitment(&self, v: G1, g: G1) -> G1 {
+        v * (*self).s + g * (*self).b
+    }
+}
+
+pub fn prove_registration(statement: &RegistrationStatement, witness: &RegistrationWitness, rng: &mut (impl RngCore + CryptoRng)) -> Proof {
+    let prover = Prover::new(REGISTER_DOMAIN, vec![(*statement).v, (*statement).g], vec![(*witness).s, (*witness).b]);
+    debug_assert!( prover.public() == (*statement).commitment, "witness does not open the commitment" );
+    prover.prove(&registration_context((*statement).id), rng)
+}
+
+pub fn verify_registration(statement: &RegistrationStatement, proof: &Proof) -> bool {
+    Verifier::new(REGISTER_DOMAIN, vec![(*statement).v, (*statement).g], (*statement).commitment)
+        .verify(proof, &registration_context((*statement).id))
+}
+
+// PRF: g = Tok·(s + vid), i.e. Tok·s = g - Tok·vid
+pub struct PrfStatement {
+    pub vid: Fr,
+    pub token: G1,
+    pub g: G1
+}
+
+pub struct PrfWitness {
+    pub s: Fr
+}
+
+pub fn prove_prf(statement: &PrfStatement, witness: &PrfWitness, rng: &mut (impl RngCore + CryptoRng)) -> Proof {
+    let prover = Prover::new(PRF_DOMAIN, vec![(*statement).token], vec![(*witness).s]);
+    debug_assert!( prover.public() == (*statement).g - (*statement).token * (*statement).vid, "token was not derived from s" );
+    prover.prove(&[], rng)
+}
+
+pub fn verify_prf(statement: &PrfStatement, proof: &Proof) -> bool {
+    Verifier::new(PRF_DOMAIN, vec![(*statement).token], (*statement).g - (*statement).token * (*statement).vid)
+        .verify(proof, &[])
+}
+
+// Schnorr signature (c, z) on body under the key x of pk = e(g, g2)^x, in its own domain:
+//      T = e(g, g2)^k,     c = H(domain ‖ pk ‖ T ‖ body),     z = k + c·x
+// The nonce k is 512 bits of H(domain ‖ x ‖ body ‖ i) reduced mod q, so signing the same body
+// always gives the same signature
+pub(crate) fn sign_gt(domain: &[u8], g: G1, g2: G2, pk: Gt, x: Fr, body: &[u8]) -> Proof {
+    let mut wide = [0u8; 64];
+    for (i, half) in wide.chunks_mut(32).enumerate() {
+        let mut transcript:Vec<u8> = domain.to_vec();
+        push_fr(&mut transcript, x);
+        push_bytes(&mut transcript, body);
+        transcript.push(i as u8);
+        half.copy_from_slice(&Sha256::digest(&transcript));
+    }
+    let k:Fr = Fr::interpret(&wide);
+    let c:Fr = signature_challenge(domain, pk, pow(pairing(g, g2), k), body);
+    Proof { c, z: vec![k + c * x] }
+}
+
+// e(g, g2)^z · pk^(-c) must hash back to c
+pub(crate) fn verify_gt(domain: &[u8], g: G1, g2: G2, pk: Gt, body: &[u8], signature: &Proof) -> bool {
+    if (*signature).z.len() != 1 {
+        return false;
+    }
+    let (c, z) = ((*signature).c, (*signature).z[0]);
+    let commitment:Gt = pow(pairing(g, g2), z) * pow(pk, -c);
+    fr_eq(signature_challenge(domain, pk, commitment, body), c)
+}
+
+fn signature_challenge(domain: &[u8], pk: Gt, commitment: Gt, body: &[u8]) -> Fr {
+    let mut transcript:Vec<u8> = domain.to_vec();
+    push_gt(&mut transcript, pk);
+    push_gt(&mut transcript, commitment);
+    push_bytes(&mut transcript, body);
+    hash_to_fr(&transcript)
+}
+
+fn linear_combination(bases: &[G1], scalars: &[Fr]) -> G1 {
+    bases.iter().zip(scalars.iter()).fold(G1::zero(), |acc, (b, x)| acc + *b * *x)
+}
+
+fn representation_challenge(domain: &[u8], bases: &[G1], public: G1, commitment: G1, context: &[u8]) -> Fr {
+    let mut transcript:Vec<u8> = domain.to_vec();
+    for b in bases {
+        push_g1(&mut transcript, *b);
+    }
+    push_g1(&mut transcript, public);
+    push_g1(&mut transcript, commitment);
+    push_bytes(&mut transcript, context);
+    hash_to_fr(&transcript)
+}
+
+
+/*
+ * Transcript helpers for Fiat-Shamir challenges
+ */
+
+// SHA-256 the transcript and keep the low 253 bits, which always fit below the order of Fr
+pub(crate) fn hash_to_fr(transcript: &[u8]) -> Fr {
+    let mut digest = Sha256::digest(transcript);
+    digest[0] &= 0x1f;
+    Fr::from_slice(&digest).expect("253-bit value is always a valid Fr")
+}
+
+// Elements of ℤ_q as 32 big-endian bytes
+pub(crate) fn push_fr(transcript: &mut Vec<u8>, x: Fr) {
+    transcript.extend_from_slice(&u256_to_be_bytes(x.into_u256()));
+}
+
+// Length-prefix variable-length data so it cannot bleed into the fields around it
+pub(crate) fn push_bytes(transcript: &mut Vec<u8>, data: &[u8]) {
+    transcript.extend_from_slice(&(data.len() as u64).to_be_bytes());
+    transcript.extend_from_slice(data);
+}
+
+// Points are hashed in affine coordinates so each point has exactly one encoding
+pub(crate) fn push_g1(transcript: &mut Vec<u8>, p: G1) {
+    match AffineG1::from_jacobian(p) {
+        Some(a) => {
+            transcript.push(1);
+            transcript.extend_from_slice(&u256_to_be_bytes(a.x().into_u256()));
+            transcript.extend_from_slice(&u256_to_be_bytes(a.y().into_u256()));
+        },
+        // Point at infinity
+        None => transcript.push(0)
+    }
+}
+
+pub(crate) fn push_g2(transcript: &mut Vec<u8>, p: G2) {
+    match AffineG2::from_jacobian(p) {
+        Some(a) => {
+            transcript.push(1);
+            for coord in &[a.x(), a.y()] {
+                transcript.extend_from_slice(&u256_to_be_bytes(coord.real().into_u256()));
+                transcript.extend_from_slice(&u256_to_be_bytes(coord.imaginary().into_u256()));
+            }
+        },
+        None => transcript.push(0)
+    }
+}
+
+// Gt goes in as its wire encoding (see encoding.rs)
+pub(crate) fn push_gt(transcript: &mut Vec<u8>, t: Gt) {
+    transcript.extend_from_slice(&gt_to_bytes(t));
+}
+
+
+/*
+ * Unit tests
+ */
+
+// Honest proofs verify, and only for the statement and context they were made for
+#[test]
+fn test_representation_proof() {
+    let rng = &mut rand::thread_rng();
+    let bases:Vec<G1> = vec![G1::random(rng), G1::random(rng)];
+    let prover = Prover::new(b"test", bases.clone(), vec![Fr::random(rng), Fr::random(rng)]);
+    let proof = prover.prove(b"alice", rng);
+
+    assert!( Verifier::new(b"test", bases.clone(), prover.public()).verify(&proof, b"alice") );
+    assert!( !Verifier::new(b"test", bases.clone(), prover.public()).verify(&proof, b"bob") );
+    assert!( !Verifier::new(b"other", bases.clone(), prover.public()).verify(&proof, b"alice") );
+    assert!( !Verifier::new(b"test", bases.clone(), G1::random(rng)).verify(&proof, b"alice") );
+    assert!( !Verifier::new(b"test", vec![bases[0]], prover.public()).verify(&proof, b"alice") );
+}
+
+// Registration proofs only verify for the commitment and id they were made for
+#[test]
+fn test_registration_relation() {
+    let rng = &mut rand::thread_rng();
+    let (v, g):(G1, G1) = (G1::random(rng), G1::random(rng));
+    let witness = RegistrationWitness { s: Fr::random(rng), b: Fr::random(rng) };
+    let statement = RegistrationStatement { id: Fr::random(rng), commitment: witness.commitment(v, g), v, g };
+    let proof = prove_registration(&statement, &witness, rng);
+    assert!( verify_registration(&statement, &proof) );
+    assert!( !verify_registration(&RegistrationStatement { id: Fr::random(rng), ..statement }, &proof) );
+}
+
+// A PRF proof shows the token was derived from the prover's key for that survey only
+#[test]
+fn test_prf_relation() {
+    let rng = &mut rand::thread_rng();
+    let (g, s, vid):(G1, Fr, Fr) = (G1::random(rng), Fr::random(rng), Fr::random(rng));
+    let token:G1 = g * (s + vid).inverse().unwrap();
+    let statement = PrfStatement { vid, token, g };
+    let proof = prove_prf(&statement, &PrfWitness { s }, rng);
+    assert!( verify_prf(&statement, &proof) );
+    assert!( !verify_prf(&PrfStatement { vid: Fr::random(rng), token, g }, &proof) );
+}
+
+// Transcripts round-trip through their serialization, and malformed ones are rejected
+#[test]
+fn test_proof_serialization() {
+    let rng = &mut rand::thread_rng();
+    let prover = Prover::new(b"test", vec![G1::random(rng)], vec![Fr::random(rng)]);
+    let proof = prover.prove(b"", rng);
+    let bytes = proof.to_bytes();
+    assert!( bytes.len() == 65 );
+    assert!( Proof::from_bytes(&bytes) == Some(proof) );
+    assert!( Proof::from_bytes(&bytes[..64]).is_none() );
+    assert!( Proof::from_bytes(&[0xff; 65]).is_none() );
+}
@@ -0,0 +1,161 @@
+extern crate tbn;
+
+use tbn::{Fr, G2};
+
+use crate::users::VerificationKey;
+use crate::keyring::VerificationKeyRing;
+use crate::survey::Survey;
+use crate::manifest::fingerprint;
+use crate::error::SurveyError;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/*
+ * ----------------------------------------------
+ * |    SA REGISTRY                             |
+ * ----------------------------------------------
+ *
+ * Several SAs can issue surveys to the users of one RA. Each SA goes by a name, and a user (or
+ * anyone checking surveys) pins the key ring of every SA they deal with in an SaRegistry. A key
+ * can only be pinned under one name, and a name's keys can only be extended (after a rotation),
+ * never replaced, so once pinned, a name stands for the same SA.
+ *
+ * An SA sharing the RA namespaces its surveys under its name with gen_namespaced_survey: the vid
+ * is H(authority ‖ nonce) (see hash.rs) and the survey carries the name and nonce. authorized_by
+ * takes the name of the SA the survey is expected from, and only accepts a survey whose vid is in
+ * that SA's namespace and whose participant signature verifies under that SA's pinned keys:
+ *
+ *      registry.pin("hr", hr.key_ring())?;
+ *      let survey = hr.gen_namespaced_survey("hr", &participants, g, g2, &ra.vk)?;
+ *      authorized_by("hr", alice.id(), &survey, &registry, &ra.vk, g2);
+ *
+ * Another SA can neither pass its surveys off as "hr"'s (its key is not pinned under "hr") nor
+ * reuse the vid of one of "hr"'s surveys in its own namespace, which would give it the same
+ * one-time tokens and link the respondents of both surveys.
+*/
+
+// An SA as pinned in the registry
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RegisteredAuthority {
+    pub name: String,
+    pub keys: VerificationKeyRing
+}
+
+// The SAs a party recognizes, by name
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SaRegistry {
+    authorities: Vec<RegisteredAuthority>
+}
+
+impl SaRegistry {
+
+    pub fn new() -> Self {
+        SaRegistry { authorities: Vec::new() }
+    }
+
+    /* Pin the keys of the SA called name */
+    // Pinning a name again is only allowed with a ring that keeps every pinned version as it was
+    // (e.g. after the SA rotated), and a key pinned under one name cannot be pinned under another
+    pub fn pin(&mut self, name: &str, keys: VerificationKeyRing) -> Result<(), SurveyError> {
+        for key in keys.iter() {
+            if let Some(other) = (*self).authority_of(&key.vk) {
+                if other != name {
+                    return Err(SurveyError::ConflictingAuthority(String::from(other)));
+                }
+            }
+        }
+        match (*self).authorities.iter_mut().find(|authority| authority.name == name) {
+            Some(authority) => {
+                let kept = authority.keys.iter().all(|pinned| keys.iter().any(|key| key.version == pinned.version
+                                                                               && fingerprint(&key.vk) == fingerprint(&pinned.vk)));
+                if !kept {
+                    return Err(SurveyError::ConflictingAuthority(String::from(name)));
+                }
+                authority.keys = keys;
+            },
+            None => (*self).authorities.push(RegisteredAuthority { name: String::from(name), keys })
+        }
+        Ok(())
+    }
+
+    // The pinned keys of the SA called name
+    pub fn keys_for(&self, name: &str) -> Option<&VerificationKeyRing> {
+        (*self).authorities.iter().find(|authority| authority.name == name).map(|authority| &authority.keys)
+    }
+
+    // The name vk is pinned under, if any
+    pub fn authority_of(&self, vk: &VerificationKey) -> Option<&str> {
+        let vk_fingerprint:String = fingerprint(vk);
+        (*self).authorities.iter()
+            .find(|authority| authority.keys.iter().any(|key| fingerprint(&key.vk) == vk_fingerprint))
+            .map(|authority| authority.name.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        (*self).authorities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (*self).authorities.is_empty()
+    }
+
+    // Whether id may take survey, as a survey of the SA called authority: the vid is in the SA's
+    // namespace and the participant signature verifies under its pinned keys
+    pub fn is_authorized(&self, authority: &str, id: Fr, survey: &Survey, vk_ra: &VerificationKey, g2: G2) -> bool {
+        match (*self).keys_for(authority) {
+            Some(keys) => (*survey).in_namespace(authority) && (*survey).is_authorized(id, keys, vk_ra, g2),
+            None => false
+        }
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use crate::users::{User, SurveyAuthority, RegistrationAuthority};
+#[cfg(test)]
+use crate::params::SystemParams;
+#[cfg(test)]
+use crate::{authorized, authorized_by};
+
+// Surveys are only accepted from the SA whose name they are namespaced under
+#[test]
+fn test_sa_registry() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let ra = RegistrationAuthority::new(&params);
+    let (mut hr, mut it):(SurveyAuthority, SurveyAuthority) = (SurveyAuthority::new(&params), SurveyAuthority::new(&params));
+    let alice = User::new();
+    let mut registry = SaRegistry::new();
+    registry.pin("hr", hr.key_ring()).expect("Pin was refused");
+    registry.pin("it", it.key_ring()).expect("Pin was refused");
+    assert!( registry.len() == 2 && registry.authority_of(&it.vk) == Some("it") );
+
+    let survey = hr.gen_namespaced_survey("hr", &vec![alice.id()], g, g2, &ra.vk).expect("Survey was refused");
+    assert!( survey.in_namespace("hr") && authorized_by("hr", alice.id(), &survey, &registry, &ra.vk, g2) );
+    assert!( !authorized_by("it", alice.id(), &survey, &registry, &ra.vk, g2) );
+    assert!( !authorized_by("finance", alice.id(), &survey, &registry, &ra.vk, g2) );
+
+    // IT claiming HR's namespace, or a survey without one
+    let claimed = it.gen_namespaced_survey("hr", &vec![alice.id()], g, g2, &ra.vk).expect("Survey was refused");
+    assert!( !authorized_by("hr", alice.id(), &claimed, &registry, &ra.vk, g2) && !authorized_by("it", alice.id(), &claimed, &registry, &ra.vk, g2) );
+    let plain = hr.gen_survey(&vec![alice.id()], g, g2, &ra.vk).expect("Survey was refused");
+    assert!( authorized(alice.id(), &plain, &hr.vk, &ra.vk, g2) && !authorized_by("hr", alice.id(), &plain, &registry, &ra.vk, g2) );
+
+    // A pinned name keeps its keys, and a pinned key keeps its name
+    assert!( registry.pin("hr", it.key_ring()) == Err(SurveyError::ConflictingAuthority(String::from("it"))) );
+    assert!( registry.pin("hr-2", hr.key_ring()) == Err(SurveyError::ConflictingAuthority(String::from("hr"))) );
+    let other = SurveyAuthority::new(&params);
+    assert!( registry.pin("hr", other.key_ring()) == Err(SurveyError::ConflictingAuthority(String::from("hr"))) );
+
+    // ... but follows a rotation
+    hr.rotate_keys(g, g2);
+    registry.pin("hr", hr.key_ring()).expect("Pin was refused");
+    let rotated = hr.gen_namespaced_survey("hr", &vec![alice.id()], g, g2, &ra.vk).expect("Survey was refused");
+    assert!( authorized_by("hr", alice.id(), &rotated, &registry, &ra.vk, g2) && authorized_by("hr", alice.id(), &survey, &registry, &ra.vk, g2) );
+}
@@ -0,0 +1,298 @@
+extern crate tbn;
+extern crate rand;
+
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+use anon_survey::{User, SurveyAuthority, RegistrationAuthority, SubmissionLedger, Survey, SystemParams, authorized};
+use anon_survey::hash;
+use tbn::{Fr, G1, G2};
+
+/*
+ * ----------------------------------------------
+ * |    INTERACTIVE PROTOCOL REPL               |
+ * ----------------------------------------------
+ *
+ * Drives the same steps as the demo (demo.rs), one command at a time, printing the intermediate
+ * values along the way (see HELP for the list of commands)
+*/
+
+const HELP:&str = "Commands:
+    new-ra                      Generate (g, g2) if needed and run GenRA
+    new-sa                      Run GenSA
+    register <name>             Create a user and register their id with the RA
+    re-identify <name>          Give a user a fresh id and re-register it
+    code [metadata]             RA mints a one-time registration code
+    redeem <name> <code>        Create a user and register them with a code
+    users                       List the id hashes in the RA's anonymity set
+    gen-survey --with <names>   SA authorizes the named users for a new survey
+    authorized <name> [survey]  Check whether a user may take a survey (default: latest)
+    submit <name> --answer ...  SubmitSurvey: answer the latest survey
+    help                        Show this message
+    quit                        Leave the REPL";
+
+// Everything created so far in the session
+pub struct Session {
+    params: Option<SystemParams>,
+    ra: Option<RegistrationAuthority>,
+    sa: Option<SurveyAuthority>,
+    // Named users, in order of creation
+    users: Vec<(String, User)>,
+    // Generated surveys, numbered from 1 in the REPL
+    surveys: Vec<Survey>,
+    // Submissions the SA has accepted so far
+    ledger: SubmissionLedger
+}
+
+impl Session {
+
+    pub fn new() -> Self {
+        Session { params: None, ra: None, sa: None, users: Vec::new(), surveys: Vec::new(), ledger: SubmissionLedger::new() }
+    }
+
+    // Returns the system parameters, generating them on first use
+    fn params(&mut self) -> SystemParams {
+        if (*self).params.is_none() {
+            let params = SystemParams::random();
+            println!("g ∈ G1 (generator) = {:?}", params.g);
+            println!("g2 ∈ G2 (generator) = {:?}", params.g2);
+            (*self).params = Some(params);
+        }
+        (*self).params.clone().unwrap()
+    }
+
+    // Returns the generator pair, generating it on first use
+    fn generators(&mut self) -> (G1, G2) {
+        (*self).params().generators()
+    }
+
+    fn user_index(&self, name: &str) -> Result<usize, String> {
+        (*self).users.iter().position(|(user_name, _)| user_name == name)
+            .ok_or(format!("no user named '{}' (use: register {})", name, name))
+    }
+
+    // Check whether the named user is authorized for survey number survey_num (counting from 1)
+    pub fn is_authorized(&mut self, name: &str, survey_num: usize) -> Result<bool, String> {
+        let index = (*self).user_index(name)?;
+        if survey_num == 0 || survey_num > (*self).surveys.len() {
+            return Err(String::from("no such survey (use: gen-survey --with <names>)"));
+        }
+        let (_, g2) = (*self).generators();
+        let vk_ra = &(*self).ra.as_ref().ok_or("no RA yet (use: new-ra)")?.vk;
+        let vk_sa = &(*self).sa.as_ref().ok_or("no SA yet (use: new-sa)")?.vk;
+        Ok(authorized((*self).users[index].1.id(), &(*self).surveys[survey_num - 1], vk_sa, vk_ra, g2))
+    }
+
+    // Number of surveys generated so far in the session
+    pub fn num_surveys(&self) -> usize {
+        (*self).surveys.len()
+    }
+
+    // Execute a single command line
+    pub fn execute(&mut self, line: &str) -> Result<(), String> {
+
+        let words:Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            return Ok(());
+        }
+
+        match words[0] {
+            "help" => println!("{}", HELP),
+
+            "new-ra" => {
+                let params = (*self).params();
+                println!("Generating signature-verification key pair (x, vk_RA) for Registration Authority (RA)...");
+                let ra = RegistrationAuthority::new(&params);
+                println!("vk_RA.u ∈ G1 = {:?}", ra.vk.u);
+                println!("vk_RA.v ∈ G1 = {:?}", ra.vk.v);
+                println!("vk_RA.h ∈ G1 = {:?}", ra.vk.h);
+                if (*self).ra.is_some() {
+                    println!("(replaces the previous RA -- earlier registrations are discarded)");
+                }
+                (*self).ra = Some(ra);
+            },
+
+            "new-sa" => {
+                let params = (*self).params();
+                println!("Generating signature-verification key pair (y, vk_SA) for Survey Authority (SA)...");
+                let sa = SurveyAuthority::new(&params);
+                println!("vk_SA.u ∈ G1 = {:?}", sa.vk.u);
+                println!("vk_SA.v ∈ G1 = {:?}", sa.vk.v);
+                println!("vk_SA.h ∈ G1 = {:?}", sa.vk.h);
+                (*self).sa = Some(sa);
+            },
+
+            "register" => {
+                let name = words.get(1).ok_or("usage: register <name>")?;
+                if (*self).user_index(name).is_ok() {
+                    return Err(format!("user '{}' already exists", name));
+                }
+                let ra = (*self).ra.as_mut().ok_or("no RA yet (use: new-ra)")?;
+                let mut user = User::new();
+                user.reg_user(ra).map_err(|e| e.to_string())?;
+                println!("Registered {} with id ∈ ℤ_q : {:?}", name, user.id());
+                (*self).users.push((name.to_string(), user));
+            },
+
+            "re-identify" => {
+                let name = words.get(1).ok_or("usage: re-identify <name>")?;
+                let index = (*self).user_index(name)?;
+                let ra = (*self).ra.as_mut().ok_or("no RA yet (use: new-ra)")?;
+                let user = &mut (*self).users[index].1;
+                let old_id = user.re_identify(ra).map_err(|e| e.to_string())?;
+                println!("{}: old id = {:?}", name, old_id);
+                println!("{}: new id = {:?}", name, user.id());
+            },
+
+            "code" => {
+                let metadata = words[1..].join(" ");
+                let ra = (*self).ra.as_mut().ok_or("no RA yet (use: new-ra)")?;
+                let reg_code = ra.issue_registration_code(&metadata, Duration::from_secs(24 * 60 * 60));
+                println!("One-time registration code (valid for 24h): {}", reg_code.code);
+            },
+
+            "redeem" => {
+                if words.len() < 3 {
+                    return Err(String::from("usage: redeem <name> <code>"));
+                }
+                let name = words[1];
+                if (*self).user_index(name).is_ok() {
+                    return Err(format!("user '{}' already exists", name));
+                }
+                let ra = (*self).ra.as_mut().ok_or("no RA yet (use: new-ra)")?;
+                let mut user = User::new();
+                user.redeem_code(words[2], ra).map_err(|e| e.to_string())?;
+                println!("Registered {} with id ∈ ℤ_q : {:?}", name, user.id());
+                (*self).users.push((name.to_string(), user));
+            },
+
+            "users" => {
+                let ra = (*self).ra.as_ref().ok_or("no RA yet (use: new-ra)")?;
+                println!("List of registered users (the RA only holds H(id)):");
+                for id_hash in ra.audit_anonymity_set() {
+                    let name = (*self).users.iter().find(|(_, user)| hash::id_hash(user.id()) == id_hash)
+                        .map(|(user_name, _)| user_name.as_str()).unwrap_or("?");
+                    println!("{}\tH(id) ∈ ℤ_q : {:?}", name, id_hash);
+                }
+            },
+
+            "gen-survey" => {
+                if words.get(1) != Some(&"--with") || words.len() < 3 {
+                    return Err(String::from("usage: gen-survey --with <name> [<name> ...]"));
+                }
+                let mut part_list:Vec<Fr> = Vec::new();
+                for name in &words[2..] {
+                    let index = (*self).user_index(name)?;
+                    part_list.push((*self).users[index].1.id());
+                }
+                let (g, g2) = (*self).generators();
+                let vk_ra = &(*self).ra.as_ref().ok_or("no RA yet (use: new-ra)")?.vk;
+                let sa = (*self).sa.as_mut().ok_or("no SA yet (use: new-sa)")?;
+                println!("SA: Generating survey signatures for {} potential users...", part_list.len());
+                let survey = sa.gen_survey(&part_list, g, g2, vk_ra).map_err(|e| e.to_string())?;
+                println!("Survey #{} generated:", (*self).surveys.len() + 1);
+                println!("\tvid ∈ ℤ_q (survey ID) = {:?}", survey.vid);
+                for entry in survey.participants() {
+                    println!("\t\tParticipant id:\t{:?}", entry.id);
+                    println!("\t\t\t(σ1, σ2) ∈ G1 × G2 = ({:?}, {:?})", entry.sigma_1, entry.sigma_2);
+                }
+                (*self).surveys.push(survey);
+            },
+
+            "authorized" => {
+                let name = words.get(1).ok_or("usage: authorized <name> [survey]")?;
+                let survey_num:usize = match words.get(2) {
+                    Some(num) => num.trim_start_matches('#').parse().map_err(|_| format!("not a survey number: {}", num))?,
+                    None => (*self).surveys.len()
+                };
+                match (*self).is_authorized(name, survey_num)? {
+                    true    => println!("{} is authorized for survey #{} \u{2713}", name, survey_num),
+                    false   => println!("{} is NOT authorized for survey #{} \u{2717}", name, survey_num)
+                }
+            },
+
+            "submit" => {
+                if words.len() < 4 || words[2] != "--answer" {
+                    return Err(String::from("usage: submit <name> --answer <text>"));
+                }
+                let index = (*self).user_index(words[1])?;
+                let survey = (*self).surveys.last().ok_or("no surveys yet (use: gen-survey --with <names>)")?;
+                let (g, g2) = (*self).params.as_ref().map(SystemParams::generators).ok_or("no RA yet (use: new-ra)")?;
+                let vk_ra = &(*self).ra.as_ref().ok_or("no RA yet (use: new-ra)")?.vk;
+                let vk_sa = &(*self).sa.as_ref().ok_or("no SA yet (use: new-sa)")?.vk;
+                let answer = words[3..].join(" ");
+                let submission = (*self).users[index].1.submit_survey(survey, answer.as_bytes(), (g, g2), vk_sa, vk_ra)
+                    .map_err(|e| format!("{}: {}", words[1], e))?;
+                println!("Submission for survey #{}:", (*self).surveys.len());
+                println!("\tTok ∈ G1 (one-time token) = {:?}", submission.token);
+                println!("\t(c1', c2') ∈ G1 × G2 = ({:?}, {:?})", submission.cred_1, submission.cred_2);
+                println!("\t(σ1', σ2') ∈ G1 × G2 = ({:?}, {:?})", submission.sigma_1, submission.sigma_2);
+                println!("\tproof challenge c ∈ ℤ_q = {:?}", submission.proof.c);
+                (*self).ledger.accept(survey.vid, submission, vk_sa, vk_ra, g, g2)
+                    .map_err(|e| format!("SA: Check \u{2717} ({})", e))?;
+                println!("SA: Check \u{2713} (response accepted)");
+            },
+
+            other => return Err(format!("unknown command '{}' (try: help)", other))
+        }
+        Ok(())
+    }
+}
+
+// Read commands from stdin until EOF or quit
+pub fn run() {
+
+    println!("ANONIZE protocol REPL -- type 'help' for a list of commands");
+    let mut session = Session::new();
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush().expect("Could not flush stdout");
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => ()
+        }
+        let line = line.trim();
+        if line == "quit" || line == "exit" {
+            break;
+        }
+        if let Err(msg) = session.execute(line) {
+            println!("error: {}", msg);
+        }
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+// Walk through a short session and make sure authorization is reported per survey participant
+#[test]
+fn test_repl_session() {
+    let mut session = Session::new();
+    assert!( session.execute("register alice").is_err() );
+    assert!( session.execute("new-ra").is_ok() );
+    assert!( session.execute("new-sa").is_ok() );
+    assert!( session.execute("register alice").is_ok() );
+    assert!( session.execute("register bob").is_ok() );
+    assert!( session.execute("register carol").is_ok() );
+    assert!( session.execute("register alice").is_err() );
+    assert!( session.execute("gen-survey --with alice bob").is_ok() );
+    assert!( session.execute("gen-survey --with dave").is_err() );
+    assert!( session.execute("authorized alice #1").is_ok() );
+    assert!( session.execute("authorized alice 2").is_err() );
+    assert!( session.execute("submit alice --answer yes").is_ok() );
+    assert!( session.execute("submit alice --answer no").is_err() );
+    assert!( session.execute("submit carol --answer yes").is_err() );
+    assert!( session.execute("frobnicate").is_err() );
+
+    let survey = &session.surveys[0];
+    let (_, g2) = session.params.as_ref().unwrap().generators();
+    let vk_ra = &session.ra.as_ref().unwrap().vk;
+    let vk_sa = &session.sa.as_ref().unwrap().vk;
+    assert!( authorized(session.users[0].1.id(), survey, vk_sa, vk_ra, g2) );
+    assert!( !authorized(session.users[2].1.id(), survey, vk_sa, vk_ra, g2) );
+}
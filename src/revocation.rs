@@ -0,0 +1,162 @@
+extern crate tbn;
+
+use std::time::SystemTime;
+use tbn::{Fr, G1, G2};
+
+use crate::users::{VerificationKey, KeyHolder};
+use crate::proofs::{Proof, verify_gt, push_fr};
+use crate::policy::unix_time;
+use crate::hash::id_hash;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/*
+ * ----------------------------------------------
+ * |    REVOCATION BATCHES                      |
+ * ----------------------------------------------
+ *
+ * Credentials are unblinded by their holders, so the RA cannot recognize (let alone revoke) one
+ * when it is used (see NotHeld). What it can do is take users out of the anonymity set, so that no
+ * SA puts them on a participant list again. When people leave, the operator feeds the departures
+ * through the panel they were invited with (Panel::revoke): every identifier is salted and hashed
+ * the way it was on import, matched to the registration that redeemed its invitation, and all of
+ * the matching registrations are taken out of the anonymity set at once. The RA signs the id
+ * hashes it removed (it keeps no ids, see hash.rs) into a RevocationBatch (a Schnorr signature in
+ * Gt, see proofs.rs) for the SAs, which drop every id with one of those hashes from the
+ * participant lists of new surveys:
+ *
+ *      let (batch, unknown) = panel.revoke(&fs::read_to_string("departed.csv")?, &mut ra)?;
+ *      sa.apply_revocations(&batch, &ra.vk, g, g2)?;      // at each SA
+ *
+ * A single registration (say, a compromised one) is revoked by its id hash with ra.revoke(H(id)).
+ * The RA keeps every revoked id hash on its revocation list: a revoked id is never registered
+ * again, and revocation_list() signs the whole list for SAs that missed earlier batches. Once an SA
+ * has applied a batch, gen_survey leaves those ids off every new survey.
+ *
+ * Submissions are not checked against the list. A submission proves that *some* listed participant
+ * made it without saying which, so the check cannot consult a list of ids without undoing that
+ * anonymity; what revocation takes away is the SA signature for every later survey. Surveys signed
+ * before a revocation keep accepting the revoked user until they close.
+*/
+
+pub const REVOCATION_DOMAIN:&[u8] = b"anon-survey/revocation/v1";
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RevocationBatch {
+    // Hashes of the ids taken out of the anonymity set
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::fr_vec"))]
+    pub id_hashes: Vec<Fr>,
+    // Seconds since the Unix epoch
+    pub issued: u64,
+    // Signature (c, z) under vk_RA
+    pub signature: Proof
+}
+
+impl RevocationBatch {
+
+    // Sign the revoked id hashes with the RA's keys (see RegistrationAuthority::revoke_batch)
+    pub(crate) fn sign(id_hashes: Vec<Fr>, ra: &impl KeyHolder, g: G1, g2: G2) -> Self {
+        let issued:u64 = unix_time(SystemTime::now());
+        let signature:Proof = (*ra).sign_body(REVOCATION_DOMAIN, g, g2, &batch_body(&id_hashes, issued));
+        RevocationBatch { id_hashes, issued, signature }
+    }
+
+    // Whether the batch was signed by the RA with key vk_ra
+    pub fn verify(&self, vk_ra: &VerificationKey, g: G1, g2: G2) -> bool {
+        verify_gt(REVOCATION_DOMAIN, g, g2, (*vk_ra).pk, &batch_body(&(*self).id_hashes, (*self).issued), &(*self).signature)
+    }
+
+    // The participants that were not revoked, in order
+    pub fn filter(&self, participants: &[Fr]) -> Vec<Fr> {
+        participants.iter().filter(|id| !(*self).id_hashes.contains(&id_hash(**id))).cloned().collect()
+    }
+}
+
+fn batch_body(id_hashes: &[Fr], issued: u64) -> Vec<u8> {
+    let mut body:Vec<u8> = issued.to_be_bytes().to_vec();
+    body.extend_from_slice(&(id_hashes.len() as u64).to_be_bytes());
+    for id_hash in id_hashes {
+        push_fr(&mut body, *id_hash);
+    }
+    body
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use std::time::Duration;
+#[cfg(test)]
+use tbn::Group;
+#[cfg(test)]
+use crate::users::{User, SurveyAuthority, RegistrationAuthority};
+#[cfg(test)]
+use crate::params::SystemParams;
+#[cfg(test)]
+use crate::panel::Panel;
+#[cfg(test)]
+use crate::error::SurveyError;
+
+// Departures are matched through the panel's salted hashes and revoked together, or not at all
+#[test]
+fn test_revoke_batch() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let other_ra = RegistrationAuthority::new(&params);
+    let roster = "alice@example.org\nbob@example.org\ncarol@example.org\n";
+    let (panel, invitations) = Panel::import("staff-2024", roster, &mut ra, Duration::from_secs(60 * 60));
+    let (mut alice, mut bob, mut dave) = (User::new(), User::new(), User::new());
+    alice.redeem_code(&invitations[0].1, &mut ra).expect("Registration failed");
+    bob.redeem_code(&invitations[1].1, &mut ra).expect("Registration failed");
+    dave.reg_user(&mut ra).expect("Registration failed");
+
+    // Carol never redeemed the invitation, and can no longer do so
+    let (batch, unknown) = panel.revoke("id,department\nAlice@example.org,Sales\ncarol@example.org\n", &mut ra).expect("Revocation failed");
+    assert!( batch.id_hashes == vec![id_hash(alice.id())] && unknown == vec![String::from("id")] );
+    assert!( !ra.in_anonymity_set(id_hash(alice.id())) && ra.in_anonymity_set(id_hash(bob.id())) );
+    assert!( ra.is_revoked(id_hash(alice.id())) );
+    assert!( !ra.is_pending(&invitations[2].1) );
+    assert!( User::new().redeem_code(&invitations[2].1, &mut ra).is_err() );
+
+    assert!( batch.verify(&ra.vk, g, g2) && !batch.verify(&other_ra.vk, g, g2) );
+    assert!( batch.filter(&[dave.id(), alice.id(), bob.id()]) == vec![dave.id(), bob.id()] );
+    let mut tampered = batch.clone();
+    tampered.id_hashes.push(id_hash(bob.id()));
+    assert!( !tampered.verify(&ra.vk, g, g2) );
+
+    // A commitment the RA never registered leaves the anonymity set untouched
+    let commitments = [bob.registration_commitment().unwrap(), G1::random(&mut rand::thread_rng())];
+    assert!( ra.revoke_batch(&commitments) == Err(SurveyError::UnregisteredUser) );
+    assert!( ra.in_anonymity_set(id_hash(bob.id())) );
+}
+
+// A revoked user is left off later surveys, and cannot register the same id again
+#[test]
+fn test_revoke_user() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let other_ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let (mut alice, mut bob) = (User::new(), User::new());
+    alice.reg_user(&mut ra).expect("Registration failed");
+    bob.reg_user(&mut ra).expect("Registration failed");
+    let before = sa.gen_survey(&vec![alice.id(), bob.id()], g, g2, &ra.vk).expect("Survey was refused");
+
+    let batch = ra.revoke(id_hash(alice.id())).expect("Revocation failed");
+    assert!( ra.revoke(id_hash(User::new().id())) == Err(SurveyError::UnregisteredUser) );
+    assert!( alice.reg_user(&mut ra) == Err(SurveyError::RevokedUser) );
+    assert!( ra.revocation_list().id_hashes == vec![id_hash(alice.id())] && ra.revocation_list().verify(&ra.vk, g, g2) );
+
+    let forged = other_ra.revocation_list();
+    assert!( sa.apply_revocations(&forged, &ra.vk, g, g2) == Err(SurveyError::InvalidSignature) );
+    sa.apply_revocations(&batch, &ra.vk, g, g2).expect("Batch was refused");
+    let after = sa.gen_survey(&vec![alice.id(), bob.id()], g, g2, &ra.vk).expect("Survey was refused");
+    assert!( after.entry_for(alice.id()).is_none() && after.entry_for(bob.id()).is_some() );
+    assert!( before.entry_for(alice.id()).is_some() );
+    assert!( sa.gen_survey(&vec![alice.id()], g, g2, &ra.vk) == Err(SurveyError::EmptySurvey) );
+}
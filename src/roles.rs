@@ -0,0 +1,189 @@
+extern crate tbn;
+extern crate rand;
+
+use rand::{RngCore, CryptoRng};
+use tbn::{Fr, G1, G2};
+
+use crate::users::{User, SurveyAuthority, RegistrationAuthority, RegistrationRequest, BlindedCredential, VerificationKey};
+use crate::combined::CombinedAuthority;
+use crate::survey::Survey;
+use crate::submission::{Submission, check_submission};
+use crate::keyring::VerificationKeyRing;
+use crate::error::SurveyError;
+use crate::storage::Storage;
+
+/*
+ * ----------------------------------------------
+ * |    PROTOCOL ROLES                          |
+ * ----------------------------------------------
+ *
+ * The three parties of the protocol, as traits, so that an implementation other than the structs
+ * in users/ (an RA behind a network call, an SA whose key sits in an HSM, a mock in a test) can
+ * stand in for one of them:
+ *      - Registrar: issues credentials for registration requests (RegistrationAuthority, CombinedAuthority)
+ *      - Surveyor: signs participant lists into surveys and checks submissions (SurveyAuthority)
+ *      - Respondent: registers with a Registrar and answers surveys (User)
+ *
+ * User::reg_user takes any Registrar, and a Surveyor checks submissions against its own key ring:
+ *
+ *      alice.reg_user(&mut registrar)?;
+ *      let survey = surveyor.gen_survey_with_rng(&participants, g, g2, registrar.vk_ra(), rng)?;
+ *      let submission = alice.submit_survey_with_rng(&survey, b"yes", (g, g2), &vk_sa, registrar.vk_ra(), rng)?;
+ *      surveyor.check_submission(survey.vid, &submission, registrar.vk_ra(), g, g2);
+ *
+ * The traits only cover the steps every implementation shares. Rotation, revocation, registration
+ * codes and the like stay on the concrete types.
+*/
+
+pub trait Registrar {
+
+    // The key credentials verify under
+    fn vk_ra(&self) -> &VerificationKey;
+
+    // The generators (g, g2) the credentials are built from
+    fn generators(&self) -> (G1, G2);
+
+    // Issue the blinded credential for a registration request, refusing requests whose proof does not verify
+    fn register_request_with_rng(&mut self, request: &RegistrationRequest, rng: &mut (impl RngCore + CryptoRng))
+                                 -> Result<BlindedCredential, SurveyError>;
+
+    fn register_request(&mut self, request: &RegistrationRequest) -> Result<BlindedCredential, SurveyError> {
+        // crytpographiclaly secure thread-local rng
+        (*self).register_request_with_rng(request, &mut rand::thread_rng())
+    }
+}
+
+pub trait Surveyor {
+
+    // Every key the surveyor has signed surveys with
+    fn key_ring(&self) -> VerificationKeyRing;
+
+    // Create a survey for the participants in L, whose credentials verify under vk_ra
+    #[allow(non_snake_case)]
+    fn gen_survey_with_rng(&mut self, L: &Vec<Fr>, g: G1, g2: G2, vk_ra: &VerificationKey, rng: &mut (impl RngCore + CryptoRng))
+                           -> Result<Survey, SurveyError>;
+
+    #[allow(non_snake_case)]
+    fn gen_survey(&mut self, L: &Vec<Fr>, g: G1, g2: G2, vk_ra: &VerificationKey) -> Result<Survey, SurveyError> {
+        // crytpographiclaly secure thread-local rng
+        (*self).gen_survey_with_rng(L, g, g2, vk_ra, &mut rand::thread_rng())
+    }
+
+    // Whether submission answers survey vid, under any key on the surveyor's ring
+    fn check_submission(&self, vid: Fr, submission: &Submission, vk_ra: &VerificationKey, g: G1, g2: G2) -> bool {
+        check_submission(vid, submission, &(*self).key_ring(), vk_ra, g, g2)
+    }
+}
+
+pub trait Respondent {
+
+    fn id(&self) -> Fr;
+
+    // Register with registrar, keeping the credential it issues
+    fn reg_user_with_rng(&mut self, registrar: &mut impl Registrar, rng: &mut (impl RngCore + CryptoRng)) -> Result<(), SurveyError>;
+
+    // Answer survey with message, given the survey's signing key vk_sa
+    fn submit_survey_with_rng(&self, survey: &Survey, message: &[u8], generators: (G1, G2), vk_sa: &VerificationKey,
+                              vk_ra: &VerificationKey, rng: &mut (impl RngCore + CryptoRng)) -> Result<Submission, SurveyError>;
+}
+
+impl<S: Storage> Registrar for RegistrationAuthority<S> {
+
+    fn vk_ra(&self) -> &VerificationKey {
+        &(*self).vk
+    }
+
+    fn generators(&self) -> (G1, G2) {
+        ((*self).g, (*self).g2)
+    }
+
+    fn register_request_with_rng(&mut self, request: &RegistrationRequest, rng: &mut (impl RngCore + CryptoRng))
+                                 -> Result<BlindedCredential, SurveyError> {
+        RegistrationAuthority::register_request_with_rng(self, request, rng)
+    }
+}
+
+impl Registrar for CombinedAuthority {
+
+    fn vk_ra(&self) -> &VerificationKey {
+        CombinedAuthority::vk_ra(self)
+    }
+
+    fn generators(&self) -> (G1, G2) {
+        CombinedAuthority::generators(self)
+    }
+
+    fn register_request_with_rng(&mut self, request: &RegistrationRequest, rng: &mut (impl RngCore + CryptoRng))
+                                 -> Result<BlindedCredential, SurveyError> {
+        CombinedAuthority::register_request_with_rng(self, request, rng)
+    }
+}
+
+impl<S: Storage> Surveyor for SurveyAuthority<S> {
+
+    fn key_ring(&self) -> VerificationKeyRing {
+        SurveyAuthority::key_ring(self)
+    }
+
+    #[allow(non_snake_case)]
+    fn gen_survey_with_rng(&mut self, L: &Vec<Fr>, g: G1, g2: G2, vk_ra: &VerificationKey, rng: &mut (impl RngCore + CryptoRng))
+                           -> Result<Survey, SurveyError> {
+        SurveyAuthority::gen_survey_with_rng(self, L, g, g2, vk_ra, rng)
+    }
+}
+
+impl Respondent for User {
+
+    fn id(&self) -> Fr {
+        User::id(self)
+    }
+
+    fn reg_user_with_rng(&mut self, registrar: &mut impl Registrar, rng: &mut (impl RngCore + CryptoRng)) -> Result<(), SurveyError> {
+        User::reg_user_with_rng(self, registrar, rng)
+    }
+
+    fn submit_survey_with_rng(&self, survey: &Survey, message: &[u8], generators: (G1, G2), vk_sa: &VerificationKey,
+                              vk_ra: &VerificationKey, rng: &mut (impl RngCore + CryptoRng)) -> Result<Submission, SurveyError> {
+        User::submit_survey_with_rng(self, survey, message, generators, vk_sa, vk_ra, rng)
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use crate::params::SystemParams;
+
+// One round of the protocol, knowing the parties only by their roles
+#[cfg(test)]
+fn run_survey(registrar: &mut impl Registrar, surveyor: &mut impl Surveyor, respondents: &mut [User]) -> bool {
+    let rng = &mut rand::thread_rng();
+    let (g, g2) = registrar.generators();
+    for respondent in respondents.iter_mut() {
+        Respondent::reg_user_with_rng(respondent, registrar, rng).expect("Registration failed");
+    }
+    let ids:Vec<Fr> = respondents.iter().map(Respondent::id).collect();
+    let survey = surveyor.gen_survey(&ids, g, g2, registrar.vk_ra()).expect("Survey was refused");
+    let vk_sa = surveyor.key_ring().current().expect("Surveyor has a key").vk.clone();
+    respondents.iter().all(|respondent| {
+        let submission = Respondent::submit_survey_with_rng(respondent, &survey, b"yes", (g, g2), &vk_sa, registrar.vk_ra(), rng)
+            .expect("Submission failed");
+        surveyor.check_submission(survey.vid, &submission, registrar.vk_ra(), g, g2)
+            && !surveyor.check_submission(survey.vid + Fr::one(), &submission, registrar.vk_ra(), g, g2)
+    })
+}
+
+// The protocol runs the same with any implementation of each role
+#[test]
+fn test_roles() {
+    let params = SystemParams::random();
+    let mut sa = SurveyAuthority::new(&params);
+
+    let mut ra = RegistrationAuthority::new(&params);
+    assert!( run_survey(&mut ra, &mut sa, &mut [User::new(), User::new()]) );
+
+    let mut combined = CombinedAuthority::new(&params, 2);
+    assert!( run_survey(&mut combined, &mut sa, &mut [User::new(), User::new()]) );
+}
@@ -0,0 +1,180 @@
+extern crate serde;
+extern crate serde_yaml;
+
+#[cfg(test)]
+use std::fs;
+use serde::Deserialize;
+
+use crate::repl::Session;
+
+/*
+ * ----------------------------------------------
+ * |    SCENARIO RUNNER                         |
+ * ----------------------------------------------
+ *
+ * Executes a YAML-described sequence of protocol steps against a fresh REPL session, checking the
+ * expected outcome of each step. For example:
+ *
+ *      name: Only listed participants are authorized
+ *      steps:
+ *        - action: new-ra
+ *        - action: new-sa
+ *        - { action: register, actor: alice }
+ *        - { action: register, actor: bob }
+ *        - { action: gen-survey, with: [alice] }
+ *        - { action: authorized, actor: alice, expect: authorized }
+ *        - { action: authorized, actor: bob, expect: not-authorized }
+ *
+ * Every action is a REPL command. The actor is the user the step is about, `with` lists the
+ * participants of gen-survey, `survey` picks a survey by number (default: latest), and `args` is
+ * appended verbatim. The expected outcome is one of ok (default), error, authorized, or
+ * not-authorized.
+*/
+
+#[derive(Debug, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    pub steps: Vec<Step>
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Step {
+    pub action: String,
+    #[serde(default)]
+    pub actor: Option<String>,
+    #[serde(default)]
+    pub with: Vec<String>,
+    #[serde(default)]
+    pub survey: Option<usize>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub expect: Expect
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Expect {
+    #[default]
+    Ok,
+    Error,
+    Authorized,
+    NotAuthorized
+}
+
+impl Step {
+
+    // Render the step as the equivalent REPL command
+    fn command(&self) -> String {
+        let mut words:Vec<String> = vec![(*self).action.clone()];
+        if let Some(actor) = &(*self).actor {
+            words.push(actor.clone());
+        }
+        if !(*self).with.is_empty() {
+            words.push(String::from("--with"));
+            words.extend((*self).with.iter().cloned());
+        }
+        if let Some(survey_num) = (*self).survey {
+            words.push(survey_num.to_string());
+        }
+        words.extend((*self).args.iter().cloned());
+        words.join(" ")
+    }
+
+    // Run the step, returning a description of the mismatch if the outcome was not the expected one
+    fn run(&self, session: &mut Session) -> Result<(), String> {
+        match (*self).expect {
+            Expect::Ok => session.execute(&(*self).command()),
+            Expect::Error => match session.execute(&(*self).command()) {
+                Ok(()) => Err(String::from("expected an error, but the step succeeded")),
+                Err(_) => Ok(())
+            },
+            Expect::Authorized | Expect::NotAuthorized => {
+                if (*self).action != "authorized" {
+                    return Err(format!("'{:?}' can only be expected of an authorized step", (*self).expect));
+                }
+                let actor = (*self).actor.as_ref().ok_or("authorized step needs an actor")?;
+                let survey_num = (*self).survey.unwrap_or(session.num_surveys());
+                let is_authorized = session.is_authorized(actor, survey_num)?;
+                match (is_authorized, &(*self).expect) {
+                    (true, Expect::Authorized) | (false, Expect::NotAuthorized) => Ok(()),
+                    (true, _) => Err(format!("{} was authorized", actor)),
+                    (false, _) => Err(format!("{} was not authorized", actor))
+                }
+            }
+        }
+    }
+}
+
+impl Scenario {
+
+    pub fn from_yaml(yaml: &str) -> Result<Scenario, String> {
+        serde_yaml::from_str(yaml).map_err(|e| format!("invalid scenario: {}", e))
+    }
+
+    // Run every step in order, stopping at the first one whose outcome does not match
+    pub fn run(&self) -> Result<(), String> {
+        println!("Scenario: {}", (*self).name);
+        let mut session = Session::new();
+        for (i, step) in (*self).steps.iter().enumerate() {
+            println!("Step {}: {} (expect {:?})", i+1, step.command(), step.expect);
+            if let Err(msg) = step.run(&mut session) {
+                println!("Step {} \u{2717}", i+1);
+                return Err(format!("step {} ({}) failed: {}", i+1, step.command(), msg));
+            }
+            println!("Step {} \u{2713}", i+1);
+        }
+        Ok(())
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+// Check that a scenario with a wrong expectation fails on that step
+#[test]
+fn test_scenario_mismatch() {
+    let scenario = Scenario::from_yaml("
+name: Bob was never listed
+steps:
+  - action: new-ra
+  - action: new-sa
+  - { action: register, actor: alice }
+  - { action: register, actor: bob }
+  - { action: gen-survey, with: [alice] }
+  - { action: authorized, actor: bob, expect: authorized }
+").expect("Could not parse scenario");
+    let err = scenario.run().unwrap_err();
+    assert!( err.starts_with("step 6") );
+}
+
+// Check that malformed scenarios are rejected when parsed
+#[test]
+fn test_scenario_invalid() {
+    assert!( Scenario::from_yaml("steps: []").is_err() );
+    assert!( Scenario::from_yaml("name: x\nsteps:\n  - { action: new-ra, expect: maybe }").is_err() );
+}
+
+
+/*
+ * Integration tests
+ */
+
+// Every scenario shipped in scenarios/ must pass
+#[test]
+fn test_shipped_scenarios() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/scenarios");
+    let mut paths:Vec<_> = fs::read_dir(dir).expect("Could not read scenarios/")
+        .map(|entry| entry.expect("Could not read scenario entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "yaml"))
+        .collect();
+    paths.sort();
+    assert!( !paths.is_empty() );
+    for path in paths {
+        let yaml = fs::read_to_string(&path).expect("Could not read scenario");
+        let scenario = Scenario::from_yaml(&yaml).unwrap();
+        assert!( scenario.run().is_ok(), "scenario {:?} failed", path );
+    }
+}
@@ -0,0 +1,84 @@
+extern crate tbn;
+extern crate zeroize;
+
+use std::fmt;
+use std::ptr;
+use std::sync::atomic::{compiler_fence, Ordering};
+use tbn::Fr;
+use zeroize::Zeroize;
+
+use crate::ct::ConstantTimeEq;
+
+/*
+ * ----------------------------------------------
+ * |    SECRET SCALARS                          |
+ * ----------------------------------------------
+ *
+ * Secret elements of ℤ_q (the RA's x, the SA's y, a threshold node's share x_i, and a user's id,
+ * PRF key s and registration blinding b) are held as SecretScalar rather than bare Fr:
+ *      - the scalar is overwritten with zero when it is dropped (or zeroized explicitly), with a
+ *        volatile write the compiler cannot optimize away
+ *      - Debug prints a placeholder, so a secret never ends up in a log through {:?}
+ *      - it is not Clone: a second copy has to be made on purpose, with SecretScalar::new(s.expose())
+ *
+ * Fr is Copy, so expose() hands out copies for the arithmetic, and those (like the temporaries of
+ * tbn's field operations) are not cleared. Keeping the long-lived copy in a SecretScalar still
+ * means a dropped authority or user leaves no key behind in its old memory.
+*/
+
+pub struct SecretScalar(Fr);
+
+impl SecretScalar {
+
+    pub fn new(x: Fr) -> Self {
+        SecretScalar(x)
+    }
+
+    // The scalar, for computing with it
+    pub fn expose(&self) -> Fr {
+        (*self).0
+    }
+}
+
+impl Zeroize for SecretScalar {
+    fn zeroize(&mut self) {
+        // Safe: self.0 is a valid, aligned Fr, and Fr has no destructor to skip
+        unsafe { ptr::write_volatile(&mut (*self).0, Fr::zero()) };
+        compiler_fence(Ordering::SeqCst);
+    }
+}
+
+impl Drop for SecretScalar {
+    fn drop(&mut self) {
+        (*self).zeroize();
+    }
+}
+
+impl fmt::Debug for SecretScalar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecretScalar(<redacted>)")
+    }
+}
+
+// In constant time (see ct.rs)
+impl PartialEq for SecretScalar {
+    fn eq(&self, other: &SecretScalar) -> bool {
+        (*self).ct_eq(other).into()
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+// Zeroizing clears the scalar, and Debug never shows it
+#[test]
+fn test_secret_scalar() {
+    let x:Fr = Fr::random(&mut rand::thread_rng());
+    let mut secret = SecretScalar::new(x);
+    assert!( secret.expose() == x && secret == SecretScalar::new(x) );
+    assert!( format!("{:?}", secret) == "SecretScalar(<redacted>)" );
+    secret.zeroize();
+    assert!( secret.expose().is_zero() );
+}
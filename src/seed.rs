@@ -0,0 +1,149 @@
+extern crate tbn;
+extern crate sha2;
+extern crate hkdf;
+extern crate pbkdf2;
+extern crate zeroize;
+
+use sha2::{Sha256, Sha512};
+use hkdf::Hkdf;
+use zeroize::Zeroizing;
+use tbn::{Fr, G1, G2};
+use crate::gt::Gt;
+
+use crate::cost::{pairing, pow};
+use crate::proofs::push_bytes;
+use crate::users::VerificationKey;
+use crate::error::SurveyError;
+
+/*
+ * ----------------------------------------------
+ * |    SEEDS                                   |
+ * ----------------------------------------------
+ *
+ * Users and authorities can derive their secrets from a Seed instead of drawing them at random, so
+ * writing down the seed is enough to regenerate them:
+ *
+ *      let seed = Seed::from_mnemonic("abandon abandon ... about", "")?;
+ *      let ra = RegistrationAuthority::from_seed(&params, &seed);
+ *      let alice = User::from_seed(&seed);
+ *
+ * A seed is either 32 random bytes, or a BIP-39-style mnemonic (12 to 24 lowercase words) stretched
+ * as BIP-39 does it: PBKDF2-HMAC-SHA512(words, "mnemonic" ‖ passphrase, 2048 rounds). The words
+ * are not checked against a wordlist, but a valid BIP-39 mnemonic gives its usual BIP-39 seed.
+ *
+ * Every secret is expanded from the seed with HKDF-SHA256 under its own derivation path, and the
+ * 64 output bytes are reduced mod q:
+ *
+ *      secret = HKDF-SHA256(salt = SEED_DOMAIN, ikm = seed, info = path ‖ len ‖ component) mod q
+ *
+ * The RA's key pair lives under RA_KEY_PATH (x, and the discrete logs of u, v, h to g), the SA's
+ * under SA_KEY_PATH (y, u, v, h), and a user's id and PRF key under USER_ID_PATH and USER_PRF_PATH,
+ * so one seed can back up several roles without any two secrets being related. Only the initial
+ * keys come from the seed: rotated keys and credentials are random, and are backed up with the
+ * keystore and the wallet.
+*/
+
+pub const SEED_DOMAIN:&[u8] = b"anon-survey/seed/v1";
+pub const RA_KEY_PATH:&[u8] = b"anon-survey/seed/ra-key/v1";
+pub const SA_KEY_PATH:&[u8] = b"anon-survey/seed/sa-key/v1";
+pub const USER_ID_PATH:&[u8] = b"anon-survey/seed/user-id/v1";
+pub const USER_PRF_PATH:&[u8] = b"anon-survey/seed/user-prf/v1";
+
+pub const SEED_BYTES:usize = 32;
+const MNEMONIC_ROUNDS:u32 = 2048;
+const MNEMONIC_LENGTHS:[usize; 5] = [12, 15, 18, 21, 24];
+
+// Input keying material every secret of a role is derived from, cleared from memory when dropped
+pub struct Seed {
+    ikm: Zeroizing<Vec<u8>>
+}
+
+impl Seed {
+
+    pub fn from_bytes(bytes: [u8; SEED_BYTES]) -> Self {
+        Seed { ikm: Zeroizing::new(bytes.to_vec()) }
+    }
+
+    /* Seed of a BIP-39-style mnemonic, with an optional passphrase ("" for none) */
+    // Words are separated by any whitespace. Refuses phrases that are not 12, 15, 18, 21 or 24
+    // words of lowercase ASCII letters with InvalidMnemonic
+    pub fn from_mnemonic(phrase: &str, passphrase: &str) -> Result<Self, SurveyError> {
+        let words:Vec<&str> = phrase.split_whitespace().collect();
+        if !MNEMONIC_LENGTHS.contains(&words.len()) || !words.iter().all(|word| word.bytes().all(|c| c.is_ascii_lowercase())) {
+            return Err(SurveyError::InvalidMnemonic);
+        }
+        let salt:String = format!("mnemonic{}", passphrase);
+        let mut ikm = Zeroizing::new(vec![0u8; 64]);
+        pbkdf2::pbkdf2_hmac::<Sha512>(Zeroizing::new(words.join(" ")).as_bytes(), salt.as_bytes(), MNEMONIC_ROUNDS, &mut ikm);
+        Ok(Seed { ikm })
+    }
+
+    // The secret for component under path
+    pub fn derive(&self, path: &[u8], component: &[u8]) -> Fr {
+        let mut info:Vec<u8> = path.to_vec();
+        push_bytes(&mut info, component);
+        let mut okm = Zeroizing::new([0u8; 64]);
+        Hkdf::<Sha256>::new(Some(SEED_DOMAIN), &(*self).ikm).expand(&info, &mut *okm)
+            .expect("64 bytes is a valid HKDF-SHA256 output length");
+        Fr::interpret(&okm)
+    }
+
+    // Authority key pair under path, as KeyHolder::gen_keys draws it: u, v, h in G1 and secret sk,
+    // with pk = e(g, g2)^sk
+    pub(crate) fn derive_keys(&self, path: &[u8], sk_name: &[u8], g: G1, g2: G2) -> (VerificationKey, Fr) {
+        let u:G1 = g * (*self).derive(path, b"u");
+        let v:G1 = g * (*self).derive(path, b"v");
+        let h:G1 = g * (*self).derive(path, b"h");
+        let sk:Fr = (*self).derive(path, sk_name);
+        let pk:Gt = pow(pairing(g, g2), sk);
+        (VerificationKey { u, v, h, pk }, sk)
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use crate::users::{User, SurveyAuthority, RegistrationAuthority};
+#[cfg(test)]
+use crate::params::SystemParams;
+#[cfg(test)]
+use crate::authorized;
+
+// A mnemonic stretches to its BIP-39 seed (first BIP-39 test vector), and malformed phrases are refused
+#[test]
+fn test_mnemonic_seed() {
+    let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+    let seed = Seed::from_mnemonic(phrase, "TREZOR").expect("Mnemonic was refused");
+    assert!( hex::encode(&seed.ikm) == "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04" );
+    let spaced = Seed::from_mnemonic(&format!("  {}\n", phrase.replace(' ', "\t ")), "TREZOR").expect("Mnemonic was refused");
+    assert!( spaced.ikm == seed.ikm && Seed::from_mnemonic(phrase, "").expect("Mnemonic was refused").ikm != seed.ikm );
+
+    assert!( Seed::from_mnemonic("abandon about", "").is_err() );
+    assert!( Seed::from_mnemonic(&phrase.replace("about", "About"), "").is_err() );
+    assert!( Seed::from_mnemonic(&phrase.replace("about", "ab0ut"), "").is_err() );
+}
+
+// The same seed regenerates the same keys and ids, and every path gives an unrelated secret
+#[test]
+fn test_seed_derivation() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let seed = Seed::from_bytes([42; SEED_BYTES]);
+    let other = Seed::from_bytes([43; SEED_BYTES]);
+
+    let (mut ra, mut sa, mut alice) = (RegistrationAuthority::from_seed(&params, &seed), SurveyAuthority::from_seed(&params, &seed), User::from_seed(&seed));
+    assert!( ra.vk.pk == RegistrationAuthority::from_seed(&params, &seed).vk.pk && ra.vk.u == RegistrationAuthority::from_seed(&params, &seed).vk.u );
+    assert!( sa.vk.pk == SurveyAuthority::from_seed(&params, &seed).vk.pk && alice.id() == User::from_seed(&seed).id() );
+    assert!( ra.vk.pk != RegistrationAuthority::from_seed(&params, &other).vk.pk && alice.id() != User::from_seed(&other).id() );
+    assert!( ra.vk.pk != sa.vk.pk && ra.vk.u != sa.vk.u );
+    assert!( seed.derive(USER_ID_PATH, b"id") != seed.derive(USER_PRF_PATH, b"s") && seed.derive(RA_KEY_PATH, b"x") != seed.derive(SA_KEY_PATH, b"y") );
+
+    // Seeded parties run the protocol like random ones, and a regenerated user has the same tokens
+    alice.reg_user(&mut ra).expect("Registration failed");
+    let survey = sa.gen_survey(&vec![alice.id()], g, g2, &ra.vk).expect("Survey was refused");
+    assert!( authorized(alice.id(), &survey, &sa.vk, &ra.vk, g2) );
+    assert!( alice.survey_token(survey.vid, g) == User::from_seed(&seed).survey_token(survey.vid, g) );
+}
@@ -0,0 +1,144 @@
+extern crate sha2;
+extern crate hkdf;
+extern crate hmac;
+extern crate tracing;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use sha2::Sha256;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use zeroize::Zeroizing;
+
+use crate::error::SurveyError;
+use crate::keystore::{KeystoreKind, KeyReader};
+use crate::proofs::push_bytes;
+
+/*
+ * ----------------------------------------------
+ * |    SNAPSHOTS                               |
+ * ----------------------------------------------
+ *
+ * The full state of an authority in one blob, to back it up, migrate it to another host, or clone
+ * it (see RegistrationAuthority::snapshot):
+ *
+ *      magic ‖ version (2, big-endian) ‖ kind ‖ keys ‖ state ‖ mac (32)
+ *
+ * keys is the authority's keystore (see keystore.rs), so the keys are encrypted under the
+ * passphrase exactly as export_keys encrypts them. state is in the clear: it is what the authority
+ * writes to its Storage, plus what it only keeps in memory. Both are length-prefixed.
+ *
+ * mac is HMAC-SHA256 over everything before it, keyed from the authority's secret key with HKDF,
+ * so only whoever can open the keys can write a snapshot that restores. Restoring opens the keys
+ * first, then checks the MAC, and only then reads the state. A snapshot of an unknown version, or
+ * one that is malformed or tampered with, fails with InvalidSnapshot. A wrong passphrase fails
+ * with InvalidKeystore, as for the keystore itself.
+*/
+
+const MAGIC:&[u8] = b"anon-survey/snapshot";
+// Written into new snapshots; restore refuses any other version
+pub const SNAPSHOT_VERSION:u16 = 1;
+const MAC_BYTES:usize = 32;
+
+// Wrap the keystore and state of an authority of kind, authenticated under its secret key
+pub(crate) fn seal(kind: KeystoreKind, keys: &[u8], state: &[u8], secret: &[u8]) -> Vec<u8> {
+    let mut snapshot:Vec<u8> = MAGIC.to_vec();
+    snapshot.extend_from_slice(&SNAPSHOT_VERSION.to_be_bytes());
+    snapshot.push(kind as u8);
+    push_bytes(&mut snapshot, keys);
+    push_bytes(&mut snapshot, state);
+    let mac = mac(kind, secret).chain_update(&snapshot).finalize().into_bytes();
+    snapshot.extend_from_slice(&mac);
+    snapshot
+}
+
+// A snapshot split into its parts, with the MAC not yet checked
+pub(crate) struct SealedSnapshot<'a> {
+    kind: KeystoreKind,
+    // Keystore to open with the passphrase
+    pub keys: &'a [u8],
+    state: &'a [u8],
+    body: &'a [u8],
+    mac: &'a [u8]
+}
+
+impl<'a> SealedSnapshot<'a> {
+
+    // Split a snapshot of an authority of kind, checking the version but not the MAC
+    pub(crate) fn parse(kind: KeystoreKind, snapshot: &'a [u8]) -> Result<Self, SurveyError> {
+        let header_len:usize = MAGIC.len() + 2 + 1;
+        if snapshot.len() < header_len + MAC_BYTES || &snapshot[..MAGIC.len()] != MAGIC || snapshot[header_len - 1] != kind as u8 {
+            return Err(SurveyError::InvalidSnapshot);
+        }
+        let version:u16 = u16::from_be_bytes([snapshot[MAGIC.len()], snapshot[MAGIC.len() + 1]]);
+        if version != SNAPSHOT_VERSION {
+            tracing::warn!(version, "snapshot does not restore: unknown version");
+            return Err(SurveyError::InvalidSnapshot);
+        }
+        let (body, mac) = snapshot.split_at(snapshot.len() - MAC_BYTES);
+        let mut reader = KeyReader::new(&body[header_len..]);
+        let (keys, state) = (reader.bytes(), reader.bytes());
+        match (keys, state, reader.finish()) {
+            (Ok(keys), Ok(state), Ok(())) => Ok(SealedSnapshot { kind, keys, state, body, mac }),
+            _ => Err(SurveyError::InvalidSnapshot)
+        }
+    }
+
+    // The state, once the MAC checks out under the secret key the keystore held
+    pub(crate) fn open(&self, secret: &[u8]) -> Result<&'a [u8], SurveyError> {
+        mac((*self).kind, secret).chain_update((*self).body).verify_slice((*self).mac)
+            .map(|_| (*self).state)
+            .map_err(|_| {
+                tracing::warn!(kind = ?(*self).kind, "snapshot does not restore: MAC does not verify");
+                SurveyError::InvalidSnapshot
+            })
+    }
+}
+
+fn mac(kind: KeystoreKind, secret: &[u8]) -> Hmac<Sha256> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(Some(MAGIC), secret).expand(&[kind as u8], &mut *key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    <Hmac<Sha256> as Mac>::new_from_slice(&*key).expect("HMAC takes keys of any length")
+}
+
+
+/*
+ * Encodings inside the state
+ */
+
+// Seconds ‖ nanoseconds since the Unix epoch (times before it are written as the epoch)
+pub(crate) fn push_time(bytes: &mut Vec<u8>, time: SystemTime) {
+    let since:Duration = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    bytes.extend_from_slice(&since.as_secs().to_be_bytes());
+    bytes.extend_from_slice(&since.subsec_nanos().to_be_bytes());
+}
+
+pub(crate) fn read_time(reader: &mut KeyReader) -> Result<SystemTime, SurveyError> {
+    let (secs, nanos):(u64, u32) = (reader.u64()?, reader.u32()?);
+    UNIX_EPOCH.checked_add(Duration::new(secs, nanos)).ok_or(SurveyError::InvalidSnapshot)
+}
+
+
+/*
+ * Unit tests
+ */
+
+// Snapshots only open under the secret they were sealed with, as the kind they were sealed as, and
+// untampered
+#[test]
+fn test_snapshot_seal_open() {
+    let snapshot:Vec<u8> = seal(KeystoreKind::RegistrationAuthority, b"keys", b"state", b"secret");
+    let sealed = SealedSnapshot::parse(KeystoreKind::RegistrationAuthority, &snapshot).expect("Snapshot was refused");
+    assert!( sealed.keys == b"keys" && sealed.open(b"secret") == Ok(&b"state"[..]) );
+    assert!( sealed.open(b"another secret") == Err(SurveyError::InvalidSnapshot) );
+    assert!( SealedSnapshot::parse(KeystoreKind::SurveyAuthority, &snapshot).is_err() );
+
+    let mut tampered:Vec<u8> = snapshot.clone();
+    let last:usize = tampered.len() - MAC_BYTES - 1;
+    tampered[last] ^= 1;
+    let sealed = SealedSnapshot::parse(KeystoreKind::RegistrationAuthority, &tampered).expect("Snapshot was refused");
+    assert!( sealed.open(b"secret") == Err(SurveyError::InvalidSnapshot) );
+    let mut newer:Vec<u8> = snapshot;
+    newer[MAGIC.len() + 1] += 1;
+    assert!( SealedSnapshot::parse(KeystoreKind::RegistrationAuthority, &newer).is_err() );
+}
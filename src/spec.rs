@@ -0,0 +1,228 @@
+extern crate tbn;
+extern crate rand;
+extern crate serde_yaml;
+
+use rand::SeedableRng;
+use rand::prng::ChaChaRng;
+use serde::Serialize;
+use serde_yaml::Value;
+
+use crate::encoding::{FR_BYTES, G1_BYTES, G2_BYTES, GT_BYTES};
+use crate::proofs::{REGISTER_DOMAIN, PRF_DOMAIN};
+use crate::submission::SUBMIT_DOMAIN;
+use crate::manifest::MANIFEST_DOMAIN;
+use crate::hash::{SURVEY_ID_DOMAIN, USER_ID_DOMAIN, EPOCH_DOMAIN, SA_VID_DOMAIN};
+use crate::survey::SurveyDescription;
+use crate::definition::{DEFINITION_DOMAIN, Question};
+use crate::policy::POLICY_DOMAIN;
+use crate::revocation::REVOCATION_DOMAIN;
+use crate::audit::AUDIT_DOMAIN;
+use crate::seed::{SEED_DOMAIN, RA_KEY_PATH, SA_KEY_PATH, USER_ID_PATH, USER_PRF_PATH};
+use crate::users::{User, SurveyAuthority, RegistrationAuthority};
+use crate::params::SystemParams;
+
+/*
+ * ----------------------------------------------
+ * |    PROTOCOL SPECIFICATION                  |
+ * ----------------------------------------------
+ *
+ * Machine-readable description of the protocol for auditors and reimplementers (printed by
+ * `anon_survey spec`). The encoding sizes and hash domains are the constants the code uses, and
+ * the message layouts are read off the serialization of objects produced by an actual protocol
+ * run, so neither can drift from the implementation. Only the relations are written out by hand,
+ * mirroring the statement/witness types in proofs.rs and submission.rs.
+*/
+
+#[derive(Serialize)]
+pub struct ProtocolSpec {
+    pub curve: &'static str,
+    pub hash_to_fr: &'static str,
+    pub encodings: Vec<EncodingSpec>,
+    pub domains: Vec<DomainSpec>,
+    pub relations: Vec<RelationSpec>,
+    pub messages: Vec<MessageSpec>
+}
+
+#[derive(Serialize)]
+pub struct EncodingSpec {
+    pub name: &'static str,
+    pub bytes: usize,
+    pub layout: &'static str
+}
+
+// Domain separation tag of a Fiat-Shamir challenge
+#[derive(Serialize)]
+pub struct DomainSpec {
+    pub relation: &'static str,
+    pub tag: String,
+    pub transcript: &'static str
+}
+
+#[derive(Serialize)]
+pub struct RelationSpec {
+    pub name: &'static str,
+    pub statement: Vec<&'static str>,
+    pub witness: Vec<&'static str>,
+    pub equations: Vec<&'static str>
+}
+
+// Fields of a serialized protocol object, in order
+#[derive(Serialize)]
+pub struct MessageSpec {
+    pub name: String,
+    pub fields: Vec<FieldSpec>
+}
+
+#[derive(Serialize, PartialEq)]
+pub struct FieldSpec {
+    pub name: String,
+    pub encoding: String
+}
+
+// Build the specification, running the protocol once (with a fixed seed) to lay out the messages
+pub fn protocol_spec() -> ProtocolSpec {
+    ProtocolSpec {
+        curve: "BN254 (tbn points, arkworks pairing): e : G1 × G2 -> Gt of prime order q",
+        hash_to_fr: "SHA-256 of the transcript, top three bits cleared, read as a big-endian integer",
+        encodings: vec![
+            EncodingSpec { name: "Fr", bytes: FR_BYTES, layout: "big-endian integer below q" },
+            EncodingSpec { name: "G1", bytes: G1_BYTES, layout: "affine x ‖ y, all zero at infinity" },
+            EncodingSpec { name: "G2", bytes: G2_BYTES, layout: "affine x.real ‖ x.imaginary ‖ y.real ‖ y.imaginary, all zero at infinity" },
+            EncodingSpec { name: "Gt", bytes: GT_BYTES, layout: "twelve Fq coefficients of the Fq12 element, each big-endian below p, c0.c0.c0 first; only elements of the order-q subgroup decode" }
+        ],
+        domains: vec![
+            DomainSpec { relation: "registration", tag: tag(REGISTER_DOMAIN), transcript: "tag ‖ bases ‖ S' ‖ T ‖ len ‖ id" },
+            DomainSpec { relation: "prf", tag: tag(PRF_DOMAIN), transcript: "tag ‖ Tok ‖ g - Tok·vid ‖ T ‖ len" },
+            DomainSpec { relation: "submission", tag: tag(SUBMIT_DOMAIN),
+                         transcript: "tag ‖ vid ‖ Tok, c1', σ1', u_SA, v_SA, u_RA, v_RA, h_RA, T_tok ‖ c2', σ2' ‖ epoch ‖ pk_SA, pk_RA, T_RA, T_SA ‖ len ‖ message" },
+            DomainSpec { relation: "manifest", tag: tag(MANIFEST_DOMAIN), transcript: "tag ‖ pk_RA ‖ T ‖ len ‖ body" },
+            DomainSpec { relation: "definition", tag: tag(DEFINITION_DOMAIN), transcript: "tag ‖ pk_SA ‖ T ‖ len ‖ (content ‖ nonce)" },
+            DomainSpec { relation: "policy", tag: tag(POLICY_DOMAIN),
+                         transcript: "tag ‖ pk_SA ‖ T ‖ len ‖ (0 ‖ vid ‖ closes_at ‖ n ‖ bucket_1 ‖ ... ‖ bucket_n), amendments (1 ‖ vid ‖ previous ‖ closes_at)" },
+            DomainSpec { relation: "revocation", tag: tag(REVOCATION_DOMAIN), transcript: "tag ‖ pk_RA ‖ T ‖ len ‖ (issued ‖ n ‖ H(id_1) ‖ ... ‖ H(id_n))" },
+            DomainSpec { relation: "audit log", tag: tag(AUDIT_DOMAIN), transcript: "SHA-256(tag ‖ digest_(i-1) ‖ seq ‖ time ‖ action ‖ S'); head signed as tag ‖ pk_RA ‖ T ‖ len ‖ (n ‖ digest_n)" },
+            DomainSpec { relation: "survey id", tag: tag(SURVEY_ID_DOMAIN), transcript: "tag ‖ len ‖ (len ‖ content ‖ u_SA, v_SA, h_SA ‖ pk_SA ‖ nonce)" },
+            DomainSpec { relation: "namespaced survey id", tag: tag(SA_VID_DOMAIN), transcript: "tag ‖ len ‖ (len ‖ authority ‖ nonce)" },
+            DomainSpec { relation: "user id", tag: tag(USER_ID_DOMAIN), transcript: "tag ‖ len ‖ id" },
+            DomainSpec { relation: "epoch", tag: tag(EPOCH_DOMAIN), transcript: "tag ‖ len ‖ epoch ‖ counter (hash to G1)" },
+            DomainSpec { relation: "seed", tag: tag(SEED_DOMAIN), transcript: "HKDF-SHA256 salt; secret = HKDF(tag, seed, path ‖ len ‖ component) mod q" },
+            DomainSpec { relation: "RA key from seed", tag: tag(RA_KEY_PATH), transcript: "path ‖ len ‖ (x | u | v | h)" },
+            DomainSpec { relation: "SA key from seed", tag: tag(SA_KEY_PATH), transcript: "path ‖ len ‖ (y | u | v | h)" },
+            DomainSpec { relation: "user id from seed", tag: tag(USER_ID_PATH), transcript: "path ‖ len ‖ id" },
+            DomainSpec { relation: "PRF key from seed", tag: tag(USER_PRF_PATH), transcript: "path ‖ len ‖ s" }
+        ],
+        relations: vec![
+            RelationSpec {
+                name: "registration",
+                statement: vec!["id", "S'", "v_RA", "g"],
+                witness: vec!["s", "b"],
+                equations: vec!["S' = v_RA·s + g·b"]
+            },
+            RelationSpec {
+                name: "prf",
+                statement: vec!["vid", "Tok", "g"],
+                witness: vec!["s"],
+                equations: vec!["g = Tok·(s + vid)"]
+            },
+            RelationSpec {
+                name: "submission",
+                statement: vec!["vid", "message", "Tok", "c1'", "c2'", "epoch", "σ1'", "σ2'", "vk_SA", "vk_RA", "g", "g2"],
+                witness: vec!["id", "s", "t1", "t2"],
+                equations: vec![
+                    "e(c1', g2) = pk_RA · e(u_RA·id + v_RA·s + h_RA + H(epoch), c2') · e(g, g2)^t1",
+                    "e(σ1', g2) = pk_SA · e(u_SA·vid + v_SA·id + h_RA, σ2') · e(g, g2)^t2",
+                    "g = Tok·(s + vid)"
+                ]
+            }
+        ],
+        messages: sample_messages()
+    }
+}
+
+fn tag(domain: &[u8]) -> String {
+    String::from_utf8_lossy(domain).into_owned()
+}
+
+// Serialize one of each protocol object and describe its fields
+fn sample_messages() -> Vec<MessageSpec> {
+    let rng = &mut ChaChaRng::from_seed([0; 32]);
+    let params = SystemParams::standard();
+    let (g, g2) = params.generators();
+    let mut ra = RegistrationAuthority::new_with_rng(&params, rng);
+    let mut sa = SurveyAuthority::new_with_rng(&params, rng);
+    let mut user = User::new_with_rng(rng);
+    user.reg_user_with_rng(&mut ra, rng).expect("Registration failed");
+    let survey = sa.gen_survey_with_rng(&vec![user.id()], g, g2, &ra.vk, rng).expect("SA survey creation failed!");
+    let submission = user.submit_survey_with_rng(&survey, b"yes", (g, g2), &sa.vk, &ra.vk, rng).expect("Submission failed");
+    let reg_code = ra.issue_registration_code_with_rng("", std::time::Duration::from_secs(0), rng);
+    let description = SurveyDescription::new_with_rng("", rng);
+    let definition = sa.define_survey_with_rng("", "", vec![Question::Likert { prompt: String::new(), points: 5 }], g, g2, rng)
+        .expect("Definition was refused");
+
+    let mut messages:Vec<MessageSpec> = Vec::new();
+    describe("SystemParams", &params, &mut messages);
+    describe("VerificationKey", &ra.vk, &mut messages);
+    describe("RegistrationCode", &reg_code, &mut messages);
+    describe("Survey", &survey, &mut messages);
+    describe("SurveyDescription", &description, &mut messages);
+    describe("SurveyDefinition", &definition, &mut messages);
+    describe("Submission", &submission, &mut messages);
+    describe("ParamsManifest", &ra.freeze_params(&[&sa.vk]), &mut messages);
+    messages
+}
+
+fn describe<T: Serialize>(name: &str, object: &T, messages: &mut Vec<MessageSpec>) {
+    let value = serde_yaml::to_value(object).expect("Could not serialize");
+    describe_value(name, &value, messages);
+}
+
+// Nested objects get their own entry, named after the path to them
+fn describe_value(name: &str, value: &Value, messages: &mut Vec<MessageSpec>) -> String {
+    match value {
+        Value::Mapping(mapping) => {
+            let index = messages.len();
+            messages.push(MessageSpec { name: String::from(name), fields: Vec::new() });
+            for (key, field) in mapping {
+                let key = key.as_str().unwrap_or("?");
+                let kind = describe_value(&format!("{}.{}", name, key), field, messages);
+                messages[index].fields.push(FieldSpec { name: String::from(key), encoding: kind });
+            }
+            String::from(name)
+        },
+        Value::Sequence(elements) => match elements.first() {
+            Some(Value::Number(_)) => String::from("bytes"),
+            Some(element) => format!("list of {}", describe_value(&format!("{}[]", name), element, messages)),
+            None => String::from("list")
+        },
+        Value::String(s) => match hex::decode(s).map(|bytes| bytes.len()) {
+            Ok(FR_BYTES) => String::from("Fr"),
+            Ok(G1_BYTES) => String::from("G1"),
+            Ok(G2_BYTES) => String::from("G2"),
+            Ok(GT_BYTES) => String::from("Gt"),
+            _ => String::from("string")
+        },
+        // Enum variants, e.g. a Question
+        Value::Tagged(tagged) => describe_value(&format!("{}.{}", name, tagged.tag.to_string().trim_start_matches('!')), &tagged.value, messages),
+        Value::Number(_) => String::from("integer"),
+        Value::Bool(_) => String::from("bool"),
+        _ => String::from("null")
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+// The specification names the real hash domains and lays out the submission from its encoding
+#[test]
+fn test_protocol_spec() {
+    let spec = protocol_spec();
+    assert!( spec.domains.iter().any(|domain| domain.tag == "anon-survey/submit/v1") );
+    let submission = spec.messages.iter().find(|message| message.name == "Submission").unwrap();
+    assert!( submission.fields.contains(&FieldSpec { name: String::from("token"), encoding: String::from("G1") }) );
+    assert!( submission.fields.contains(&FieldSpec { name: String::from("proof"), encoding: String::from("Submission.proof") }) );
+    let proof = spec.messages.iter().find(|message| message.name == "Submission.proof").unwrap();
+    assert!( proof.fields.len() == 5 && proof.fields.iter().all(|field| field.encoding == "Fr") );
+    assert!( serde_yaml::to_string(&spec).is_ok() );
+}
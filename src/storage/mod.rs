@@ -0,0 +1,328 @@
+extern crate tbn;
+
+use tbn::{Fr, G1};
+
+use crate::users::RegistrationRecord;
+use crate::survey::Survey;
+use crate::submission::Submission;
+use crate::error::SurveyError;
+#[cfg(any(feature = "sled", feature = "sqlite", feature = "postgres"))]
+use serde::{Serialize, de::DeserializeOwned};
+
+#[cfg(feature = "sled")]
+pub mod sled;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+#[cfg(feature = "sled")]
+pub use self::sled::SledStorage;
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::SqliteStorage;
+#[cfg(feature = "postgres")]
+pub use self::postgres::PostgresStorage;
+
+/*
+ * ----------------------------------------------
+ * |    STORAGE                                 |
+ * ----------------------------------------------
+ *
+ * The state an authority has to keep across restarts, behind one trait so it can live in memory,
+ * in an embedded database or elsewhere:
+ *      - users: the RA's registration records (keyed by commitment S') and its anonymity set (id hashes)
+ *      - surveys: the SA's signed surveys (keyed by vid)
+ *      - submissions: the submissions a ledger accepted, per survey
+ *      - revocations: revoked id hashes, at the RA and at every SA that applied them
+ *
+ * RegistrationAuthority, SurveyAuthority and SubmissionLedger are generic over their Storage, and
+ * default to MemoryStorage, which is what new() gives. Their state stays in memory for reads, and
+ * every change is written through to the storage before the step returns. with_storage moves an
+ * authority onto another storage, writing what it holds there and then loading everything the
+ * storage already had, so an authority restarted from its keystore picks up where it stopped:
+ *
+ *      let ra = RegistrationAuthority::from_keys(&keystore, passphrase)?.with_storage(SledStorage::open("ra.db")?)?;
+ *
+ * Backends other than memory are behind features: `sled` (SledStorage), `sqlite` (SqliteStorage)
+ * and `postgres` (PostgresStorage, for SA instances sharing a database). All store records as
+ * JSON, so they turn on `serde` as well. Keys are not
+ * stored here (see keystore.rs), and neither are registration codes, the RA's audit log, or a
+ * ledger's closed surveys, windows and checkpoints.
+*/
+
+pub trait Storage {
+
+    // Store a registration record, replacing the one with the same commitment
+    fn put_user(&mut self, record: &RegistrationRecord) -> Result<(), SurveyError>;
+
+    fn get_user(&self, commitment: G1) -> Result<Option<RegistrationRecord>, SurveyError>;
+
+    fn remove_user(&mut self, commitment: G1) -> Result<(), SurveyError>;
+
+    // Every registration record, in the order they were first stored
+    fn users(&self) -> Result<Vec<RegistrationRecord>, SurveyError>;
+
+    // Add an id hash to the anonymity set (no-op if it is in already)
+    fn add_member(&mut self, id_hash: Fr) -> Result<(), SurveyError>;
+
+    fn remove_member(&mut self, id_hash: Fr) -> Result<(), SurveyError>;
+
+    fn members(&self) -> Result<Vec<Fr>, SurveyError>;
+
+    // Store a survey, replacing the one with the same vid
+    fn put_survey(&mut self, survey: &Survey) -> Result<(), SurveyError>;
+
+    fn get_survey(&self, vid: Fr) -> Result<Option<Survey>, SurveyError>;
+
+    // Every survey, in the order they were first stored
+    fn surveys(&self) -> Result<Vec<Survey>, SurveyError>;
+
+    // Store a submission accepted for survey vid. A backend shared between ledgers may refuse one
+    // whose token it already holds, with DuplicateSubmission
+    fn put_submission(&mut self, vid: Fr, submission: &Submission) -> Result<(), SurveyError>;
+
+    // Submissions accepted for survey vid, in the order they were stored
+    fn get_submissions(&self, vid: Fr) -> Result<Vec<Submission>, SurveyError>;
+
+    // Every stored submission as (vid, submission), in the order they were stored
+    fn submissions(&self) -> Result<Vec<(Fr, Submission)>, SurveyError>;
+
+    // Record a revoked id hash (no-op if it is recorded already)
+    fn put_revocation(&mut self, id_hash: Fr) -> Result<(), SurveyError>;
+
+    fn revocations(&self) -> Result<Vec<Fr>, SurveyError>;
+}
+
+// Storage that lasts as long as the authority holding it
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStorage {
+    users: Vec<RegistrationRecord>,
+    members: Vec<Fr>,
+    surveys: Vec<Survey>,
+    submissions: Vec<(Fr, Submission)>,
+    revocations: Vec<Fr>
+}
+
+impl MemoryStorage {
+
+    pub fn new() -> Self {
+        MemoryStorage::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+
+    fn put_user(&mut self, record: &RegistrationRecord) -> Result<(), SurveyError> {
+        match (*self).users.iter_mut().find(|user| user.commitment == (*record).commitment) {
+            Some(user) => *user = record.clone(),
+            None => (*self).users.push(record.clone())
+        }
+        Ok(())
+    }
+
+    fn get_user(&self, commitment: G1) -> Result<Option<RegistrationRecord>, SurveyError> {
+        Ok((*self).users.iter().find(|user| user.commitment == commitment).cloned())
+    }
+
+    fn remove_user(&mut self, commitment: G1) -> Result<(), SurveyError> {
+        (*self).users.retain(|user| user.commitment != commitment);
+        Ok(())
+    }
+
+    fn users(&self) -> Result<Vec<RegistrationRecord>, SurveyError> {
+        Ok((*self).users.clone())
+    }
+
+    fn add_member(&mut self, id_hash: Fr) -> Result<(), SurveyError> {
+        if !(*self).members.contains(&id_hash) {
+            (*self).members.push(id_hash);
+        }
+        Ok(())
+    }
+
+    fn remove_member(&mut self, id_hash: Fr) -> Result<(), SurveyError> {
+        (*self).members.retain(|member| *member != id_hash);
+        Ok(())
+    }
+
+    fn members(&self) -> Result<Vec<Fr>, SurveyError> {
+        Ok((*self).members.clone())
+    }
+
+    fn put_survey(&mut self, survey: &Survey) -> Result<(), SurveyError> {
+        match (*self).surveys.iter_mut().find(|stored| stored.vid == (*survey).vid) {
+            Some(stored) => *stored = survey.clone(),
+            None => (*self).surveys.push(survey.clone())
+        }
+        Ok(())
+    }
+
+    fn get_survey(&self, vid: Fr) -> Result<Option<Survey>, SurveyError> {
+        Ok((*self).surveys.iter().find(|survey| survey.vid == vid).cloned())
+    }
+
+    fn surveys(&self) -> Result<Vec<Survey>, SurveyError> {
+        Ok((*self).surveys.clone())
+    }
+
+    fn put_submission(&mut self, vid: Fr, submission: &Submission) -> Result<(), SurveyError> {
+        (*self).submissions.push((vid, submission.clone()));
+        Ok(())
+    }
+
+    fn get_submissions(&self, vid: Fr) -> Result<Vec<Submission>, SurveyError> {
+        Ok((*self).submissions.iter().filter(|(stored_vid, _)| *stored_vid == vid).map(|(_, submission)| submission.clone()).collect())
+    }
+
+    fn submissions(&self) -> Result<Vec<(Fr, Submission)>, SurveyError> {
+        Ok((*self).submissions.clone())
+    }
+
+    fn put_revocation(&mut self, id_hash: Fr) -> Result<(), SurveyError> {
+        if !(*self).revocations.contains(&id_hash) {
+            (*self).revocations.push(id_hash);
+        }
+        Ok(())
+    }
+
+    fn revocations(&self) -> Result<Vec<Fr>, SurveyError> {
+        Ok((*self).revocations.clone())
+    }
+}
+
+// Stored values of the database backends are JSON
+#[cfg(any(feature = "sled", feature = "sqlite", feature = "postgres"))]
+pub(crate) fn to_json(value: &impl Serialize) -> Result<String, SurveyError> {
+    serde_json::to_string(value).map_err(storage_error)
+}
+
+#[cfg(any(feature = "sled", feature = "sqlite", feature = "postgres"))]
+pub(crate) fn from_json<T: DeserializeOwned>(json: &[u8]) -> Result<T, SurveyError> {
+    serde_json::from_slice(json).map_err(storage_error)
+}
+
+#[cfg(any(feature = "sled", feature = "sqlite", feature = "postgres"))]
+pub(crate) fn storage_error(error: impl std::fmt::Display) -> SurveyError {
+    SurveyError::Storage(error.to_string())
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use std::time::SystemTime;
+#[cfg(test)]
+use tbn::{Group, G2};
+#[cfg(test)]
+use crate::users::{User, SurveyAuthority, RegistrationAuthority};
+#[cfg(test)]
+use crate::params::SystemParams;
+#[cfg(test)]
+use crate::ledger::SubmissionLedger;
+#[cfg(test)]
+use crate::hash::id_hash;
+#[cfg(test)]
+use crate::authorized;
+
+// What every backend has to do: replace by key, keep insertion order, and ignore repeats where
+// documented
+#[cfg(test)]
+pub(crate) fn check_storage(storage: &mut impl Storage) {
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let (g, g2) = params.generators();
+
+    let records:Vec<RegistrationRecord> = (0..3).map(|i| RegistrationRecord {
+        id_hash: Fr::random(rng), commitment: G1::random(rng), registered: SystemTime::now(), code_metadata: Some(format!("cohort-{}", i))
+    }).collect();
+    for record in &records {
+        storage.put_user(record).expect("Storage failed");
+    }
+    let mut renamed = records[1].clone();
+    renamed.code_metadata = None;
+    storage.put_user(&renamed).expect("Storage failed");
+    storage.remove_user(records[0].commitment).expect("Storage failed");
+    let users = storage.users().expect("Storage failed");
+    assert!( users.len() == 2 && users[0].commitment == records[1].commitment && users[0].code_metadata.is_none() && users[1].code_metadata == records[2].code_metadata );
+    assert!( storage.get_user(records[2].commitment).expect("Storage failed").map(|user| user.id_hash) == Some(records[2].id_hash) );
+    assert!( storage.get_user(records[0].commitment).expect("Storage failed").is_none() );
+
+    for record in &records {
+        storage.add_member(record.id_hash).expect("Storage failed");
+    }
+    storage.add_member(records[0].id_hash).expect("Storage failed");
+    storage.remove_member(records[1].id_hash).expect("Storage failed");
+    let members = storage.members().expect("Storage failed");
+    assert!( members.len() == 2 && members.contains(&records[0].id_hash) && members.contains(&records[2].id_hash) );
+
+    // Surveys and submissions from an actual run
+    let (mut ra, mut sa) = (RegistrationAuthority::new(&params), SurveyAuthority::new(&params));
+    let mut alice = User::new();
+    alice.reg_user(&mut ra).expect("Registration failed");
+    let first = sa.gen_survey(&vec![alice.id()], g, g2, &ra.vk).expect("Survey was refused");
+    let mut second = sa.gen_survey(&vec![alice.id(), Fr::random(rng)], g, g2, &ra.vk).expect("Survey was refused");
+    storage.put_survey(&first).expect("Storage failed");
+    storage.put_survey(&second).expect("Storage failed");
+    second.key_version = 7;
+    storage.put_survey(&second).expect("Storage failed");
+    let surveys = storage.surveys().expect("Storage failed");
+    assert!( surveys.len() == 2 && surveys[0] == first && surveys[1] == second );
+    assert!( storage.get_survey(first.vid).expect("Storage failed") == Some(first.clone()) );
+    assert!( storage.get_survey(Fr::random(rng)).expect("Storage failed").is_none() );
+
+    let answer = alice.submit_survey(&first, b"yes", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    let other = alice.submit_survey(&second, b"no", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    storage.put_submission(first.vid, &answer).expect("Storage failed");
+    storage.put_submission(second.vid, &other).expect("Storage failed");
+    let stored = storage.get_submissions(first.vid).expect("Storage failed");
+    assert!( stored.len() == 1 && stored[0].token == answer.token && stored[0].message == answer.message );
+    let all = storage.submissions().expect("Storage failed");
+    assert!( all.len() == 2 && all[0].0 == first.vid && all[1].0 == second.vid && all[1].1.token == other.token );
+
+    storage.put_revocation(records[0].id_hash).expect("Storage failed");
+    storage.put_revocation(records[1].id_hash).expect("Storage failed");
+    storage.put_revocation(records[0].id_hash).expect("Storage failed");
+    assert!( storage.revocations().expect("Storage failed") == vec![records[0].id_hash, records[1].id_hash] );
+}
+
+#[test]
+fn test_memory_storage() {
+    check_storage(&mut MemoryStorage::new());
+}
+
+// An authority moved onto a storage writes its state there, and one moved onto a storage that
+// already has state picks it up
+#[test]
+fn test_with_storage() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let (mut alice, mut bob) = (User::new(), User::new());
+    alice.reg_user(&mut ra).expect("Registration failed");
+    let keystore:Vec<u8> = ra.export_keys("correct horse");
+
+    let mut ra = ra.with_storage(MemoryStorage::new()).expect("Storage failed");
+    bob.reg_user(&mut ra).expect("Registration failed");
+    ra.revoke_batch(&[bob.registration_commitment().unwrap()]).expect("Revocation failed");
+    let mut sa = SurveyAuthority::new(&params).with_storage(MemoryStorage::new()).expect("Storage failed");
+    let sa_keystore:Vec<u8> = sa.export_keys("battery staple");
+    sa.apply_revocations(&ra.revocation_list(), &ra.vk, g, g2).expect("Revocation list was refused");
+    let survey = sa.gen_survey(&vec![alice.id(), bob.id()], g, g2, &ra.vk).expect("Survey was refused");
+    let mut ledger = SubmissionLedger::new().with_storage(MemoryStorage::new()).expect("Storage failed");
+    let submission = alice.submit_survey(&survey, b"yes", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    ledger.accept(survey.vid, submission.clone(), &sa.vk, &ra.vk, g, g2).expect("Submission was refused");
+
+    // "Restart" every party from its keys and its storage
+    let restarted = RegistrationAuthority::from_keys(&keystore, "correct horse").expect("Keystore was rejected")
+        .with_storage(ra.into_storage()).expect("Storage failed");
+    assert!( restarted.in_anonymity_set(id_hash(alice.id())) && !restarted.in_anonymity_set(id_hash(bob.id())) && restarted.is_revoked(id_hash(bob.id())) );
+    assert!( restarted.export_user_data(bob.registration_commitment().unwrap()).is_some() );
+    let mut sa = SurveyAuthority::from_keys(&sa_keystore, "battery staple").expect("Keystore was rejected")
+        .with_storage(sa.into_storage()).expect("Storage failed");
+    assert!( sa.verid_list == vec![survey.clone()] && !authorized(bob.id(), &survey, &sa.vk, &restarted.vk, g2) );
+    assert!( sa.gen_survey(&vec![bob.id()], g, g2, &restarted.vk) == Err(crate::error::SurveyError::EmptySurvey) );
+    let mut ledger = SubmissionLedger::new().with_storage(ledger.into_storage()).expect("Storage failed");
+    assert!( ledger.seen(survey.vid, submission.token) );
+    assert!( ledger.accept(survey.vid, submission, &sa.vk, &restarted.vk, g, g2).is_err() );
+}
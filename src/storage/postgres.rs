@@ -0,0 +1,246 @@
+extern crate tbn;
+
+use sqlx::{PgPool, Row};
+use sqlx::postgres::PgPoolOptions;
+use tokio::runtime::{Builder, Runtime};
+use tbn::{Fr, G1};
+
+use super::{Storage, to_json, from_json, storage_error};
+use crate::users::RegistrationRecord;
+use crate::survey::Survey;
+use crate::submission::Submission;
+use crate::encoding::{fr_to_bytes, fr_from_bytes, g1_to_bytes};
+use crate::error::SurveyError;
+
+/*
+ * ----------------------------------------------
+ * |    POSTGRES STORAGE                        |
+ * ----------------------------------------------
+ *
+ * Storage in PostgreSQL, for SA deployments that run several instances on one database. The schema
+ * lives in migrations/postgres and is applied on connect. Two things make it safe to share:
+ *      - submissions are unique per (vid, Tok), so when two instances accept the same one-time
+ *        token, the second put_submission fails with DuplicateSubmission and its ledger refuses
+ *        the response, even though the first instance's ledger is the only one that saw it
+ *      - a survey and its participant rows are written in one transaction
+ *
+ * Every query is async (sqlx). Servers already on a tokio runtime call the async methods
+ * (put_survey_async, put_submission_async, ...) directly; the Storage impl runs the same queries
+ * on the storage's own single-threaded runtime, so it must not be used from inside an async task:
+ *
+ *      let sa = SurveyAuthority::from_keys(&keystore, passphrase)?.with_storage(PostgresStorage::connect(&url)?)?;
+*/
+
+// Connections each PostgresStorage keeps open
+const MAX_CONNECTIONS:u32 = 4;
+
+pub struct PostgresStorage {
+    pool: PgPool,
+    runtime: Runtime
+}
+
+impl PostgresStorage {
+
+    /* Connect to the database at url (postgres://...), applying any pending migrations */
+    pub fn connect(url: &str) -> Result<Self, SurveyError> {
+        let runtime:Runtime = Builder::new_current_thread().enable_all().build().map_err(storage_error)?;
+        let pool:PgPool = runtime.block_on(PostgresStorage::connect_async(url))?;
+        Ok(PostgresStorage { pool, runtime })
+    }
+
+    /* Pool connected to url with the migrations applied, for servers that share it between tasks */
+    pub async fn connect_async(url: &str) -> Result<PgPool, SurveyError> {
+        let pool:PgPool = PgPoolOptions::new().max_connections(MAX_CONNECTIONS).connect(url).await.map_err(storage_error)?;
+        sqlx::migrate!("./migrations/postgres").run(&pool).await.map_err(storage_error)?;
+        Ok(pool)
+    }
+
+    pub fn pool(&self) -> &PgPool {
+        &(*self).pool
+    }
+
+    /* Store survey and its participant list in one transaction */
+    pub async fn put_survey_async(pool: &PgPool, survey: &Survey) -> Result<(), SurveyError> {
+        let vid:Vec<u8> = fr_to_bytes((*survey).vid);
+        let mut transaction = pool.begin().await.map_err(storage_error)?;
+        sqlx::query("INSERT INTO surveys (vid, key_version, survey) VALUES ($1, $2, $3::jsonb)
+                     ON CONFLICT (vid) DO UPDATE SET key_version = excluded.key_version, survey = excluded.survey")
+            .bind(&vid).bind((*survey).key_version as i32).bind(to_json(survey)?)
+            .execute(&mut *transaction).await.map_err(storage_error)?;
+        sqlx::query("DELETE FROM survey_participants WHERE vid = $1").bind(&vid).execute(&mut *transaction).await.map_err(storage_error)?;
+        for entry in (*survey).participants() {
+            sqlx::query("INSERT INTO survey_participants (vid, id) VALUES ($1, $2)")
+                .bind(&vid).bind(fr_to_bytes(entry.id))
+                .execute(&mut *transaction).await.map_err(storage_error)?;
+        }
+        transaction.commit().await.map_err(storage_error)
+    }
+
+    /* Store a submission accepted for survey vid, failing with DuplicateSubmission if any
+     * instance already stored one with the same token */
+    pub async fn put_submission_async(pool: &PgPool, vid: Fr, submission: &Submission) -> Result<(), SurveyError> {
+        sqlx::query("INSERT INTO submissions (vid, token, submission) VALUES ($1, $2, $3::jsonb)")
+            .bind(fr_to_bytes(vid)).bind(g1_to_bytes((*submission).token)).bind(to_json(submission)?)
+            .execute(pool).await
+            .map(|_| ())
+            .map_err(|error| match error.as_database_error() {
+                Some(database_error) if database_error.is_unique_violation() => SurveyError::DuplicateSubmission,
+                _ => storage_error(error)
+            })
+    }
+
+    pub async fn put_user_async(pool: &PgPool, record: &RegistrationRecord) -> Result<(), SurveyError> {
+        execute(pool, sqlx::query("INSERT INTO users (commitment, record) VALUES ($1, $2::jsonb)
+                                   ON CONFLICT (commitment) DO UPDATE SET record = excluded.record")
+            .bind(g1_to_bytes((*record).commitment)).bind(to_json(record)?)).await
+    }
+
+    pub async fn put_revocation_async(pool: &PgPool, id_hash: Fr) -> Result<(), SurveyError> {
+        execute(pool, sqlx::query("INSERT INTO revocations (id_hash) VALUES ($1) ON CONFLICT DO NOTHING").bind(fr_to_bytes(id_hash))).await
+    }
+
+    // Run a future on the storage's runtime
+    fn block_on<T>(&self, future: impl std::future::Future<Output = Result<T, SurveyError>>) -> Result<T, SurveyError> {
+        (*self).runtime.block_on(future)
+    }
+}
+
+type Query<'q> = sqlx::query::Query<'q, sqlx::Postgres, sqlx::postgres::PgArguments>;
+
+async fn execute(pool: &PgPool, query: Query<'_>) -> Result<(), SurveyError> {
+    query.execute(pool).await.map(|_| ()).map_err(storage_error)
+}
+
+// The JSON column (selected as text) of every row the query returns
+async fn json_rows<T: serde::de::DeserializeOwned>(pool: &PgPool, query: Query<'_>) -> Result<Vec<T>, SurveyError> {
+    let rows = query.fetch_all(pool).await.map_err(storage_error)?;
+    rows.iter().map(|row| from_json(row.try_get::<String, _>(0).map_err(storage_error)?.as_bytes())).collect()
+}
+
+async fn fr_rows(pool: &PgPool, query: Query<'_>) -> Result<Vec<Fr>, SurveyError> {
+    let rows = query.fetch_all(pool).await.map_err(storage_error)?;
+    rows.iter().map(|row| decode_fr(&row.try_get::<Vec<u8>, _>(0).map_err(storage_error)?)).collect()
+}
+
+fn decode_fr(bytes: &[u8]) -> Result<Fr, SurveyError> {
+    fr_from_bytes(bytes).ok_or_else(|| SurveyError::Storage(String::from("stored id hash or vid does not decode")))
+}
+
+impl Storage for PostgresStorage {
+
+    fn put_user(&mut self, record: &RegistrationRecord) -> Result<(), SurveyError> {
+        (*self).block_on(PostgresStorage::put_user_async(&(*self).pool, record))
+    }
+
+    fn get_user(&self, commitment: G1) -> Result<Option<RegistrationRecord>, SurveyError> {
+        let query = sqlx::query("SELECT record::text FROM users WHERE commitment = $1").bind(g1_to_bytes(commitment));
+        Ok((*self).block_on(json_rows(&(*self).pool, query))?.pop())
+    }
+
+    fn remove_user(&mut self, commitment: G1) -> Result<(), SurveyError> {
+        (*self).block_on(execute(&(*self).pool, sqlx::query("DELETE FROM users WHERE commitment = $1").bind(g1_to_bytes(commitment))))
+    }
+
+    fn users(&self) -> Result<Vec<RegistrationRecord>, SurveyError> {
+        (*self).block_on(json_rows(&(*self).pool, sqlx::query("SELECT record::text FROM users ORDER BY seq")))
+    }
+
+    fn add_member(&mut self, id_hash: Fr) -> Result<(), SurveyError> {
+        let query = sqlx::query("INSERT INTO members (id_hash) VALUES ($1) ON CONFLICT DO NOTHING").bind(fr_to_bytes(id_hash));
+        (*self).block_on(execute(&(*self).pool, query))
+    }
+
+    fn remove_member(&mut self, id_hash: Fr) -> Result<(), SurveyError> {
+        (*self).block_on(execute(&(*self).pool, sqlx::query("DELETE FROM members WHERE id_hash = $1").bind(fr_to_bytes(id_hash))))
+    }
+
+    fn members(&self) -> Result<Vec<Fr>, SurveyError> {
+        (*self).block_on(fr_rows(&(*self).pool, sqlx::query("SELECT id_hash FROM members")))
+    }
+
+    fn put_survey(&mut self, survey: &Survey) -> Result<(), SurveyError> {
+        (*self).block_on(PostgresStorage::put_survey_async(&(*self).pool, survey))
+    }
+
+    fn get_survey(&self, vid: Fr) -> Result<Option<Survey>, SurveyError> {
+        let query = sqlx::query("SELECT survey::text FROM surveys WHERE vid = $1").bind(fr_to_bytes(vid));
+        Ok((*self).block_on(json_rows(&(*self).pool, query))?.pop())
+    }
+
+    fn surveys(&self) -> Result<Vec<Survey>, SurveyError> {
+        (*self).block_on(json_rows(&(*self).pool, sqlx::query("SELECT survey::text FROM surveys ORDER BY seq")))
+    }
+
+    fn put_submission(&mut self, vid: Fr, submission: &Submission) -> Result<(), SurveyError> {
+        (*self).block_on(PostgresStorage::put_submission_async(&(*self).pool, vid, submission))
+    }
+
+    fn get_submissions(&self, vid: Fr) -> Result<Vec<Submission>, SurveyError> {
+        let query = sqlx::query("SELECT submission::text FROM submissions WHERE vid = $1 ORDER BY seq").bind(fr_to_bytes(vid));
+        (*self).block_on(json_rows(&(*self).pool, query))
+    }
+
+    fn submissions(&self) -> Result<Vec<(Fr, Submission)>, SurveyError> {
+        (*self).block_on(async {
+            let rows = sqlx::query("SELECT vid, submission::text FROM submissions ORDER BY seq")
+                .fetch_all(&(*self).pool).await.map_err(storage_error)?;
+            rows.iter().map(|row| {
+                let vid:Fr = decode_fr(&row.try_get::<Vec<u8>, _>(0).map_err(storage_error)?)?;
+                Ok((vid, from_json(row.try_get::<String, _>(1).map_err(storage_error)?.as_bytes())?))
+            }).collect()
+        })
+    }
+
+    fn put_revocation(&mut self, id_hash: Fr) -> Result<(), SurveyError> {
+        (*self).block_on(PostgresStorage::put_revocation_async(&(*self).pool, id_hash))
+    }
+
+    fn revocations(&self) -> Result<Vec<Fr>, SurveyError> {
+        (*self).block_on(fr_rows(&(*self).pool, sqlx::query("SELECT id_hash FROM revocations ORDER BY seq")))
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use super::check_storage;
+#[cfg(test)]
+use crate::users::{User, SurveyAuthority, RegistrationAuthority};
+#[cfg(test)]
+use crate::params::SystemParams;
+
+// Needs an empty database at ANON_SURVEY_POSTGRES_URL, e.g.
+//      ANON_SURVEY_POSTGRES_URL=postgres://localhost/anon_survey_test cargo test --features postgres -- --ignored postgres
+#[cfg(test)]
+fn test_database() -> PostgresStorage {
+    let url:String = std::env::var("ANON_SURVEY_POSTGRES_URL").expect("ANON_SURVEY_POSTGRES_URL is not set");
+    PostgresStorage::connect(&url).expect("Could not connect to the database")
+}
+
+#[test]
+#[ignore]
+fn test_postgres_storage() {
+    check_storage(&mut test_database());
+}
+
+// Two instances sharing the database accept a token once between them
+#[test]
+#[ignore]
+fn test_postgres_shared_tokens() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params).with_storage(test_database()).expect("Storage failed");
+    let mut alice = User::new();
+    alice.reg_user(&mut ra).expect("Registration failed");
+    let survey = sa.gen_survey(&vec![alice.id()], g, g2, &ra.vk).expect("Survey was refused");
+    let submission = alice.submit_survey(&survey, b"yes", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+
+    let mut first = crate::ledger::SubmissionLedger::new().with_storage(test_database()).expect("Storage failed");
+    let mut second = crate::ledger::SubmissionLedger::new().with_storage(test_database()).expect("Storage failed");
+    assert!( first.accept(survey.vid, submission.clone(), &sa.vk, &ra.vk, g, g2).is_ok() );
+    assert!( second.accept(survey.vid, submission, &sa.vk, &ra.vk, g, g2) == Err(SurveyError::DuplicateSubmission) );
+}
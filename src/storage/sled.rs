@@ -0,0 +1,219 @@
+extern crate tbn;
+
+use std::path::Path;
+use tbn::{Fr, G1};
+
+use super::{Storage, to_json, from_json, storage_error};
+use crate::users::RegistrationRecord;
+use crate::survey::Survey;
+use crate::submission::Submission;
+use crate::encoding::{FR_BYTES, fr_to_bytes, fr_from_bytes, g1_to_bytes};
+use crate::error::SurveyError;
+
+/*
+ * ----------------------------------------------
+ * |    SLED STORAGE                            |
+ * ----------------------------------------------
+ *
+ * Storage in an embedded sled database, one tree per kind of record. Records are stored as JSON
+ * under a sequence number from the database (big-endian, so trees iterate in insertion order):
+ *      - users: seq -> record, with user_index: S' -> seq
+ *      - members: H(id) -> ()
+ *      - surveys: seq -> survey, with survey_index: vid -> seq
+ *      - submissions: vid ‖ seq -> submission
+ *      - revocations: H(id) -> seq
+ * Every write is flushed to disk before it returns.
+*/
+
+pub struct SledStorage {
+    db: ::sled::Db,
+    users: ::sled::Tree,
+    user_index: ::sled::Tree,
+    members: ::sled::Tree,
+    surveys: ::sled::Tree,
+    survey_index: ::sled::Tree,
+    submissions: ::sled::Tree,
+    revocations: ::sled::Tree
+}
+
+impl SledStorage {
+
+    /* Open (or create) the database at path */
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, SurveyError> {
+        SledStorage::with_db(::sled::open(path).map_err(storage_error)?)
+    }
+
+    /* Database that is deleted when dropped, for tests */
+    pub fn temporary() -> Result<Self, SurveyError> {
+        SledStorage::with_db(::sled::Config::new().temporary(true).open().map_err(storage_error)?)
+    }
+
+    fn with_db(db: ::sled::Db) -> Result<Self, SurveyError> {
+        let tree = |name: &str| db.open_tree(name).map_err(storage_error);
+        Ok(SledStorage {
+            users: tree("users")?,
+            user_index: tree("user_index")?,
+            members: tree("members")?,
+            surveys: tree("surveys")?,
+            survey_index: tree("survey_index")?,
+            submissions: tree("submissions")?,
+            revocations: tree("revocations")?,
+            db
+        })
+    }
+
+    // Key for a new record: the next sequence number of the database
+    fn next_key(&self) -> Result<[u8; 8], SurveyError> {
+        Ok((*self).db.generate_id().map_err(storage_error)?.to_be_bytes())
+    }
+
+    fn flush(&self) -> Result<(), SurveyError> {
+        (*self).db.flush().map(|_| ()).map_err(storage_error)
+    }
+
+    // Store json under the key index holds for name, or under a new key
+    fn put_indexed(&self, tree: &::sled::Tree, index: &::sled::Tree, name: &[u8], json: String) -> Result<(), SurveyError> {
+        let key:Vec<u8> = match index.get(name).map_err(storage_error)? {
+            Some(key) => key.to_vec(),
+            None => (*self).next_key()?.to_vec()
+        };
+        tree.insert(&key, json.as_bytes()).map_err(storage_error)?;
+        index.insert(name, key).map_err(storage_error)?;
+        (*self).flush()
+    }
+}
+
+fn get_indexed<T: serde::de::DeserializeOwned>(tree: &::sled::Tree, index: &::sled::Tree, name: &[u8]) -> Result<Option<T>, SurveyError> {
+    match index.get(name).map_err(storage_error)? {
+        Some(key) => match tree.get(key).map_err(storage_error)? {
+            Some(json) => Ok(Some(from_json(&json)?)),
+            None => Ok(None)
+        },
+        None => Ok(None)
+    }
+}
+
+fn values<T: serde::de::DeserializeOwned>(tree: &::sled::Tree) -> Result<Vec<T>, SurveyError> {
+    tree.iter().values().map(|json| from_json(&json.map_err(storage_error)?)).collect()
+}
+
+fn fr_key(bytes: &[u8]) -> Result<Fr, SurveyError> {
+    fr_from_bytes(bytes).ok_or_else(|| SurveyError::Storage(String::from("stored id hash or vid does not decode")))
+}
+
+impl Storage for SledStorage {
+
+    fn put_user(&mut self, record: &RegistrationRecord) -> Result<(), SurveyError> {
+        (*self).put_indexed(&(*self).users, &(*self).user_index, &g1_to_bytes((*record).commitment), to_json(record)?)
+    }
+
+    fn get_user(&self, commitment: G1) -> Result<Option<RegistrationRecord>, SurveyError> {
+        get_indexed(&(*self).users, &(*self).user_index, &g1_to_bytes(commitment))
+    }
+
+    fn remove_user(&mut self, commitment: G1) -> Result<(), SurveyError> {
+        if let Some(key) = (*self).user_index.remove(g1_to_bytes(commitment)).map_err(storage_error)? {
+            (*self).users.remove(key).map_err(storage_error)?;
+        }
+        (*self).flush()
+    }
+
+    fn users(&self) -> Result<Vec<RegistrationRecord>, SurveyError> {
+        values(&(*self).users)
+    }
+
+    fn add_member(&mut self, id_hash: Fr) -> Result<(), SurveyError> {
+        (*self).members.insert(fr_to_bytes(id_hash), &[]).map_err(storage_error)?;
+        (*self).flush()
+    }
+
+    fn remove_member(&mut self, id_hash: Fr) -> Result<(), SurveyError> {
+        (*self).members.remove(fr_to_bytes(id_hash)).map_err(storage_error)?;
+        (*self).flush()
+    }
+
+    fn members(&self) -> Result<Vec<Fr>, SurveyError> {
+        (*self).members.iter().keys().map(|key| fr_key(&key.map_err(storage_error)?)).collect()
+    }
+
+    fn put_survey(&mut self, survey: &Survey) -> Result<(), SurveyError> {
+        (*self).put_indexed(&(*self).surveys, &(*self).survey_index, &fr_to_bytes((*survey).vid), to_json(survey)?)
+    }
+
+    fn get_survey(&self, vid: Fr) -> Result<Option<Survey>, SurveyError> {
+        get_indexed(&(*self).surveys, &(*self).survey_index, &fr_to_bytes(vid))
+    }
+
+    fn surveys(&self) -> Result<Vec<Survey>, SurveyError> {
+        values(&(*self).surveys)
+    }
+
+    fn put_submission(&mut self, vid: Fr, submission: &Submission) -> Result<(), SurveyError> {
+        let key:Vec<u8> = [fr_to_bytes(vid), (*self).next_key()?.to_vec()].concat();
+        (*self).submissions.insert(key, to_json(submission)?.as_bytes()).map_err(storage_error)?;
+        (*self).flush()
+    }
+
+    fn get_submissions(&self, vid: Fr) -> Result<Vec<Submission>, SurveyError> {
+        (*self).submissions.scan_prefix(fr_to_bytes(vid)).values().map(|json| from_json(&json.map_err(storage_error)?)).collect()
+    }
+
+    fn submissions(&self) -> Result<Vec<(Fr, Submission)>, SurveyError> {
+        // Keys sort by survey first, so order by the sequence number after the vid
+        let mut submissions:Vec<([u8; 8], Fr, Submission)> = Vec::new();
+        for entry in (*self).submissions.iter() {
+            let (key, json) = entry.map_err(storage_error)?;
+            let mut seq = [0u8; 8];
+            seq.copy_from_slice(&key[FR_BYTES..]);
+            submissions.push((seq, fr_key(&key[..FR_BYTES])?, from_json(&json)?));
+        }
+        submissions.sort_by_key(|(seq, _, _)| *seq);
+        Ok(submissions.into_iter().map(|(_, vid, submission)| (vid, submission)).collect())
+    }
+
+    fn put_revocation(&mut self, id_hash: Fr) -> Result<(), SurveyError> {
+        if !(*self).revocations.contains_key(fr_to_bytes(id_hash)).map_err(storage_error)? {
+            (*self).revocations.insert(fr_to_bytes(id_hash), &(*self).next_key()?).map_err(storage_error)?;
+        }
+        (*self).flush()
+    }
+
+    fn revocations(&self) -> Result<Vec<Fr>, SurveyError> {
+        let mut revocations:Vec<(Vec<u8>, Fr)> = Vec::new();
+        for entry in (*self).revocations.iter() {
+            let (key, seq) = entry.map_err(storage_error)?;
+            revocations.push((seq.to_vec(), fr_key(&key)?));
+        }
+        revocations.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(revocations.into_iter().map(|(_, id_hash)| id_hash).collect())
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use super::check_storage;
+
+#[test]
+fn test_sled_storage() {
+    check_storage(&mut SledStorage::temporary().expect("Could not open database"));
+}
+
+// What was written is still there after the database is closed and opened again
+#[test]
+fn test_sled_reopen() {
+    let path = std::env::temp_dir().join(format!("anon-survey-sled-{}", hex::encode(fr_to_bytes(Fr::random(&mut rand::thread_rng())))));
+    let id_hash:Fr = Fr::random(&mut rand::thread_rng());
+    {
+        let mut storage = SledStorage::open(&path).expect("Could not open database");
+        storage.put_revocation(id_hash).expect("Storage failed");
+        storage.add_member(id_hash).expect("Storage failed");
+    }
+    let storage = SledStorage::open(&path).expect("Could not open database");
+    assert!( storage.revocations().expect("Storage failed") == vec![id_hash] && storage.members().expect("Storage failed") == vec![id_hash] );
+    drop(storage);
+    std::fs::remove_dir_all(&path).expect("Could not remove database");
+}
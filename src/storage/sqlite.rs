@@ -0,0 +1,181 @@
+extern crate tbn;
+
+use std::path::Path;
+use rusqlite::{Connection, OptionalExtension, params};
+use tbn::{Fr, G1};
+
+use super::{Storage, to_json, from_json, storage_error};
+use crate::users::RegistrationRecord;
+use crate::survey::Survey;
+use crate::submission::Submission;
+use crate::encoding::{fr_to_bytes, fr_from_bytes, g1_to_bytes};
+use crate::error::SurveyError;
+
+/*
+ * ----------------------------------------------
+ * |    SQLITE STORAGE                          |
+ * ----------------------------------------------
+ *
+ * Storage in an SQLite database, one table per kind of record (see SCHEMA). Records are stored as
+ * JSON next to the key they are looked up by (S', vid or H(id), in their canonical encodings), and
+ * the rowid keeps them in insertion order. Every statement commits on its own.
+*/
+
+const SCHEMA:&str = "
+    CREATE TABLE IF NOT EXISTS users (
+        seq INTEGER PRIMARY KEY AUTOINCREMENT,
+        commitment BLOB NOT NULL UNIQUE,
+        record TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS members (
+        id_hash BLOB PRIMARY KEY
+    );
+    CREATE TABLE IF NOT EXISTS surveys (
+        seq INTEGER PRIMARY KEY AUTOINCREMENT,
+        vid BLOB NOT NULL UNIQUE,
+        survey TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS submissions (
+        seq INTEGER PRIMARY KEY AUTOINCREMENT,
+        vid BLOB NOT NULL,
+        submission TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS submissions_by_vid ON submissions (vid);
+    CREATE TABLE IF NOT EXISTS revocations (
+        seq INTEGER PRIMARY KEY AUTOINCREMENT,
+        id_hash BLOB NOT NULL UNIQUE
+    );
+";
+
+pub struct SqliteStorage {
+    connection: Connection
+}
+
+impl SqliteStorage {
+
+    /* Open (or create) the database file at path */
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, SurveyError> {
+        SqliteStorage::with_connection(Connection::open(path).map_err(storage_error)?)
+    }
+
+    /* Database that only lives in memory, for tests */
+    pub fn in_memory() -> Result<Self, SurveyError> {
+        SqliteStorage::with_connection(Connection::open_in_memory().map_err(storage_error)?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, SurveyError> {
+        connection.execute_batch(SCHEMA).map_err(storage_error)?;
+        Ok(SqliteStorage { connection })
+    }
+
+    fn execute(&self, sql: &str, params: impl rusqlite::Params) -> Result<(), SurveyError> {
+        (*self).connection.execute(sql, params).map(|_| ()).map_err(storage_error)
+    }
+
+    // The JSON column of every row the query returns
+    fn json_rows(&self, sql: &str, params: impl rusqlite::Params) -> Result<Vec<String>, SurveyError> {
+        let mut statement = (*self).connection.prepare(sql).map_err(storage_error)?;
+        let rows = statement.query_map(params, |row| row.get::<_, String>(0)).map_err(storage_error)?;
+        rows.map(|json| json.map_err(storage_error)).collect()
+    }
+
+    fn json_row(&self, sql: &str, params: impl rusqlite::Params) -> Result<Option<String>, SurveyError> {
+        (*self).connection.query_row(sql, params, |row| row.get::<_, String>(0)).optional().map_err(storage_error)
+    }
+
+    fn fr_rows(&self, sql: &str) -> Result<Vec<Fr>, SurveyError> {
+        let mut statement = (*self).connection.prepare(sql).map_err(storage_error)?;
+        let rows = statement.query_map([], |row| row.get::<_, Vec<u8>>(0)).map_err(storage_error)?;
+        rows.map(|bytes| fr_from_bytes(&bytes.map_err(storage_error)?)
+                 .ok_or_else(|| SurveyError::Storage(String::from("stored id hash does not decode")))).collect()
+    }
+}
+
+impl Storage for SqliteStorage {
+
+    fn put_user(&mut self, record: &RegistrationRecord) -> Result<(), SurveyError> {
+        (*self).execute("INSERT INTO users (commitment, record) VALUES (?1, ?2)
+                         ON CONFLICT (commitment) DO UPDATE SET record = excluded.record",
+                        params![g1_to_bytes((*record).commitment), to_json(record)?])
+    }
+
+    fn get_user(&self, commitment: G1) -> Result<Option<RegistrationRecord>, SurveyError> {
+        (*self).json_row("SELECT record FROM users WHERE commitment = ?1", params![g1_to_bytes(commitment)])?
+            .map(|json| from_json(json.as_bytes())).transpose()
+    }
+
+    fn remove_user(&mut self, commitment: G1) -> Result<(), SurveyError> {
+        (*self).execute("DELETE FROM users WHERE commitment = ?1", params![g1_to_bytes(commitment)])
+    }
+
+    fn users(&self) -> Result<Vec<RegistrationRecord>, SurveyError> {
+        (*self).json_rows("SELECT record FROM users ORDER BY seq", [])?.iter().map(|json| from_json(json.as_bytes())).collect()
+    }
+
+    fn add_member(&mut self, id_hash: Fr) -> Result<(), SurveyError> {
+        (*self).execute("INSERT OR IGNORE INTO members (id_hash) VALUES (?1)", params![fr_to_bytes(id_hash)])
+    }
+
+    fn remove_member(&mut self, id_hash: Fr) -> Result<(), SurveyError> {
+        (*self).execute("DELETE FROM members WHERE id_hash = ?1", params![fr_to_bytes(id_hash)])
+    }
+
+    fn members(&self) -> Result<Vec<Fr>, SurveyError> {
+        (*self).fr_rows("SELECT id_hash FROM members")
+    }
+
+    fn put_survey(&mut self, survey: &Survey) -> Result<(), SurveyError> {
+        (*self).execute("INSERT INTO surveys (vid, survey) VALUES (?1, ?2)
+                         ON CONFLICT (vid) DO UPDATE SET survey = excluded.survey",
+                        params![fr_to_bytes((*survey).vid), to_json(survey)?])
+    }
+
+    fn get_survey(&self, vid: Fr) -> Result<Option<Survey>, SurveyError> {
+        (*self).json_row("SELECT survey FROM surveys WHERE vid = ?1", params![fr_to_bytes(vid)])?
+            .map(|json| from_json(json.as_bytes())).transpose()
+    }
+
+    fn surveys(&self) -> Result<Vec<Survey>, SurveyError> {
+        (*self).json_rows("SELECT survey FROM surveys ORDER BY seq", [])?.iter().map(|json| from_json(json.as_bytes())).collect()
+    }
+
+    fn put_submission(&mut self, vid: Fr, submission: &Submission) -> Result<(), SurveyError> {
+        (*self).execute("INSERT INTO submissions (vid, submission) VALUES (?1, ?2)", params![fr_to_bytes(vid), to_json(submission)?])
+    }
+
+    fn get_submissions(&self, vid: Fr) -> Result<Vec<Submission>, SurveyError> {
+        (*self).json_rows("SELECT submission FROM submissions WHERE vid = ?1 ORDER BY seq", params![fr_to_bytes(vid)])?
+            .iter().map(|json| from_json(json.as_bytes())).collect()
+    }
+
+    fn submissions(&self) -> Result<Vec<(Fr, Submission)>, SurveyError> {
+        let mut statement = (*self).connection.prepare("SELECT vid, submission FROM submissions ORDER BY seq").map_err(storage_error)?;
+        let rows = statement.query_map([], |row| Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, String>(1)?))).map_err(storage_error)?;
+        rows.map(|row| {
+            let (vid, json) = row.map_err(storage_error)?;
+            let vid:Fr = fr_from_bytes(&vid).ok_or_else(|| SurveyError::Storage(String::from("stored vid does not decode")))?;
+            Ok((vid, from_json(json.as_bytes())?))
+        }).collect()
+    }
+
+    fn put_revocation(&mut self, id_hash: Fr) -> Result<(), SurveyError> {
+        (*self).execute("INSERT OR IGNORE INTO revocations (id_hash) VALUES (?1)", params![fr_to_bytes(id_hash)])
+    }
+
+    fn revocations(&self) -> Result<Vec<Fr>, SurveyError> {
+        (*self).fr_rows("SELECT id_hash FROM revocations ORDER BY seq")
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use super::check_storage;
+
+#[test]
+fn test_sqlite_storage() {
+    check_storage(&mut SqliteStorage::in_memory().expect("Could not open database"));
+}
@@ -0,0 +1,410 @@
+extern crate tbn;
+extern crate rand;
+
+use rand::{RngCore, CryptoRng};
+use tbn::{Group, Fr, G1, G2};
+use crate::gt::Gt;
+
+use crate::ct::fr_eq;
+use crate::cost::{pairing, pow};
+use crate::users::VerificationKey;
+use crate::keyring::SurveyKeys;
+use crate::error::SurveyError;
+use crate::hash::epoch_point;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+use crate::proofs::{hash_to_fr, push_fr, push_bytes, push_g1, push_g2, push_gt};
+use crate::padding::unpad;
+
+/*
+ * ----------------------------------------------
+ * |    SURVEY SUBMISSION                       |
+ * ----------------------------------------------
+ *
+ * A submission carries the user's response to survey vid together with:
+ *      - A one-time token Tok = g^(1/(s + vid)), where s is the PRF key certified by the RA. The
+ *        same user always derives the same token for the same survey, so repeats can be detected
+ *        without learning who submitted.
+ *      - The user's RA credential and SA signature, re-randomized and blinded so they cannot be
+ *        linked to the copies the RA and SA handed out, and the epoch the credential was issued for.
+ *      - A non-interactive (Fiat-Shamir) Σ-protocol proof of knowledge of (id, s, t1, t2) such that
+ *          e(c1', g2) = pk_RA · e(u_RA·id + v_RA·s + h_RA + H(epoch), c2') · e(g, g2)^t1   (RA credential)
+ *          e(σ1', g2) = pk_SA · e(u_SA·vid + v_SA·id + h_RA, σ2') · e(g, g2)^t2           (SA signature)
+ *          g = Tok·(s + vid)                                                              (token)
+ *        i.e. the submitter is registered, authorized for vid, and computed the token correctly.
+ *        The response message is bound into the challenge, so it cannot be swapped out later.
+ *
+ * The epoch is public: every credential of an epoch shares it, so it says no more about the
+ * submitter than that they registered or renewed since. A submission claiming a later epoch than
+ * its credential's fails the proof, and verifiers refuse epochs they no longer accept (see
+ * SubmissionLedger::set_min_epoch).
+*/
+
+// Domain separation tag for the Fiat-Shamir challenge of a submission proof
+pub const SUBMIT_DOMAIN:&[u8] = b"anon-survey/submit/v1";
+
+// NIZK proof: the challenge and the responses for each witness
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SubmissionProof {
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::fr"))]
+    pub c: Fr,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::fr"))]
+    pub z_id: Fr,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::fr"))]
+    pub z_s: Fr,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::fr"))]
+    pub z_t1: Fr,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::fr"))]
+    pub z_t2: Fr
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Submission {
+    // Survey being responded to
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::fr"))]
+    pub vid: Fr,
+    // The response itself, padded to the survey's buckets by User::submit_survey (see padding.rs)
+    pub message: Vec<u8>,
+    // One-time token Tok = g^(1/(s + vid))
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g1"))]
+    pub token: G1,
+    // Re-randomized and blinded RA credential (c1', c2')
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g1"))]
+    pub cred_1: G1,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g2"))]
+    pub cred_2: G2,
+    // Epoch the RA credential was issued for
+    pub epoch: u64,
+    // Re-randomized and blinded SA signature (σ1', σ2')
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g1"))]
+    pub sigma_1: G1,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g2"))]
+    pub sigma_2: G2,
+    pub proof: SubmissionProof
+}
+
+// What a submission is built from: the response to survey vid, and the user's secrets: their id,
+// PRF key s, RA credential (c1, c2) on (id, s) for epoch, and SA signature (σ1, σ2) on (vid, id)
+#[derive(Clone, Copy)]
+pub struct SubmissionInput<'a> {
+    pub vid: Fr,
+    pub message: &'a [u8],
+    pub id: Fr,
+    pub s: Fr,
+    pub credential: (G1, G2),
+    pub epoch: u64,
+    pub signature: (G1, G2)
+}
+
+impl Submission {
+
+    // Build a submission from input. Fails in the (negligible) case that s + vid = 0
+    pub fn new(input: SubmissionInput, g: G1, g2: G2, vk_sa: &VerificationKey, vk_ra: &VerificationKey) -> Result<Submission, SurveyError> {
+        // crytpographiclaly secure thread-local rng
+        Submission::new_with_rng(input, g, g2, vk_sa, vk_ra, &mut rand::thread_rng())
+    }
+
+    pub fn new_with_rng(input: SubmissionInput, g: G1, g2: G2, vk_sa: &VerificationKey, vk_ra: &VerificationKey,
+                        rng: &mut (impl RngCore + CryptoRng)) -> Result<Submission, SurveyError> {
+        let SubmissionInput { vid, message, id, s, credential, epoch, signature } = input;
+
+        // One-time token for this survey
+        let token:G1 = survey_token(s, vid, g).ok_or(SurveyError::DegenerateToken)?;
+
+        // Re-randomize the RA credential with r1 and blind it with t1
+        let (r1, t1):(Fr, Fr) = (Fr::random(rng), Fr::random(rng));
+        let cred_2:G2 = credential.1 + g2 * r1;
+        let cred_1:G1 = credential.0 + ((*vk_ra).u * id + (*vk_ra).v * s + (*vk_ra).h + epoch_point(epoch)) * r1 + g * t1;
+
+        // Re-randomize the SA signature with r2 and blind it with t2
+        let (r2, t2):(Fr, Fr) = (Fr::random(rng), Fr::random(rng));
+        let sigma_2:G2 = signature.1 + g2 * r2;
+        let sigma_1:G1 = signature.0 + ((*vk_sa).u * vid + (*vk_sa).v * id + (*vk_ra).h) * r2 + g * t2;
+
+        let statement = SubmissionStatement { vid, message, token, cred_1, cred_2, epoch, sigma_1, sigma_2, vk_sa, vk_ra, g, g2 };
+        let proof:SubmissionProof = prove_submission(&statement, &SubmissionWitness { id, s, t1, t2 }, rng);
+        Ok(Submission { vid, message: message.to_vec(), token, cred_1, cred_2, epoch, sigma_1, sigma_2, proof })
+    }
+
+    // The response without its padding (the message as it is, if it was not padded)
+    pub fn answer(&self) -> &[u8] {
+        unpad(&(*self).message).unwrap_or(&(*self).message)
+    }
+
+    // The statement the submission's proof is about
+    pub fn statement<'a>(&'a self, vk_sa: &'a VerificationKey, vk_ra: &'a VerificationKey, g: G1, g2: G2) -> SubmissionStatement<'a> {
+        SubmissionStatement {
+            vid: (*self).vid, message: &(*self).message, token: (*self).token,
+            cred_1: (*self).cred_1, cred_2: (*self).cred_2, epoch: (*self).epoch, sigma_1: (*self).sigma_1, sigma_2: (*self).sigma_2,
+            vk_sa, vk_ra, g, g2
+        }
+    }
+}
+
+/*
+ * Submission relation
+ */
+
+// Everything public in a submission, together with the keys and generators it is checked against
+pub struct SubmissionStatement<'a> {
+    pub vid: Fr,
+    pub message: &'a [u8],
+    pub token: G1,
+    pub cred_1: G1,
+    pub cred_2: G2,
+    pub epoch: u64,
+    pub sigma_1: G1,
+    pub sigma_2: G2,
+    pub vk_sa: &'a VerificationKey,
+    pub vk_ra: &'a VerificationKey,
+    pub g: G1,
+    pub g2: G2
+}
+
+// The submitter's id, PRF key, and the blinding of their credential and signature
+pub struct SubmissionWitness {
+    pub id: Fr,
+    pub s: Fr,
+    pub t1: Fr,
+    pub t2: Fr
+}
+
+pub fn prove_submission(statement: &SubmissionStatement, witness: &SubmissionWitness, rng: &mut (impl RngCore + CryptoRng)) -> SubmissionProof {
+
+    let (vk_sa, vk_ra, g, g2) = ((*statement).vk_sa, (*statement).vk_ra, (*statement).g, (*statement).g2);
+
+    /* Σ-protocol commitments */
+    let (k_id, k_s, k_t1, k_t2):(Fr, Fr, Fr, Fr) = (Fr::random(rng), Fr::random(rng), Fr::random(rng), Fr::random(rng));
+    let t_ra:Gt = pairing((*vk_ra).u * k_id + (*vk_ra).v * k_s, (*statement).cred_2) * pairing(g * k_t1, g2);
+    let t_sa:Gt = pairing((*vk_sa).v * k_id, (*statement).sigma_2) * pairing(g * k_t2, g2);
+    let t_tok:G1 = (*statement).token * k_s;
+
+    // Fiat-Shamir challenge over the statement, the commitments, and the message
+    let c:Fr = challenge(statement, t_ra, t_sa, t_tok);
+
+    SubmissionProof {
+        c,
+        z_id: k_id + c * (*witness).id,
+        z_s: k_s + c * (*witness).s,
+        z_t1: k_t1 + c * (*witness).t1,
+        z_t2: k_t2 + c * (*witness).t2
+    }
+}
+
+pub fn verify_submission(statement: &SubmissionStatement, proof: &SubmissionProof) -> bool {
+
+    let (vk_sa, vk_ra, g, g2) = ((*statement).vk_sa, (*statement).vk_ra, (*statement).g, (*statement).g2);
+
+    /* Recompute the Σ-protocol commitments from the responses and the challenge */
+    let c:Fr = (*proof).c;
+    let h_ra:G1 = (*vk_ra).h + epoch_point((*statement).epoch);
+    let t_ra:Gt = pairing((*vk_ra).u * (*proof).z_id + (*vk_ra).v * (*proof).z_s + h_ra * c, (*statement).cred_2)
+        * pairing(g * (*proof).z_t1 - (*statement).cred_1 * c, g2)
+        * pow((*vk_ra).pk, c);
+    let t_sa:Gt = pairing((*vk_sa).v * (*proof).z_id + ((*vk_sa).u * (*statement).vid + (*vk_ra).h) * c, (*statement).sigma_2)
+        * pairing(g * (*proof).z_t2 - (*statement).sigma_1 * c, g2)
+        * pow((*vk_sa).pk, c);
+    let t_tok:G1 = (*statement).token * (*proof).z_s - (g - (*statement).token * (*statement).vid) * c;
+
+    // The proof is valid iff the recomputed commitments hash to the same challenge
+    fr_eq(challenge(statement, t_ra, t_sa, t_tok), c)
+}
+
+// One-time token Tok = g^(1/(s + vid)) of the user with PRF key s for survey vid (None if s + vid = 0)
+pub fn survey_token(s: Fr, vid: Fr, g: G1) -> Option<G1> {
+    Some(g * (s + vid).inverse()?)
+}
+
+/* Check: verify a submission for survey vid without learning who submitted it */
+// Accepts iff the submission is for vid, its token and re-randomized signatures are well-formed,
+// and the proof shows that the token was computed from a registered user's PRF key by a user the
+// SA authorized for vid (under any of the SA's keys, see keyring.rs)
+pub fn check_submission(vid: Fr, submission: &Submission, vk_sa: &impl SurveyKeys, vk_ra: &VerificationKey,
+                        g: G1, g2: G2) -> bool {
+
+    // Reject submissions for another survey and degenerate tokens/signatures
+    if (*submission).vid != vid {
+        return false;
+    }
+    if (*submission).token.is_zero() || (*submission).cred_2.is_zero() || (*submission).sigma_2.is_zero() {
+        return false;
+    }
+
+    // A submission does not name the SA key version, so try every key the SA published
+    (*vk_sa).keys().iter().any(|vk_sa| verify_submission(&(*submission).statement(vk_sa, vk_ra, g, g2), &(*submission).proof))
+}
+
+/* Check a submission against every verification key the RA currently publishes */
+// During the grace window after an RA key rotation (see RegistrationAuthority::verification_keys)
+// a credential may be under the old or the new key. The check tells the two apart, so until the
+// window ends a submission reveals whether its author has migrated yet, and nothing more
+pub fn check_submission_under(vid: Fr, submission: &Submission, vk_sa: &impl SurveyKeys, vk_ras: &[&VerificationKey],
+                              g: G1, g2: G2) -> bool {
+    vk_ras.iter().any(|vk_ra| check_submission(vid, submission, vk_sa, vk_ra, g, g2))
+}
+
+// Hash everything the proof talks about into a challenge in Fr
+fn challenge(statement: &SubmissionStatement, t_ra: Gt, t_sa: Gt, t_tok: G1) -> Fr {
+
+    let (vk_sa, vk_ra) = ((*statement).vk_sa, (*statement).vk_ra);
+    let mut transcript:Vec<u8> = SUBMIT_DOMAIN.to_vec();
+    push_fr(&mut transcript, (*statement).vid);
+    for p in &[(*statement).token, (*statement).cred_1, (*statement).sigma_1, (*vk_sa).u, (*vk_sa).v, (*vk_ra).u, (*vk_ra).v, (*vk_ra).h, t_tok] {
+        push_g1(&mut transcript, *p);
+    }
+    push_g2(&mut transcript, (*statement).cred_2);
+    push_g2(&mut transcript, (*statement).sigma_2);
+    transcript.extend_from_slice(&(*statement).epoch.to_be_bytes());
+    for t in &[(*vk_sa).pk, (*vk_ra).pk, t_ra, t_sa] {
+        push_gt(&mut transcript, *t);
+    }
+    push_bytes(&mut transcript, (*statement).message);
+
+    hash_to_fr(&transcript)
+}
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use crate::users::{User, SurveyAuthority, RegistrationAuthority};
+#[cfg(test)]
+use crate::params::SystemParams;
+
+// The token only depends on the user and the survey, not on the randomness of each submission
+#[test]
+fn test_token_one_time() {
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let (g, g2):(G1, G2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let mut user = User::new();
+    user.reg_user(&mut ra).expect("Registration failed");
+
+    let survey = sa.gen_survey(&vec![user.id()], g, g2, &ra.vk).expect("SA survey creation failed!");
+    let first = user.submit_survey(&survey, b"yes", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    let second = user.submit_survey(&survey, b"no", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    assert!( first.token == second.token );
+    assert!( first.sigma_1 != second.sigma_1 && first.cred_2 != second.cred_2 );
+
+    let survey2 = sa.gen_survey(&vec![user.id()], g, g2, &ra.vk).expect("SA survey creation failed!");
+    let other = user.submit_survey(&survey2, b"yes", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    assert!( other.token != first.token );
+}
+
+// Users that are unregistered or not on the participant list cannot build a submission
+#[test]
+fn test_submit_requires_registration_and_authorization() {
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let (g, g2):(G1, G2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let unregistered = User::new();
+    let mut outsider = User::new();
+    outsider.reg_user(&mut ra).expect("Registration failed");
+
+    let survey = sa.gen_survey(&vec![unregistered.id()], g, g2, &ra.vk).expect("SA survey creation failed!");
+    assert!( unregistered.submit_survey(&survey, b"yes", (g, g2), &sa.vk, &ra.vk).unwrap_err() == SurveyError::UnregisteredUser );
+    assert!( outsider.submit_survey(&survey, b"yes", (g, g2), &sa.vk, &ra.vk).unwrap_err() == SurveyError::NotAuthorized );
+}
+
+// Honest submissions are accepted, and tampering with any part of one is caught
+#[test]
+fn test_check_submission() {
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let (g, g2):(G1, G2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let mut user = User::new();
+    user.reg_user(&mut ra).expect("Registration failed");
+
+    let survey = sa.gen_survey(&vec![user.id()], g, g2, &ra.vk).expect("SA survey creation failed!");
+    let vid:Fr = survey.vid;
+    let submission = user.submit_survey(&survey, b"yes", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    assert!( check_submission(vid, &submission, &sa.vk, &ra.vk, g, g2) );
+
+    // Wrong survey, or the right survey under other authorities
+    assert!( !check_submission(Fr::random(rng), &submission, &sa.vk, &ra.vk, g, g2) );
+    let other_sa = SurveyAuthority::new(&params);
+    assert!( !check_submission(vid, &submission, &other_sa.vk, &ra.vk, g, g2) );
+
+    // Swapped message, forged token, or malformed token
+    let mut forged = submission.clone();
+    forged.message = b"no".to_vec();
+    assert!( !check_submission(vid, &forged, &sa.vk, &ra.vk, g, g2) );
+    let mut forged = submission.clone();
+    forged.token = G1::random(rng);
+    assert!( !check_submission(vid, &forged, &sa.vk, &ra.vk, g, g2) );
+    let mut forged = submission.clone();
+    forged.token = G1::zero();
+    assert!( !check_submission(vid, &forged, &sa.vk, &ra.vk, g, g2) );
+}
+
+// A user cannot answer with a signature issued to someone else, even one they know
+#[test]
+fn test_check_rejects_borrowed_signature() {
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let (g, g2):(G1, G2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let mut alice = User::new();
+    let mut mallory = User::new();
+    alice.reg_user(&mut ra).expect("Registration failed");
+    mallory.reg_user(&mut ra).expect("Registration failed");
+
+    let survey = sa.gen_survey(&vec![alice.id()], g, g2, &ra.vk).expect("SA survey creation failed!");
+    let vid:Fr = survey.vid;
+    let entry = survey.entry_for(alice.id()).unwrap();
+    let (cred, prf_key) = (mallory.credential.unwrap(), mallory.prf_key.expose());
+    let input = SubmissionInput { vid, message: b"yes", id: mallory.id(), s: prf_key, credential: cred, epoch: 0, signature: (entry.sigma_1, entry.sigma_2) };
+    let forged = Submission::new(input, g, g2, &sa.vk, &ra.vk).expect("Submission failed");
+    assert!( !check_submission(vid, &forged, &sa.vk, &ra.vk, g, g2) );
+}
+
+// The submission relation holds only for the witness behind the submission
+#[test]
+fn test_submission_relation() {
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let (g, g2):(G1, G2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let mut user = User::new();
+    user.reg_user(&mut ra).expect("Registration failed");
+
+    let survey = sa.gen_survey(&vec![user.id()], g, g2, &ra.vk).expect("SA survey creation failed!");
+    let submission = user.submit_survey(&survey, b"yes", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    let statement = submission.statement(&sa.vk, &ra.vk, g, g2);
+    assert!( verify_submission(&statement, &submission.proof) );
+
+    // Without the blinding, even the real id and PRF key do not satisfy the relation
+    let witness = SubmissionWitness { id: user.id(), s: user.prf_key.expose(), t1: Fr::random(rng), t2: Fr::random(rng) };
+    assert!( !verify_submission(&statement, &prove_submission(&statement, &witness, rng)) );
+}
+
+// A submission survives serialization, and still passes the check on the other side
+#[test]
+#[cfg(feature = "serde")]
+fn test_submission_serde() {
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let (g, g2):(G1, G2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let mut user = User::new();
+    user.reg_user(&mut ra).expect("Registration failed");
+
+    let survey = sa.gen_survey(&vec![user.id()], g, g2, &ra.vk).expect("SA survey creation failed!");
+    let vid:Fr = survey.vid;
+    let submission = user.submit_survey(&survey, b"yes", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    let yaml = serde_yaml::to_string(&(submission, &sa.vk, &ra.vk)).expect("Could not serialize");
+    let (received, vk_sa, vk_ra):(Submission, VerificationKey, VerificationKey) = serde_yaml::from_str(&yaml).expect("Could not deserialize");
+    assert!( check_submission(vid, &received, &vk_sa, &vk_ra, g, g2) );
+}
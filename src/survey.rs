@@ -0,0 +1,419 @@
+extern crate tbn;
+extern crate rand;
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+#[cfg(feature = "serde")]
+use std::convert::TryFrom;
+use tbn::{Fr, G1, G2};
+
+use crate::ct::engine_gt_eq;
+use crate::engine::{PairingEngine, Tbn};
+use crate::users::VerificationKey;
+use crate::keyring::SurveyKeys;
+use crate::padding::PaddingPolicy;
+use crate::encoding::fr_to_bytes;
+use crate::hash::{survey_id, namespaced_vid, NONCE_BYTES};
+use crate::error::SurveyError;
+use rand::{RngCore, CryptoRng};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/*
+ * ----------------------------------------------
+ * |    SURVEYS                                 |
+ * ----------------------------------------------
+ *
+ * A survey as published by its SA: the survey id vid, and for every participant their id together
+ * with the SA's signature (σ1, σ2) on (vid, id), where
+ *      σ1 = g·y + (u_SA·vid + v_SA·id + h_RA)·r,   σ2 = g2·r
+ *
+ * The survey records the version of the SA key that signed it (see keyring.rs), and the buckets
+ * participants pad their answers to (see padding.rs).
+ *
+ * The signature only takes group operations, so signing and checking it (sign_participant,
+ * participant_verifies, participants_verify) are written over any PairingEngine (see engine.rs).
+ * Surveys hold tbn points, and use them with Tbn.
+ *
+ * The vid is random, or derived from a SurveyDescription the SA publishes alongside the survey:
+ * vid = H(title ‖ vk_SA ‖ nonce), so participants can check the survey is the one described. An SA
+ * that shares its RA with other SAs namespaces its vids under its registered name instead, and the
+ * survey carries the SurveyNamespace the vid was derived from (see registry.rs).
+*/
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParticipantEntry {
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::fr"))]
+    pub id: Fr,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g1"))]
+    pub sigma_1: G1,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g2"))]
+    pub sigma_2: G2
+}
+
+// Fr has no Hash impl, so participants are keyed by the canonical encoding of their id
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct UserId(Fr);
+
+impl Hash for UserId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        fr_to_bytes((*self).0).hash(state);
+    }
+}
+
+// Participant signatures are kept in a map so looking up a participant (e.g. in authorized) does
+// not scan the whole list. It is published as a plain list of entries
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "PublishedSurvey", into = "PublishedSurvey"))]
+pub struct Survey {
+    pub vid: Fr,
+    // Version of the SA key the participants were signed with
+    pub key_version: u32,
+    // The SA name and nonce the vid was derived from, for namespaced surveys
+    pub namespace: Option<SurveyNamespace>,
+    // Buckets answers are padded to, the same for every participant
+    pub padding: PaddingPolicy,
+    signatures: HashMap<UserId, (G1, G2)>
+}
+
+impl Survey {
+
+    // Survey vid without participants yet, signed with the SA's first key
+    pub fn new(vid: Fr) -> Self {
+        Survey { vid, key_version: 0, namespace: None, padding: PaddingPolicy::default(), signatures: HashMap::new() }
+    }
+
+    // Whether the vid is in the namespace of the SA registered as authority
+    pub fn in_namespace(&self, authority: &str) -> bool {
+        match &(*self).namespace {
+            Some(namespace) => namespace.authority == authority && namespace.vid() == (*self).vid,
+            None => false
+        }
+    }
+
+    // Add (or replace) the SA's signature for a participant
+    pub fn insert(&mut self, entry: ParticipantEntry) {
+        (*self).signatures.insert(UserId(entry.id), (entry.sigma_1, entry.sigma_2));
+    }
+
+    // Add a participant who is not on the list yet, refusing one who is (e.g. reading a published list)
+    pub fn try_insert(&mut self, entry: ParticipantEntry) -> Result<(), SurveyError> {
+        match (*self).signatures.insert(UserId(entry.id), (entry.sigma_1, entry.sigma_2)) {
+            Some(_) => Err(SurveyError::DuplicateParticipant),
+            None => Ok(())
+        }
+    }
+
+    // The SA's signature for participant id, if they are on the list
+    pub fn entry_for(&self, id: Fr) -> Option<ParticipantEntry> {
+        (*self).signatures.get(&UserId(id)).map(|(sigma_1, sigma_2)| ParticipantEntry { id, sigma_1: *sigma_1, sigma_2: *sigma_2 })
+    }
+
+    // All participants with their signatures, ordered by the encoding of their id, so a survey is
+    // always published the same way
+    pub fn participants(&self) -> impl Iterator<Item = ParticipantEntry> + '_ {
+        let mut entries:Vec<(&UserId, &(G1, G2))> = (*self).signatures.iter().collect();
+        entries.sort_by_cached_key(|(id, _)| fr_to_bytes(id.0));
+        entries.into_iter().map(|(id, (sigma_1, sigma_2))| ParticipantEntry { id: id.0, sigma_1: *sigma_1, sigma_2: *sigma_2 })
+    }
+
+    // Number of participants
+    pub fn len(&self) -> usize {
+        (*self).signatures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (*self).signatures.is_empty()
+    }
+
+    // Anyone can test if a user is authorized to take the survey:
+    // e(σ1, g2) = pk_SA · e(u_SA·vid + v_SA·id + h_RA, σ2), under the SA key of the survey's version
+    pub fn is_authorized(&self, id: Fr, vk_sa: &impl SurveyKeys, vk_ra: &VerificationKey, g2: G2) -> bool {
+        match ((*self).entry_for(id), (*vk_sa).key((*self).key_version)) {
+            (Some(entry), Some(vk_sa)) => participant_verifies::<Tbn>((*vk_sa).pk, participant_message::<Tbn>((*vk_sa).u, (*vk_sa).v, (*vk_ra).h, (*self).vid, id),
+                                                                     entry.sigma_1, entry.sigma_2, g2),
+            _ => false
+        }
+    }
+
+    // Check every participant's signature at once (see participants_verify), returning the ids whose
+    // signature fails. Only if the batch fails are the entries checked one by one. Every participant
+    // fails if vk_sa holds no key of the survey's version
+    pub fn verify_all(&self, vk_sa: &impl SurveyKeys, vk_ra: &VerificationKey, g2: G2) -> Vec<Fr> {
+        let vk_sa:&VerificationKey = match (*vk_sa).key((*self).key_version) {
+            Some(vk_sa) => vk_sa,
+            None => return (*self).participants().map(|entry| entry.id).collect()
+        };
+
+        // crytpographiclaly secure thread-local rng
+        let rng = &mut rand::thread_rng();
+
+        let signed:Vec<(G1, G1, G2)> = (*self).participants()
+            .map(|entry| (participant_message::<Tbn>((*vk_sa).u, (*vk_sa).v, (*vk_ra).h, (*self).vid, entry.id), entry.sigma_1, entry.sigma_2))
+            .collect();
+        if participants_verify::<Tbn>((*vk_sa).pk, &signed, g2, rng) {
+            return Vec::new();
+        }
+        (*self).participants().map(|entry| entry.id)
+            .filter(|id| !(*self).is_authorized(*id, vk_sa, vk_ra, g2))
+            .collect()
+    }
+}
+
+// M = u_SA·vid + v_SA·id + h_RA, the message the SA signs for participant id of survey vid
+pub fn participant_message<E: PairingEngine>(u_sa: E::G1, v_sa: E::G1, h_ra: E::G1, vid: E::Scalar, id: E::Scalar) -> E::G1 {
+    u_sa * vid + v_sa * id + h_ra
+}
+
+// The SA's signature (σ1, σ2) = (g·y + M·r, g2·r) on message M, with sign_val = g·y and a fresh r
+pub fn sign_participant<E: PairingEngine>(sign_val: E::G1, message: E::G1, r: E::Scalar, g2: E::G2) -> (E::G1, E::G2) {
+    (sign_val + message * r, g2 * r)
+}
+
+// e(σ1, g2) = pk_SA · e(M, σ2), compared in constant time
+pub fn participant_verifies<E: PairingEngine>(pk_sa: E::Gt, message: E::G1, sigma_1: E::G1, sigma_2: E::G2, g2: E::G2) -> bool {
+    engine_gt_eq::<E>(E::pairing(sigma_1, g2), E::gt_mul(pk_sa, E::pairing(message, sigma_2)))
+}
+
+// Check signatures (M_i, σ1_i, σ2_i) at once. With random weights ρ_i, all of them verify (except
+// with negligible probability) iff
+//      e(Σ ρ_i·σ1_i, g2) · Π e(-ρ_i·M_i, σ2_i) = pk_SA^(Σ ρ_i)
+// The left side is a single product of n + 1 pairings (E::multi_pairing), sharing one final
+// exponentiation, instead of 2n full pairings
+pub fn participants_verify<E: PairingEngine>(pk_sa: E::Gt, signed: &[(E::G1, E::G1, E::G2)], g2: E::G2, rng: &mut (impl RngCore + CryptoRng)) -> bool {
+    let mut sigma_1_sum:E::G1 = E::g1_generator() * E::scalar_from_u64(0);
+    let mut weight_sum:E::Scalar = E::scalar_from_u64(0);
+    let mut pairs:Vec<(E::G1, E::G2)> = Vec::with_capacity(signed.len() + 1);
+    for (message, sigma_1, sigma_2) in signed {
+        let rho:E::Scalar = E::scalar_random(rng);
+        sigma_1_sum = sigma_1_sum + *sigma_1 * rho;
+        weight_sum = weight_sum + rho;
+        pairs.push((-(*message * rho), *sigma_2));
+    }
+    pairs.push((sigma_1_sum, g2));
+    engine_gt_eq::<E>(E::multi_pairing(&pairs), E::gt_pow(pk_sa, weight_sum))
+}
+
+// What a survey is about, published next to it so its vid can be checked (see hash.rs)
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SurveyDescription {
+    pub title: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::nonce"))]
+    pub nonce: [u8; NONCE_BYTES]
+}
+
+impl SurveyDescription {
+
+    // Description with a fresh nonce
+    pub fn new(title: &str) -> Self {
+        // crytpographiclaly secure thread-local rng
+        SurveyDescription::new_with_rng(title, &mut rand::thread_rng())
+    }
+
+    pub fn new_with_rng(title: &str, rng: &mut (impl RngCore + CryptoRng)) -> Self {
+        let mut nonce = [0u8; NONCE_BYTES];
+        rng.fill_bytes(&mut nonce);
+        SurveyDescription { title: String::from(title), nonce }
+    }
+
+    // The vid the SA with key vk_sa derives from this description
+    pub fn vid(&self, vk_sa: &VerificationKey) -> Fr {
+        survey_id((*self).title.as_bytes(), vk_sa, &(*self).nonce)
+    }
+
+    // The survey is the one described, created by the SA with key vk_sa
+    pub fn describes(&self, survey: &Survey, vk_sa: &VerificationKey) -> bool {
+        (*self).vid(vk_sa) == (*survey).vid
+    }
+}
+
+// Where a namespaced vid comes from: vid = H(authority ‖ nonce) (see hash.rs)
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SurveyNamespace {
+    // Name the SA is registered under
+    pub authority: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::nonce"))]
+    pub nonce: [u8; NONCE_BYTES]
+}
+
+impl SurveyNamespace {
+
+    // Namespace entry for a new survey of authority, with a fresh nonce
+    pub fn new(authority: &str) -> Self {
+        // crytpographiclaly secure thread-local rng
+        SurveyNamespace::new_with_rng(authority, &mut rand::thread_rng())
+    }
+
+    pub fn new_with_rng(authority: &str, rng: &mut (impl RngCore + CryptoRng)) -> Self {
+        let mut nonce = [0u8; NONCE_BYTES];
+        rng.fill_bytes(&mut nonce);
+        SurveyNamespace { authority: String::from(authority), nonce }
+    }
+
+    pub fn vid(&self) -> Fr {
+        namespaced_vid(&(*self).authority, &(*self).nonce)
+    }
+}
+
+// Wire form of a survey
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct PublishedSurvey {
+    #[serde(with = "crate::encoding::fr")]
+    vid: Fr,
+    #[serde(default)]
+    key_version: u32,
+    #[serde(default)]
+    namespace: Option<SurveyNamespace>,
+    #[serde(default)]
+    padding: PaddingPolicy,
+    participants: Vec<ParticipantEntry>
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<PublishedSurvey> for Survey {
+    type Error = SurveyError;
+
+    fn try_from(published: PublishedSurvey) -> Result<Self, SurveyError> {
+        let mut survey = Survey::new(published.vid);
+        survey.key_version = published.key_version;
+        survey.namespace = published.namespace;
+        survey.padding = published.padding;
+        for entry in published.participants {
+            survey.try_insert(entry)?;
+        }
+        Ok(survey)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Survey> for PublishedSurvey {
+    fn from(survey: Survey) -> Self {
+        let participants:Vec<ParticipantEntry> = survey.participants().collect();
+        PublishedSurvey { vid: survey.vid, key_version: survey.key_version, namespace: survey.namespace, padding: survey.padding, participants }
+    }
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use crate::users::{User, SurveyAuthority, RegistrationAuthority};
+#[cfg(test)]
+use crate::params::SystemParams;
+
+// Only listed participants have an entry, and only the signing SA's key authorizes them
+#[test]
+fn test_survey_entries() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let other_sa = SurveyAuthority::new(&params);
+    let (alice, bob, carol) = (User::new(), User::new(), User::new());
+
+    let survey:Survey = sa.gen_survey(&vec![alice.id(), bob.id()], g, g2, &ra.vk).expect("SA survey creation failed!");
+    assert!( survey.len() == 2 && !survey.is_empty() );
+    assert!( survey.entry_for(bob.id()).map(|entry| entry.id) == Some(bob.id()) );
+    assert!( survey.participants().count() == 2 );
+    assert!( survey.entry_for(carol.id()).is_none() );
+    assert!( survey.is_authorized(alice.id(), &sa.vk, &ra.vk, g2) );
+    assert!( !survey.is_authorized(carol.id(), &sa.vk, &ra.vk, g2) );
+    assert!( !survey.is_authorized(alice.id(), &other_sa.vk, &ra.vk, g2) );
+    assert!( sa.verid_list == vec![survey] );
+}
+
+// Participants are published in the order of their ids, and a published list that names someone
+// twice is refused
+#[cfg(feature = "serde")]
+#[test]
+fn test_published_participants() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let ids:Vec<Fr> = (0..5).map(|_| User::new().id()).collect();
+    let survey:Survey = sa.gen_survey(&ids, g, g2, &ra.vk).expect("SA survey creation failed!");
+    let published = PublishedSurvey::from(survey.clone());
+    assert!( published.participants.windows(2).all(|pair| fr_to_bytes(pair[0].id) < fr_to_bytes(pair[1].id)) );
+    assert!( Survey::try_from(published).ok() == Some(survey.clone()) );
+
+    let mut doubled = PublishedSurvey::from(survey.clone());
+    doubled.participants.push(doubled.participants[2].clone());
+    assert!( Survey::try_from(doubled).err() == Some(SurveyError::DuplicateParticipant) );
+    let json:String = serde_json::to_string(&survey).unwrap();
+    let entry:String = serde_json::to_string(&survey.participants().next().unwrap()).unwrap();
+    assert!( serde_json::from_str::<Survey>(&json.replacen(&entry, &format!("{},{}", entry, entry), 1)).is_err() );
+}
+
+// Batch verification accepts an honest survey and pinpoints tampered entries
+#[test]
+fn test_survey_verify_all() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let ids:Vec<Fr> = (0..5).map(|_| User::new().id()).collect();
+    let mut survey:Survey = sa.gen_survey(&ids, g, g2, &ra.vk).expect("SA survey creation failed!");
+    assert!( survey.verify_all(&sa.vk, &ra.vk, g2).is_empty() );
+
+    // Swap two participants' signatures
+    let (first, second) = (survey.entry_for(ids[1]).unwrap(), survey.entry_for(ids[3]).unwrap());
+    survey.insert(ParticipantEntry { id: ids[1], sigma_1: second.sigma_1, sigma_2: second.sigma_2 });
+    survey.insert(ParticipantEntry { id: ids[3], sigma_1: first.sigma_1, sigma_2: first.sigma_2 });
+    let mut failing = survey.verify_all(&sa.vk, &ra.vk, g2);
+    failing.sort_by_key(|id| fr_to_bytes(*id));
+    let mut expected = vec![ids[1], ids[3]];
+    expected.sort_by_key(|id| fr_to_bytes(*id));
+    assert!( failing == expected );
+}
+
+// A described survey's vid is bound to its title, nonce and SA
+#[test]
+fn test_described_survey() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let other_sa = SurveyAuthority::new(&params);
+    let ids:Vec<Fr> = vec![User::new().id(), User::new().id()];
+
+    let description = SurveyDescription::new("Course feedback, spring term");
+    let survey:Survey = sa.gen_described_survey(&description, &ids, g, g2, &ra.vk).expect("SA survey creation failed!");
+    assert!( description.describes(&survey, &sa.vk) && survey.verify_all(&sa.vk, &ra.vk, g2).is_empty() );
+    assert!( !description.describes(&survey, &other_sa.vk) );
+    assert!( !SurveyDescription::new("Course feedback, spring term").describes(&survey, &sa.vk) );
+    let mut renamed = description.clone();
+    renamed.title.push('!');
+    assert!( !renamed.describes(&survey, &sa.vk) );
+
+    // A description names one survey only, and a random survey matches no description
+    assert!( sa.gen_described_survey(&description, &ids, g, g2, &ra.vk) == Err(SurveyError::DuplicateSurvey) );
+    let random:Survey = sa.gen_survey(&ids, g, g2, &ra.vk).expect("SA survey creation failed!");
+    assert!( !description.describes(&random, &sa.vk) );
+}
+
+// A published survey is read back with the same participants
+#[test]
+#[cfg(feature = "serde")]
+fn test_survey_serde() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let survey:Survey = sa.gen_survey(&vec![User::new().id(), User::new().id()], g, g2, &ra.vk).expect("SA survey creation failed!");
+    let yaml = serde_yaml::to_string(&survey).expect("Could not serialize");
+    let received:Survey = serde_yaml::from_str(&yaml).expect("Could not deserialize");
+    assert!( received == survey );
+
+    let description = SurveyDescription::new("Course feedback");
+    let yaml = serde_yaml::to_string(&description).expect("Could not serialize");
+    assert!( serde_yaml::from_str::<SurveyDescription>(&yaml).expect("Could not deserialize") == description );
+    assert!( serde_yaml::from_str::<SurveyDescription>("title: x\nnonce: '00ff'").is_err() );
+}
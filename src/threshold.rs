@@ -0,0 +1,308 @@
+extern crate tbn;
+extern crate rand;
+extern crate tracing;
+
+use rand::{RngCore, CryptoRng};
+use tbn::{Group, Fr, G1, G2};
+use crate::gt::Gt;
+
+use crate::ct::gt_eq;
+use crate::users::{VerificationKey, RegistrationRequest, BlindedCredential};
+use crate::cost::{pairing, pow};
+use crate::secret::SecretScalar;
+use crate::proofs::{RegistrationStatement, verify_registration};
+use crate::params::SystemParams;
+use crate::hash::{id_hash, epoch_point};
+use crate::error::SurveyError;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/*
+ * ----------------------------------------------
+ * |    THRESHOLD RA                            |
+ * ----------------------------------------------
+ *
+ * A single RA holds sk_RA = x on its own, so whoever controls it can issue credentials at will. A
+ * threshold RA splits x into n Shamir shares x_i = f(i) of a random polynomial f of degree t - 1
+ * with f(0) = x, one per RaNode. The published vk_RA is unchanged (pk = e(g, g2)^x), so users,
+ * SAs and check_submission cannot tell a threshold RA from a single one.
+ *
+ * Every node checks the registration proof and answers on its own with a partial credential
+ * (g·x_i + M·r_i, g2·r_i, g·r_i) for M = u·id + S' + h + H(epoch), with its own randomness r_i.
+ * The credential is linear in (x, r), so any t partials combine with the Lagrange coefficients λ_i
+ * at 0 into a credential (g·x + M·r, g2·r, g·r) for r = Σ λ_i·r_i:
+ *
+ *      let (tra, nodes) = ThresholdRA::new(&params, 2, 3)?;   // hand one node to each operator
+ *      let (request, pending) = alice.request_registration(&tra.vk, g);
+ *      let partials = vec![nodes[0].register_request(&request)?, nodes[2].register_request(&request)?];
+ *      let wallet = pending.complete(&tra.combine(&request, &partials)?, &tra.vk, g2)?;
+ *
+ * ThresholdRA holds public values only (vk_RA and each node's e(g, g2)^x_i), so the user can run
+ * combine themselves; it checks every partial against its node's share and skips the ones that
+ * fail. The shares are dealt by ThresholdRA::new, which sees x once and drops it: the dealer must
+ * be trusted during setup. Each node keeps its own anonymity set and epoch. Revocation, key
+ * rotation and registration codes are only available on the single RegistrationAuthority.
+*/
+
+// Coordinator for a t-of-n RA: the joint key and the public part of every node's share
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ThresholdRA {
+    pub vk: VerificationKey,
+    // Number of partial credentials needed for a credential
+    pub threshold: usize,
+    // e(g, g2)^x_i of node i, at position i - 1
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::gt_vec"))]
+    share_keys: Vec<Gt>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g1"))]
+    g: G1,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g2"))]
+    g2: G2
+}
+
+// One of the n nodes of a threshold RA, holding the share x_i
+pub struct RaNode {
+    // i, from 1 to n
+    pub index: u32,
+    share: SecretScalar,
+    pub vk: VerificationKey,
+    g: G1,
+    g2: G2,
+    epoch: u64,
+    // Hashes of the ids this node has registered
+    id_hashes: Vec<Fr>
+}
+
+// A node's answer to a RegistrationRequest: its share of the blinded credential
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PartialCredential {
+    pub index: u32,
+    pub epoch: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g1"))]
+    pub sigma_1: G1,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g2"))]
+    pub sigma_2: G2,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g1"))]
+    pub sigma_3: G1
+}
+
+impl ThresholdRA {
+
+    /* Deal a fresh RA key to nodes nodes, any threshold of which can issue credentials */
+    pub fn new(params: &SystemParams, threshold: usize, nodes: usize) -> Result<(ThresholdRA, Vec<RaNode>), SurveyError> {
+        // crytpographiclaly secure thread-local rng
+        ThresholdRA::new_with_rng(params, threshold, nodes, &mut rand::thread_rng())
+    }
+
+    pub fn new_with_rng(params: &SystemParams, threshold: usize, nodes: usize, rng: &mut (impl RngCore + CryptoRng))
+                        -> Result<(ThresholdRA, Vec<RaNode>), SurveyError> {
+        if threshold == 0 || threshold > nodes || nodes > u32::MAX as usize {
+            return Err(SurveyError::InvalidThreshold);
+        }
+        let (g, g2):(G1, G2) = (*params).generators();
+
+        // f(X) = x + a_1·X + ... + a_{t-1}·X^{t-1}, so x = f(0) is never handed out
+        let coefficients:Vec<Fr> = (0..threshold).map(|_| Fr::random(rng)).collect();
+        let base:Gt = pairing(g, g2);
+        let vk = VerificationKey { u: G1::random(rng), v: G1::random(rng), h: G1::random(rng), pk: pow(base, coefficients[0]) };
+
+        let mut share_keys:Vec<Gt> = Vec::new();
+        let mut ra_nodes:Vec<RaNode> = Vec::new();
+        for index in 1..=nodes as u32 {
+            let share:Fr = evaluate(&coefficients, scalar(index));
+            share_keys.push(pow(base, share));
+            ra_nodes.push(RaNode { index, share: SecretScalar::new(share), vk: vk.clone(), g, g2, epoch: 0, id_hashes: Vec::new() });
+        }
+        Ok((ThresholdRA { vk, threshold, share_keys, g, g2 }, ra_nodes))
+    }
+
+    // Number of nodes the key was dealt to
+    pub fn nodes(&self) -> usize {
+        (*self).share_keys.len()
+    }
+
+    // Whether partial is node partial.index's share of a credential on request
+    // e(σ1_i, g2) = e(g, g2)^x_i · e(M, σ2_i) and e(σ3_i, g2) = e(g, σ2_i)
+    pub fn verify_partial(&self, request: &RegistrationRequest, partial: &PartialCredential) -> bool {
+        let share_key:Gt = match (partial.index as usize).checked_sub(1).and_then(|i| (*self).share_keys.get(i)) {
+            Some(share_key) => *share_key,
+            None => return false
+        };
+        let message:G1 = (*self).vk.u * request.id + request.commitment + (*self).vk.h + epoch_point(partial.epoch);
+        !partial.sigma_2.is_zero()
+            && gt_eq(pairing(partial.sigma_1, (*self).g2), share_key * pairing(message, partial.sigma_2))
+            && gt_eq(pairing(partial.sigma_3, (*self).g2), pairing((*self).g, partial.sigma_2))
+    }
+
+    /* Combine the partial credentials of at least threshold nodes into the blinded credential */
+    // Partials that do not verify, repeat a node, or are for another epoch than the first valid one
+    // are skipped. The result is completed with PendingRegistration::complete like the single RA's
+    pub fn combine(&self, request: &RegistrationRequest, partials: &[PartialCredential]) -> Result<BlindedCredential, SurveyError> {
+        let mut valid:Vec<&PartialCredential> = Vec::new();
+        for partial in partials {
+            if valid.len() == (*self).threshold {
+                break;
+            }
+            if valid.iter().any(|other| other.index == partial.index || other.epoch != partial.epoch) {
+                continue;
+            }
+            if (*self).verify_partial(request, partial) {
+                valid.push(partial);
+            }
+        }
+        if valid.len() < (*self).threshold {
+            tracing::warn!(valid = valid.len(), threshold = (*self).threshold, "cannot combine credential: too few valid shares");
+            return Err(SurveyError::InsufficientShares);
+        }
+
+        let indices:Vec<u32> = valid.iter().map(|partial| partial.index).collect();
+        let (mut sigma_1, mut sigma_2, mut sigma_3):(G1, G2, G1) = (G1::zero(), G2::zero(), G1::zero());
+        for partial in &valid {
+            let lambda:Fr = lagrange_at_zero(partial.index, &indices);
+            sigma_1 = sigma_1 + partial.sigma_1 * lambda;
+            sigma_2 = sigma_2 + partial.sigma_2 * lambda;
+            sigma_3 = sigma_3 + partial.sigma_3 * lambda;
+        }
+        Ok(BlindedCredential { epoch: valid[0].epoch, sigma_1, sigma_2, sigma_3 })
+    }
+}
+
+impl RaNode {
+
+    /* Check a registration request and answer with this node's partial credential */
+    pub fn register_request(&mut self, request: &RegistrationRequest) -> Result<PartialCredential, SurveyError> {
+        (*self).register_request_with_rng(request, &mut rand::thread_rng())
+    }
+
+    pub fn register_request_with_rng(&mut self, request: &RegistrationRequest, rng: &mut (impl RngCore + CryptoRng))
+                                     -> Result<PartialCredential, SurveyError> {
+        let statement = RegistrationStatement { id: request.id, commitment: request.commitment, v: (*self).vk.v, g: (*self).g };
+        if !verify_registration(&statement, &request.proof) {
+            return Err(SurveyError::InvalidProof);
+        }
+        // Like the RA, a node issues one partial credential per id
+        let id_hash:Fr = id_hash(request.id);
+        if (*self).id_hashes.contains(&id_hash) {
+            return Err(SurveyError::AlreadyRegistered);
+        }
+
+        let r:Fr = Fr::random(rng);
+        let message:G1 = (*self).vk.u * request.id + request.commitment + (*self).vk.h + epoch_point((*self).epoch);
+        let partial = PartialCredential {
+            index: (*self).index,
+            epoch: (*self).epoch,
+            sigma_1: (*self).g * (*self).share.expose() + message * r,
+            sigma_2: (*self).g2 * r,
+            sigma_3: (*self).g * r
+        };
+
+        (*self).id_hashes.push(id_hash);
+        Ok(partial)
+    }
+
+    pub fn epoch(&self) -> u64 {
+        (*self).epoch
+    }
+
+    // Nodes only combine partials for the same epoch, so every node has to advance together
+    pub fn advance_epoch(&mut self) -> u64 {
+        (*self).epoch += 1;
+        (*self).epoch
+    }
+
+    // Whether this node has registered the id with hash id_hash
+    pub fn in_anonymity_set(&self, id_hash: Fr) -> bool {
+        (*self).id_hashes.contains(&id_hash)
+    }
+}
+
+// f(X) for the coefficients of f, lowest first (Horner's rule)
+fn evaluate(coefficients: &[Fr], x: Fr) -> Fr {
+    coefficients.iter().rev().fold(Fr::zero(), |acc, coefficient| acc * x + *coefficient)
+}
+
+// λ_i = Π_{j ≠ i} j / (j - i), so that f(0) = Σ λ_i·f(i) over the given indices
+fn lagrange_at_zero(index: u32, indices: &[u32]) -> Fr {
+    let i:Fr = scalar(index);
+    let mut numerator:Fr = Fr::one();
+    let mut denominator:Fr = Fr::one();
+    for other in indices.iter().filter(|other| **other != index) {
+        let j:Fr = scalar(*other);
+        numerator = numerator * j;
+        denominator = denominator * (j - i);
+    }
+    // Indices are distinct, so the denominator is non-zero
+    numerator * denominator.inverse().expect("Indices are distinct")
+}
+
+fn scalar(n: u32) -> Fr {
+    Fr::from_str(&n.to_string()).expect("Small integers are in Fr")
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use crate::users::User;
+#[cfg(test)]
+use crate::submission::check_submission;
+#[cfg(test)]
+use crate::users::SurveyAuthority;
+
+// Any t nodes issue a credential that verifies under the joint key, fewer than t cannot
+#[test]
+fn test_threshold_registration() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    assert!( ThresholdRA::new(&params, 0, 3).is_err() && ThresholdRA::new(&params, 4, 3).is_err() );
+    let (tra, mut nodes) = ThresholdRA::new(&params, 2, 3).expect("Setup failed");
+    assert!( tra.nodes() == 3 );
+
+    let alice = User::new();
+    let (request, pending) = alice.request_registration(&tra.vk, g);
+    let partials:Vec<PartialCredential> = nodes.iter_mut().map(|node| node.register_request(&request).expect("Request was refused")).collect();
+    assert!( partials.iter().all(|partial| tra.verify_partial(&request, partial)) );
+    assert!( nodes.iter().all(|node| node.in_anonymity_set(id_hash(alice.id()))) );
+    assert!( nodes[0].register_request(&alice.request_registration(&tra.vk, g).0).err() == Some(SurveyError::AlreadyRegistered) );
+
+    // Every pair of nodes gives a credential on alice's (id, s)
+    for pair in &[[0, 1], [1, 2], [2, 0]] {
+        let chosen = vec![partials[pair[0]].clone(), partials[pair[1]].clone()];
+        let credential = tra.combine(&request, &chosen).expect("Partials were refused");
+        assert!( pending.complete(&credential, &tra.vk, g2).is_ok() );
+    }
+
+    // One node, the same node twice, or a tampered partial are not enough
+    assert!( tra.combine(&request, &partials[..1]) == Err(SurveyError::InsufficientShares) );
+    assert!( tra.combine(&request, &[partials[0].clone(), partials[0].clone()]) == Err(SurveyError::InsufficientShares) );
+    let mut tampered = partials[1].clone();
+    tampered.sigma_1 = tampered.sigma_1 + g;
+    assert!( !tra.verify_partial(&request, &tampered) );
+    assert!( tra.combine(&request, &[partials[0].clone(), tampered.clone()]) == Err(SurveyError::InsufficientShares) );
+    assert!( tra.combine(&request, &[partials[0].clone(), tampered, partials[2].clone()]).is_ok() );
+
+    // A partial for someone else's request does not count toward alice's
+    let (other, _) = User::new().request_registration(&tra.vk, g);
+    let foreign = nodes[1].register_request(&other).expect("Request was refused");
+    assert!( tra.combine(&request, &[partials[0].clone(), foreign]) == Err(SurveyError::InsufficientShares) );
+}
+
+// A threshold credential submits to surveys like one from a single RA
+#[test]
+fn test_threshold_submission() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let (tra, mut nodes) = ThresholdRA::new(&params, 3, 5).expect("Setup failed");
+    let mut sa = SurveyAuthority::new(&params);
+    let alice = User::new();
+    let (request, pending) = alice.request_registration(&tra.vk, g);
+    let partials:Vec<PartialCredential> = nodes[1..4].iter_mut().map(|node| node.register_request(&request).expect("Request was refused")).collect();
+    let alice = User::from_wallet(pending.complete(&tra.combine(&request, &partials).expect("Partials were refused"), &tra.vk, g2).expect("Credential was refused"));
+
+    let survey = sa.gen_survey(&vec![alice.id()], g, g2, &tra.vk).expect("Survey was refused");
+    let submission = alice.submit_survey(&survey, b"yes", (g, g2), &sa.vk, &tra.vk).expect("Submission failed");
+    assert!( check_submission(survey.vid, &submission, &sa.vk, &tra.vk, g, g2) );
+}
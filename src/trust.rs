@@ -0,0 +1,73 @@
+use crate::manifest::ParamsManifest;
+
+/*
+ * ----------------------------------------------
+ * |    TRUST GRAPH                             |
+ * ----------------------------------------------
+ *
+ * A deployment's authority topology is what its parameter manifest says: the RA that signed it,
+ * on which generators and curve, and the SA keys clients accept. export_graphviz draws that as a
+ * Graphviz DOT graph for review, one node per key (labelled with the start of its fingerprint):
+ *
+ *      anon_survey --manifest manifest.yaml params graph --ra ra.yaml | dot -Tsvg > trust.svg
+ *
+ * There are no sub-registrars, trustees or operator keys in this protocol, so the graph only ever
+ * has the RA at its root and SAs below it. The graph is drawn from the manifest as given; check it
+ * against the RA's key first (ParamsManifest::check), as the command above does.
+*/
+
+// Characters of a fingerprint shown in node labels
+const FINGERPRINT_CHARS:usize = 16;
+
+pub fn export_graphviz(manifest: &ParamsManifest) -> String {
+    let mut dot:String = String::from("digraph trust {\n    rankdir=TB;\n    node [shape=box];\n");
+    dot.push_str(&format!("    params [shape=note, label=\"{}\\nanon_survey {}\"];\n",
+                          escape(&(*manifest).curve), escape(&(*manifest).version)));
+    dot.push_str(&format!("    ra [label=\"RA\\n{}\"];\n", short(&(*manifest).ra)));
+    dot.push_str("    ra -> params [label=\"signs\"];\n");
+    for (i, sa) in (*manifest).sa.iter().enumerate() {
+        dot.push_str(&format!("    sa{} [label=\"SA\\n{}\"];\n", i, short(sa)));
+        dot.push_str(&format!("    ra -> sa{} [label=\"accepts\"];\n", i));
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+fn short(fingerprint: &str) -> String {
+    escape(&fingerprint.chars().take(FINGERPRINT_CHARS).collect::<String>())
+}
+
+// Quote-safe label text
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use crate::users::{SurveyAuthority, RegistrationAuthority};
+#[cfg(test)]
+use crate::params::SystemParams;
+#[cfg(test)]
+use crate::manifest::fingerprint;
+
+// Every accepted SA hangs off the RA, and labels cannot break out of their quotes
+#[test]
+fn test_export_graphviz() {
+    let params = SystemParams::random();
+    let ra = RegistrationAuthority::new(&params);
+    let (sa, other_sa):(SurveyAuthority, SurveyAuthority) = (SurveyAuthority::new(&params), SurveyAuthority::new(&params));
+    let mut manifest = ra.freeze_params(&[&sa.vk, &other_sa.vk]);
+
+    let dot = export_graphviz(&manifest);
+    assert!( dot.starts_with("digraph trust {") && dot.ends_with("}\n") );
+    assert!( dot.contains(&fingerprint(&ra.vk)[..FINGERPRINT_CHARS]) );
+    assert!( dot.contains(&fingerprint(&sa.vk)[..FINGERPRINT_CHARS]) && dot.contains(&fingerprint(&other_sa.vk)[..FINGERPRINT_CHARS]) );
+    assert!( dot.matches("[label=\"accepts\"]").count() == 2 );
+
+    manifest.version = String::from("1\"]; evil [label=\"");
+    assert!( export_graphviz(&manifest).contains("1\\\"]; evil [label=\\\"") );
+}
@@ -0,0 +1,233 @@
+extern crate tbn;
+extern crate rand;
+
+use tbn::{Group, Fr, G1, G2};
+
+use crate::users::{User, SurveyAuthority, RegistrationAuthority};
+use crate::survey::{Survey, ParticipantEntry};
+use crate::submission::{Submission, SubmissionInput, check_submission, verify_submission};
+use crate::params::SystemParams;
+use crate::authorized;
+
+/*
+ * ----------------------------------------------
+ * |    UNFORGEABILITY                          |
+ * ----------------------------------------------
+ *
+ * Executable statements of what the scheme promises against an adversary without secret keys.
+ * The adversary sees everything that is published -- system parameters, vk_RA, vk_SA, surveys with
+ * their participant signatures, honest submissions -- and may register users of its own, but
+ * never learns sk_RA, sk_SA, or an honest user's PRF key. Every strategy below must be refused by
+ * authorized (participant signatures) or check_submission / verify_submission (responses):
+ *      - random group elements in place of signatures, credentials, tokens and proofs
+ *      - recombining honest signatures and submissions across ids and surveys
+ *      - algebraic manipulation of honest (σ1, σ2) pairs and of submission components
+ *      - passing off a credential as one from a later epoch
+ *
+ * These are tests of the implementation, not a proof: they catch a check that was dropped or
+ * weakened, not a flaw in the underlying assumptions. Only compiled for tests.
+*/
+
+// Everything the adversary can see: two surveys over the same honest participants, and one
+// honest submission to the first
+struct Transcript {
+    g: G1,
+    g2: G2,
+    ra: RegistrationAuthority,
+    sa: SurveyAuthority,
+    alice: User,
+    bob: User,
+    first: Survey,
+    second: Survey,
+    submission: Submission
+}
+
+fn transcript() -> Transcript {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let (mut alice, mut bob) = (User::new(), User::new());
+    alice.reg_user(&mut ra).expect("Registration failed");
+    bob.reg_user(&mut ra).expect("Registration failed");
+    let first = sa.gen_survey(&vec![alice.id(), bob.id()], g, g2, &ra.vk).expect("Survey was refused");
+    let second = sa.gen_survey(&vec![alice.id(), bob.id()], g, g2, &ra.vk).expect("Survey was refused");
+    let submission = alice.submit_survey(&first, b"yes", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    assert!( check_submission(first.vid, &submission, &sa.vk, &ra.vk, g, g2) );
+    Transcript { g, g2, ra, sa, alice, bob, first, second, submission }
+}
+
+// Whether id would be authorized for survey vid with the forged signature (σ1, σ2)
+fn accepts(t: &Transcript, vid: Fr, id: Fr, sigma_1: G1, sigma_2: G2) -> bool {
+    let mut survey = Survey::new(vid);
+    survey.insert(ParticipantEntry { id, sigma_1, sigma_2 });
+    authorized(id, &survey, &t.sa.vk, &t.ra.vk, t.g2)
+}
+
+fn entry(survey: &Survey, user: &User) -> ParticipantEntry {
+    survey.entry_for(user.id()).expect("User is not a participant")
+}
+
+
+/*
+ * Unit tests
+ */
+
+// Random elements, or the identity, are never a participant signature
+#[test]
+fn test_forge_random_signature() {
+    let t = transcript();
+    let rng = &mut rand::thread_rng();
+    let mallory:Fr = Fr::random(rng);
+    for _ in 0..5 {
+        assert!( !accepts(&t, t.first.vid, mallory, G1::random(rng), G2::random(rng)) );
+    }
+    assert!( !accepts(&t, t.first.vid, mallory, G1::zero(), G2::zero()) );
+    assert!( !accepts(&t, t.first.vid, mallory, G1::zero(), G2::random(rng)) );
+    assert!( !authorized(mallory, &t.first, &t.sa.vk, &t.ra.vk, t.g2) );
+}
+
+// An honest signature is bound to its id and its survey
+#[test]
+fn test_forge_recombined_signature() {
+    let t = transcript();
+    let (alice, bob) = (entry(&t.first, &t.alice), entry(&t.first, &t.bob));
+    let mallory:Fr = Fr::random(&mut rand::thread_rng());
+    // Someone else's signature, or one from another survey
+    assert!( !accepts(&t, t.first.vid, mallory, alice.sigma_1, alice.sigma_2) );
+    assert!( !accepts(&t, t.first.vid, t.bob.id(), alice.sigma_1, alice.sigma_2) );
+    assert!( !accepts(&t, t.second.vid, t.alice.id(), alice.sigma_1, alice.sigma_2) );
+    // Halves of two signatures
+    assert!( !accepts(&t, t.first.vid, t.alice.id(), alice.sigma_1, bob.sigma_2) );
+    assert!( !accepts(&t, t.first.vid, t.alice.id(), entry(&t.second, &t.alice).sigma_1, alice.sigma_2) );
+}
+
+// Linear combinations and shifts of honest signatures are not signatures on anything new
+#[test]
+fn test_forge_algebraic_signature() {
+    let t = transcript();
+    let rng = &mut rand::thread_rng();
+    let (alice, bob) = (entry(&t.first, &t.alice), entry(&t.first, &t.bob));
+    let (k, mallory):(Fr, Fr) = (Fr::random(rng), Fr::random(rng));
+
+    // Sums and multiples
+    assert!( !accepts(&t, t.first.vid, t.alice.id(), alice.sigma_1 + bob.sigma_1, alice.sigma_2 + bob.sigma_2) );
+    assert!( !accepts(&t, t.first.vid, t.alice.id() + t.bob.id(), alice.sigma_1 + bob.sigma_1, alice.sigma_2 + bob.sigma_2) );
+    assert!( !accepts(&t, t.first.vid, t.alice.id(), alice.sigma_1 * k, alice.sigma_2 * k) );
+    // Moving a signature to another id or survey without knowing its randomness r
+    assert!( !accepts(&t, t.first.vid, mallory, alice.sigma_1 + t.sa.vk.v * ((mallory - t.alice.id()) * k), alice.sigma_2) );
+    assert!( !accepts(&t, t.second.vid, t.alice.id(), alice.sigma_1 + t.sa.vk.u * ((t.second.vid - t.first.vid) * k), alice.sigma_2) );
+    // Shifting only one half
+    assert!( !accepts(&t, t.first.vid, t.alice.id(), alice.sigma_1 + t.g * k, alice.sigma_2) );
+    assert!( !accepts(&t, t.first.vid, t.alice.id(), alice.sigma_1, alice.sigma_2 + t.g2 * k) );
+
+    // Honest re-randomization is not a forgery: the signature stays on (vid, alice)
+    let m:G1 = t.sa.vk.u * t.first.vid + t.sa.vk.v * t.alice.id() + t.ra.vk.h;
+    assert!( accepts(&t, t.first.vid, t.alice.id(), alice.sigma_1 + m * k, alice.sigma_2 + t.g2 * k) );
+}
+
+// Responses made of random elements never pass the check
+#[test]
+fn test_forge_random_submission() {
+    let t = transcript();
+    let rng = &mut rand::thread_rng();
+    let mut forged = t.submission.clone();
+    forged.token = G1::random(rng);
+    forged.cred_1 = G1::random(rng);
+    forged.cred_2 = G2::random(rng);
+    forged.sigma_1 = G1::random(rng);
+    forged.sigma_2 = G2::random(rng);
+    assert!( !check_submission(t.first.vid, &forged, &t.sa.vk, &t.ra.vk, t.g, t.g2) );
+    let mut forged = t.submission.clone();
+    forged.proof.c = Fr::random(rng);
+    forged.proof.z_s = Fr::random(rng);
+    assert!( !check_submission(t.first.vid, &forged, &t.sa.vk, &t.ra.vk, t.g, t.g2) );
+    assert!( !verify_submission(&forged.statement(&t.sa.vk, &t.ra.vk, t.g, t.g2), &forged.proof) );
+}
+
+// An honest submission cannot be moved to another survey, answer or token, or mixed with another
+#[test]
+fn test_forge_recombined_submission() {
+    let t = transcript();
+    let accepted = |s: &Submission, vid: Fr| check_submission(vid, s, &t.sa.vk, &t.ra.vk, t.g, t.g2);
+
+    let mut forged = t.submission.clone();
+    forged.vid = t.second.vid;
+    assert!( !accepted(&forged, t.second.vid) && !accepted(&t.submission, t.second.vid) );
+    let mut forged = t.submission.clone();
+    forged.message = b"no".to_vec();
+    assert!( !accepted(&forged, t.first.vid) );
+
+    // Bob's token on Alice's proof, or the proof of another honest submission
+    let other = t.bob.submit_survey(&t.first, b"yes", (t.g, t.g2), &t.sa.vk, &t.ra.vk).expect("Submission failed");
+    let mut forged = t.submission.clone();
+    forged.token = other.token;
+    assert!( !accepted(&forged, t.first.vid) );
+    let mut forged = t.submission.clone();
+    forged.proof = other.proof;
+    assert!( !accepted(&forged, t.first.vid) );
+    let mut forged = t.submission.clone();
+    forged.sigma_1 = other.sigma_1;
+    forged.sigma_2 = other.sigma_2;
+    assert!( !accepted(&forged, t.first.vid) );
+}
+
+// Scaling or shifting the parts of an honest submission breaks it
+#[test]
+fn test_forge_algebraic_submission() {
+    let t = transcript();
+    let k:Fr = Fr::random(&mut rand::thread_rng());
+    let accepted = |s: &Submission| check_submission(t.first.vid, s, &t.sa.vk, &t.ra.vk, t.g, t.g2);
+    let mut forged = t.submission.clone();
+    forged.token = forged.token * k;
+    assert!( !accepted(&forged) );
+    let mut forged = t.submission.clone();
+    forged.cred_1 = forged.cred_1 + t.g * k;
+    assert!( !accepted(&forged) );
+    let mut forged = t.submission.clone();
+    forged.sigma_2 = forged.sigma_2 * k;
+    assert!( !accepted(&forged) );
+    let mut forged = t.submission.clone();
+    forged.proof.z_id = forged.proof.z_id + k;
+    assert!( !accepted(&forged) );
+}
+
+// Honest protocol runs with the wrong secrets: a registered user who is not on the list borrows a
+// participant's signature, and a listed id submits with a credential the RA never issued
+#[test]
+fn test_forge_without_secrets() {
+    let t = transcript();
+    let rng = &mut rand::thread_rng();
+    let mut mallory = User::new();
+    let mut ra = t.ra;
+    mallory.reg_user(&mut ra).expect("Registration failed");
+    let alice = entry(&t.first, &t.alice);
+    let credential = mallory.credential.expect("Mallory is registered");
+    let input = SubmissionInput { vid: t.first.vid, message: b"yes", id: mallory.id(), s: mallory.prf_key.expose(), credential,
+                                  epoch: mallory.credential_epoch(), signature: (alice.sigma_1, alice.sigma_2) };
+    let borrowed = Submission::new(input, t.g, t.g2, &t.sa.vk, &ra.vk).expect("Token is degenerate");
+    assert!( !check_submission(t.first.vid, &borrowed, &t.sa.vk, &ra.vk, t.g, t.g2) );
+
+    let input = SubmissionInput { vid: t.first.vid, message: b"yes", id: t.alice.id(), s: Fr::random(rng), credential: (G1::random(rng), G2::random(rng)),
+                                  epoch: 0, signature: (alice.sigma_1, alice.sigma_2) };
+    let unregistered = Submission::new(input, t.g, t.g2, &t.sa.vk, &ra.vk).expect("Token is degenerate");
+    assert!( !check_submission(t.first.vid, &unregistered, &t.sa.vk, &ra.vk, t.g, t.g2) );
+}
+
+// A credential only counts for the epoch it was issued for, whatever epoch the submission claims
+#[test]
+fn test_forge_epoch() {
+    let mut t = transcript();
+    t.ra.advance_epoch();
+    let accepted = |s: &Submission| check_submission(t.first.vid, s, &t.sa.vk, &t.ra.vk, t.g, t.g2);
+    let mut forged = t.submission.clone();
+    forged.epoch = t.ra.epoch();
+    assert!( !accepted(&forged) );
+
+    let alice = entry(&t.first, &t.alice);
+    let credential = t.alice.credential.expect("Alice is registered");
+    let input = SubmissionInput { vid: t.first.vid, message: b"yes", id: t.alice.id(), s: t.alice.prf_key.expose(), credential,
+                                  epoch: t.ra.epoch(), signature: (alice.sigma_1, alice.sigma_2) };
+    let claimed = Submission::new(input, t.g, t.g2, &t.sa.vk, &t.ra.vk).expect("Token is degenerate");
+    assert!( !accepted(&claimed) );
+}
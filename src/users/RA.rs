@@ -1,8 +1,32 @@
 extern crate tbn;
 extern crate rand;
+extern crate hex;
+extern crate tracing;
 
-use tbn::{Group, Fr, G1, G2, Gt, pairing};
-use super::{VerificationKey};
+use std::time::{Duration, SystemTime};
+use rand::{Rng, RngCore, CryptoRng};
+use zeroize::Zeroizing;
+use tbn::{Fr, G1, G2};
+use super::{VerificationKey, KeyHolder};
+use crate::cost::{pairing, pow};
+use crate::proofs::{Proof, RegistrationStatement, verify_registration, sign_gt, push_bytes};
+use crate::error::SurveyError;
+use crate::keystore::{self, KeystoreKind, KeyReader, push_vk};
+use crate::snapshot::{self, SealedSnapshot, push_time, read_time};
+use crate::manifest::ParamsManifest;
+use crate::events::{Event, EventBus};
+use crate::params::SystemParams;
+use crate::secret::SecretScalar;
+use crate::seed::{Seed, RA_KEY_PATH};
+use crate::encoding::{fr_to_bytes, g1_to_bytes, g2_to_bytes};
+use crate::revocation::RevocationBatch;
+use crate::hash::{id_hash, epoch_point};
+use crate::policy::unix_time;
+use crate::logging::Redacted;
+use crate::audit::{AuditLog, AuditAction, AuditHead};
+use crate::storage::{Storage, MemoryStorage};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
 
 /*
  * ----------------------------------------------
@@ -15,52 +39,675 @@ use super::{VerificationKey};
  *          + Issue master user token to allow users to participate in surveys
  *          + Generate signature key-pair that allows them to sign and others to verify values
  *          + Specify a list of user IDs (authenticated by the RA) to send the survey to
+ *      - Keep the anonymity set as id hashes only (see hash.rs), answering membership queries and
+ *        exporting the set for audits on request
+ *      - Mint one-time registration codes for users without a device at hand
+ *      - Issue credentials for the current epoch, and renew them when a new epoch starts
+ *      - Rotate its signing key (e.g. after a leak), publishing the old key next to the new one for a
+ *        grace window in which users migrate their credentials (see rotate_keys)
+ *      - Export or erase what it stores about a user, on that user's request
+ *      - Revoke users (e.g. departed or compromised), keeping a revocation list and signing each
+ *        revocation batch for the SAs
+ *      - Keep its registrations, anonymity set and revocation list in a Storage (see storage/mod.rs)
+ *      - Keep a hash-chained audit log of registrations, re-identifications, revocations and
+ *        erasures (see audit.rs)
+ *      - Snapshot its full state into one authenticated blob, and be restored from it (see
+ *        snapshot.rs)
 */
 
-pub struct RegistrationAuthority {
+// Number of random bytes in a printable registration code
+const REG_CODE_BYTES:usize = 10;
+
+// One-time code the RA hands out (e.g. on paper) that a user later redeems from any client to
+// complete registration
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RegistrationCode {
+    // Printable code, e.g. 3F9A-1C2B-77D0-E415-08AB
+    pub code: String,
+    // Limited metadata bound to the code (e.g. cohort or department), returned on redemption
+    pub metadata: String,
+    // The code can no longer be redeemed after this point
+    pub expires: SystemTime
+}
+
+// RegUser request a user sends when the RA runs elsewhere: the id, the commitment S' = v·s + g·b
+// to the PRF key, and the proof of knowledge of (s, b)
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RegistrationRequest {
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::fr"))]
+    pub id: Fr,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g1"))]
+    pub commitment: G1,
+    pub proof: Proof
+}
+
+// The RA's answer to a RegistrationRequest: the credential (σ1', σ2, σ3) for epoch, still blinded by b
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BlindedCredential {
+    pub epoch: u64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g1"))]
+    pub sigma_1: G1,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g2"))]
+    pub sigma_2: G2,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g1"))]
+    pub sigma_3: G1
+}
+
+// What the RA keeps about each registration
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RegistrationRecord {
+    // H(id) of the registered id
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::fr"))]
+    pub id_hash: Fr,
+    // Hiding commitment S' = v·s + g·b the credential was issued on (known to the user as well)
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g1"))]
+    pub commitment: G1,
+    pub registered: SystemTime,
+    // Metadata of the registration code that was redeemed, if any
+    pub code_metadata: Option<String>
+}
+
+// Data about a user that the RA can neither export nor erase, because it never holds it in a form
+// linked to the user, or (the audit log) cannot change it without breaking the hash chain
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum NotHeld {
+    // Submissions only carry a one-time token derived from s, which the RA never learns
+    AnonymousSubmissions,
+    // The user unblinds the credential, so the RA cannot recognize (or revoke) it when it is used
+    IssuedCredential,
+    // Copies of the id already published by SAs in participant lists are outside the RA's control
+    PublishedParticipantLists,
+    // Audit log entries name the registration by its commitment S' (never the id), and stay after an
+    // erasure, which is itself logged (see audit.rs)
+    AuditLogEntries,
+    // An erased id's hash stays on the revocation list (without its commitment), so the id cannot
+    // register again
+    RevokedIdHash
+}
+
+pub const NOT_HELD:[NotHeld; 4] = [NotHeld::AnonymousSubmissions, NotHeld::IssuedCredential, NotHeld::PublishedParticipantLists,
+                                   NotHeld::AuditLogEntries];
+
+// Everything the RA stores about one user
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DataExport {
+    pub record: RegistrationRecord,
+    // Whether the id is still part of the anonymity set (i.e. can be put on participant lists)
+    pub in_anonymity_set: bool,
+    pub not_included: Vec<NotHeld>
+}
+
+// What an erasure removed, and what it could not
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ErasureReport {
+    pub erased: RegistrationRecord,
+    pub not_erased: Vec<NotHeld>
+}
+
+pub struct RegistrationAuthority<S: Storage = MemoryStorage> {
+    // Generators (g, g2) the RA's keys and credentials are built from
+    pub g: G1,
+    pub g2: G2,
     pub vk: VerificationKey,
-    sk: Fr,
-    // A list of users for the anonymous survey system. Essentially an anonymity set
-    pub userid_list: Vec<Fr>
+    sk: SecretScalar,
+    // Credentials are issued for this epoch (see advance_epoch)
+    epoch: u64,
+    // Keys replaced by rotate_keys, as (vk, end of the grace window in seconds since the Unix epoch)
+    retired: Vec<(VerificationKey, u64)>,
+    // The anonymity set, as hashes of the registered ids
+    id_hashes: Vec<Fr>,
+    // Hashes of the revoked ids, which are never registered again
+    revoked: Vec<Fr>,
+    // Registration codes that were minted but not yet redeemed
+    pending_codes: Vec<RegistrationCode>,
+    // One record per registration, for data export and erasure requests
+    registrations: Vec<RegistrationRecord>,
+    // Every registration, re-identification and revocation, by commitment
+    audit: AuditLog,
+    // Published to on every registration
+    pub events: EventBus,
+    // Where registrations, the anonymity set and revocations are written through to
+    storage: S
+}
+
+impl<S: Storage> KeyHolder for RegistrationAuthority<S> {
+
+    fn vk(&self) -> &VerificationKey {
+        &(*self).vk
+    }
+
+    fn sign_body(&self, domain: &[u8], g: G1, g2: G2, body: &[u8]) -> Proof {
+        sign_gt(domain, g, g2, (*self).vk.pk, (*self).sk.expose(), body)
+    }
 }
 
 impl RegistrationAuthority {
     
-    /* Create Registration Authority */
-    pub fn new(g:G1, g2:G2) -> Self {
+    /* Create Registration Authority on the deployment's system parameters */
+    pub fn new(params: &SystemParams) -> Self {
+        // crytpographiclaly secure thread-local rng
+        RegistrationAuthority::new_with_rng(params, &mut rand::thread_rng())
+    }
+
+    pub fn new_with_rng(params: &SystemParams, rng: &mut (impl RngCore + CryptoRng)) -> Self {
+
+        let (g, g2):(G1, G2) = (*params).generators();
 
         // TODO: Call user initialization
         
         // Generate parameters for RA
-        let (vk, x) =  Self::gen_RA(g, g2);
+        let (vk, x) = Self::gen_keys(g, g2, rng);
 
-        let userid_list:Vec<Fr> = Vec::new();
         // Return user with verification and signing key for registering users
-        RegistrationAuthority {vk, sk: x, userid_list}
+        RegistrationAuthority {g, g2, vk, sk: SecretScalar::new(x), epoch: 0, retired: Vec::new(), id_hashes: Vec::new(), revoked: Vec::new(), pending_codes: Vec::new(), registrations: Vec::new(), audit: AuditLog::new(), events: EventBus::new(), storage: MemoryStorage::new()}
+    }
+
+    /* Regenerate the RA's initial key pair from seed (see seed.rs) */
+    // Only the keys come from the seed: the RA starts out at epoch 0 with an empty anonymity set
+    pub fn from_seed(params: &SystemParams, seed: &Seed) -> Self {
+        let (g, g2):(G1, G2) = (*params).generators();
+        let (vk, x) = (*seed).derive_keys(RA_KEY_PATH, b"x", g, g2);
+        RegistrationAuthority {g, g2, vk, sk: SecretScalar::new(x), epoch: 0, retired: Vec::new(), id_hashes: Vec::new(), revoked: Vec::new(), pending_codes: Vec::new(), registrations: Vec::new(), audit: AuditLog::new(), events: EventBus::new(), storage: MemoryStorage::new()}
+    }
+
+    /* Restore an RA from a keystore written by export_keys */
+    // Credentials issued before the export stay valid, and so do retired keys still in their grace
+    // window. The anonymity set, revocation list, registration records and pending codes are not
+    // part of the keystore and start out empty
+    pub fn from_keys(keystore: &[u8], passphrase: &str) -> Result<Self, SurveyError> {
+        let plaintext = keystore::open(KeystoreKind::RegistrationAuthority, keystore, passphrase)?;
+        let mut reader = KeyReader::new(&plaintext);
+        let (g, g2, vk, sk, epoch) = (reader.g1()?, reader.g2()?, reader.vk()?, reader.fr()?, reader.u64()?);
+        let mut retired:Vec<(VerificationKey, u64)> = Vec::new();
+        for _ in 0..reader.u64()? {
+            retired.push((reader.vk()?, reader.u64()?));
+        }
+        reader.finish()?;
+        Ok(RegistrationAuthority {g, g2, vk, sk: SecretScalar::new(sk), epoch, retired, id_hashes: Vec::new(), revoked: Vec::new(), pending_codes: Vec::new(), registrations: Vec::new(), audit: AuditLog::new(), events: EventBus::new(), storage: MemoryStorage::new()})
+    }
+
+    /* Restore an RA from a snapshot written by snapshot, with the passphrase its keys were sealed under */
+    // Gives back the keys, epoch, anonymity set, revocation list, registration records, pending
+    // codes and audit log as they were at the snapshot, in memory (move them with with_storage).
+    // Fails with InvalidKeystore for a wrong passphrase, and InvalidSnapshot if the snapshot is of
+    // another version or was altered
+    pub fn restore(snapshot: &[u8], passphrase: &str) -> Result<Self, SurveyError> {
+        let sealed = SealedSnapshot::parse(KeystoreKind::RegistrationAuthority, snapshot)?;
+        let mut ra = RegistrationAuthority::from_keys(sealed.keys, passphrase)?;
+        let state:&[u8] = sealed.open(&fr_to_bytes(ra.sk.expose()))?;
+        ra.read_state(state).map_err(|_| SurveyError::InvalidSnapshot)?;
+        tracing::info!(registrations = ra.registrations.len(), epoch = ra.epoch, "RA restored from a snapshot");
+        Ok(ra)
+    }
+
+    // Take on the state written by write_state
+    fn read_state(&mut self, state: &[u8]) -> Result<(), SurveyError> {
+        let mut reader = KeyReader::new(state);
+        for _ in 0..reader.u64()? {
+            (*self).id_hashes.push(reader.fr()?);
+        }
+        for _ in 0..reader.u64()? {
+            (*self).revoked.push(reader.fr()?);
+        }
+        for _ in 0..reader.u64()? {
+            let code:String = String::from_utf8(reader.bytes()?.to_vec()).map_err(|_| SurveyError::InvalidSnapshot)?;
+            let metadata:String = String::from_utf8(reader.bytes()?.to_vec()).map_err(|_| SurveyError::InvalidSnapshot)?;
+            (*self).pending_codes.push(RegistrationCode { code, metadata, expires: read_time(&mut reader)? });
+        }
+        for _ in 0..reader.u64()? {
+            let (id_hash, commitment, registered) = (reader.fr()?, reader.g1()?, read_time(&mut reader)?);
+            let code_metadata:Option<String> = match reader.bytes()? {
+                [] => None,
+                [_, metadata @ ..] => Some(String::from_utf8(metadata.to_vec()).map_err(|_| SurveyError::InvalidSnapshot)?)
+            };
+            (*self).registrations.push(RegistrationRecord { id_hash, commitment, registered, code_metadata });
+        }
+        (*self).audit = AuditLog::from_transcript(reader.bytes()?)?;
+        reader.finish()
+    }
+
+}
+
+impl<S: Storage> RegistrationAuthority<S> {
+
+    /* Move the RA onto storage, e.g. after restoring it with from_keys (see storage/mod.rs) */
+    // The registrations, anonymity set and revocations the RA holds are written to storage first,
+    // then the RA takes on everything storage holds
+    pub fn with_storage<T: Storage>(self, mut storage: T) -> Result<RegistrationAuthority<T>, SurveyError> {
+        for record in &self.registrations {
+            storage.put_user(record)?;
+        }
+        for id_hash in &self.id_hashes {
+            storage.add_member(*id_hash)?;
+        }
+        for id_hash in &self.revoked {
+            storage.put_revocation(*id_hash)?;
+        }
+        let RegistrationAuthority { g, g2, vk, sk, epoch, retired, pending_codes, audit, events, .. } = self;
+        Ok(RegistrationAuthority { g, g2, vk, sk, epoch, retired, id_hashes: storage.members()?, revoked: storage.revocations()?,
+                                   pending_codes, registrations: storage.users()?, audit, events, storage })
+    }
+
+    /* The storage the RA writes through to, e.g. to hand it to the RA's next process */
+    pub fn into_storage(self) -> S {
+        self.storage
+    }
+
+    /* Export the generators, keys, current epoch and retired keys, encrypted under passphrase */
+    pub fn export_keys(&self, passphrase: &str) -> Vec<u8> {
+        // crytpographiclaly secure thread-local rng
+        (*self).export_keys_with_rng(passphrase, &mut rand::thread_rng())
     }
 
-    /* Generate public and private keys for registration authority */
-    #[allow(non_snake_case)]
-    fn gen_RA(g:G1, g2:G2) -> (VerificationKey, Fr) {
+    pub fn export_keys_with_rng(&self, passphrase: &str, rng: &mut (impl RngCore + CryptoRng)) -> Vec<u8> {
+        let mut plaintext:Zeroizing<Vec<u8>> = Zeroizing::new([g1_to_bytes((*self).g), g2_to_bytes((*self).g2)].concat());
+        push_vk(&mut plaintext, &(*self).vk);
+        plaintext.extend(fr_to_bytes((*self).sk.expose()));
+        plaintext.extend_from_slice(&(*self).epoch.to_be_bytes());
+        plaintext.extend_from_slice(&((*self).retired.len() as u64).to_be_bytes());
+        for (vk, until) in &(*self).retired {
+            push_vk(&mut plaintext, vk);
+            plaintext.extend_from_slice(&until.to_be_bytes());
+        }
+        keystore::seal(KeystoreKind::RegistrationAuthority, &plaintext, passphrase, rng)
+    }
 
+    /* Snapshot of the RA's full state, to back it up, migrate or clone it (see snapshot.rs) */
+    // The keys are sealed under passphrase as export_keys seals them, the rest is in the clear,
+    // and the whole snapshot is authenticated under the RA's secret key. Subscribers to events
+    // and the storage itself are not part of it
+    pub fn snapshot(&self, passphrase: &str) -> Vec<u8> {
         // crytpographiclaly secure thread-local rng
-        let rng = &mut rand::thread_rng();
+        (*self).snapshot_with_rng(passphrase, &mut rand::thread_rng())
+    }
 
-        // Generate random u,v,h in G_1
-        let u:G1 = G1::random(rng);
-        let v:G1 = G1::random(rng);
-        let h:G1 = G1::random(rng);
+    pub fn snapshot_with_rng(&self, passphrase: &str, rng: &mut (impl RngCore + CryptoRng)) -> Vec<u8> {
+        let keys:Vec<u8> = (*self).export_keys_with_rng(passphrase, rng);
+        snapshot::seal(KeystoreKind::RegistrationAuthority, &keys, &(*self).write_state(), &fr_to_bytes((*self).sk.expose()))
+    }
+
+    // Anonymity set ‖ revocation list ‖ pending codes ‖ registration records ‖ audit transcript,
+    // each list prefixed with its length
+    fn write_state(&self) -> Vec<u8> {
+        let mut state:Vec<u8> = ((*self).id_hashes.len() as u64).to_be_bytes().to_vec();
+        for id_hash in &(*self).id_hashes {
+            state.extend(fr_to_bytes(*id_hash));
+        }
+        state.extend_from_slice(&((*self).revoked.len() as u64).to_be_bytes());
+        for id_hash in &(*self).revoked {
+            state.extend(fr_to_bytes(*id_hash));
+        }
+        state.extend_from_slice(&((*self).pending_codes.len() as u64).to_be_bytes());
+        for code in &(*self).pending_codes {
+            push_bytes(&mut state, code.code.as_bytes());
+            push_bytes(&mut state, code.metadata.as_bytes());
+            push_time(&mut state, code.expires);
+        }
+        state.extend_from_slice(&((*self).registrations.len() as u64).to_be_bytes());
+        for record in &(*self).registrations {
+            state.extend(fr_to_bytes(record.id_hash));
+            state.extend(g1_to_bytes(record.commitment));
+            push_time(&mut state, record.registered);
+            // Empty for no metadata, and 0 ‖ metadata otherwise
+            match &record.code_metadata {
+                None => push_bytes(&mut state, &[]),
+                Some(metadata) => push_bytes(&mut state, &[&[0u8][..], metadata.as_bytes()].concat())
+            }
+        }
+        push_bytes(&mut state, &(*self).audit.transcript());
+        state
+    }
 
-        // Generate secret x as element of cyclic group with order r (q, in ANONIZE's notation)
+    /* Replace the signing key, keeping the old verification key published for grace_period */
+    // Only x (and so pk_RA) changes: u, v and h stay, so registration commitments, participant
+    // signatures (which use h_RA) and the surveys built on them stay valid. Credentials issued under
+    // the old key keep verifying under it until the grace window ends, and their holders migrate
+    // with renew_credential (or reissue_batch for requests received from other processes).
+    // Manifests frozen before the rotation name the old key, so freeze_params again afterwards
+    pub fn rotate_keys(&mut self, grace_period: Duration) -> &VerificationKey {
+        // crytpographiclaly secure thread-local rng
+        (*self).rotate_keys_with_rng(grace_period, &mut rand::thread_rng())
+    }
+
+    pub fn rotate_keys_with_rng(&mut self, grace_period: Duration, rng: &mut (impl RngCore + CryptoRng)) -> &VerificationKey {
         let x:Fr = Fr::random(rng);
+        let vk = VerificationKey { u: (*self).vk.u, v: (*self).vk.v, h: (*self).vk.h, pk: pow(pairing((*self).g, (*self).g2), x) };
+        let old = std::mem::replace(&mut (*self).vk, vk);
+        (*self).retired.push((old, unix_time(SystemTime::now() + grace_period)));
+        (*self).sk = SecretScalar::new(x);
+        tracing::info!(grace_secs = grace_period.as_secs(), retired = (*self).retired.len(), "RA rotated its signing key");
+        &(*self).vk
+    }
+
+    /* Verification keys credentials are currently accepted under: the current key first, then
+     * retired keys whose grace window has not ended */
+    pub fn verification_keys(&self) -> Vec<&VerificationKey> {
+        let now:u64 = unix_time(SystemTime::now());
+        let retired = (*self).retired.iter().rev().filter(|(_, until)| now < *until).map(|(vk, _)| vk);
+        std::iter::once(&(*self).vk).chain(retired).collect()
+    }
+
+    /* Freeze the parameters clients should accept into a manifest signed with sk_RA */
+    pub fn freeze_params(&self, sa_keys: &[&VerificationKey]) -> ParamsManifest {
+        ParamsManifest::freeze((*self).g, (*self).g2, self, sa_keys)
+    }
+
+    /* Epoch that credentials are currently issued for */
+    pub fn epoch(&self) -> u64 {
+        (*self).epoch
+    }
+
+    /* Start a new epoch, returning its number */
+    // Credentials from earlier epochs stay valid until verifiers stop accepting them (see
+    // SubmissionLedger::set_min_epoch), so users have until then to renew
+    pub fn advance_epoch(&mut self) -> u64 {
+        (*self).epoch += 1;
+        tracing::info!(epoch = (*self).epoch, "RA advanced to a new epoch");
+        (*self).epoch
+    }
+
+    /* Register a user id, blindly issuing a master credential on (id, s) for the current epoch */
+    // The user only hands over a hiding commitment S' = v·s + g·b to their PRF key s, so the RA
+    // never learns s and cannot recompute the user's survey tokens. The user proves knowledge of
+    // (s, b) (bound to id), and is refused if that proof does not verify. Otherwise returns the
+    // blinded credential (σ1', σ2, σ3) with σ1' = g·x + (u·id + S' + h + H(epoch))·r, σ2 = g2·r,
+    // σ3 = g·r, from which only the user can remove the blinding: σ1 = σ1' - σ3·b
+    pub fn reg_user(&mut self, id: Fr, commitment: G1, proof: &Proof) -> Result<(G1, G2, G1), SurveyError> {
+        (*self).reg_user_with_rng(id, commitment, proof, &mut rand::thread_rng())
+    }
+
+    pub fn reg_user_with_rng(&mut self, id: Fr, commitment: G1, proof: &Proof, rng: &mut (impl RngCore + CryptoRng))
+                             -> Result<(G1, G2, G1), SurveyError> {
+        (*self).register(id, commitment, proof, None, rng)
+    }
+
+    // Same as reg_user, for a request received from another process
+    pub fn register_request(&mut self, request: &RegistrationRequest) -> Result<BlindedCredential, SurveyError> {
+        (*self).register_request_with_rng(request, &mut rand::thread_rng())
+    }
+
+    pub fn register_request_with_rng(&mut self, request: &RegistrationRequest, rng: &mut (impl RngCore + CryptoRng))
+                                     -> Result<BlindedCredential, SurveyError> {
+        let (sigma_1, sigma_2, sigma_3) = (*self).register((*request).id, (*request).commitment, &(*request).proof, None, rng)?;
+        Ok(BlindedCredential { epoch: (*self).epoch, sigma_1, sigma_2, sigma_3 })
+    }
+
+    /* Renew the credential of a registered id for the current epoch */
+    // The user proves knowledge of (s, b) behind the commitment S' they registered with, so only
+    // the original registrant can renew, and the renewed credential is on the same (id, s). Revoked
+    // and erased registrations cannot be renewed. Returns the blinded credential as reg_user does
+    pub fn renew_credential(&mut self, id: Fr, proof: &Proof) -> Result<(G1, G2, G1), SurveyError> {
+        (*self).renew_credential_with_rng(id, proof, &mut rand::thread_rng())
+    }
+
+    pub fn renew_credential_with_rng(&mut self, id: Fr, proof: &Proof, rng: &mut (impl RngCore + CryptoRng))
+                                     -> Result<(G1, G2, G1), SurveyError> {
+        let id_hash:Fr = id_hash(id);
+        if (*self).revoked.contains(&id_hash) {
+            return Err(SurveyError::RevokedUser);
+        }
+        let commitment:G1 = match (*self).registrations.iter().rev().find(|record| record.id_hash == id_hash) {
+            Some(record) if (*self).id_hashes.contains(&id_hash) => record.commitment,
+            _ => return Err(SurveyError::UnregisteredUser)
+        };
+        let statement = RegistrationStatement { id, commitment, v: (*self).vk.v, g: (*self).g };
+        if !verify_registration(&statement, proof) {
+            return Err(SurveyError::InvalidProof);
+        }
+        Ok((*self).issue(id, commitment, rng))
+    }
+
+    /* Reissue credentials under the current key and epoch for a batch of renewal requests */
+    // Each request is handled as renew_credential would, independently of the others, e.g. to
+    // migrate every user after rotate_keys. Requests come from User::renewal_request
+    pub fn reissue_batch(&mut self, requests: &[RegistrationRequest]) -> Vec<Result<BlindedCredential, SurveyError>> {
+        (*self).reissue_batch_with_rng(requests, &mut rand::thread_rng())
+    }
+
+    pub fn reissue_batch_with_rng(&mut self, requests: &[RegistrationRequest], rng: &mut (impl RngCore + CryptoRng))
+                                  -> Vec<Result<BlindedCredential, SurveyError>> {
+        let epoch:u64 = (*self).epoch;
+        requests.iter().map(|request| {
+            let (sigma_1, sigma_2, sigma_3) = (*self).renew_credential_with_rng(request.id, &request.proof, rng)?;
+            Ok(BlindedCredential { epoch, sigma_1, sigma_2, sigma_3 })
+        }).collect()
+    }
+
+    fn register(&mut self, id: Fr, commitment: G1, proof: &Proof, code_metadata: Option<String>,
+                rng: &mut (impl RngCore + CryptoRng)) -> Result<(G1, G2, G1), SurveyError> {
+        let _span = tracing::debug_span!("register", epoch = (*self).epoch, commitment = %Redacted(commitment)).entered();
+
+        // Check the user's proof of knowledge of (s, b) behind S' = v·s + g·b
+        let statement = RegistrationStatement { id, commitment, v: (*self).vk.v, g: (*self).g };
+        if !verify_registration(&statement, proof) {
+            tracing::warn!("refused registration: proof of knowledge does not verify");
+            return Err(SurveyError::InvalidProof);
+        }
+
+        let id_hash:Fr = id_hash(id);
+        if (*self).revoked.contains(&id_hash) {
+            tracing::warn!(id_hash = %Redacted(id_hash), "refused registration: id was revoked");
+            return Err(SurveyError::RevokedUser);
+        }
+        // A second credential on the same id would come with a second PRF key, and so a second
+        // token for every survey the id is on
+        if (*self).id_hashes.contains(&id_hash) || (*self).registrations.iter().any(|record| record.id_hash == id_hash) {
+            tracing::warn!(id_hash = %Redacted(id_hash), "refused registration: id already registered");
+            return Err(SurveyError::AlreadyRegistered);
+        }
+
+        let credential = (*self).issue(id, commitment, rng);
+        tracing::debug!(id_hash = %Redacted(id_hash), anonymity_set = (*self).id_hashes.len() + 1, "issued credential");
+
+        // Add id to the anonymity set, keeping only its hash
+        let record = RegistrationRecord { id_hash, commitment, registered: SystemTime::now(), code_metadata };
+        (*self).storage.put_user(&record)?;
+        (*self).storage.add_member(id_hash)?;
+        (*self).id_hashes.push(id_hash);
+        (*self).registrations.push(record);
+        (*self).audit.append(AuditAction::Registration, commitment);
+        (*self).events.publish(Event::UserRegistered { id_hash });
+
+        return Ok(credential);
+    }
+
+    // Same Boneh-Boyen style signature the SA uses for surveys, here over (id, s) and the epoch
+    fn issue(&self, id: Fr, commitment: G1, rng: &mut (impl RngCore + CryptoRng)) -> (G1, G2, G1) {
+        let r:Fr = Fr::random(rng);
+        let cred_1:G1 = (*self).g * (*self).sk.expose() + ((*self).vk.u * id + commitment + (*self).vk.h + epoch_point((*self).epoch)) * r;
+        let cred_2:G2 = (*self).g2 * r;
+        let cred_3:G1 = (*self).g * r;
+        (cred_1, cred_2, cred_3)
+    }
+
+    /* Mint a one-time registration code that expires after valid_for */
+    pub fn issue_registration_code(&mut self, metadata: &str, valid_for: Duration) -> RegistrationCode {
+        // crytpographiclaly secure thread-local rng
+        (*self).issue_registration_code_with_rng(metadata, valid_for, &mut rand::thread_rng())
+    }
+
+    pub fn issue_registration_code_with_rng(&mut self, metadata: &str, valid_for: Duration, rng: &mut (impl RngCore + CryptoRng))
+                                            -> RegistrationCode {
+
+        // Group the hex encoding in blocks of 4 so the code is easy to copy by hand
+        let bytes:[u8; REG_CODE_BYTES] = rng.gen();
+        let hex_code:String = hex::encode_upper(bytes);
+        let blocks:Vec<&str> = (0..hex_code.len()).step_by(4).map(|i| &hex_code[i..i+4]).collect();
+
+        let reg_code = RegistrationCode {
+            code: blocks.join("-"),
+            metadata: String::from(metadata),
+            expires: SystemTime::now() + valid_for
+        };
+        (*self).pending_codes.push(reg_code.clone());
+        return reg_code;
+    }
+
+    /* Redeem a registration code to register (id, S'), returning the code's metadata and the blinded credential */
+    // Each code can only be redeemed once, and never after it has expired. A code is not used up by
+    // a registration proof that does not verify
+    pub fn redeem_registration_code(&mut self, code: &str, id: Fr, commitment: G1, proof: &Proof) -> Result<(String, (G1, G2, G1)), SurveyError> {
+        (*self).redeem_registration_code_with_rng(code, id, commitment, proof, &mut rand::thread_rng())
+    }
+
+    pub fn redeem_registration_code_with_rng(&mut self, code: &str, id: Fr, commitment: G1, proof: &Proof, rng: &mut (impl RngCore + CryptoRng))
+                                             -> Result<(String, (G1, G2, G1)), SurveyError> {
+
+        // Forget about codes that can no longer be redeemed
+        let now = SystemTime::now();
+        (*self).pending_codes.retain(|reg_code| now <= reg_code.expires);
 
-        // Compute e(g, g2)^x
-        let pair:Gt = pairing(g, g2).pow(x);
+        // Accept codes typed without dashes or in lowercase as well
+        let normalize = |c: &str| -> String { c.chars().filter(|ch| *ch != '-').collect::<String>().to_uppercase() };
+        let typed_code = normalize(code);
+        let opt_index = (*self).pending_codes.iter().position(|reg_code| normalize(&reg_code.code) == typed_code);
+        match opt_index {
+            Some(index) => {
+                let metadata = (*self).pending_codes[index].metadata.clone();
+                let credential = (*self).register(id, commitment, proof, Some(metadata.clone()), rng)?;
+                (*self).pending_codes.remove(index);
+                Ok((metadata, credential))
+            },
+            None => Err(SurveyError::InvalidRegistrationCode)
+        }
+    }
+
+    /* Export everything stored about the user who registered with the given commitment */
+    // The commitment is supplied by the user, so only they (or whoever they hand it to) can ask
+    pub fn export_user_data(&self, commitment: G1) -> Option<DataExport> {
+        let record = (*self).registrations.iter().find(|record| record.commitment == commitment)?;
+        Some(DataExport {
+            record: record.clone(),
+            in_anonymity_set: (*self).id_hashes.contains(&record.id_hash),
+            not_included: NOT_HELD.to_vec()
+        })
+    }
 
-        let vk = VerificationKey { u, v, h, pk: pair };
+    /* Erase the registration made with the given commitment, removing its id from the anonymity set */
+    // Credentials already issued stay cryptographically valid (see NotHeld), so the id hash goes on
+    // the revocation list: the id stays spent and SAs leave it off new surveys. Fails with
+    // UnregisteredUser if nothing was registered with the commitment
+    pub fn erase_user_data(&mut self, commitment: G1) -> Result<ErasureReport, SurveyError> {
+        let index = (*self).registrations.iter().position(|record| record.commitment == commitment).ok_or(SurveyError::UnregisteredUser)?;
+        let id_hash:Fr = (*self).registrations[index].id_hash;
+        (*self).storage.put_revocation(id_hash)?;
+        (*self).storage.remove_user(commitment)?;
+        (*self).storage.remove_member(id_hash)?;
+        let record = (*self).registrations.remove(index);
+        (*self).id_hashes.retain(|member| *member != id_hash);
+        if !(*self).revoked.contains(&id_hash) {
+            (*self).revoked.push(id_hash);
+        }
+        (*self).audit.append(AuditAction::Erasure, commitment);
+        let mut not_erased:Vec<NotHeld> = NOT_HELD.to_vec();
+        not_erased.push(NotHeld::RevokedIdHash);
+        Ok(ErasureReport { erased: record, not_erased })
+    }
 
-        // Return parameters for Registration Authority
-        (vk, x)
+    /* Take the users registered with the given commitments out of the anonymity set, signing their id hashes for the SAs */
+    // All or nothing: if any commitment was never registered, no one is revoked. Records stay for
+    // data export, and credentials already issued stay cryptographically valid (see NotHeld)
+    pub fn revoke_batch(&mut self, commitments: &[G1]) -> Result<RevocationBatch, SurveyError> {
+        let mut id_hashes:Vec<Fr> = Vec::new();
+        for commitment in commitments {
+            let record = (*self).registrations.iter().find(|record| record.commitment == *commitment).ok_or(SurveyError::UnregisteredUser)?;
+            if !id_hashes.contains(&record.id_hash) {
+                id_hashes.push(record.id_hash);
+            }
+        }
+        (*self).revoke_id_hashes(id_hashes)
+    }
+
+    /* Revoke the registered id with hash id_hash (see hash::id_hash), signing it for the SAs */
+    // The id leaves the anonymity set and can never be registered again
+    pub fn revoke(&mut self, id_hash: Fr) -> Result<RevocationBatch, SurveyError> {
+        if !(*self).registrations.iter().any(|record| record.id_hash == id_hash) {
+            return Err(SurveyError::UnregisteredUser);
+        }
+        (*self).revoke_id_hashes(vec![id_hash])
+    }
+
+    /* Whether the id with hash id_hash was revoked */
+    pub fn is_revoked(&self, id_hash: Fr) -> bool {
+        (*self).revoked.contains(&id_hash)
+    }
+
+    /* Every revocation so far in one signed batch, for SAs that missed (or predate) earlier ones */
+    pub fn revocation_list(&self) -> RevocationBatch {
+        RevocationBatch::sign((*self).revoked.clone(), self, (*self).g, (*self).g2)
+    }
+
+    fn revoke_id_hashes(&mut self, id_hashes: Vec<Fr>) -> Result<RevocationBatch, SurveyError> {
+        for id_hash in &id_hashes {
+            (*self).storage.remove_member(*id_hash)?;
+            (*self).storage.put_revocation(*id_hash)?;
+        }
+        (*self).id_hashes.retain(|id_hash| !id_hashes.contains(id_hash));
+        for id_hash in &id_hashes {
+            if !(*self).revoked.contains(id_hash) {
+                (*self).revoked.push(*id_hash);
+            }
+        }
+        for record in (*self).registrations.iter().filter(|record| id_hashes.contains(&record.id_hash)) {
+            (*self).audit.append(AuditAction::Revocation, record.commitment);
+        }
+        tracing::info!(revoked = id_hashes.len(), anonymity_set = (*self).id_hashes.len(), "RA revoked users");
+        Ok(RevocationBatch::sign(id_hashes, self, (*self).g, (*self).g2))
+    }
+
+    /* Whether the id with hash id_hash (see hash::id_hash) is in the anonymity set */
+    pub fn in_anonymity_set(&self, id_hash: Fr) -> bool {
+        (*self).id_hashes.contains(&id_hash)
+    }
+
+    /* Export the whole anonymity set for an audit, as id hashes in order of registration */
+    // The only way to read the set in bulk. It never held the ids themselves
+    pub fn audit_anonymity_set(&self) -> Vec<Fr> {
+        (*self).id_hashes.clone()
+    }
+
+    // Take a (re-identified) user's previous id out of the anonymity set
+    pub(crate) fn forget_id(&mut self, id: Fr) -> Result<(), SurveyError> {
+        let old_hash:Fr = id_hash(id);
+        (*self).storage.remove_member(old_hash)?;
+        (*self).id_hashes.retain(|id_hash| *id_hash != old_hash);
+        for record in (*self).registrations.iter().filter(|record| record.id_hash == old_hash) {
+            (*self).audit.append(AuditAction::Reidentification, record.commitment);
+        }
+        Ok(())
+    }
+
+    // Commitment of the registration that redeemed a code with this metadata, if any
+    pub(crate) fn registered_with(&self, metadata: &str) -> Option<G1> {
+        (*self).registrations.iter().find(|record| record.code_metadata.as_deref() == Some(metadata)).map(|record| record.commitment)
+    }
+
+    // Stop a pending registration code from being redeemed
+    pub(crate) fn withdraw_registration_code(&mut self, code: &str) {
+        (*self).pending_codes.retain(|reg_code| reg_code.code != code);
+    }
+
+    /* Audit log of registrations, re-identifications, revocations and erasures (see audit.rs) */
+    pub fn audit_log(&self) -> &AuditLog {
+        &(*self).audit
+    }
+
+    /* Sign the current head of the audit log, for auditors to check an exported transcript against */
+    pub fn audit_head(&self) -> AuditHead {
+        AuditHead::sign(&(*self).audit, self, (*self).g, (*self).g2)
+    }
+
+    /* Whether a registration code was minted, has not been redeemed, and has not expired */
+    pub fn is_pending(&self, code: &str) -> bool {
+        let now = SystemTime::now();
+        (*self).pending_codes.iter().any(|reg_code| reg_code.code == code && now <= reg_code.expires)
     }
 }
 
@@ -69,14 +716,270 @@ impl RegistrationAuthority {
  * Unit tests
  */
 
+#[cfg(test)]
+use tbn::Group;
+
 #[test]
 #[allow(non_snake_case)]
 // Test to ensure that e(g, g2)^(sk_RA) = vk_RA
 fn test_RA_keys() {
     let rng = &mut rand::thread_rng();
-    let (g, g2):(G1, G2) = (G1::random(rng), G2::random(rng));
-    let ra = RegistrationAuthority::new(g, g2);
-    assert!( pairing(g, g2).pow(ra.sk) == ra.vk.pk ); 
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let (g, g2):(G1, G2) = params.generators();
+    let ra = RegistrationAuthority::new(&params);
+    assert!( pairing(g, g2).pow(ra.sk.expose()) == ra.vk.pk ); 
+}
+
+
+#[cfg(test)]
+use crate::proofs::{Prover, REGISTER_DOMAIN, registration_context};
+
+// Test that a registration code registers the user exactly once
+#[test]
+fn test_registration_code_one_time() {
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let g:G1 = params.g;
+    let mut ra = RegistrationAuthority::new(&params);
+
+    let reg_code = ra.issue_registration_code("cohort-a", Duration::from_secs(3600));
+    let id = Fr::random(rng);
+    let prover = Prover::new(REGISTER_DOMAIN, vec![ra.vk.v, g], vec![Fr::random(rng), Fr::random(rng)]);
+    let proof = prover.prove(&registration_context(id), rng);
+    let (metadata, _) = ra.redeem_registration_code(&reg_code.code.to_lowercase(), id, prover.public(), &proof).expect("Code was rejected");
+    assert!( metadata == "cohort-a" );
+    assert!( ra.in_anonymity_set(id_hash(id)) );
+    let other_id = Fr::random(rng);
+    let other_proof = prover.prove(&registration_context(other_id), rng);
+    assert!( ra.redeem_registration_code(&reg_code.code, other_id, prover.public(), &other_proof) == Err(SurveyError::InvalidRegistrationCode) );
+    assert!( ra.audit_anonymity_set().len() == 1 );
+}
+
+// Test that an expired registration code is rejected
+#[test]
+fn test_registration_code_expired() {
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let g:G1 = params.g;
+    let mut ra = RegistrationAuthority::new(&params);
+
+    let reg_code = ra.issue_registration_code("cohort-b", Duration::from_secs(3600));
+    ra.pending_codes[0].expires = SystemTime::now() - Duration::from_secs(1);
+    let id = Fr::random(rng);
+    let proof = Prover::new(REGISTER_DOMAIN, vec![ra.vk.v, g], vec![Fr::one(), Fr::zero()]).prove(&registration_context(id), rng);
+    assert!( ra.redeem_registration_code(&reg_code.code, id, ra.vk.v, &proof) == Err(SurveyError::InvalidRegistrationCode) );
+    assert!( ra.audit_anonymity_set().is_empty() );
+}
+
+
+// Test that an unblinded credential verifies: e(σ1, g2) = pk_RA · e(u·id + v·s + h + H(epoch), σ2)
+#[test]
+fn test_RA_credential() {
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let (g, g2):(G1, G2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let (id, s, b):(Fr, Fr, Fr) = (Fr::random(rng), Fr::random(rng), Fr::random(rng));
+    let prover = Prover::new(REGISTER_DOMAIN, vec![ra.vk.v, g], vec![s, b]);
+    let proof = prover.prove(&registration_context(id), rng);
+    let (blind_1, cred_2, cred_3) = ra.reg_user(id, prover.public(), &proof).expect("Registration proof was rejected");
+    let cred_1 = blind_1 - cred_3 * b;
+    let h:G1 = ra.vk.h + epoch_point(ra.epoch());
+    assert!( pairing(blind_1, g2) != ra.vk.pk * pairing(ra.vk.u * id + ra.vk.v * s + h, cred_2) );
+    assert!( pairing(cred_1, g2) == ra.vk.pk * pairing(ra.vk.u * id + ra.vk.v * s + h, cred_2) );
+    assert!( pairing(cred_1, g2) != ra.vk.pk * pairing(ra.vk.u * id + ra.vk.v * (s + Fr::one()) + h, cred_2) );
+    assert!( pairing(cred_1, g2) != ra.vk.pk * pairing(ra.vk.u * id + ra.vk.v * s + ra.vk.h + epoch_point(ra.epoch() + 1), cred_2) );
+}
+
+
+// Test that an id gets one credential: registering it again, under any PRF key, is refused, and
+// once erased the id stays spent
+#[test]
+fn test_RA_registers_id_once() {
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let g:G1 = params.g;
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut alice = crate::users::User::new();
+    alice.reg_user(&mut ra).expect("Registration failed");
+    assert!( alice.reg_user(&mut ra) == Err(SurveyError::AlreadyRegistered) );
+
+    let (s, b):(Fr, Fr) = (Fr::random(rng), Fr::random(rng));
+    let prover = Prover::new(REGISTER_DOMAIN, vec![ra.vk.v, g], vec![s, b]);
+    let proof = prover.prove(&registration_context(alice.id()), rng);
+    assert!( ra.reg_user(alice.id(), prover.public(), &proof) == Err(SurveyError::AlreadyRegistered) );
+    assert!( ra.audit_anonymity_set().len() == 1 );
+
+    ra.erase_user_data(alice.registration_commitment().unwrap()).expect("Nothing erased");
+    assert!( alice.reg_user(&mut ra) == Err(SurveyError::RevokedUser) );
+}
+
+// Test that the RA refuses to register a commitment without a valid proof of knowledge for that id
+#[test]
+fn test_RA_requires_registration_proof() {
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let g:G1 = params.g;
+    let mut ra = RegistrationAuthority::new(&params);
+    let (id, s, b):(Fr, Fr, Fr) = (Fr::random(rng), Fr::random(rng), Fr::random(rng));
+    let prover = Prover::new(REGISTER_DOMAIN, vec![ra.vk.v, g], vec![s, b]);
+    let proof = prover.prove(&registration_context(id), rng);
+
+    // Someone else's commitment (e.g. copied from another registration), or a proof for another id
+    assert!( ra.reg_user(id, ra.vk.v * Fr::random(rng), &proof) == Err(SurveyError::InvalidProof) );
+    assert!( ra.reg_user(Fr::random(rng), prover.public(), &proof) == Err(SurveyError::InvalidProof) );
+    assert!( ra.audit_anonymity_set().is_empty() );
+}
+
+
+// Test that only the registrant of an id in the anonymity set can renew its credential
+#[test]
+fn test_RA_renew_credential() {
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let g:G1 = params.g;
+    let mut ra = RegistrationAuthority::new(&params);
+    let (id, s, b):(Fr, Fr, Fr) = (Fr::random(rng), Fr::random(rng), Fr::random(rng));
+    let prover = Prover::new(REGISTER_DOMAIN, vec![ra.vk.v, g], vec![s, b]);
+    ra.reg_user(id, prover.public(), &prover.prove(&registration_context(id), rng)).expect("Registration proof was rejected");
+    ra.advance_epoch();
+
+    // Knowing the commitment is not enough, nor is registering a new one for the id
+    let other = Prover::new(REGISTER_DOMAIN, vec![ra.vk.v, g], vec![s, Fr::random(rng)]);
+    assert!( ra.renew_credential(id, &other.prove(&registration_context(id), rng)) == Err(SurveyError::InvalidProof) );
+    assert!( ra.renew_credential(Fr::random(rng), &prover.prove(&registration_context(id), rng)) == Err(SurveyError::UnregisteredUser) );
+    assert!( ra.renew_credential(id, &prover.prove(&registration_context(id), rng)).is_ok() );
+    assert!( ra.audit_anonymity_set().len() == 1 && ra.registrations.len() == 1 );
+
+    ra.revoke(id_hash(id)).expect("Revocation failed");
+    assert!( ra.renew_credential(id, &prover.prove(&registration_context(id), rng)) == Err(SurveyError::RevokedUser) );
+}
+
+
+// Test that an RA restored from its keystore keeps honouring the credentials it issued
+#[test]
+fn test_RA_keystore() {
+    use crate::users::User;
+
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let (g, g2):(G1, G2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut user = User::new();
+    user.reg_user(&mut ra).expect("Registration failed");
+    ra.advance_epoch();
+
+    let keystore = ra.export_keys("correct horse");
+    assert!( RegistrationAuthority::from_keys(&keystore, "battery staple").err() == Some(SurveyError::InvalidKeystore) );
+    let restored = RegistrationAuthority::from_keys(&keystore, "correct horse").expect("Keystore was rejected");
+    assert!( restored.g == g && restored.g2 == g2 && restored.sk == ra.sk && restored.epoch() == 1 );
+    assert!( restored.vk.u == ra.vk.u && restored.vk.v == ra.vk.v && restored.vk.h == ra.vk.h && restored.vk.pk == ra.vk.pk );
+    let (cred_1, cred_2) = user.credential.unwrap();
+    assert!( pairing(cred_1, g2) == restored.vk.pk * pairing(restored.vk.u * user.id() + restored.vk.v * user.prf_key.expose() + restored.vk.h + epoch_point(0), cred_2) );
+}
+
+// Test that a snapshot restores the whole RA, and only with the right passphrase and untampered
+#[test]
+fn test_RA_snapshot() {
+    use crate::users::User;
+    use crate::audit::verify_transcript;
+
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let (g, g2):(G1, G2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let (mut alice, mut bob) = (User::new(), User::new());
+    alice.reg_user(&mut ra).expect("Registration failed");
+    bob.reg_user(&mut ra).expect("Registration failed");
+    ra.revoke(id_hash(bob.id())).expect("Revocation failed");
+    let reg_code = ra.issue_registration_code("cohort-a", Duration::from_secs(3600));
+    ra.advance_epoch();
+
+    let snapshot:Vec<u8> = ra.snapshot("correct horse");
+    assert!( RegistrationAuthority::restore(&snapshot, "battery staple").err() == Some(SurveyError::InvalidKeystore) );
+    let mut tampered:Vec<u8> = snapshot.clone();
+    let last:usize = tampered.len() - 33;
+    tampered[last] ^= 1;
+    assert!( RegistrationAuthority::restore(&tampered, "correct horse").err() == Some(SurveyError::InvalidSnapshot) );
+
+    let restored = RegistrationAuthority::restore(&snapshot, "correct horse").expect("Snapshot was refused");
+    assert!( restored.sk == ra.sk && restored.vk.pk == ra.vk.pk && restored.epoch() == 1 );
+    assert!( restored.in_anonymity_set(id_hash(alice.id())) && restored.is_revoked(id_hash(bob.id())) && restored.is_pending(&reg_code.code) );
+    let record = restored.export_user_data(alice.registration_commitment().unwrap()).expect("Alice's record was not restored").record;
+    assert!( record.registered == ra.export_user_data(alice.registration_commitment().unwrap()).unwrap().record.registered );
+    assert!( restored.audit_log().entries() == ra.audit_log().entries() );
+    assert!( verify_transcript(&restored.audit_log().transcript(), &ra.audit_head(), &restored.vk, g, g2).is_ok() );
+}
+
+
+// Test that credentials under the old key keep verifying during the grace window, and that users
+// migrate to the new key locally or in a batch
+#[test]
+fn test_RA_rotate_keys() {
+    use crate::users::{User, SurveyAuthority};
+    use crate::submission::{check_submission, check_submission_under};
+
+    let params = SystemParams::random();
+    let (g, g2):(G1, G2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let (mut alice, mut bob) = (User::new(), User::new());
+    alice.reg_user(&mut ra).expect("Registration failed");
+    bob.reg_user(&mut ra).expect("Registration failed");
+    let survey = sa.gen_survey(&vec![alice.id(), bob.id()], g, g2, &ra.vk).expect("Survey was refused");
+    let old_sk:Fr = ra.sk.expose();
+
+    ra.rotate_keys(Duration::from_secs(3600));
+    assert!( ra.sk.expose() != old_sk && ra.verification_keys().len() == 2 && ra.verification_keys()[0].pk == ra.vk.pk );
+    let old = alice.submit_survey(&survey, b"yes", (g, g2), &sa.vk, ra.verification_keys()[1]).expect("Submission failed");
+    assert!( !check_submission(survey.vid, &old, &sa.vk, &ra.vk, g, g2) );
+    assert!( check_submission_under(survey.vid, &old, &sa.vk, &ra.verification_keys(), g, g2) );
+
+    // Alice renews in place, Bob through a batch of requests; the survey signed before still works
+    alice.renew_credential(&mut ra).expect("Renewal failed");
+    let requests = vec![bob.renewal_request(&ra.vk, g).expect("Bob is registered"), User::new().request_registration(&ra.vk, g).0];
+    let reissued = ra.reissue_batch(&requests);
+    assert!( reissued[1] == Err(SurveyError::UnregisteredUser) );
+    bob.accept_renewal(reissued[0].as_ref().expect("Reissuance failed"), &ra.vk, g2).expect("Credential was rejected");
+    for user in &[&alice, &bob] {
+        let submission = user.submit_survey(&survey, b"yes", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+        assert!( check_submission(survey.vid, &submission, &sa.vk, &ra.vk, g, g2) );
+    }
+
+    // The retired key survives the keystore, and is dropped once its window ends
+    let restored = RegistrationAuthority::from_keys(&ra.export_keys("correct horse"), "correct horse").expect("Keystore was rejected");
+    assert!( restored.verification_keys().len() == 2 );
+    ra.rotate_keys(Duration::from_secs(0));
+    assert!( ra.verification_keys().len() == 2 && ra.retired.len() == 2 );
+}
+
+
+// Test that a user can see and erase what the RA stores about them, and nothing else
+#[test]
+fn test_export_and_erase_user_data() {
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut alice = crate::users::User::new();
+    let mut bob = crate::users::User::new();
+    alice.reg_user(&mut ra).expect("Registration failed");
+    let reg_code = ra.issue_registration_code("cohort-c", Duration::from_secs(3600));
+    bob.redeem_code(&reg_code.code, &mut ra).expect("Registration failed");
+
+    let commitment = alice.registration_commitment().unwrap();
+    let export = ra.export_user_data(commitment).expect("No data for alice");
+    assert!( export.record.id_hash == id_hash(alice.id()) && export.record.code_metadata.is_none() && export.in_anonymity_set );
+    assert!( export.not_included.contains(&NotHeld::AnonymousSubmissions) );
+    assert!( ra.export_user_data(bob.registration_commitment().unwrap()).unwrap().record.code_metadata == Some(String::from("cohort-c")) );
+    assert!( ra.export_user_data(G1::random(rng)).is_none() );
+
+    let report = ra.erase_user_data(commitment).expect("Nothing erased");
+    assert!( report.erased.id_hash == id_hash(alice.id()) && report.not_erased.contains(&NotHeld::AuditLogEntries) );
+    assert!( report.not_erased.contains(&NotHeld::RevokedIdHash) && ra.is_revoked(id_hash(alice.id())) );
+    assert!( ra.audit_log().entries().last().is_some_and(|entry| entry.action == AuditAction::Erasure && entry.commitment == commitment) );
+    assert!( !ra.in_anonymity_set(id_hash(alice.id())) && ra.in_anonymity_set(id_hash(bob.id())) );
+    assert!( ra.export_user_data(commitment).is_none() );
+    assert!( ra.erase_user_data(commitment).is_err() );
 }
 
 
@@ -94,8 +997,7 @@ fn bench_100_gen_RA() {
 
     // Setup 
     let rng = &mut rand::thread_rng();
-    let (g, g2):(G1, G2) = (G1::random(rng), G2::random(rng));
-    
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
     // 100 irerations of GenRA
     const NUM_TRIALS:usize = 100;
     assert!(NUM_TRIALS > 1);
@@ -104,7 +1006,7 @@ fn bench_100_gen_RA() {
     let mut durs:[Duration;NUM_TRIALS] = [Duration::new(0,0);NUM_TRIALS];
     for i in 0..NUM_TRIALS {
         let start = Instant::now(); 
-        let _ra = RegistrationAuthority::new(g, g2);
+        let _ra = RegistrationAuthority::new(&params);
         durs[i] = start.elapsed();
         sum += durs[i];
         println!("Trial {}:\t{:?}", i+1, durs[i]);
@@ -133,8 +1035,7 @@ fn bench_5_gen_RA() {
 
     // Setup 
     let rng = &mut rand::thread_rng();
-    let (g, g2):(G1, G2) = (G1::random(rng), G2::random(rng));
-    
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
     // 5 irerations of GenRA
     const NUM_TRIALS:usize = 5;
     assert!(NUM_TRIALS > 1);
@@ -143,7 +1044,7 @@ fn bench_5_gen_RA() {
     let mut durs:[Duration;NUM_TRIALS] = [Duration::new(0,0);NUM_TRIALS];
     for i in 0..NUM_TRIALS {
         let start = Instant::now(); 
-        let _ra = RegistrationAuthority::new(g, g2);
+        let _ra = RegistrationAuthority::new(&params);
         durs[i] = start.elapsed();
         sum += durs[i];
         println!("Trial {}:\t{:?}", i+1, durs[i]);
@@ -0,0 +1,641 @@
+extern crate tbn;
+extern crate rand;
+extern crate tracing;
+
+use rand::{RngCore, CryptoRng};
+use zeroize::Zeroizing;
+use tbn::{Fr, G1, G2};
+use super::{VerificationKey, KeyHolder};
+use crate::proofs::{Proof, sign_gt};
+use crate::error::SurveyError;
+use crate::keystore::{self, KeystoreKind, KeyReader, push_vk};
+use crate::encoding::fr_to_bytes;
+use crate::survey::{Survey, SurveyDescription, SurveyNamespace, ParticipantEntry, participant_message, sign_participant};
+use crate::engine::Tbn;
+use crate::definition::{SurveyDefinition, Question};
+use crate::policy::{SurveyPolicy, Amendment};
+use crate::padding::PaddingPolicy;
+use crate::revocation::RevocationBatch;
+use crate::keyring::VerificationKeyRing;
+use crate::hash::id_hash;
+use crate::events::{Event, EventBus};
+use crate::params::SystemParams;
+use crate::secret::SecretScalar;
+use crate::seed::{Seed, SA_KEY_PATH};
+use crate::logging::vid_hex;
+use crate::storage::{Storage, MemoryStorage};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/*
+ * ----------------------------------------------
+ * |    SURVEY AUTHORITY (SA)                   |
+ * ----------------------------------------------
+ *
+ * The survey authority is a party of its own, apart from the users it surveys, and can perform
+ * the following actions:
+ *      - Create a survey
+ *          + Choose survey identity vid
+ *          + Generate signature key-pair that allows them to sign and others to verify values
+ *          + Specify a list of user IDs (authenticated by the RA) to send the survey to
+ *      - Apply the RA's revocation batches, leaving revoked users off every survey created later
+ *      - Keep its surveys and revocations in a Storage (see storage/mod.rs)
+ *
+ * Someone who both runs surveys and answers them holds a SurveyAuthority and a User.
+*/
+
+pub struct SurveyAuthority<S: Storage = MemoryStorage> {
+    pub vk: VerificationKey,
+    // Secret signing key y, with pk_SA = e(g, g2)^y
+    sk: SecretScalar,
+    // Version of the signing key, and the keys it replaced as (version, vk) (see keyring.rs)
+    key_version: u32,
+    retired_keys: Vec<(u32, VerificationKey)>,
+    // List of owned surveys (by vid)
+    pub owned_surveys: Vec<Fr>,
+    // Owned surveys with their published participant signatures
+    pub verid_list: Vec<Survey>,
+    // Id hashes revoked by the RA, never signed into new surveys
+    revoked: Vec<Fr>,
+    // Published to whenever the SA opens a survey
+    pub events: EventBus,
+    // Buckets the participants of new surveys pad their answers to (see padding.rs)
+    pub padding: PaddingPolicy,
+    // Where surveys and revocations are written through to
+    storage: S
+}
+
+impl<S: Storage> KeyHolder for SurveyAuthority<S> {
+
+    fn vk(&self) -> &VerificationKey {
+        &(*self).vk
+    }
+
+    fn sign_body(&self, domain: &[u8], g: G1, g2: G2, body: &[u8]) -> Proof {
+        sign_gt(domain, g, g2, (*self).vk.pk, (*self).sk.expose(), body)
+    }
+}
+
+impl SurveyAuthority {
+
+    /* Create Survey Authority on the deployment's system parameters */
+    pub fn new(params: &SystemParams) -> Self {
+        // crytpographiclaly secure thread-local rng
+        SurveyAuthority::new_with_rng(params, &mut rand::thread_rng())
+    }
+
+    pub fn new_with_rng(params: &SystemParams, rng: &mut (impl RngCore + CryptoRng)) -> Self {
+        let (g, g2):(G1, G2) = (*params).generators();
+        let (vk, y) = Self::gen_keys(g, g2, rng);
+        SurveyAuthority::with_keys(vk, y, 0, Vec::new())
+    }
+
+    /* Regenerate the SA's initial key pair (version 0) from seed (see seed.rs) */
+    pub fn from_seed(params: &SystemParams, seed: &Seed) -> Self {
+        let (g, g2):(G1, G2) = (*params).generators();
+        let (vk, y) = (*seed).derive_keys(SA_KEY_PATH, b"y", g, g2);
+        SurveyAuthority::with_keys(vk, y, 0, Vec::new())
+    }
+
+    fn with_keys(vk: VerificationKey, sk: Fr, key_version: u32, retired_keys: Vec<(u32, VerificationKey)>) -> Self {
+        SurveyAuthority {
+            vk,
+            sk: SecretScalar::new(sk),
+            key_version,
+            retired_keys,
+            owned_surveys: Vec::new(),
+            verid_list: Vec::new(),
+            revoked: Vec::new(),
+            events: EventBus::new(),
+            padding: PaddingPolicy::default(),
+            storage: MemoryStorage::new()
+        }
+    }
+
+    /* Restore an SA from a keystore written by export_keys, so surveys it signed stay verifiable */
+    // Only the keys (with their versions and the retired keys) are kept: the restored SA has no
+    // surveys
+    pub fn from_keys(keystore: &[u8], passphrase: &str) -> Result<Self, SurveyError> {
+        let plaintext = keystore::open(KeystoreKind::SurveyAuthority, keystore, passphrase)?;
+        let mut reader = KeyReader::new(&plaintext);
+        let (vk, sk, key_version) = (reader.vk()?, reader.fr()?, reader.u32()?);
+        let mut retired_keys:Vec<(u32, VerificationKey)> = Vec::new();
+        for _ in 0..reader.u32()? {
+            retired_keys.push((reader.u32()?, reader.vk()?));
+        }
+        reader.finish()?;
+        Ok(SurveyAuthority::with_keys(vk, sk, key_version, retired_keys))
+    }
+
+}
+
+impl<S: Storage> SurveyAuthority<S> {
+
+    /* Move the SA onto storage, e.g. after restoring it with from_keys (see storage/mod.rs) */
+    // The surveys and revocations the SA holds are written to storage first, then the SA takes on
+    // everything storage holds
+    pub fn with_storage<T: Storage>(self, mut storage: T) -> Result<SurveyAuthority<T>, SurveyError> {
+        for survey in &self.verid_list {
+            storage.put_survey(survey)?;
+        }
+        for id_hash in &self.revoked {
+            storage.put_revocation(*id_hash)?;
+        }
+        let SurveyAuthority { vk, sk, key_version, retired_keys, events, padding, .. } = self;
+        let verid_list:Vec<Survey> = storage.surveys()?;
+        let owned_surveys:Vec<Fr> = verid_list.iter().map(|survey| survey.vid).collect();
+        Ok(SurveyAuthority { vk, sk, key_version, retired_keys, owned_surveys, verid_list, revoked: storage.revocations()?, events, padding, storage })
+    }
+
+    /* The storage the SA writes through to, e.g. to hand it to the SA's next process */
+    pub fn into_storage(self) -> S {
+        self.storage
+    }
+
+    /* Export the signing keys, encrypted under passphrase */
+    pub fn export_keys(&self, passphrase: &str) -> Vec<u8> {
+        // crytpographiclaly secure thread-local rng
+        (*self).export_keys_with_rng(passphrase, &mut rand::thread_rng())
+    }
+
+    pub fn export_keys_with_rng(&self, passphrase: &str, rng: &mut (impl RngCore + CryptoRng)) -> Vec<u8> {
+        let mut plaintext:Zeroizing<Vec<u8>> = Zeroizing::new(Vec::new());
+        push_vk(&mut plaintext, &(*self).vk);
+        plaintext.extend(fr_to_bytes((*self).sk.expose()));
+        plaintext.extend_from_slice(&(*self).key_version.to_be_bytes());
+        plaintext.extend_from_slice(&((*self).retired_keys.len() as u32).to_be_bytes());
+        for (version, vk) in &(*self).retired_keys {
+            plaintext.extend_from_slice(&version.to_be_bytes());
+            push_vk(&mut plaintext, vk);
+        }
+        keystore::seal(KeystoreKind::SurveyAuthority, &plaintext, passphrase, rng)
+    }
+
+    /* Generate survey with signature for each provided user id */
+    #[allow(non_snake_case)]
+    pub fn gen_survey(&mut self, L:&Vec<Fr>, g:G1, g2:G2, vk_ra: &VerificationKey) -> Result<Survey, SurveyError> {
+        // crytpographiclaly secure thread-local rng
+        (*self).gen_survey_with_rng(L, g, g2, vk_ra, &mut rand::thread_rng())
+    }
+
+    #[allow(non_snake_case)]
+    pub fn gen_survey_with_rng(&mut self, L:&Vec<Fr>, g:G1, g2:G2, vk_ra: &VerificationKey, rng: &mut (impl RngCore + CryptoRng))
+                               -> Result<Survey, SurveyError> {
+
+        // Choose random survey id as well
+        let vid = Fr::random(rng);
+        sign_survey(self, vid, L, g, g2, vk_ra, rng)
+    }
+
+    /* Same as gen_survey, but the vid is derived from a published description (see hash.rs) */
+    // Anyone holding the description can confirm which survey the vid stands for
+    #[allow(non_snake_case)]
+    pub fn gen_described_survey(&mut self, description: &SurveyDescription, L:&Vec<Fr>, g:G1, g2:G2, vk_ra: &VerificationKey)
+                                -> Result<Survey, SurveyError> {
+        // crytpographiclaly secure thread-local rng
+        (*self).gen_described_survey_with_rng(description, L, g, g2, vk_ra, &mut rand::thread_rng())
+    }
+
+    #[allow(non_snake_case)]
+    pub fn gen_described_survey_with_rng(&mut self, description: &SurveyDescription, L:&Vec<Fr>, g:G1, g2:G2, vk_ra: &VerificationKey,
+                                         rng: &mut (impl RngCore + CryptoRng)) -> Result<Survey, SurveyError> {
+        let vid = description.vid(&(*self).vk);
+        sign_survey(self, vid, L, g, g2, vk_ra, rng)
+    }
+
+    /* Define a survey's questions, signed with the SA's key (see definition.rs) */
+    pub fn define_survey(&self, title: &str, description: &str, questions: Vec<Question>, g:G1, g2:G2) -> Result<SurveyDefinition, SurveyError> {
+        // crytpographiclaly secure thread-local rng
+        (*self).define_survey_with_rng(title, description, questions, g, g2, &mut rand::thread_rng())
+    }
+
+    pub fn define_survey_with_rng(&self, title: &str, description: &str, questions: Vec<Question>, g:G1, g2:G2,
+                                  rng: &mut (impl RngCore + CryptoRng)) -> Result<SurveyDefinition, SurveyError> {
+        SurveyDefinition::sign(title, description, questions, self, g, g2, rng)
+    }
+
+    /* Same as gen_described_survey, with the vid derived from a survey definition */
+    #[allow(non_snake_case)]
+    pub fn gen_defined_survey(&mut self, definition: &SurveyDefinition, L:&Vec<Fr>, g:G1, g2:G2, vk_ra: &VerificationKey)
+                              -> Result<Survey, SurveyError> {
+        // crytpographiclaly secure thread-local rng
+        (*self).gen_defined_survey_with_rng(definition, L, g, g2, vk_ra, &mut rand::thread_rng())
+    }
+
+    #[allow(non_snake_case)]
+    pub fn gen_defined_survey_with_rng(&mut self, definition: &SurveyDefinition, L:&Vec<Fr>, g:G1, g2:G2, vk_ra: &VerificationKey,
+                                       rng: &mut (impl RngCore + CryptoRng)) -> Result<Survey, SurveyError> {
+        let vid = definition.vid(&(*self).vk);
+        sign_survey(self, vid, L, g, g2, vk_ra, rng)
+    }
+
+    /* Same as gen_survey, but the vid is in the namespace of the name the SA is registered under */
+    // No other SA sharing the RA can issue a survey with the same vid (see registry.rs)
+    #[allow(non_snake_case)]
+    pub fn gen_namespaced_survey(&mut self, authority: &str, L:&Vec<Fr>, g:G1, g2:G2, vk_ra: &VerificationKey)
+                                 -> Result<Survey, SurveyError> {
+        // crytpographiclaly secure thread-local rng
+        (*self).gen_namespaced_survey_with_rng(authority, L, g, g2, vk_ra, &mut rand::thread_rng())
+    }
+
+    #[allow(non_snake_case)]
+    pub fn gen_namespaced_survey_with_rng(&mut self, authority: &str, L:&Vec<Fr>, g:G1, g2:G2, vk_ra: &VerificationKey,
+                                          rng: &mut (impl RngCore + CryptoRng)) -> Result<Survey, SurveyError> {
+        let namespace = SurveyNamespace::new_with_rng(authority, rng);
+        let vid = namespace.vid();
+        sign_survey(self, vid, L, g, g2, vk_ra, rng)?;
+        // Keep the namespace on the SA's own copy as well
+        match (*self).verid_list.iter_mut().find(|survey| survey.vid == vid) {
+            Some(survey) => {
+                survey.namespace = Some(namespace);
+                (*self).storage.put_survey(survey)?;
+                Ok(survey.clone())
+            },
+            None => Err(SurveyError::EmptySurvey)
+        }
+    }
+
+    /* Announce when survey vid stops taking responses (seconds since the Unix epoch, see policy.rs) */
+    // The policy also fixes the survey's padding: the buckets it was published with, or the SA's
+    // current ones for a survey it does not hold
+    pub fn set_closing_time(&self, vid: Fr, closes_at: u64, g:G1, g2:G2) -> SurveyPolicy {
+        let padding:PaddingPolicy = match (*self).verid_list.iter().find(|survey| survey.vid == vid) {
+            Some(survey) => survey.padding.clone(),
+            None => (*self).padding.clone()
+        };
+        SurveyPolicy::sign(vid, closes_at, padding, self, g, g2)
+    }
+
+    /* Move the closing time of a survey later, amending the policy and the amendments so far */
+    pub fn extend_closing_time(&self, policy: &SurveyPolicy, amendments: &[Amendment], closes_at: u64, g:G1, g2:G2)
+                               -> Result<Amendment, SurveyError> {
+        if closes_at <= (*policy).closing_time(amendments, &(*self).vk, g, g2)? {
+            return Err(SurveyError::InvalidAmendment);
+        }
+        let previous:String = match amendments.last() {
+            Some(amendment) => amendment.hash((*policy).vid),
+            None => (*policy).hash()
+        };
+        Ok(Amendment::sign((*policy).vid, previous, closes_at, self, g, g2))
+    }
+
+    /* Stop signing the ids revoked in a batch the RA signed (see revocation.rs) */
+    // Surveys signed before are not changed
+    pub fn apply_revocations(&mut self, batch: &RevocationBatch, vk_ra: &VerificationKey, g:G1, g2:G2) -> Result<(), SurveyError> {
+        if !(*batch).verify(vk_ra, g, g2) {
+            tracing::warn!("refused revocation batch: RA signature does not verify");
+            return Err(SurveyError::InvalidSignature);
+        }
+        for id_hash in &(*batch).id_hashes {
+            if !(*self).revoked.contains(id_hash) {
+                (*self).storage.put_revocation(*id_hash)?;
+                (*self).revoked.push(*id_hash);
+            }
+        }
+        tracing::info!(batch = (*batch).id_hashes.len(), revoked = (*self).revoked.len(), "SA applied revocations");
+        Ok(())
+    }
+
+    /* Replace the signing key with a fresh one, returning its version */
+    // Surveys signed before keep the version of the key that signed them, and stay verifiable under
+    // key_ring
+    pub fn rotate_keys(&mut self, g:G1, g2:G2) -> u32 {
+        // crytpographiclaly secure thread-local rng
+        (*self).rotate_keys_with_rng(g, g2, &mut rand::thread_rng())
+    }
+
+    pub fn rotate_keys_with_rng(&mut self, g:G1, g2:G2, rng: &mut (impl RngCore + CryptoRng)) -> u32 {
+        let (vk, y) = Self::gen_keys(g, g2, rng);
+        let old = std::mem::replace(&mut (*self).vk, vk);
+        (*self).retired_keys.push(((*self).key_version, old));
+        (*self).sk = SecretScalar::new(y);
+        (*self).key_version += 1;
+        tracing::info!(key_version = (*self).key_version, "SA rotated its signing key");
+        (*self).key_version
+    }
+
+    /* Every key the SA has signed with, current and retired, for verifiers to check surveys against */
+    pub fn key_ring(&self) -> VerificationKeyRing {
+        let mut ring = VerificationKeyRing::new();
+        for (version, vk) in &(*self).retired_keys {
+            ring.insert(*version, vk.clone());
+        }
+        ring.insert((*self).key_version, (*self).vk.clone());
+        ring
+    }
+
+    /* Same as gen_survey, but signs the participants in parallel */
+    #[cfg(feature = "parallel")]
+    #[allow(non_snake_case)]
+    pub fn gen_survey_parallel(&mut self, L:&Vec<Fr>, g:G1, g2:G2, vk_ra: &VerificationKey) -> Result<Survey, SurveyError> {
+        // crytpographiclaly secure thread-local rng
+        (*self).gen_survey_parallel_with_rng(L, g, g2, vk_ra, &mut rand::thread_rng())
+    }
+
+    #[cfg(feature = "parallel")]
+    #[allow(non_snake_case)]
+    pub fn gen_survey_parallel_with_rng(&mut self, L:&Vec<Fr>, g:G1, g2:G2, vk_ra: &VerificationKey, rng: &mut (impl RngCore + CryptoRng))
+                                        -> Result<Survey, SurveyError> {
+        let vid = Fr::random(rng);
+        let _span = tracing::debug_span!("gen_survey", vid = %vid_hex(vid), participants = L.len()).entered();
+        let participants:Vec<Fr> = survey_participants(self, vid, L)?;
+
+        // Same signatures as gen_survey. Every r is drawn from rng up front, in participant order, so
+        // only the group operations run on the worker threads
+        let randoms:Vec<Fr> = participants.iter().map(|_| Fr::random(rng)).collect();
+        let sign_val:G1 = g * (*self).sk.expose();
+        let (u, v):(G1, G1) = ((*self).vk.u, (*self).vk.v);
+        let entries:Vec<ParticipantEntry> = participants.par_iter().zip(randoms.par_iter()).map(|(id, r)| {
+            let (sigma_1, sigma_2):(G1, G2) = sign_participant::<Tbn>(sign_val, participant_message::<Tbn>(u, v, (*vk_ra).h, vid, *id), *r, g2);
+            ParticipantEntry { id: *id, sigma_1, sigma_2 }
+        }).collect();
+
+        let mut survey = new_survey(self, vid);
+        for entry in entries {
+            survey.insert(entry);
+        }
+        publish_survey(self, survey)
+    }
+}
+
+
+// The participants of L the SA signs for survey vid: everyone on it who is not revoked. Refuses a vid
+// the SA already signed (which would merge two participant lists into one survey), and a survey with
+// no one left to sign for
+#[allow(non_snake_case)]
+fn survey_participants<S: Storage>(sa: &SurveyAuthority<S>, vid: Fr, L:&Vec<Fr>) -> Result<Vec<Fr>, SurveyError> {
+    if L.is_empty() {
+        tracing::warn!("refused survey: no participants");
+        return Err(SurveyError::EmptySurvey);
+    }
+    if (*sa).owned_surveys.contains(&vid) {
+        tracing::warn!("refused survey: vid already signed");
+        return Err(SurveyError::DuplicateSurvey);
+    }
+    let participants:Vec<Fr> = L.iter().filter(|id| !(*sa).revoked.contains(&id_hash(**id))).cloned().collect();
+    if participants.is_empty() {
+        tracing::warn!("refused survey: every participant was revoked");
+        return Err(SurveyError::EmptySurvey);
+    }
+    Ok(participants)
+}
+
+// Empty survey vid under the SA's current key version and padding policy
+fn new_survey<S: Storage>(sa: &SurveyAuthority<S>, vid: Fr) -> Survey {
+    let mut survey = Survey::new(vid);
+    survey.key_version = (*sa).key_version;
+    survey.padding = (*sa).padding.clone();
+    survey
+}
+
+// "Publish" the list of signatures for each participant of a newly signed survey. Storage goes
+// first: the SA only owns (and announces) a survey once it has been stored
+fn publish_survey<S: Storage>(sa: &mut SurveyAuthority<S>, survey: Survey) -> Result<Survey, SurveyError> {
+    (*sa).storage.put_survey(&survey)?;
+    // Add vid to the list of owned surveys (by ID)
+    (*sa).owned_surveys.push(survey.vid);
+    (*sa).verid_list.push(survey.clone());
+    (*sa).events.publish(Event::SurveyOpened { vid: survey.vid, participants: survey.len(), id_hashes: survey.participants().map(|entry| id_hash(entry.id)).collect() });
+    tracing::info!(signed = survey.len(), key_version = (*sa).key_version, "SA opened survey");
+    Ok(survey)
+}
+
+// Sign every participant in L under survey id vid
+#[allow(non_snake_case)]
+fn sign_survey<S: Storage>(sa: &mut SurveyAuthority<S>, vid: Fr, L:&Vec<Fr>, g:G1, g2:G2, vk_ra: &VerificationKey, rng: &mut (impl RngCore + CryptoRng))
+               -> Result<Survey, SurveyError> {
+    let _span = tracing::debug_span!("gen_survey", vid = %vid_hex(vid), participants = L.len()).entered();
+    // Authorize all users in L (even if they're not registered -- this would be caught later), skipping revoked users
+    let participants:Vec<Fr> = survey_participants(sa, vid, L)?;
+
+    /* --------------------------------------------------------------------------
+     *          Variation of Boneh-Boyen (BB) ID-based Signature Scheme
+     * --------------------------------------------------------------------------
+     */
+
+    /* Hoist invariant code to loop pre-header for efficiency */
+    // Sign with secret key
+    let sign_val:G1 = g * (*sa).sk.expose();
+
+    let mut survey = new_survey(sa, vid);
+    for id in participants {
+        // Choose random r in Z_q (TODO: Move this and sigma_2 outside of loop???)
+        let r = Fr::random(rng);
+        // Sign with participant ID, and the 2nd group generator with random to get second signature
+        let (sigma_1, sigma_2):(G1, G2) = sign_participant::<Tbn>(sign_val, participant_message::<Tbn>((*sa).vk.u, (*sa).vk.v, (*vk_ra).h, vid, id), r, g2);
+        survey.insert(ParticipantEntry { id, sigma_1, sigma_2 });
+    }
+    publish_survey(sa, survey)
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use tbn::Group;
+#[cfg(test)]
+use crate::gt::pairing;
+#[cfg(test)]
+use super::RegistrationAuthority;
+
+#[test]
+#[allow(non_snake_case)]
+// Test to ensure that e(g, g2)^(sk_SA) = vk_SA
+fn test_SA_keys() {
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let (g, g2):(G1, G2) = params.generators();
+    let sa = SurveyAuthority::new(&params);
+    assert!( pairing(g, g2).pow(sa.sk.expose()) == sa.vk.pk ); 
+}
+
+// Test that an SA restored from its keystore signs surveys under the same verification key
+#[test]
+fn test_SA_keystore() {
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let (g, g2):(G1, G2) = params.generators();
+    let ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let id = Fr::random(rng);
+    let before = sa.gen_survey(&vec![id], g, g2, &ra.vk).expect("SA survey creation failed!");
+
+    let keystore = sa.export_keys("correct horse");
+    let mut restored = SurveyAuthority::from_keys(&keystore, "correct horse").expect("Keystore was rejected");
+    assert!( restored.vk.pk == sa.vk.pk && restored.sk == sa.sk );
+    assert!( before.is_authorized(id, &restored.vk, &ra.vk, g2) );
+    let after = restored.gen_survey(&vec![id], g, g2, &ra.vk).expect("SA survey creation failed!");
+    assert!( after.is_authorized(id, &sa.vk, &ra.vk, g2) );
+    assert!( RegistrationAuthority::from_keys(&keystore, "correct horse").is_err() );
+}
+
+// Test that a survey without participants is refused
+#[test]
+fn test_empty_survey() {
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let (g, g2):(G1, G2) = params.generators();
+    let ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    assert!( sa.gen_survey(&vec![], g, g2, &ra.vk) == Err(SurveyError::EmptySurvey) );
+}
+
+// Test that signing in parallel gives every participant a valid signature
+#[test]
+#[cfg(feature = "parallel")]
+fn test_gen_survey_parallel() {
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let (g, g2):(G1, G2) = params.generators();
+    let ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let ids:Vec<Fr> = (0..20).map(|_| Fr::random(rng)).collect();
+    let survey = sa.gen_survey_parallel(&ids, g, g2, &ra.vk).expect("SA survey creation failed!");
+    assert!( survey.len() == ids.len() );
+    assert!( survey.verify_all(&sa.vk, &ra.vk, g2).is_empty() );
+    assert!( sa.gen_survey_parallel(&vec![], g, g2, &ra.vk) == Err(SurveyError::EmptySurvey) );
+}
+
+// Test that signing in parallel from an injected rng gives the same survey as signing sequentially from it
+#[test]
+#[cfg(feature = "parallel")]
+fn test_gen_survey_parallel_with_rng() {
+    use rand::SeedableRng;
+    use rand::prng::ChaChaRng;
+
+    let params = SystemParams::random();
+    let (g, g2):(G1, G2) = params.generators();
+    let ra = RegistrationAuthority::new(&params);
+    let ids:Vec<Fr> = (0..20).map(|_| Fr::random(&mut rand::thread_rng())).collect();
+    let sign = |parallel: bool| {
+        let rng = &mut ChaChaRng::from_seed([7; 32]);
+        let mut sa = SurveyAuthority::new_with_rng(&params, rng);
+        match parallel {
+            true => sa.gen_survey_parallel_with_rng(&ids, g, g2, &ra.vk, rng),
+            false => sa.gen_survey_with_rng(&ids, g, g2, &ra.vk, rng)
+        }.expect("SA survey creation failed!")
+    };
+    assert!( sign(true) == sign(false) && sign(true) == sign(true) );
+}
+
+
+// Storage that refuses to store surveys
+#[cfg(test)]
+struct NoSurveyStorage;
+
+#[cfg(test)]
+impl Storage for NoSurveyStorage {
+    fn put_user(&mut self, _: &crate::users::RegistrationRecord) -> Result<(), SurveyError> { Ok(()) }
+    fn get_user(&self, _: G1) -> Result<Option<crate::users::RegistrationRecord>, SurveyError> { Ok(None) }
+    fn remove_user(&mut self, _: G1) -> Result<(), SurveyError> { Ok(()) }
+    fn users(&self) -> Result<Vec<crate::users::RegistrationRecord>, SurveyError> { Ok(Vec::new()) }
+    fn add_member(&mut self, _: Fr) -> Result<(), SurveyError> { Ok(()) }
+    fn remove_member(&mut self, _: Fr) -> Result<(), SurveyError> { Ok(()) }
+    fn members(&self) -> Result<Vec<Fr>, SurveyError> { Ok(Vec::new()) }
+    fn put_survey(&mut self, _: &Survey) -> Result<(), SurveyError> { Err(SurveyError::Storage(String::from("read-only"))) }
+    fn get_survey(&self, _: Fr) -> Result<Option<Survey>, SurveyError> { Ok(None) }
+    fn surveys(&self) -> Result<Vec<Survey>, SurveyError> { Ok(Vec::new()) }
+    fn put_submission(&mut self, _: Fr, _: &crate::submission::Submission) -> Result<(), SurveyError> { Ok(()) }
+    fn get_submissions(&self, _: Fr) -> Result<Vec<crate::submission::Submission>, SurveyError> { Ok(Vec::new()) }
+    fn submissions(&self) -> Result<Vec<(Fr, crate::submission::Submission)>, SurveyError> { Ok(Vec::new()) }
+    fn put_revocation(&mut self, _: Fr) -> Result<(), SurveyError> { Ok(()) }
+    fn revocations(&self) -> Result<Vec<Fr>, SurveyError> { Ok(Vec::new()) }
+}
+
+// Test that a survey the storage refused is not left owned by the SA, on either signing path
+#[test]
+fn test_unstored_survey_not_owned() {
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    let (g, g2):(G1, G2) = params.generators();
+    let ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params).with_storage(NoSurveyStorage).expect("Storage was refused");
+    let ids:Vec<Fr> = (0..3).map(|_| Fr::random(rng)).collect();
+    assert!( matches!(sa.gen_survey(&ids, g, g2, &ra.vk), Err(SurveyError::Storage(_))) );
+    #[cfg(feature = "parallel")]
+    assert!( matches!(sa.gen_survey_parallel(&ids, g, g2, &ra.vk), Err(SurveyError::Storage(_))) );
+    assert!( sa.owned_surveys.is_empty() && sa.verid_list.is_empty() );
+}
+
+
+/*
+ * Benchmark tests
+ */
+
+#[test]
+#[ignore]
+#[allow(non_snake_case)]
+// Test 100 iterations of GenSA to get mean and standard deviation
+fn bench_100_gen_SA() {
+
+    use std::time::{Duration, Instant};
+
+    // Setup 
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    // 100 irerations of GenSA
+    const NUM_TRIALS:usize = 100;
+    assert!(NUM_TRIALS > 1);
+    println!("GenSA Benchmark Test ({} trials)", NUM_TRIALS);
+    let mut sum:Duration = Duration::new(0,0);
+    let mut durs:[Duration;NUM_TRIALS] = [Duration::new(0,0);NUM_TRIALS];
+    for i in 0..NUM_TRIALS {
+        let start = Instant::now(); 
+        let _sa = SurveyAuthority::new(&params);
+        durs[i] = start.elapsed();
+        sum += durs[i];
+        println!("Trial {}:\t{:?}", i+1, durs[i]);
+    }
+    println!();
+    // Calculate mean
+    let mean = sum / (NUM_TRIALS as u32);
+    // Calculate standard deviation
+    let mut sum_of_diff:f32 = 0.0;
+    for i in 0..NUM_TRIALS {
+        sum_of_diff += f32::powf((((durs[i].as_millis() as i128) - (mean.as_millis() as i128)) as f32)/1000.0, 2.0);
+    }
+    let sd = ( sum_of_diff / ((NUM_TRIALS as f32)- 1.0)).sqrt();
+ 
+    println!("Mean:\t\t{:?}", mean);
+    println!("Std Dev:\t{:?}s", sd);
+}
+
+
+#[test]
+#[allow(non_snake_case)]
+// Test 5 iterations of GenSA to get mean and standard deviation
+fn bench_5_gen_SA() {
+
+    use std::time::{Duration, Instant};
+
+    // Setup 
+    let rng = &mut rand::thread_rng();
+    let params = SystemParams::new(G1::random(rng), G2::random(rng)).expect("Random generators are valid");
+    // 5 irerations of GenSA
+    const NUM_TRIALS:usize = 5;
+    assert!(NUM_TRIALS > 1);
+    println!("GenSA Benchmark Test ({} trials)", NUM_TRIALS);
+    let mut sum:Duration = Duration::new(0,0);
+    let mut durs:[Duration;NUM_TRIALS] = [Duration::new(0,0);NUM_TRIALS];
+    for i in 0..NUM_TRIALS {
+        let start = Instant::now(); 
+        let _sa = SurveyAuthority::new(&params);
+        durs[i] = start.elapsed();
+        sum += durs[i];
+        println!("Trial {}:\t{:?}", i+1, durs[i]);
+    }
+    println!();
+    // Calculate mean
+    let mean = sum / (NUM_TRIALS as u32);
+    // Calculate standard deviation
+    let mut sum_of_diff:f32 = 0.0;
+    for i in 0..NUM_TRIALS {
+        sum_of_diff += f32::powf((((durs[i].as_millis() as i128) - (mean.as_millis() as i128)) as f32)/1000.0, 2.0);
+    }
+    let sd = ( sum_of_diff / ((NUM_TRIALS as f32)- 1.0)).sqrt();
+ 
+    println!("Mean:\t\t{:?}", mean);
+    println!("Std Dev:\t{:?}s", sd);
+}
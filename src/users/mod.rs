@@ -4,29 +4,90 @@ extern crate rand;
 
 #[allow(non_snake_case)]
 pub mod RA;
-pub use self::RA::RegistrationAuthority;
-use tbn::{Group, Fr, G1, G2, Gt, pairing};
+#[allow(non_snake_case)]
+pub mod SA;
+pub use self::RA::{RegistrationAuthority, RegistrationCode, RegistrationRequest, BlindedCredential, RegistrationRecord, NotHeld, DataExport, ErasureReport};
+pub use self::SA::SurveyAuthority;
+use rand::{RngCore, CryptoRng};
+use tbn::{Group, Fr, G1, G2};
+use crate::gt::Gt;
+use crate::ct::gt_eq;
+use crate::cost::{pairing, pow};
+use crate::submission::{Submission, SubmissionInput, survey_token};
+use crate::proofs::{Proof, RegistrationStatement, RegistrationWitness, prove_registration};
+use crate::error::SurveyError;
+use crate::survey::{Survey, ParticipantEntry};
+use crate::hash::epoch_point;
+use crate::manifest::fingerprint;
+use crate::wallet::{Wallet, PendingRegistration};
+use crate::roles::Registrar;
+use crate::seed::{Seed, USER_ID_PATH, USER_PRF_PATH};
+use crate::secret::SecretScalar;
+use crate::storage::Storage;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
 
 // Signaure verification key used by Survey & Registration Authorities
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct VerificationKey {
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g1"))]
     pub u: G1,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g1"))]
     pub v: G1,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::g1"))]
     pub h: G1,
+    #[cfg_attr(feature = "serde", serde(with = "crate::encoding::gt"))]
     pub pk: Gt
 }
 
+// Signing machinery shared by the RA and the SA: a key pair (vk, sk) with vk = (u, v, h, pk) and
+// pk = e(g, g2)^sk, of which only vk ever leaves the authority
+pub trait KeyHolder {
+
+    // The current verification key
+    fn vk(&self) -> &VerificationKey;
+
+    // Schnorr signature in Gt on body under the current key (see proofs.rs)
+    fn sign_body(&self, domain: &[u8], g: G1, g2: G2, body: &[u8]) -> Proof;
+
+    // SHA-256 fingerprint of the current key, as listed in a params manifest
+    fn fingerprint(&self) -> String {
+        fingerprint((*self).vk())
+    }
+
+    // Fresh key pair: random u, v, h in G1 and secret x, with pk = e(g, g2)^x
+    fn gen_keys(g: G1, g2: G2, rng: &mut (impl RngCore + CryptoRng)) -> (VerificationKey, Fr) where Self: Sized {
+
+        // Generate random u,v,h in G_1
+        let u:G1 = G1::random(rng);
+        let v:G1 = G1::random(rng);
+        let h:G1 = G1::random(rng);
+
+        // Generate secret x as element of cyclic group with order r (q, in ANONIZE's notation)
+        let x:Fr = Fr::random(rng);
+
+        // Compute e(g, g2)^x
+        let pair:Gt = pow(pairing(g, g2), x);
+
+        (VerificationKey { u, v, h, pk: pair }, x)
+    }
+}
+
 pub struct User {
     
     // Unique user ID (kept hidden to others when submitting surveys -- user chooses when to
     // reveal
-    id: Fr,
-    pub vk: VerificationKey,
-    // Secret signing key used by Survey & Registration Authorities
-    sk: Fr,
-    // List of owned surveys (by vid)
-    pub owned_surveys: Vec<Fr>,
-    // (survey id, {RA's published user ids -> their signature})
-    pub verid_list: Vec<(Fr, Vec<(Fr, G1, G2)>)>
+    id: SecretScalar,
+    // Secret PRF key s used to derive one-time survey tokens (never revealed, not even to the RA)
+    pub(crate) prf_key: SecretScalar,
+    // Master credential (σ1, σ2) on (id, s) issued by the RA at registration, and its epoch
+    pub(crate) credential: Option<(G1, G2)>,
+    credential_epoch: u64,
+    // Commitment S' the credential was issued on, which identifies the registration to the RA, and
+    // its blinding b (needed to renew the credential)
+    registration_commitment: Option<G1>,
+    registration_blinding: Option<SecretScalar>
 }
 
 impl User {
@@ -34,291 +95,299 @@ impl User {
 
     // Generate (hopefully) unique id and return new User
     pub fn new() -> Self {
-
         // crytpographiclaly secure thread-local rng
-        let rng = &mut rand::thread_rng();
-        
-        // Create empty struct if user ever decides to become SA
-        let vk = VerificationKey {
-            u: G1::zero(),
-            v: G1::zero(),
-            h: G1::zero(),
-            pk: Gt::one(),
-        };
+        User::new_with_rng(&mut rand::thread_rng())
+    }
+
+    // Same as new, drawing the id and PRF key from rng
+    pub fn new_with_rng(rng: &mut (impl RngCore + CryptoRng)) -> Self {
+        User {
+            id: SecretScalar::new(Fr::random(rng)),
+            prf_key: SecretScalar::new(Fr::random(rng)),
+            credential: None,
+            credential_epoch: 0,
+            registration_commitment: None,
+            registration_blinding: None
+        }
+    }
+
+    // Regenerate the id and PRF key from seed (see seed.rs), and with them every survey token. The
+    // credential is not derived, and is restored from the wallet
+    pub fn from_seed(seed: &Seed) -> Self {
+        User {
+            id: SecretScalar::new((*seed).derive(USER_ID_PATH, b"id")),
+            prf_key: SecretScalar::new((*seed).derive(USER_PRF_PATH, b"s")),
+            credential: None,
+            credential_epoch: 0,
+            registration_commitment: None,
+            registration_blinding: None
+        }
+    }
+
+    // Returns the user's current id (e.g. to hand to an SA building a participant list)
+    pub fn id(&self) -> Fr {
+        (*self).id.expose()
+    }
+
+    // Commitment of the latest registration, to hand to the RA with a data export or erasure request
+    pub fn registration_commitment(&self) -> Option<G1> {
+        (*self).registration_commitment
+    }
 
+    // Epoch the RA issued the current credential for (see RegistrationAuthority::advance_epoch)
+    pub fn credential_epoch(&self) -> u64 {
+        (*self).credential_epoch
+    }
+
+    // Everything needed to act as this user elsewhere, once registered
+    pub fn wallet(&self) -> Result<Wallet, SurveyError> {
+        match ((*self).credential, (*self).registration_commitment, (*self).registration_blinding.as_ref().map(SecretScalar::expose)) {
+            (Some(credential), Some(registration_commitment), Some(registration_blinding)) =>
+                Ok(Wallet { id: SecretScalar::new((*self).id.expose()), prf_key: SecretScalar::new((*self).prf_key.expose()), credential,
+                            epoch: (*self).credential_epoch, registration_commitment, registration_blinding: SecretScalar::new(registration_blinding) }),
+            _ => Err(SurveyError::UnregisteredUser)
+        }
+    }
+
+    // Registered user holding the wallet's credential (owning no surveys)
+    pub fn from_wallet(wallet: Wallet) -> Self {
+        // Built directly rather than from User::new, so no randomness is drawn (see wasm.rs)
         User {
-            id: Fr::random(rng),
-            vk,
-            sk: Fr::zero(),
-            owned_surveys: Vec::new(),
-            verid_list: Vec::new()
+            id: wallet.id,
+            prf_key: wallet.prf_key,
+            credential: Some(wallet.credential),
+            credential_epoch: wallet.epoch,
+            registration_commitment: Some(wallet.registration_commitment),
+            registration_blinding: Some(wallet.registration_blinding)
         }
     }
 
     // Re-generate id and returns old ID
-    pub fn re_identify(&mut self, ra: &mut RegistrationAuthority) -> Fr {
+    pub fn re_identify(&mut self, ra: &mut RegistrationAuthority<impl Storage>) -> Result<Fr, SurveyError> {
+        (*self).re_identify_with_rng(ra, &mut rand::thread_rng())
+    }
+
+    pub fn re_identify_with_rng(&mut self, ra: &mut RegistrationAuthority<impl Storage>, rng: &mut (impl RngCore + CryptoRng)) -> Result<Fr, SurveyError> {
 
         // Generate new ID
-        let old_id:Fr = (*self).id;
-        let rng = &mut rand::thread_rng();
-        (*self).id = Fr::random(rng);
-    
-        // Re-register new ID with RA, removing old ID if necessary
-        let opt_index = (*ra).userid_list.iter().position(|id| *id == old_id);
-        match opt_index {
-            Some(old_id_index) => (*ra).userid_list.remove(old_id_index),
-            _ => Fr::zero()
+        let old_id:Fr = (*self).id.expose();
+        let mut renamed = User {
+            id: SecretScalar::new(Fr::random(rng)),
+            prf_key: SecretScalar::new((*self).prf_key.expose()),
+            credential: None,
+            credential_epoch: 0,
+            registration_commitment: None,
+            registration_blinding: None
         };
-        (*self).reg_user(ra);
 
-        return old_id;
+        // Register the new ID with the RA first, and only then remove the old ID: if the RA refuses
+        // the new ID, the user and the RA's anonymity set (and audit log) are left as they were
+        renamed.reg_user_with_rng(ra, rng)?;
+        (*ra).forget_id(old_id)?;
+        *self = renamed;
+
+        return Ok(old_id);
     }
 
     
-    pub fn reg_user(&mut self, ra: &mut RegistrationAuthority) {
-        // TODO: Follow the protocol to register with RA, send id to RA, and receive master token
-        
-        // Add own id to list provided by RA
-        (*ra).userid_list.push((*self).id);
+    // Register id with the RA (or any other Registrar), receiving the master credential on (id, s) in return
+    pub fn reg_user(&mut self, ra: &mut impl Registrar) -> Result<(), SurveyError> {
+        (*self).reg_user_with_rng(ra, &mut rand::thread_rng())
     }
 
-    // TODO: Allow user to dynamically implement SurveyAuthority trait if they wish to do so after
-    // initialization.
-
-    // Generate signature-verification keys on the fly to be able to generate surveys
-//    pub fn become_SA(&mut self, g:G1, g2:G2) -> impl SurveyAuthority {
-        
-//        let (vk, y) = SurveyAuthority::gen_SA(g, g2);
-//        (*self).vk = vk;
-//        (*self).y = y;
-//        println!("{:?}", (*self).vk.u);
-//        self
-//    }
-}
+    pub fn reg_user_with_rng(&mut self, ra: &mut impl Registrar, rng: &mut (impl RngCore + CryptoRng)) -> Result<(), SurveyError> {
+        let (g, g2):(G1, G2) = (*ra).generators();
+        let (commitment, blinding, proof) = (*self).registration_proof((*ra).vk_ra(), g, rng);
+        let credential = (*ra).register_request_with_rng(&RegistrationRequest { id: (*self).id.expose(), commitment, proof }, rng)?;
+        let blinded = (credential.sigma_1, credential.sigma_2, credential.sigma_3);
+        (*self).accept_credential(blinded, credential.epoch, commitment, blinding, (*ra).vk_ra(), g2)
+    }
 
+    // Complete registration with a one-time code minted by the RA, returning the code's metadata
+    pub fn redeem_code(&mut self, code: &str, ra: &mut RegistrationAuthority<impl Storage>) -> Result<String, SurveyError> {
+        (*self).redeem_code_with_rng(code, ra, &mut rand::thread_rng())
+    }
 
+    pub fn redeem_code_with_rng(&mut self, code: &str, ra: &mut RegistrationAuthority<impl Storage>, rng: &mut (impl RngCore + CryptoRng))
+                                -> Result<String, SurveyError> {
+        let (commitment, blinding, proof) = (*self).registration_proof(&(*ra).vk, (*ra).g, rng);
+        let (metadata, blinded) = (*ra).redeem_registration_code_with_rng(code, (*self).id.expose(), commitment, &proof, rng)?;
+        (*self).accept_credential(blinded, (*ra).epoch(), commitment, blinding, &(*ra).vk, (*ra).g2)?;
+        Ok(metadata)
+    }
 
-/*
- * ----------------------------------------------
- * |    SURVEY AUTHORITY (SA)                   |
- * ----------------------------------------------
- *
- * Any user can be a survey authority. As an SA, they can perform the following actions:
- *      - Create a survey
- *          + Choose survey identity vid
- *          + Generate signature key-pair that allows them to sign and others to verify values
- *          + Specify a list of user IDs (authenticated by the RA) to send the survey to
-*/
-
-pub trait SurveyAuthority {
-    
-    // Static method aliasing gen_SA
-    fn new(g:G1, g2:G2) -> Self;
-    
-    #[allow(non_snake_case)]
-    // Static method that creates values for SA
-    fn gen_SA(g:G1, g2:G2) -> (VerificationKey, Fr) {
+    // Renew the credential for the RA's current epoch, proving knowledge of the (s, b) behind the
+    // registration's commitment again. The id, PRF key and commitment stay the same
+    pub fn renew_credential(&mut self, ra: &mut RegistrationAuthority<impl Storage>) -> Result<(), SurveyError> {
+        (*self).renew_credential_with_rng(ra, &mut rand::thread_rng())
+    }
 
-        // crytpographiclaly secure thread-local rng
-        let rng = &mut rand::thread_rng();
+    pub fn renew_credential_with_rng(&mut self, ra: &mut RegistrationAuthority<impl Storage>, rng: &mut (impl RngCore + CryptoRng))
+                                     -> Result<(), SurveyError> {
+        let request = (*self).renewal_request_with_rng(&(*ra).vk, (*ra).g, rng)?;
+        let (sigma_1, sigma_2, sigma_3) = (*ra).renew_credential_with_rng((*self).id.expose(), &request.proof, rng)?;
+        (*self).accept_renewal(&BlindedCredential { epoch: (*ra).epoch(), sigma_1, sigma_2, sigma_3 }, &(*ra).vk, (*ra).g2)
+    }
 
-        // Generate random u,v,h in G_1
-        let u:G1 = G1::random(rng);
-        let v:G1 = G1::random(rng);
-        let h:G1 = G1::random(rng);
+    // Renewal request to send when the RA runs elsewhere (see RegistrationAuthority::reissue_batch),
+    // e.g. to migrate the credential after the RA rotated its keys. Complete with accept_renewal
+    pub fn renewal_request(&self, vk_ra: &VerificationKey, g: G1) -> Result<RegistrationRequest, SurveyError> {
+        (*self).renewal_request_with_rng(vk_ra, g, &mut rand::thread_rng())
+    }
 
-        // Generate secret y as element of cyclic group with order r (q, in ANONIZE's notation???)
-        let y:Fr = Fr::random(rng);
+    pub fn renewal_request_with_rng(&self, vk_ra: &VerificationKey, g: G1, rng: &mut (impl RngCore + CryptoRng))
+                                    -> Result<RegistrationRequest, SurveyError> {
+        let (commitment, blinding) = match ((*self).registration_commitment, (*self).registration_blinding.as_ref().map(SecretScalar::expose)) {
+            (Some(commitment), Some(blinding)) => (commitment, blinding),
+            _ => return Err(SurveyError::UnregisteredUser)
+        };
+        let witness = RegistrationWitness { s: (*self).prf_key.expose(), b: blinding };
+        let statement = RegistrationStatement { id: (*self).id.expose(), commitment, v: (*vk_ra).v, g };
+        Ok(RegistrationRequest { id: (*self).id.expose(), commitment, proof: prove_registration(&statement, &witness, rng) })
+    }
 
-        // Compute e(g, g2)^y
-        let pair:Gt = pairing(g, g2).pow(y);
+    // Replace the credential with the one the RA reissued for a renewal request, refusing it if it
+    // does not verify under vk_ra (the RA's current key)
+    pub fn accept_renewal(&mut self, credential: &BlindedCredential, vk_ra: &VerificationKey, g2: G2) -> Result<(), SurveyError> {
+        let (commitment, blinding) = match ((*self).registration_commitment, (*self).registration_blinding.as_ref().map(SecretScalar::expose)) {
+            (Some(commitment), Some(blinding)) => (commitment, blinding),
+            _ => return Err(SurveyError::UnregisteredUser)
+        };
+        let blinded = ((*credential).sigma_1, (*credential).sigma_2, (*credential).sigma_3);
+        (*self).accept_credential(blinded, (*credential).epoch, commitment, blinding, vk_ra, g2)
+    }
 
-        // Construct public verification key
-        let vk = VerificationKey { u, v, h, pk: pair };
-        
-        // Return the public and private keys
-        (vk, y)
+    // Keep the RA's credential for epoch once it is unblinded and verifies
+    fn accept_credential(&mut self, blinded: (G1, G2, G1), epoch: u64, commitment: G1, blinding: Fr,
+                         vk_ra: &VerificationKey, g2: G2) -> Result<(), SurveyError> {
+        (*self).credential = Some(unblind((*self).id.expose(), (*self).prf_key.expose(), blinded, blinding, epoch, vk_ra, g2)?);
+        (*self).credential_epoch = epoch;
+        (*self).registration_commitment = Some(commitment);
+        (*self).registration_blinding = Some(SecretScalar::new(blinding));
+        Ok(())
     }
 
-    // Instance method that generate survey with signature for each provided user id
-    fn gen_survey(&mut self, L:&Vec<Fr>, g:G1, g2:G2, vk_ra: &VerificationKey) -> Option<(Fr, Vec<(Fr, G1, G2)>)>;
-}
+    // First half of reg_user, for when the RA runs elsewhere: the request to send, and what to keep
+    // (secretly) until the RA's answer arrives, see PendingRegistration::complete
+    pub fn request_registration(&self, vk_ra: &VerificationKey, g: G1) -> (RegistrationRequest, PendingRegistration) {
+        (*self).request_registration_with_rng(vk_ra, g, &mut rand::thread_rng())
+    }
 
-impl SurveyAuthority for User {
-    
-    /* Create Survey Authority */
-    fn new(g:G1, g2:G2) -> User {
- 
-        let mut sa = User::new();
-        
-        // Return user with verification and signing key for creating surveys
-        let (vk, y) = Self::gen_SA(g, g2);
-        sa.vk = vk;
-        sa.sk = y;
-        return sa;
+    pub fn request_registration_with_rng(&self, vk_ra: &VerificationKey, g: G1, rng: &mut (impl RngCore + CryptoRng))
+                                         -> (RegistrationRequest, PendingRegistration) {
+        let (commitment, blinding, proof) = (*self).registration_proof(vk_ra, g, rng);
+        let pending = PendingRegistration { id: SecretScalar::new((*self).id.expose()), prf_key: SecretScalar::new((*self).prf_key.expose()),
+                                           blinding: SecretScalar::new(blinding), commitment };
+        (RegistrationRequest { id: (*self).id.expose(), commitment, proof }, pending)
     }
 
+    // Only a hiding commitment S' = v·s + g·b to the PRF key leaves the user, with a proof of
+    // knowledge of (s, b). Returns (S', b, proof)
+    fn registration_proof(&self, vk_ra: &VerificationKey, g: G1, rng: &mut (impl RngCore + CryptoRng)) -> (G1, Fr, Proof) {
+        let witness = RegistrationWitness { s: (*self).prf_key.expose(), b: Fr::random(rng) };
+        let statement = RegistrationStatement { id: (*self).id.expose(), commitment: witness.commitment((*vk_ra).v, g), v: (*vk_ra).v, g };
+        (statement.commitment, witness.b, prove_registration(&statement, &witness, rng))
+    }
 
-        
-    fn gen_survey(&mut self, L:&Vec<Fr>, g:G1, g2:G2, vk_ra: &VerificationKey) -> Option<(Fr, Vec<(Fr, G1, G2)>)> {
-        // crytpographiclaly secure thread-local rng
-        let rng = &mut rand::thread_rng();
-
-        // Choose random survey id as well
-        let vid = Fr::random(rng);
-        // Add vid to the list of owned surveys (by ID)
-        (*self).owned_surveys.push(vid);
-
-        /* --------------------------------------------------------------------------
-         *          Variation of Boneh-Boyen (BB) ID-based Signature Scheme
-         * --------------------------------------------------------------------------
-         */
-
-        /* Hoist invariant code to loop pre-header for efficiency */
-        // Sign with secret key
-        let sign_val:G1 = g * (*self).sk;
-        // Sign with vid
-        let vid_val:G1 = (*self).vk.u * vid;
-        
-        // Authorize all users in L (even if they're not registered -- this would be caught later)
-        // to submit a survey by constructing a signature with their id
-        for id in L {
-            
-            // Choose random r in Z_q (TODO: Move this and sigma_2 outside of loop???)
-            let r = Fr::random(rng);
-            // Sign with participant ID
-            let user_val:G1 = (*self).vk.v * *id;
-            // Put it all together to get the first signature
-            let sigma_1:G1 = sign_val + (vid_val + user_val + (*vk_ra).h) * r;
-            // Also sign 2nd group generator with random to get second signature
-            let sigma_2:G2 = g2 * r;
-            let user_signature:(Fr, G1, G2) = (*id, sigma_1, sigma_2);
-            
-            let mut found:bool = false;
-            // Loop through the various survey(s) the SA owns
-            for (owned_vid, id_list) in &mut (*self).verid_list {
-                // Found entry to add (id, signature) to
-                if *owned_vid == vid {
-                    found = true;
-                    id_list.push(user_signature);
-                    break;
-                }
-            }
-            if !found {   
-                // Add (sigma_1, sigma_2) to the list assoc with this vid and participant id
-                let mut user_list:Vec<(Fr, G1, G2)> = Vec::new();
-                user_list.push(user_signature);
-                let survey_entry:(Fr, Vec<(Fr, G1, G2)>)  = (vid, user_list);
-                // Must create new entry for (vid, {(ids, signatures)})
-                (*self).verid_list.push(survey_entry);
-            }
-        }
-        // "Publish" list of signatures for each participant of survey vid
-        for (owned_vid, id_list) in &(*self).verid_list {
-            // NOTE: unless something were to remove it during a race condition, should always return
-            if *owned_vid == vid {
-                let vid_list:(Fr, Vec<(Fr, G1, G2)>) = (vid, (*id_list).clone());
-                return Some(vid_list);
-            }
-        }
-        return None;
+    // Own one-time token for survey vid, e.g. to look up whether a response was already accepted
+    pub fn survey_token(&self, vid: Fr, g: G1) -> Result<G1, SurveyError> {
+        survey_token((*self).prf_key.expose(), vid, g).ok_or(SurveyError::DegenerateToken)
     }
-}
 
+    // SubmitSurvey: respond to a survey with message, given its published participant signatures.
+    // The message is padded to the survey's buckets first (see padding.rs), so the submission's size
+    // only gives away the bucket; the SA reads it back with Submission::answer
+    pub fn submit_survey(&self, survey: &Survey, message: &[u8], generators: (G1, G2),
+                         vk_sa: &VerificationKey, vk_ra: &VerificationKey) -> Result<Submission, SurveyError> {
+        (*self).submit_survey_with_rng(survey, message, generators, vk_sa, vk_ra, &mut rand::thread_rng())
+    }
 
-/*
- * Unit tests
- */
+    pub fn submit_survey_with_rng(&self, survey: &Survey, message: &[u8], generators: (G1, G2), vk_sa: &VerificationKey,
+                                  vk_ra: &VerificationKey, rng: &mut (impl RngCore + CryptoRng)) -> Result<Submission, SurveyError> {
 
-#[test]
-#[allow(non_snake_case)]
-// Test to ensure that e(g, g2)^(sk_SA) = vk_SA
-fn test_SA_keys() {
-    let rng = &mut rand::thread_rng();
-    let (g, g2):(G1, G2) = (G1::random(rng), G2::random(rng));
-    let sa:User = SurveyAuthority::new(g, g2);
-    assert!( pairing(g, g2).pow(sa.sk) == sa.vk.pk ); 
+        let credential = (*self).credential.ok_or(SurveyError::UnregisteredUser)?;
+        // Find the SA's signature for own id
+        let entry:ParticipantEntry = (*survey).entry_for((*self).id.expose()).ok_or(SurveyError::NotAuthorized)?;
+        let input = SubmissionInput {
+            vid: (*survey).vid, message: &(*survey).padding.pad(message), id: (*self).id.expose(), s: (*self).prf_key.expose(),
+            credential, epoch: (*self).credential_epoch, signature: (entry.sigma_1, entry.sigma_2)
+        };
+        let (g, g2) = generators;
+        Submission::new_with_rng(input, g, g2, vk_sa, vk_ra, rng)
+    }
 }
 
+impl Default for User {
+    fn default() -> Self {
+        User::new()
+    }
+}
 
-/*
- * Benchmark tests
- */
+// Remove the blinding from the RA's credential (σ1', σ2, σ3), and check that the result is a
+// valid credential on (id, s) for epoch: e(σ1, g2) = pk_RA · e(u·id + v·s + h + H(epoch), σ2)
+pub(crate) fn unblind(id: Fr, prf_key: Fr, blinded: (G1, G2, G1), blinding: Fr, epoch: u64, vk_ra: &VerificationKey, g2: G2)
+                      -> Result<(G1, G2), SurveyError> {
+    let (blind_1, cred_2, cred_3) = blinded;
+    let cred_1:G1 = blind_1 - cred_3 * blinding;
+    let h:G1 = (*vk_ra).h + epoch_point(epoch);
+    match gt_eq(pairing(cred_1, g2), (*vk_ra).pk * pairing((*vk_ra).u * id + (*vk_ra).v * prf_key + h, cred_2)) {
+        true => Ok((cred_1, cred_2)),
+        false => Err(SurveyError::InvalidSignature)
+    }
+}
 
-#[test]
-#[ignore]
-#[allow(non_snake_case)]
-// Test 100 iterations of GenSA to get mean and standard deviation
-fn bench_100_gen_SA() {
 
-    use std::time::{Duration, Instant};
 
-    // Setup 
-    let rng = &mut rand::thread_rng();
-    let (g, g2):(G1, G2) = (G1::random(rng), G2::random(rng));
-    
-    // 100 irerations of GenSA
-    const NUM_TRIALS:usize = 100;
-    assert!(NUM_TRIALS > 1);
-    println!("GenSA Benchmark Test ({} trials)", NUM_TRIALS);
-    let mut sum:Duration = Duration::new(0,0);
-    let mut durs:[Duration;NUM_TRIALS] = [Duration::new(0,0);NUM_TRIALS];
-    for i in 0..NUM_TRIALS {
-        let start = Instant::now(); 
-        let _sa:User = SurveyAuthority::new(g, g2);
-        durs[i] = start.elapsed();
-        sum += durs[i];
-        println!("Trial {}:\t{:?}", i+1, durs[i]);
-    }
-    println!();
-    // Calculate mean
-    let mean = sum / (NUM_TRIALS as u32);
-    // Calculate standard deviation
-    let mut sum_of_diff:f32 = 0.0;
-    for i in 0..NUM_TRIALS {
-        sum_of_diff += f32::powf((((durs[i].as_millis() as i128) - (mean.as_millis() as i128)) as f32)/1000.0, 2.0);
-    }
-    let sd = ( sum_of_diff / ((NUM_TRIALS as f32)- 1.0)).sqrt();
- 
-    println!("Mean:\t\t{:?}", mean);
-    println!("Std Dev:\t{:?}s", sd);
-}
+/*
+ * Unit tests
+ */
 
+#[cfg(test)]
+use crate::params::SystemParams;
+#[cfg(test)]
+use crate::hash::id_hash;
 
+// Test that a user whose new id is refused by the RA keeps their old id and credential
 #[test]
-#[allow(non_snake_case)]
-// Test 5 iterations of GenSA to get mean and standard deviation
-fn bench_5_gen_SA() {
-
-    use std::time::{Duration, Instant};
+fn test_re_identify_refused() {
+    use rand::SeedableRng;
+    use rand::prng::ChaChaRng;
+
+    let params = SystemParams::random();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut alice = User::new();
+    alice.reg_user(&mut ra).expect("Registration failed");
+    let (old_id, old_credential) = (alice.id(), alice.credential);
+
+    // The id alice would draw next is already revoked
+    let rng = &mut ChaChaRng::from_seed([7; 32]);
+    let mut mallory = User::new_with_rng(&mut rng.clone());
+    mallory.reg_user(&mut ra).expect("Registration failed");
+    ra.revoke(id_hash(mallory.id())).expect("Revocation failed");
+
+    let audit_entries:usize = ra.audit_log().entries().len();
+    assert!( alice.re_identify_with_rng(&mut ra, rng) == Err(SurveyError::RevokedUser) );
+    assert!( alice.id() == old_id && alice.credential == old_credential );
+    assert!( ra.in_anonymity_set(id_hash(old_id)) && ra.audit_log().entries().len() == audit_entries );
+}
 
-    // Setup 
-    let rng = &mut rand::thread_rng();
-    let (g, g2):(G1, G2) = (G1::random(rng), G2::random(rng));
-    
-    // 5 irerations of GenSA
-    const NUM_TRIALS:usize = 5;
-    assert!(NUM_TRIALS > 1);
-    println!("GenSA Benchmark Test ({} trials)", NUM_TRIALS);
-    let mut sum:Duration = Duration::new(0,0);
-    let mut durs:[Duration;NUM_TRIALS] = [Duration::new(0,0);NUM_TRIALS];
-    for i in 0..NUM_TRIALS {
-        let start = Instant::now(); 
-        let _sa:User = SurveyAuthority::new(g, g2);
-        durs[i] = start.elapsed();
-        sum += durs[i];
-        println!("Trial {}:\t{:?}", i+1, durs[i]);
-    }
-    println!();
-    // Calculate mean
-    let mean = sum / (NUM_TRIALS as u32);
-    // Calculate standard deviation
-    let mut sum_of_diff:f32 = 0.0;
-    for i in 0..NUM_TRIALS {
-        sum_of_diff += f32::powf((((durs[i].as_millis() as i128) - (mean.as_millis() as i128)) as f32)/1000.0, 2.0);
-    }
-    let sd = ( sum_of_diff / ((NUM_TRIALS as f32)- 1.0)).sqrt();
- 
-    println!("Mean:\t\t{:?}", mean);
-    println!("Std Dev:\t{:?}s", sd);
+// Test that everything drawn from an injected rng is reproducible from its seed
+#[test]
+fn test_injected_rng() {
+    use rand::SeedableRng;
+    use rand::prng::ChaChaRng;
+
+    let params = SystemParams::random();
+
+    let (g, g2) = params.generators();
+    let run = || {
+        let rng = &mut ChaChaRng::from_seed([7; 32]);
+        let ra = RegistrationAuthority::new_with_rng(&params, rng);
+        let mut sa = SurveyAuthority::new_with_rng(&params, rng);
+        let user = User::new_with_rng(rng);
+        let survey = sa.gen_survey_with_rng(&vec![user.id()], g, g2, &ra.vk, rng).expect("SA survey creation failed!");
+        (ra.vk.u, sa.vk.u, user.id(), survey)
+    };
+    assert!( run() == run() );
 }
+
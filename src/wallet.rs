@@ -0,0 +1,191 @@
+extern crate tbn;
+extern crate rand;
+
+use rand::{RngCore, CryptoRng};
+use zeroize::Zeroizing;
+use tbn::{Fr, G1, G2};
+
+use crate::error::SurveyError;
+use crate::keystore::{self, KeystoreKind, KeyReader};
+use crate::users::{VerificationKey, BlindedCredential, unblind};
+use crate::encoding::{fr_to_bytes, g1_to_bytes, g2_to_bytes};
+use crate::secret::SecretScalar;
+
+/*
+ * ----------------------------------------------
+ * |    CREDENTIAL WALLETS                      |
+ * ----------------------------------------------
+ *
+ * Everything a registered user needs to take surveys later, or on another machine: the id, the
+ * secret PRF key s, the RA's credential (σ1, σ2) on (id, s) with the epoch it was issued for, and
+ * the commitment S' = v·s + g·b the credential was issued on with its blinding b (to renew the
+ * credential). Taken from a User with User::wallet and turned back into one with
+ * User::from_wallet. On disk a wallet is a keystore (see keystore.rs) of its own kind:
+ *
+ *      id ‖ s ‖ σ1 ‖ σ2 ‖ epoch (8, big-endian) ‖ S' ‖ b
+ *
+ * When the RA runs in another process, registration is split in two: User::request_registration
+ * returns the request to send along with a PendingRegistration (id ‖ s ‖ b ‖ S', sealed the same
+ * way), which turns into a Wallet once the RA's blinded credential comes back.
+*/
+
+#[derive(Debug, PartialEq)]
+pub struct Wallet {
+    pub(crate) id: SecretScalar,
+    pub(crate) prf_key: SecretScalar,
+    pub(crate) credential: (G1, G2),
+    pub(crate) epoch: u64,
+    pub(crate) registration_commitment: G1,
+    pub(crate) registration_blinding: SecretScalar
+}
+
+impl Wallet {
+
+    // Id the credential was issued to
+    pub fn id(&self) -> Fr {
+        (*self).id.expose()
+    }
+
+    /* Encrypt the wallet under passphrase, e.g. to write it to disk */
+    pub fn seal(&self, passphrase: &str) -> Vec<u8> {
+        // crytpographiclaly secure thread-local rng
+        (*self).seal_with_rng(passphrase, &mut rand::thread_rng())
+    }
+
+    pub fn seal_with_rng(&self, passphrase: &str, rng: &mut (impl RngCore + CryptoRng)) -> Vec<u8> {
+        let mut plaintext:Zeroizing<Vec<u8>> = Zeroizing::new(Vec::new());
+        plaintext.extend(fr_to_bytes((*self).id.expose()));
+        plaintext.extend(fr_to_bytes((*self).prf_key.expose()));
+        plaintext.extend(g1_to_bytes((*self).credential.0));
+        plaintext.extend(g2_to_bytes((*self).credential.1));
+        plaintext.extend_from_slice(&(*self).epoch.to_be_bytes());
+        plaintext.extend(g1_to_bytes((*self).registration_commitment));
+        plaintext.extend(fr_to_bytes((*self).registration_blinding.expose()));
+        keystore::seal(KeystoreKind::Wallet, &plaintext, passphrase, rng)
+    }
+
+    /* Decrypt a wallet written by seal */
+    pub fn open(sealed: &[u8], passphrase: &str) -> Result<Wallet, SurveyError> {
+        let plaintext = keystore::open(KeystoreKind::Wallet, sealed, passphrase)?;
+        let mut reader = KeyReader::new(&plaintext);
+        let wallet = Wallet {
+            id: SecretScalar::new(reader.fr()?),
+            prf_key: SecretScalar::new(reader.fr()?),
+            credential: (reader.g1()?, reader.g2()?),
+            epoch: reader.u64()?,
+            registration_commitment: reader.g1()?,
+            registration_blinding: SecretScalar::new(reader.fr()?)
+        };
+        reader.finish()?;
+        Ok(wallet)
+    }
+}
+
+
+// A registration waiting for the RA's answer, with the blinding b of S' = v·s + g·b
+#[derive(Debug, PartialEq)]
+pub struct PendingRegistration {
+    pub(crate) id: SecretScalar,
+    pub(crate) prf_key: SecretScalar,
+    pub(crate) blinding: SecretScalar,
+    pub(crate) commitment: G1
+}
+
+impl PendingRegistration {
+
+    // Unblind the RA's credential, refusing it if it is not valid on (id, s) for its epoch
+    pub fn complete(&self, credential: &BlindedCredential, vk_ra: &VerificationKey, g2: G2) -> Result<Wallet, SurveyError> {
+        let blinded = ((*credential).sigma_1, (*credential).sigma_2, (*credential).sigma_3);
+        let (id, prf_key, blinding):(Fr, Fr, Fr) = ((*self).id.expose(), (*self).prf_key.expose(), (*self).blinding.expose());
+        Ok(Wallet {
+            id: SecretScalar::new(id),
+            prf_key: SecretScalar::new(prf_key),
+            credential: unblind(id, prf_key, blinded, blinding, (*credential).epoch, vk_ra, g2)?,
+            epoch: (*credential).epoch,
+            registration_commitment: (*self).commitment,
+            registration_blinding: SecretScalar::new(blinding)
+        })
+    }
+
+    /* Encrypt under passphrase until the RA answers */
+    pub fn seal(&self, passphrase: &str) -> Vec<u8> {
+        // crytpographiclaly secure thread-local rng
+        (*self).seal_with_rng(passphrase, &mut rand::thread_rng())
+    }
+
+    pub fn seal_with_rng(&self, passphrase: &str, rng: &mut (impl RngCore + CryptoRng)) -> Vec<u8> {
+        let mut plaintext:Zeroizing<Vec<u8>> = Zeroizing::new(Vec::new());
+        for x in &[&(*self).id, &(*self).prf_key, &(*self).blinding] {
+            plaintext.extend(fr_to_bytes(x.expose()));
+        }
+        plaintext.extend(g1_to_bytes((*self).commitment));
+        keystore::seal(KeystoreKind::PendingRegistration, &plaintext, passphrase, rng)
+    }
+
+    pub fn open(sealed: &[u8], passphrase: &str) -> Result<PendingRegistration, SurveyError> {
+        let plaintext = keystore::open(KeystoreKind::PendingRegistration, sealed, passphrase)?;
+        let mut reader = KeyReader::new(&plaintext);
+        let pending = PendingRegistration { id: SecretScalar::new(reader.fr()?), prf_key: SecretScalar::new(reader.fr()?), blinding: SecretScalar::new(reader.fr()?),
+                                           commitment: reader.g1()? };
+        reader.finish()?;
+        Ok(pending)
+    }
+}
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use crate::users::{User, RegistrationAuthority, SurveyAuthority};
+#[cfg(test)]
+use crate::params::SystemParams;
+#[cfg(test)]
+use crate::submission::check_submission;
+#[cfg(test)]
+use crate::hash::id_hash;
+
+// Test that a user restored from a sealed wallet can still answer surveys with the same credential
+#[test]
+fn test_wallet_seal_open() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let mut user = User::new();
+    assert!( user.wallet().err() == Some(SurveyError::UnregisteredUser) );
+    user.reg_user(&mut ra).expect("Registration failed");
+
+    let sealed = user.wallet().expect("Registered user has no wallet").seal("correct horse");
+    assert!( Wallet::open(&sealed, "battery staple").err() == Some(SurveyError::InvalidKeystore) );
+    assert!( RegistrationAuthority::from_keys(&sealed, "correct horse").is_err() );
+    let wallet = Wallet::open(&sealed, "correct horse").expect("Wallet was rejected");
+    assert!( wallet == user.wallet().unwrap() );
+
+    let restored = User::from_wallet(wallet);
+    assert!( restored.id() == user.id() && restored.registration_commitment() == user.registration_commitment() );
+    let survey = sa.gen_survey(&vec![restored.id()], g, g2, &ra.vk).expect("SA survey creation failed!");
+    let submission = restored.submit_survey(&survey, b"yes", (g, g2), &sa.vk, &ra.vk).expect("Submission failed");
+    assert!( check_submission(survey.vid, &submission, &sa.vk, &ra.vk, g, g2) );
+    assert!( restored.survey_token(survey.vid, g) == user.survey_token(survey.vid, g) );
+}
+
+// Test registration through serialized messages, as when user and RA run as separate processes
+#[test]
+fn test_pending_registration() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let user = User::new();
+    let (request, pending) = user.request_registration(&ra.vk, g);
+    let pending = PendingRegistration::open(&pending.seal("correct horse"), "correct horse").expect("Pending registration was rejected");
+
+    let mut tampered = request.clone();
+    tampered.id = Fr::one();
+    assert!( ra.register_request(&tampered).err() == Some(SurveyError::InvalidProof) );
+    let credential = ra.register_request(&request).expect("Registration failed");
+    let wallet = pending.complete(&credential, &ra.vk, g2).expect("Credential was rejected");
+    assert!( wallet.id() == user.id() && ra.in_anonymity_set(id_hash(user.id())) );
+    let forged = BlindedCredential { sigma_1: credential.sigma_3, ..credential };
+    assert!( pending.complete(&forged, &ra.vk, g2).err() == Some(SurveyError::InvalidSignature) );
+}
@@ -0,0 +1,189 @@
+extern crate rand;
+extern crate hex;
+extern crate serde_json;
+extern crate wasm_bindgen;
+extern crate getrandom;
+
+use rand::{RngCore, CryptoRng};
+use serde::{Serialize, de::DeserializeOwned};
+use wasm_bindgen::prelude::*;
+
+use crate::users::{User, VerificationKey, BlindedCredential};
+use crate::wallet::{Wallet, PendingRegistration};
+use crate::params::SystemParams;
+use crate::survey::Survey;
+use crate::encoding::fr_to_bytes;
+use crate::error::SurveyError;
+
+/*
+ * ----------------------------------------------
+ * |    WASM BINDINGS                           |
+ * ----------------------------------------------
+ *
+ * The User role for browsers, built for wasm32-unknown-unknown with wasm-bindgen (e.g.
+ * `wasm-pack build --target web --no-default-features --features wasm`). Protocol messages cross
+ * the boundary as the JSON of their serde encoding (see encoding.rs), the same objects the
+ * anon_survey binary reads and writes as YAML. Secrets only cross it sealed under a passphrase
+ * (see wallet.rs), as a Uint8Array that can go straight into IndexedDB:
+ *
+ *      const user = new User();
+ *      const registration = user.requestRegistration(params, vkRa, passphrase);
+ *      // send registration.request to the RA, keep registration.pending until it answers
+ *      const wallet = User.completeRegistration(registration.pending, credential, params, vkRa, passphrase);
+ *      // later: User.openWallet(wallet, passphrase).submitSurvey(survey, message, params, vkSa, vkRa)
+ *
+ * rand 0.5 (which tbn is built on) has no browser entropy source, so thread_rng is never used
+ * here: every call goes through the _with_rng variant with BrowserRng, which reads
+ * crypto.getRandomValues through getrandom. Errors are thrown as strings.
+*/
+
+// crypto.getRandomValues in a browser (the OS elsewhere), as a rand 0.5 rng
+struct BrowserRng;
+
+impl RngCore for BrowserRng {
+
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        (*self).fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        (*self).fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        getrandom::getrandom(dest).expect("No entropy source available");
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        getrandom::getrandom(dest).map_err(|_| rand::Error::new(rand::ErrorKind::Unavailable, "no entropy source available"))
+    }
+}
+
+impl CryptoRng for BrowserRng {}
+
+#[wasm_bindgen(js_name = User)]
+pub struct WasmUser {
+    user: User
+}
+
+// What requestRegistration returns: the request for the RA, and the sealed secrets to keep until it answers
+#[wasm_bindgen(getter_with_clone)]
+pub struct Registration {
+    // JSON RegistrationRequest
+    pub request: String,
+    // PendingRegistration sealed under the passphrase
+    pub pending: Vec<u8>
+}
+
+#[wasm_bindgen(js_class = User)]
+impl WasmUser {
+
+    /* New user with a random id and PRF key */
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmUser {
+        WasmUser { user: User::new_with_rng(&mut BrowserRng) }
+    }
+
+    /* Registered user from a wallet sealed by completeRegistration or sealWallet */
+    #[wasm_bindgen(js_name = openWallet)]
+    pub fn open_wallet(wallet: &[u8], passphrase: &str) -> Result<WasmUser, JsValue> {
+        let wallet = Wallet::open(wallet, passphrase).map_err(thrown)?;
+        Ok(WasmUser { user: User::from_wallet(wallet) })
+    }
+
+    /* The id to give an SA for its participant lists, in hex */
+    pub fn id(&self) -> String {
+        hex::encode(fr_to_bytes((*self).user.id()))
+    }
+
+    /* RegUser: the request to send to the RA, and the secrets behind it sealed under passphrase */
+    #[wasm_bindgen(js_name = requestRegistration)]
+    pub fn request_registration(&self, params: &str, vk_ra: &str, passphrase: &str) -> Result<Registration, JsValue> {
+        let (params, vk_ra):(SystemParams, VerificationKey) = (from_json(params)?, from_json(vk_ra)?);
+        let (request, pending) = (*self).user.request_registration_with_rng(&vk_ra, params.g, &mut BrowserRng);
+        Ok(Registration { request: to_json(&request)?, pending: pending.seal_with_rng(passphrase, &mut BrowserRng) })
+    }
+
+    /* Unblind the RA's answer (JSON BlindedCredential) with the pending registration, into a sealed wallet */
+    #[wasm_bindgen(js_name = completeRegistration)]
+    pub fn complete_registration(pending: &[u8], credential: &str, params: &str, vk_ra: &str, passphrase: &str) -> Result<Vec<u8>, JsValue> {
+        let (credential, params, vk_ra):(BlindedCredential, SystemParams, VerificationKey) = (from_json(credential)?, from_json(params)?, from_json(vk_ra)?);
+        let pending = PendingRegistration::open(pending, passphrase).map_err(thrown)?;
+        let wallet = pending.complete(&credential, &vk_ra, params.g2).map_err(thrown)?;
+        Ok(wallet.seal_with_rng(passphrase, &mut BrowserRng))
+    }
+
+    /* The user's credential sealed under passphrase, e.g. after a renewal */
+    #[wasm_bindgen(js_name = sealWallet)]
+    pub fn seal_wallet(&self, passphrase: &str) -> Result<Vec<u8>, JsValue> {
+        Ok((*self).user.wallet().map_err(thrown)?.seal_with_rng(passphrase, &mut BrowserRng))
+    }
+
+    /* SubmitSurvey: the JSON Submission answering survey (JSON) with message */
+    #[wasm_bindgen(js_name = submitSurvey)]
+    pub fn submit_survey(&self, survey: &str, message: &[u8], params: &str, vk_sa: &str, vk_ra: &str) -> Result<String, JsValue> {
+        let (survey, params):(Survey, SystemParams) = (from_json(survey)?, from_json(params)?);
+        let (vk_sa, vk_ra):(VerificationKey, VerificationKey) = (from_json(vk_sa)?, from_json(vk_ra)?);
+        let submission = (*self).user.submit_survey_with_rng(&survey, message, (params.g, params.g2), &vk_sa, &vk_ra, &mut BrowserRng)
+            .map_err(thrown)?;
+        to_json(&submission)
+    }
+}
+
+impl Default for WasmUser {
+    fn default() -> Self {
+        WasmUser::new()
+    }
+}
+
+fn from_json<T: DeserializeOwned>(json: &str) -> Result<T, JsValue> {
+    serde_json::from_str(json).map_err(|e| thrown(SurveyError::SerializationError(e.to_string())))
+}
+
+fn to_json<T: Serialize>(object: &T) -> Result<String, JsValue> {
+    serde_json::to_string(object).map_err(|e| thrown(SurveyError::SerializationError(e.to_string())))
+}
+
+fn thrown(error: SurveyError) -> JsValue {
+    JsValue::from_str(&error.to_string())
+}
+
+
+/*
+ * Unit tests
+ */
+
+#[cfg(test)]
+use crate::users::{RegistrationAuthority, SurveyAuthority};
+#[cfg(test)]
+use crate::submission::{Submission, check_submission};
+
+// A browser user registers, keeps its wallet sealed, and submits, all through the exported calls
+// (JsValue is only touched on errors, which need a JS host)
+#[test]
+fn test_wasm_user_flow() {
+    let params = SystemParams::random();
+    let (g, g2) = params.generators();
+    let mut ra = RegistrationAuthority::new(&params);
+    let mut sa = SurveyAuthority::new(&params);
+    let (params_json, vk_ra_json, vk_sa_json) = (serde_json::to_string(&params).unwrap(), serde_json::to_string(&ra.vk).unwrap(),
+                                                 serde_json::to_string(&sa.vk).unwrap());
+
+    let user = WasmUser::new();
+    let registration = user.request_registration(&params_json, &vk_ra_json, "correct horse").unwrap_or_else(|_| panic!("Request failed"));
+    let credential = ra.register_request(&serde_json::from_str(&registration.request).unwrap()).expect("Registration was refused");
+    let wallet = WasmUser::complete_registration(&registration.pending, &serde_json::to_string(&credential).unwrap(),
+                                                 &params_json, &vk_ra_json, "correct horse").unwrap_or_else(|_| panic!("Completion failed"));
+
+    let user = WasmUser::open_wallet(&wallet, "correct horse").unwrap_or_else(|_| panic!("Wallet did not open"));
+    assert!( user.id() == hex::encode(fr_to_bytes(Wallet::open(&wallet, "correct horse").unwrap().id())) );
+    let survey = sa.gen_survey(&vec![user.user.id()], g, g2, &ra.vk).expect("Survey was refused");
+    let submission = user.submit_survey(&serde_json::to_string(&survey).unwrap(), b"yes", &params_json, &vk_sa_json, &vk_ra_json)
+        .unwrap_or_else(|_| panic!("Submission failed"));
+    let submission:Submission = serde_json::from_str(&submission).unwrap();
+    assert!( check_submission(survey.vid, &submission, &sa.vk, &ra.vk, g, g2) );
+}